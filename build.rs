@@ -0,0 +1,5 @@
+// Embedded migrations are only picked up when the crate is rebuilt, which cargo does not do for new
+// files in the migrations directory on its own.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,6 @@
+-- Whether direct messages could be delivered to a user when this was last checked.
+CREATE TABLE dm_reachability (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    dm_reachable INTEGER NOT NULL,
+    checked_at INTEGER NOT NULL
+);
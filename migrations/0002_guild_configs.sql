@@ -0,0 +1,6 @@
+-- The configuration of each guild that deviates from the defaults, stored as JSON so that new
+-- settings do not require a migration.
+CREATE TABLE guild_configs (
+    guild_id INTEGER PRIMARY KEY NOT NULL,
+    config TEXT NOT NULL
+);
@@ -0,0 +1,18 @@
+-- Actions taken in each guild, bucketed by the hour they were taken in. Buckets are pruned after a
+-- retention period, so the totals are kept separately.
+CREATE TABLE guild_action_counts (
+    guild_id INTEGER NOT NULL,
+    hour INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, hour, action)
+);
+
+CREATE INDEX guild_action_counts_hour ON guild_action_counts (hour);
+
+CREATE TABLE guild_action_totals (
+    guild_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, action)
+);
@@ -0,0 +1,25 @@
+-- Every action taken by the bot or by moderators through it, e.g. to show the history of a user.
+CREATE TABLE action_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    channel_id INTEGER,
+    moderator_id INTEGER,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX action_history_user ON action_history (guild_id, user_id, created_at);
+
+-- Proposals to ban a user that are awaiting the decision of a moderator, identified by the message
+-- they were posted as. The outcome is set once they were decided on or expired.
+CREATE TABLE ban_proposals (
+    message_id INTEGER PRIMARY KEY NOT NULL,
+    channel_id INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    outcome TEXT
+);
+
+CREATE INDEX ban_proposals_open ON ban_proposals (guild_id, user_id) WHERE outcome IS NULL;
@@ -0,0 +1,7 @@
+-- Hashes of normalized contents that were removed as duplicates in several guilds sharing their
+-- fingerprints, along with the guilds that removed them. Contents themselves are never stored.
+CREATE TABLE scam_fingerprints (
+    fingerprint TEXT PRIMARY KEY NOT NULL,
+    guild_ids TEXT NOT NULL,
+    expires_at INTEGER NOT NULL
+);
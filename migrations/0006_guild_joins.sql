@@ -0,0 +1,7 @@
+-- When the bot joined each guild, which decides whether it is still bootstrapping there, and
+-- whether moderators were told that the bootstrap period is over.
+CREATE TABLE guild_joins (
+    guild_id INTEGER PRIMARY KEY NOT NULL,
+    joined_at INTEGER NOT NULL,
+    bootstrap_completed INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Preferences users set for themselves, which apply in every guild.
+CREATE TABLE user_preferences (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    dm_format TEXT NOT NULL
+);
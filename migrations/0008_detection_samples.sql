@@ -0,0 +1,13 @@
+-- Detections sampled for offline analysis of false positives, along with the full content of the
+-- message and a snapshot of the configuration it was detected with.
+CREATE TABLE detection_samples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    detection TEXT NOT NULL,
+    content TEXT NOT NULL,
+    similarity REAL,
+    config TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Messages archived in full before the bot deleted them, in guilds that enabled archiving.
+CREATE TABLE deleted_messages (
+    message_id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    message TEXT NOT NULL,
+    deleted_at INTEGER NOT NULL
+);
+
+CREATE INDEX deleted_messages_age ON deleted_messages (guild_id, deleted_at);
@@ -0,0 +1,11 @@
+-- Users who accumulated many strikes in a guild and are held to stricter settings while they are
+-- on probation. A record is kept once probation ended, but no longer counts.
+CREATE TABLE user_strike_records (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    on_probation INTEGER NOT NULL,
+    probation_started_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX user_strike_records_probation ON user_strike_records (guild_id) WHERE on_probation = 1;
@@ -0,0 +1,12 @@
+-- Detections during the quiet hours of a guild, which are acted on once they end. They are kept
+-- here so that they are not lost if the bot restarts in the meantime.
+CREATE TABLE deferred_actions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    detection TEXT NOT NULL,
+    deferred_at INTEGER NOT NULL
+);
+
+CREATE INDEX deferred_actions_guild ON deferred_actions (guild_id);
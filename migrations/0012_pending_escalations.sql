@@ -0,0 +1,12 @@
+-- Bans whose author was warned and which are executed once the warning window passed, unless a
+-- moderator cancels them by deleting their row.
+CREATE TABLE pending_escalations (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    moderator_id INTEGER,
+    reason TEXT NOT NULL,
+    fire_at INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX pending_escalations_fire_at ON pending_escalations (fire_at);
@@ -0,0 +1,7 @@
+-- Users who posted in a guild at least once while its first messages were exempt, so that their
+-- later messages are checked even after the bot restarts.
+CREATE TABLE known_users (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
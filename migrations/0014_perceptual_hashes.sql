@@ -0,0 +1,11 @@
+-- Perceptual hashes of the images users posted, which are compared against the images they post
+-- afterwards to find reposts with small changes. They are pruned along with the events of their
+-- guild.
+CREATE TABLE perceptual_hashes (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    phash INTEGER NOT NULL,
+    first_seen INTEGER NOT NULL
+);
+
+CREATE INDEX perceptual_hashes_user ON perceptual_hashes (guild_id, user_id, first_seen);
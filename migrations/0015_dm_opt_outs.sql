@@ -0,0 +1,5 @@
+-- Users who asked the bot not to message them about their deleted messages, in any guild.
+CREATE TABLE dm_opt_outs (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    opted_out_at INTEGER NOT NULL
+);
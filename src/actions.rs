@@ -0,0 +1,845 @@
+//! The requests to Discord made while handling messages, so that messages can also be handled
+//! without a connection to Discord, see [`crate::headless`].
+
+use std::sync::Arc;
+
+use serde_json::json;
+use serenity::{
+    builder::{CreateActionRow, CreateEmbed, CreateMessage},
+    client::Context,
+    model::{
+        channel::ChannelType,
+        permissions::Permissions,
+        prelude::{ChannelId, GuildId, MessageId, PartialMember, UserId},
+    },
+    prelude::{RwLock, TypeMap},
+};
+
+use crate::{
+    config::GuildConfig,
+    error::BroomError,
+    escalation,
+    forum::{ThreadTagCaches, ThreadTags},
+    invites::InviteCaches,
+    locale::UserLocaleCaches,
+    moderation,
+    monitor::{self, ErrorRateMonitors},
+    ratelimit::ActionRateLimiter,
+    state::shared,
+};
+
+#[serenity::async_trait]
+pub trait DiscordActions: Send + Sync {
+    fn guild_name(&self, guild_id: GuildId) -> Option<String>;
+
+    /// Returns the approximate number of members of the given guild, or `None` if it is unknown.
+    fn guild_member_count(&self, guild_id: GuildId) -> Option<u64>;
+
+    /// Returns the permissions of the bot in the given channel, or `None` if they are unknown.
+    fn channel_permissions(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Permissions>;
+
+    /// Whether the given channel is marked as NSFW, or `None` if that is unknown.
+    fn channel_nsfw(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<bool>;
+
+    /// Returns the type of the given channel or thread, or `None` if it is unknown.
+    fn channel_type(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelType>;
+
+    /// Returns the category of the given channel or thread, or `None` if it is in none or that is
+    /// unknown.
+    fn channel_category(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelId>;
+
+    /// Returns the permissions of the given member in the guild, or `None` if they are unknown.
+    fn member_permissions(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        member: &PartialMember,
+    ) -> Option<Permissions>;
+
+    async fn delete_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), serenity::Error>;
+
+    async fn direct_message(
+        &self,
+        user_id: UserId,
+        message: CreateMessage,
+    ) -> Result<(), serenity::Error>;
+
+    /// Posts the given embed to the audit channel of the guild, if one is configured.
+    async fn post_audit_log(&self, config: &GuildConfig, embed: CreateEmbed);
+
+    /// Posts the given embed to the audit channel of the guild along with buttons to act on the
+    /// message it reports, if one is configured.
+    async fn post_audit_report(
+        &self,
+        config: &GuildConfig,
+        embed: CreateEmbed,
+        buttons: Vec<CreateActionRow>,
+    );
+
+    /// Posts the given embed to the alert channel of the operator, if one is configured. Nothing
+    /// posted there may be shown to guilds.
+    async fn post_operator_alert(&self, embed: CreateEmbed);
+
+    /// Posts the given embed to the verbose mode debug channel of a guild.
+    async fn post_debug_log(&self, channel_id: ChannelId, embed: CreateEmbed);
+
+    /// Prevents the given member from communicating for the given number of seconds.
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        duration_secs: u64,
+        reason: &str,
+    ) -> Result<(), serenity::Error>;
+
+    /// Bans the given member right away, deleting their messages of the given number of days.
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        delete_message_days: u8,
+        reason: &str,
+    ) -> Result<(), serenity::Error>;
+
+    /// Raises the verification level of the guild to the highest one, so that only members with a
+    /// verified phone number can send messages.
+    async fn lock_down(&self, guild_id: GuildId, reason: &str) -> Result<(), serenity::Error>;
+
+    /// Returns the tags of the forum post with the given ID, or `None` if the channel is not a
+    /// forum post.
+    async fn thread_tags(&self, channel_id: ChannelId) -> Result<Option<ThreadTags>, BroomError>;
+
+    /// Whether the invite with the given code leads to the given guild.
+    async fn invite_leads_to(&self, code: &str, guild_id: GuildId) -> Result<bool, BroomError>;
+
+    /// Returns the language of the bundled catalog matching the locale of the given user, if known.
+    async fn user_language(&self, user_id: UserId) -> Result<Option<&'static str>, BroomError>;
+
+    /// Proposes to ban the given user if they reached the configured number of strikes.
+    async fn propose_ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        config: &GuildConfig,
+    ) -> Result<(), BroomError>;
+}
+
+/// Everything handling a message needs besides the message itself.
+#[derive(Clone)]
+pub struct MessageContext {
+    pub data: Arc<RwLock<TypeMap>>,
+    pub actions: Arc<dyn DiscordActions>,
+}
+
+impl MessageContext {
+    /// Creates a context making actual requests to Discord.
+    pub fn new(context: &Context) -> Self {
+        Self {
+            data: context.data.clone(),
+            actions: Arc::new(SerenityActions {
+                context: context.clone(),
+            }),
+        }
+    }
+
+    /// Returns a context whose deletions, direct messages and audit logs count towards the rate
+    /// limit of the given guild.
+    pub fn rate_limited(&self, guild_id: GuildId, limiter: Arc<ActionRateLimiter>) -> Self {
+        Self {
+            data: self.data.clone(),
+            actions: Arc::new(RateLimitedActions {
+                inner: self.actions.clone(),
+                guild_id,
+                limiter,
+            }),
+        }
+    }
+}
+
+/// Makes actual requests to Discord.
+pub struct SerenityActions {
+    context: Context,
+}
+
+#[serenity::async_trait]
+impl DiscordActions for SerenityActions {
+    fn guild_name(&self, guild_id: GuildId) -> Option<String> {
+        guild_id.name(&self.context.cache)
+    }
+
+    fn guild_member_count(&self, guild_id: GuildId) -> Option<u64> {
+        Some(self.context.cache.guild(guild_id)?.member_count)
+    }
+
+    fn channel_permissions(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Permissions> {
+        let guild = self.context.cache.guild(guild_id)?;
+        let member = guild.members.get(&self.context.cache.current_user().id)?;
+        // Threads inherit the permissions of the channel they were created in.
+        let channel = guild.channels.get(&channel_id).or_else(|| {
+            let thread = guild
+                .threads
+                .iter()
+                .find(|thread| thread.id == channel_id)?;
+            guild.channels.get(&thread.parent_id?)
+        })?;
+
+        Some(guild.user_permissions_in(channel, member))
+    }
+
+    fn channel_nsfw(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<bool> {
+        let guild = self.context.cache.guild(guild_id)?;
+        // Threads are as NSFW as the channel they were created in.
+        let channel = guild.channels.get(&channel_id).or_else(|| {
+            let thread = guild
+                .threads
+                .iter()
+                .find(|thread| thread.id == channel_id)?;
+            guild.channels.get(&thread.parent_id?)
+        })?;
+
+        Some(channel.nsfw)
+    }
+
+    fn channel_type(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelType> {
+        let guild = self.context.cache.guild(guild_id)?;
+        let channel = guild
+            .channels
+            .get(&channel_id)
+            .or_else(|| guild.threads.iter().find(|thread| thread.id == channel_id))?;
+
+        Some(channel.kind)
+    }
+
+    fn channel_category(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelId> {
+        let guild = self.context.cache.guild(guild_id)?;
+        // Threads are in the category of the channel they were created in.
+        let channel = guild.channels.get(&channel_id).or_else(|| {
+            let thread = guild
+                .threads
+                .iter()
+                .find(|thread| thread.id == channel_id)?;
+            guild.channels.get(&thread.parent_id?)
+        })?;
+
+        channel.parent_id
+    }
+
+    fn member_permissions(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        member: &PartialMember,
+    ) -> Option<Permissions> {
+        let guild = self.context.cache.guild(guild_id)?;
+
+        Some(guild.partial_member_permissions(user_id, member))
+    }
+
+    async fn delete_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), serenity::Error> {
+        let result = channel_id.delete_message(&self.context, message_id).await;
+        monitor::record(&self.context, &result).await;
+
+        result
+    }
+
+    async fn direct_message(
+        &self,
+        user_id: UserId,
+        message: CreateMessage,
+    ) -> Result<(), serenity::Error> {
+        let result = user_id.direct_message(&self.context, message).await;
+        monitor::record(&self.context, &result).await;
+        result?;
+
+        Ok(())
+    }
+
+    async fn post_audit_log(&self, config: &GuildConfig, embed: CreateEmbed) {
+        moderation::post_audit_log(&self.context, config, embed).await;
+    }
+
+    async fn post_audit_report(
+        &self,
+        config: &GuildConfig,
+        embed: CreateEmbed,
+        buttons: Vec<CreateActionRow>,
+    ) {
+        let message = CreateMessage::new().embed(embed).components(buttons);
+        moderation::post_audit_message(&self.context, config, message).await;
+    }
+
+    async fn post_operator_alert(&self, embed: CreateEmbed) {
+        let monitor = self
+            .context
+            .data
+            .read()
+            .await
+            .get::<ErrorRateMonitors>()
+            .cloned();
+        let Some(channel_id) = monitor.and_then(|monitor| monitor.alert_channel_id()) else {
+            return;
+        };
+
+        let result = channel_id
+            .send_message(&self.context, CreateMessage::new().embed(embed))
+            .await;
+        monitor::record(&self.context, &result).await;
+        if let Err(e) = result {
+            tracing::error!(
+                "There was an error while attempting to post an operator alert: {:?}",
+                e
+            );
+        }
+    }
+
+    async fn post_debug_log(&self, channel_id: ChannelId, embed: CreateEmbed) {
+        let result = channel_id
+            .send_message(&self.context, CreateMessage::new().embed(embed))
+            .await;
+        monitor::record(&self.context, &result).await;
+        if let Err(e) = result {
+            tracing::error!(
+                "There was an error while attempting to post to the debug channel: {:?}",
+                e
+            );
+        }
+    }
+
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        duration_secs: u64,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        moderation::timeout(&self.context, guild_id, user_id, duration_secs, reason).await
+    }
+
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        delete_message_days: u8,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        let result = guild_id
+            .ban_with_reason(&self.context, user_id, delete_message_days, reason)
+            .await;
+        monitor::record(&self.context, &result).await;
+
+        result
+    }
+
+    async fn lock_down(&self, guild_id: GuildId, reason: &str) -> Result<(), serenity::Error> {
+        moderation::lock_down(&self.context, guild_id, reason).await
+    }
+
+    async fn thread_tags(&self, channel_id: ChannelId) -> Result<Option<ThreadTags>, BroomError> {
+        let thread_tags = shared::<ThreadTagCaches>(&*self.context.data.read().await)?;
+
+        Ok(thread_tags.get(&self.context, channel_id).await)
+    }
+
+    async fn invite_leads_to(&self, code: &str, guild_id: GuildId) -> Result<bool, BroomError> {
+        let invites = shared::<InviteCaches>(&*self.context.data.read().await)?;
+
+        Ok(invites.leads_to(&self.context, code, guild_id).await)
+    }
+
+    async fn user_language(&self, user_id: UserId) -> Result<Option<&'static str>, BroomError> {
+        let locales = shared::<UserLocaleCaches>(&*self.context.data.read().await)?;
+
+        Ok(locales.language(&self.context, user_id).await)
+    }
+
+    async fn propose_ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        escalation::check(&self.context, guild_id, user_id, config).await
+    }
+}
+
+/// Waits for the rate limit of a guild before deleting messages, messaging users or posting audit
+/// logs. Actions dropped from the full queue are not taken at all.
+pub struct RateLimitedActions {
+    inner: Arc<dyn DiscordActions>,
+    guild_id: GuildId,
+    limiter: Arc<ActionRateLimiter>,
+}
+
+/// Returned for actions that were dropped from the full queue of the rate limit.
+const DROPPED_ACTION: serenity::Error = serenity::Error::Other(DROPPED_ACTION_MESSAGE);
+const DROPPED_ACTION_MESSAGE: &str = "The action was dropped since too many actions are queued.";
+
+/// Whether the error was returned for an action that was dropped from the full queue of the rate
+/// limit rather than failing.
+pub fn is_dropped(error: &serenity::Error) -> bool {
+    matches!(error, serenity::Error::Other(message) if *message == DROPPED_ACTION_MESSAGE)
+}
+
+#[serenity::async_trait]
+impl DiscordActions for RateLimitedActions {
+    fn guild_name(&self, guild_id: GuildId) -> Option<String> {
+        self.inner.guild_name(guild_id)
+    }
+
+    fn guild_member_count(&self, guild_id: GuildId) -> Option<u64> {
+        self.inner.guild_member_count(guild_id)
+    }
+
+    fn channel_permissions(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<Permissions> {
+        self.inner.channel_permissions(guild_id, channel_id)
+    }
+
+    fn channel_nsfw(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<bool> {
+        self.inner.channel_nsfw(guild_id, channel_id)
+    }
+
+    fn channel_type(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelType> {
+        self.inner.channel_type(guild_id, channel_id)
+    }
+
+    fn channel_category(&self, guild_id: GuildId, channel_id: ChannelId) -> Option<ChannelId> {
+        self.inner.channel_category(guild_id, channel_id)
+    }
+
+    fn member_permissions(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        member: &PartialMember,
+    ) -> Option<Permissions> {
+        self.inner.member_permissions(guild_id, user_id, member)
+    }
+
+    async fn delete_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), serenity::Error> {
+        if !self.limiter.acquire(self.guild_id).await {
+            return Err(DROPPED_ACTION);
+        }
+
+        self.inner.delete_message(channel_id, message_id).await
+    }
+
+    async fn direct_message(
+        &self,
+        user_id: UserId,
+        message: CreateMessage,
+    ) -> Result<(), serenity::Error> {
+        if !self.limiter.acquire(self.guild_id).await {
+            return Err(DROPPED_ACTION);
+        }
+
+        self.inner.direct_message(user_id, message).await
+    }
+
+    async fn post_audit_log(&self, config: &GuildConfig, embed: CreateEmbed) {
+        if self.limiter.acquire(self.guild_id).await {
+            self.inner.post_audit_log(config, embed).await;
+        }
+    }
+
+    async fn post_audit_report(
+        &self,
+        config: &GuildConfig,
+        embed: CreateEmbed,
+        buttons: Vec<CreateActionRow>,
+    ) {
+        if self.limiter.acquire(self.guild_id).await {
+            self.inner.post_audit_report(config, embed, buttons).await;
+        }
+    }
+
+    async fn post_operator_alert(&self, embed: CreateEmbed) {
+        self.inner.post_operator_alert(embed).await;
+    }
+
+    // Explanations are no actions, so they do not count towards the rate limit.
+    async fn post_debug_log(&self, channel_id: ChannelId, embed: CreateEmbed) {
+        self.inner.post_debug_log(channel_id, embed).await;
+    }
+
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        duration_secs: u64,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        self.inner
+            .timeout(guild_id, user_id, duration_secs, reason)
+            .await
+    }
+
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        delete_message_days: u8,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        self.inner
+            .ban(guild_id, user_id, delete_message_days, reason)
+            .await
+    }
+
+    async fn lock_down(&self, guild_id: GuildId, reason: &str) -> Result<(), serenity::Error> {
+        self.inner.lock_down(guild_id, reason).await
+    }
+
+    async fn thread_tags(&self, channel_id: ChannelId) -> Result<Option<ThreadTags>, BroomError> {
+        self.inner.thread_tags(channel_id).await
+    }
+
+    async fn invite_leads_to(&self, code: &str, guild_id: GuildId) -> Result<bool, BroomError> {
+        self.inner.invite_leads_to(code, guild_id).await
+    }
+
+    async fn user_language(&self, user_id: UserId) -> Result<Option<&'static str>, BroomError> {
+        self.inner.user_language(user_id).await
+    }
+
+    async fn propose_ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        self.inner.propose_ban(guild_id, user_id, config).await
+    }
+}
+
+/// Prints the actions that would be taken as JSON lines to stdout instead of taking them. Nothing
+/// is known about guilds, channels, forum posts, invites or users, and no bans are ever proposed.
+pub struct PrintedActions;
+
+impl PrintedActions {
+    fn print(&self, action: serde_json::Value) {
+        println!("{}", action);
+    }
+}
+
+#[serenity::async_trait]
+impl DiscordActions for PrintedActions {
+    fn guild_name(&self, _guild_id: GuildId) -> Option<String> {
+        None
+    }
+
+    fn guild_member_count(&self, _guild_id: GuildId) -> Option<u64> {
+        None
+    }
+
+    fn channel_permissions(
+        &self,
+        _guild_id: GuildId,
+        _channel_id: ChannelId,
+    ) -> Option<Permissions> {
+        None
+    }
+
+    fn channel_nsfw(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<bool> {
+        None
+    }
+
+    fn channel_type(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<ChannelType> {
+        None
+    }
+
+    fn channel_category(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<ChannelId> {
+        None
+    }
+
+    fn member_permissions(
+        &self,
+        _guild_id: GuildId,
+        _user_id: UserId,
+        _member: &PartialMember,
+    ) -> Option<Permissions> {
+        None
+    }
+
+    async fn delete_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), serenity::Error> {
+        self.print(json!({
+            "action": "delete_message",
+            "channel_id": channel_id,
+            "message_id": message_id,
+        }));
+
+        Ok(())
+    }
+
+    async fn direct_message(
+        &self,
+        user_id: UserId,
+        message: CreateMessage,
+    ) -> Result<(), serenity::Error> {
+        self.print(json!({
+            "action": "direct_message",
+            "user_id": user_id,
+            "message": message,
+        }));
+
+        Ok(())
+    }
+
+    async fn post_audit_log(&self, config: &GuildConfig, embed: CreateEmbed) {
+        let Some(channel_id) = config.audit_channel_id else {
+            return;
+        };
+
+        self.print(json!({
+            "action": "post_audit_log",
+            "channel_id": channel_id,
+            "embed": embed,
+        }));
+    }
+
+    async fn post_audit_report(
+        &self,
+        config: &GuildConfig,
+        embed: CreateEmbed,
+        buttons: Vec<CreateActionRow>,
+    ) {
+        let Some(channel_id) = config.audit_channel_id else {
+            return;
+        };
+
+        self.print(json!({
+            "action": "post_audit_report",
+            "channel_id": channel_id,
+            "embed": embed,
+            "buttons": buttons,
+        }));
+    }
+
+    async fn post_operator_alert(&self, embed: CreateEmbed) {
+        self.print(json!({
+            "action": "post_operator_alert",
+            "embed": embed,
+        }));
+    }
+
+    async fn post_debug_log(&self, channel_id: ChannelId, embed: CreateEmbed) {
+        self.print(json!({
+            "action": "post_debug_log",
+            "channel_id": channel_id,
+            "embed": embed,
+        }));
+    }
+
+    async fn timeout(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        duration_secs: u64,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        self.print(json!({
+            "action": "timeout",
+            "guild_id": guild_id,
+            "user_id": user_id,
+            "duration_secs": duration_secs,
+            "reason": reason,
+        }));
+
+        Ok(())
+    }
+
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        delete_message_days: u8,
+        reason: &str,
+    ) -> Result<(), serenity::Error> {
+        self.print(json!({
+            "action": "ban",
+            "guild_id": guild_id,
+            "user_id": user_id,
+            "delete_message_days": delete_message_days,
+            "reason": reason,
+        }));
+
+        Ok(())
+    }
+
+    async fn lock_down(&self, guild_id: GuildId, reason: &str) -> Result<(), serenity::Error> {
+        self.print(json!({
+            "action": "lock_down",
+            "guild_id": guild_id,
+            "reason": reason,
+        }));
+
+        Ok(())
+    }
+
+    async fn thread_tags(&self, _channel_id: ChannelId) -> Result<Option<ThreadTags>, BroomError> {
+        Ok(None)
+    }
+
+    async fn invite_leads_to(&self, _code: &str, _guild_id: GuildId) -> Result<bool, BroomError> {
+        Ok(false)
+    }
+
+    async fn user_language(&self, _user_id: UserId) -> Result<Option<&'static str>, BroomError> {
+        Ok(None)
+    }
+
+    async fn propose_ban(
+        &self,
+        _guild_id: GuildId,
+        _user_id: UserId,
+        _config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        Ok(())
+    }
+}
+
+/// Records the bans and direct messages instead of taking any actions, for tests. Nothing is known
+/// about guilds, channels, forum posts or users.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordedActions {
+    pub bans: std::sync::Mutex<Vec<(GuildId, UserId)>>,
+    pub direct_messages: std::sync::Mutex<Vec<UserId>>,
+    /// The guilds invites lead to by code.
+    pub invites: std::collections::HashMap<String, GuildId>,
+}
+
+#[cfg(test)]
+#[serenity::async_trait]
+impl DiscordActions for RecordedActions {
+    fn guild_name(&self, _guild_id: GuildId) -> Option<String> {
+        None
+    }
+
+    fn guild_member_count(&self, _guild_id: GuildId) -> Option<u64> {
+        None
+    }
+
+    fn channel_permissions(
+        &self,
+        _guild_id: GuildId,
+        _channel_id: ChannelId,
+    ) -> Option<Permissions> {
+        None
+    }
+
+    fn channel_nsfw(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<bool> {
+        None
+    }
+
+    fn channel_type(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<ChannelType> {
+        None
+    }
+
+    fn channel_category(&self, _guild_id: GuildId, _channel_id: ChannelId) -> Option<ChannelId> {
+        None
+    }
+
+    fn member_permissions(
+        &self,
+        _guild_id: GuildId,
+        _user_id: UserId,
+        _member: &PartialMember,
+    ) -> Option<Permissions> {
+        None
+    }
+
+    async fn delete_message(
+        &self,
+        _channel_id: ChannelId,
+        _message_id: MessageId,
+    ) -> Result<(), serenity::Error> {
+        Ok(())
+    }
+
+    async fn direct_message(
+        &self,
+        user_id: UserId,
+        _message: CreateMessage,
+    ) -> Result<(), serenity::Error> {
+        self.direct_messages.lock().unwrap().push(user_id);
+
+        Ok(())
+    }
+
+    async fn post_audit_log(&self, _config: &GuildConfig, _embed: CreateEmbed) {}
+
+    async fn post_audit_report(
+        &self,
+        _config: &GuildConfig,
+        _embed: CreateEmbed,
+        _buttons: Vec<CreateActionRow>,
+    ) {
+    }
+
+    async fn post_operator_alert(&self, _embed: CreateEmbed) {}
+
+    async fn post_debug_log(&self, _channel_id: ChannelId, _embed: CreateEmbed) {}
+
+    async fn timeout(
+        &self,
+        _guild_id: GuildId,
+        _user_id: UserId,
+        _duration_secs: u64,
+        _reason: &str,
+    ) -> Result<(), serenity::Error> {
+        Ok(())
+    }
+
+    async fn ban(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        _delete_message_days: u8,
+        _reason: &str,
+    ) -> Result<(), serenity::Error> {
+        self.bans.lock().unwrap().push((guild_id, user_id));
+
+        Ok(())
+    }
+
+    async fn lock_down(&self, _guild_id: GuildId, _reason: &str) -> Result<(), serenity::Error> {
+        Ok(())
+    }
+
+    async fn thread_tags(&self, _channel_id: ChannelId) -> Result<Option<ThreadTags>, BroomError> {
+        Ok(None)
+    }
+
+    async fn invite_leads_to(&self, code: &str, guild_id: GuildId) -> Result<bool, BroomError> {
+        Ok(self.invites.get(code) == Some(&guild_id))
+    }
+
+    async fn user_language(&self, _user_id: UserId) -> Result<Option<&'static str>, BroomError> {
+        Ok(None)
+    }
+
+    async fn propose_ban(
+        &self,
+        _guild_id: GuildId,
+        _user_id: UserId,
+        _config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! Prefixes of messages that are legitimately posted in several channels and therefore never
+//! checked, e.g. code snippets shared in several help channels.
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, Anchored, Input, StartKind};
+
+/// Maximum number of allowed prefixes per guild.
+pub const MAX_ALLOWED_PREFIXES: usize = 50;
+
+/// The allowed prefixes of a guild compiled into a single automaton up front, since every message
+/// is matched against all of them.
+#[derive(Default)]
+pub struct PrefixAllowlist {
+    automaton: Option<AhoCorasick>,
+}
+
+impl PrefixAllowlist {
+    pub fn new(prefixes: &[String]) -> Self {
+        // Building only fails for enormous sets of prefixes, which the limit rules out.
+        let automaton = AhoCorasickBuilder::new()
+            .ascii_case_insensitive(true)
+            .start_kind(StartKind::Anchored)
+            .build(prefixes.iter().filter(|prefix| !prefix.is_empty()))
+            .ok()
+            .filter(|automaton| automaton.patterns_len() > 0);
+
+        Self { automaton }
+    }
+
+    /// Whether the given content starts with one of the prefixes, ignoring leading whitespace and
+    /// the case of ASCII letters.
+    pub fn matches(&self, content: &str) -> bool {
+        let Some(automaton) = &self.automaton else {
+            return false;
+        };
+        let input = Input::new(content.trim_start()).anchored(Anchored::Yes);
+
+        automaton.is_match(input)
+    }
+}
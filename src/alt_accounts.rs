@@ -0,0 +1,77 @@
+//! Alerts about members that might be new accounts of users the guild banned recently. Banned
+//! users commonly return with a freshly created account, but so do many legitimate users, which is
+//! why such members are only watched more closely and moderators are merely told about them.
+
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::{guild::Member, mention::Mentionable},
+};
+
+use crate::{
+    db::{unix_timestamp, SharedDatabase},
+    error::BroomError,
+    history::HistoryAction,
+    i18n, moderation,
+    state::{shared, GuildStates},
+};
+
+/// Watches the given member and alerts moderators if their account is younger than the configured
+/// age and the guild banned an account within the lookback period.
+pub async fn check(context: &Context, member: &Member) -> Result<(), BroomError> {
+    let (guilds, database) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SharedDatabase>(&data_read)?,
+        )
+    };
+    let state = guilds.get(member.guild_id).await?;
+    let config = state.config.read().await.clone();
+    let Some(threshold_days) = config.alt_account_age_threshold_days else {
+        return Ok(());
+    };
+    if member.user.bot {
+        return Ok(());
+    }
+
+    let now = unix_timestamp();
+    let age = now - member.user.id.created_at().unix_timestamp();
+    if age >= i64::from(threshold_days) * 24 * 60 * 60 {
+        return Ok(());
+    }
+    let since = now - i64::from(config.ban_lookback_days) * 24 * 60 * 60;
+    let bans = database
+        .count_guild_actions(member.guild_id, HistoryAction::BanApproved, since)
+        .await?;
+    if bans == 0 {
+        return Ok(());
+    }
+
+    let until = now + config.alt_account_watch_secs as i64;
+    state.watch(member.user.id, until).await;
+    tracing::info!(
+        "Watching member {} of guild {} as a possible alt account.",
+        member.user.id,
+        member.guild_id
+    );
+
+    let embed = CreateEmbed::new()
+        .title(i18n::translate(
+            &config.language,
+            "audit_alt_account_title",
+            &[],
+        ))
+        .description(i18n::translate(
+            &config.language,
+            "audit_alt_account",
+            &[
+                ("user", &member.user.id.mention()),
+                ("hours", &(age / (60 * 60))),
+                ("until", &format!("<t:{}:R>", until)),
+            ],
+        ));
+    moderation::post_audit_log(context, &config, embed).await;
+
+    Ok(())
+}
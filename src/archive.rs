@@ -0,0 +1,58 @@
+//! Archiving of messages before the bot deletes them, for guilds that need a record of every
+//! deleted message. Archived messages are purged once they are older than the retention of their
+//! guild.
+
+use std::{sync::Arc, time::Duration};
+
+use serenity::model::channel::Message;
+
+use crate::{
+    db::{unix_timestamp, Database},
+    error::BroomError,
+    state::Guilds,
+};
+
+/// Interval in seconds in which archived messages past their retention are purged.
+const PURGE_INTERVAL_IN_SECS: u64 = 60 * 60;
+
+/// A deleted message as it was archived.
+pub struct ArchivedMessage {
+    pub message: Message,
+    /// The name of the action the message was deleted by, e.g. `deleted`.
+    pub reason: String,
+    pub deleted_at: i64,
+}
+
+/// Purges archived messages past the retention of their guild periodically, until the process
+/// exits. Archives are purged even if the guild disabled archiving in the meantime.
+pub async fn run_purge(database: Arc<Database>, guilds: Arc<Guilds>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(PURGE_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = purge_expired(&database, &guilds).await {
+            tracing::error!(
+                "There was an error while attempting to purge archived messages: {}",
+                e
+            );
+        }
+    }
+}
+
+async fn purge_expired(database: &Database, guilds: &Guilds) -> Result<(), BroomError> {
+    for guild_id in database.archiving_guilds().await? {
+        let retention_days = guilds
+            .get(guild_id)
+            .await?
+            .config
+            .read()
+            .await
+            .archive_retention_days;
+        let before = unix_timestamp() - i64::from(retention_days) * 24 * 60 * 60;
+        let purged = database.purge_archived_messages(guild_id, before).await?;
+        if purged > 0 {
+            tracing::info!("Purged {} archived messages of guild {}.", purged, guild_id);
+        }
+    }
+
+    Ok(())
+}
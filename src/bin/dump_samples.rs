@@ -0,0 +1,84 @@
+//! Dumps the most recent detection samples to stdout for offline analysis of false positives, e.g.
+//! `cargo run --bin dump_samples -- --limit 100 --format json`.
+//!
+//! Reads the database given by `DATABASE_URL` like the bot does, but never writes to it.
+
+use std::{
+    env,
+    error::Error,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use clap::{Parser, ValueEnum};
+use serde_json::{json, Value};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Row,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// A single JSON array of all samples.
+    Json,
+    /// One JSON object per line, which suits streaming into other tools.
+    Jsonl,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Dump the most recent detection samples.")]
+struct Args {
+    /// Maximum number of samples to dump, newest first.
+    #[arg(long, default_value_t = 100)]
+    limit: u32,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://broom.db".to_string());
+    let options = SqliteConnectOptions::from_str(&database_url)?.read_only(true);
+    let pool = SqlitePoolOptions::new().connect_with(options).await?;
+
+    let rows = sqlx::query(
+        "SELECT id, guild_id, channel_id, user_id, detection, content, similarity, config, created_at
+        FROM detection_samples ORDER BY id DESC LIMIT ?",
+    )
+    .bind(args.limit)
+    .fetch_all(&pool)
+    .await?;
+    let samples = rows.iter().map(|row| {
+        // Snowflakes are printed as strings like Discord does, since they exceed the precision of
+        // numbers in JavaScript.
+        Ok::<_, serde_json::Error>(json!({
+            "id": row.get::<i64, _>("id"),
+            "guild_id": (row.get::<i64, _>("guild_id") as u64).to_string(),
+            "channel_id": (row.get::<i64, _>("channel_id") as u64).to_string(),
+            "user_id": (row.get::<i64, _>("user_id") as u64).to_string(),
+            "detection": row.get::<String, _>("detection"),
+            "content": row.get::<String, _>("content"),
+            "similarity": row.get::<Option<f64>, _>("similarity"),
+            "config": serde_json::from_str::<Value>(&row.get::<String, _>("config"))?,
+            "created_at": row.get::<i64, _>("created_at"),
+        }))
+    });
+
+    let mut stdout = io::stdout().lock();
+    match args.format {
+        Format::Json => {
+            let samples = samples.collect::<Result<Vec<_>, _>>()?;
+            serde_json::to_writer_pretty(&mut stdout, &samples)?;
+            writeln!(stdout)?;
+        }
+        Format::Jsonl => {
+            for sample in samples {
+                serde_json::to_writer(&mut stdout, &sample?)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    Ok(())
+}
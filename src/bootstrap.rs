@@ -0,0 +1,89 @@
+//! The bootstrap period after the bot joined a guild, during which messages are tracked but no
+//! action is taken on them. The cache is cold at first, while moderators commonly repost
+//! announcements and welcome messages as they set up new channels.
+
+use std::{sync::Arc, time::Duration};
+
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    http::Http,
+};
+
+use crate::{
+    config::GuildConfig,
+    db::{unix_timestamp, Database},
+    error::BroomError,
+    i18n,
+    state::Guilds,
+};
+
+/// Interval in seconds in which guilds whose bootstrap period is over are switched to live mode.
+const COMPLETION_INTERVAL_IN_SECS: u64 = 10 * 60;
+
+/// When the bot joined a guild and whether its bootstrap period is over.
+#[derive(Clone, Copy, Debug)]
+pub struct GuildJoin {
+    pub joined_at: i64,
+    pub bootstrap_completed: bool,
+}
+
+impl GuildJoin {
+    /// Whether the bot is still bootstrapping in the guild at the given time. A period that is
+    /// over is treated as such even before it was marked as completed.
+    pub fn bootstrapping(&self, config: &GuildConfig, now: i64) -> bool {
+        !self.bootstrap_completed && now < config.bootstrap_ends_at(self.joined_at)
+    }
+}
+
+/// Completes the bootstrap periods that are over periodically and tells moderators about it,
+/// until the process exits.
+pub async fn run_completion(http: Arc<Http>, database: Arc<Database>, guilds: Arc<Guilds>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(COMPLETION_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = complete_due(&http, &database, &guilds).await {
+            tracing::error!(
+                "There was an error while attempting to complete bootstrap periods: {}",
+                e
+            );
+        }
+    }
+}
+
+async fn complete_due(http: &Http, database: &Database, guilds: &Guilds) -> Result<(), BroomError> {
+    let now = unix_timestamp();
+    for (guild_id, joined_at) in database.bootstrapping_guilds().await? {
+        let config = guilds.get(guild_id).await?.config.read().await.clone();
+        if now < config.bootstrap_ends_at(joined_at) || !guilds.complete_bootstrap(guild_id).await?
+        {
+            continue;
+        }
+        tracing::info!("The bootstrap period in guild {} is over.", guild_id);
+
+        let Some(channel_id) = config.audit_channel_id else {
+            continue;
+        };
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_bootstrap_completed_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_bootstrap_completed",
+                &[("days", &config.bootstrap_days)],
+            ));
+        if let Err(e) = channel_id
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await
+        {
+            tracing::error!(
+                "There was an error while attempting to post to the audit channel: {:?}",
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
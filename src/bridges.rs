@@ -0,0 +1,92 @@
+//! Bridge bots reposting messages from linked communities, whose messages are attributed to the
+//! original authors rather than the bot, which would otherwise be flagged for posting everything
+//! everywhere.
+
+use std::collections::HashMap;
+
+use regex::{Regex, RegexBuilder};
+use serenity::model::{channel::Embed, prelude::UserId};
+
+/// Maximum number of trusted bridges per guild.
+pub const MAX_TRUSTED_BRIDGES: usize = 10;
+/// Pattern extracting the original author from messages formatted like `**Author**: content`,
+/// which is used if no pattern is given.
+pub const DEFAULT_AUTHOR_PATTERN: &str = r"(?s)^\*\*(?P<author>[^*\n]+)\*\*:\s*(?P<content>.*)$";
+/// Maximum size in bytes of a compiled pattern, which keeps pathological patterns from exhausting
+/// memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// A message of a bridge bot attributed to its original author.
+pub struct BridgedMessage {
+    pub author: String,
+    pub content: String,
+}
+
+/// Compiles the given pattern, or returns `None` if it is invalid or has no group named `author`.
+pub fn compile(pattern: &str) -> Option<Regex> {
+    let regex = RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .ok()?;
+
+    regex
+        .capture_names()
+        .any(|name| name == Some("author"))
+        .then_some(regex)
+}
+
+/// The trusted bridges of a guild with their patterns compiled up front.
+#[derive(Default)]
+pub struct BridgeList {
+    bridges: HashMap<UserId, Regex>,
+}
+
+impl BridgeList {
+    pub fn new(patterns: &HashMap<UserId, String>) -> Self {
+        // Patterns are validated when added, so this only skips patterns that became invalid due
+        // to a change of the regex syntax.
+        let bridges = patterns
+            .iter()
+            .filter_map(|(user_id, pattern)| Some((*user_id, compile(pattern)?)))
+            .collect();
+
+        Self { bridges }
+    }
+
+    /// Attributes the given content of a message posted by a bridge to its original author. The
+    /// pattern of the bridge is matched against the content, and the `content` group replaces it
+    /// if the pattern has one. Otherwise, the author and description of the first embed are used.
+    /// Returns `None` if the author could not be determined either way.
+    pub fn attribute(
+        &self,
+        bridge_id: UserId,
+        content: &str,
+        embeds: &[Embed],
+    ) -> Option<BridgedMessage> {
+        let pattern = self.bridges.get(&bridge_id)?;
+        if let Some(captures) = pattern.captures(content) {
+            let author = captures.name("author")?.as_str().trim();
+            if !author.is_empty() {
+                return Some(BridgedMessage {
+                    author: author.to_string(),
+                    content: captures
+                        .name("content")
+                        .map_or(content, |content| content.as_str())
+                        .to_string(),
+                });
+            }
+        }
+
+        let embed = embeds.first()?;
+        let author = embed.author.as_ref()?.name.trim();
+        let description = embed.description.as_deref().unwrap_or_default();
+        if author.is_empty() || description.is_empty() {
+            return None;
+        }
+
+        Some(BridgedMessage {
+            author: author.to_string(),
+            content: description.to_string(),
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use serenity::{
+    builder::{CreateCommandOption, CreateInteractionResponseMessage},
+    model::application::CommandOptionType,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    error::BroomError,
+    maintenance::{self, MaintenanceModes},
+    monitor::ErrorRateMonitors,
+    sampling::Samplers,
+    state::shared,
+};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "admin",
+        "Inspect the state of the bot.",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "status",
+        "Show the error rate of requests to Discord.",
+    ))
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "maintenance",
+            "Track messages without taking any actions, e.g. during a deployment. Owner only.",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "state",
+                "Whether to enable or disable maintenance mode.",
+            )
+            .required(true)
+            .add_string_choice("on", "on")
+            .add_string_choice("off", "off"),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "sampling_rate",
+            "Change the share of detections stored for analyzing false positives. Owner only.",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Number,
+                "rate",
+                "The share of detections to store, between 0 and 1.",
+            )
+            .required(true)
+            .min_number_value(0.0)
+            .max_number_value(1.0),
+        ),
+    )
+}
+
+pub async fn status(invocation: &Invocation<'_>) -> CommandResult {
+    let monitor = shared::<ErrorRateMonitors>(&*invocation.context.data.read().await)?;
+    let rate = monitor.error_rate();
+
+    let mut content = invocation.translate(
+        "admin_status_error_rate",
+        &[
+            ("rate", &format!("{:.1}", rate.percent())),
+            ("errors", &format!("{:.0}", rate.errors)),
+            ("total", &format!("{:.0}", rate.total)),
+        ],
+    );
+    if let Some((error, at)) = &rate.last_error {
+        content.push('\n');
+        content.push_str(&invocation.translate(
+            "admin_status_last_error",
+            &[
+                ("time", &format!("<t:{}:R>", at.timestamp())),
+                ("error", error),
+            ],
+        ));
+    }
+
+    Ok(CreateInteractionResponseMessage::new().content(content))
+}
+
+/// Enables or disables maintenance mode for every guild, which is why only the owner of the bot
+/// may do so rather than the administrators of the guild it is invoked in.
+pub async fn maintenance(invocation: &Invocation<'_>) -> CommandResult {
+    if !is_owner(invocation).await? {
+        return invocation.reply("admin_owner_only", &[]);
+    }
+
+    let active = invocation.string_option("state") == Some("on");
+    let mode = shared::<MaintenanceModes>(&*invocation.context.data.read().await)?;
+    if !mode.set(active) {
+        return match active {
+            true => invocation.reply("admin_maintenance_already_enabled", &[]),
+            false => invocation.reply("admin_maintenance_already_disabled", &[]),
+        };
+    }
+
+    tracing::warn!(
+        "Maintenance mode was {} by {}.",
+        if active { "enabled" } else { "disabled" },
+        invocation.command.user.id
+    );
+    // Posting to every audit channel takes longer than an interaction may go unanswered.
+    tokio::spawn(maintenance::announce(invocation.context.clone(), active));
+
+    match active {
+        true => invocation.reply("admin_maintenance_enabled", &[]),
+        false => invocation.reply("admin_maintenance_disabled", &[]),
+    }
+}
+
+/// Changes the share of detections that are sampled in every guild, which is why only the owner
+/// of the bot may do so.
+pub async fn sampling_rate(invocation: &Invocation<'_>) -> CommandResult {
+    if !is_owner(invocation).await? {
+        return invocation.reply("admin_owner_only", &[]);
+    }
+
+    let Some(rate) = invocation.number_option("rate") else {
+        return invocation.reply("command_unexpected_error", &[]);
+    };
+    let sampler = shared::<Samplers>(&*invocation.context.data.read().await)?;
+    if !sampler.set_rate(rate) {
+        return invocation.reply("admin_sampling_rate_invalid", &[("rate", &rate)]);
+    }
+
+    tracing::info!(
+        "The sampling rate was set to {} by {}.",
+        rate,
+        invocation.command.user.id
+    );
+    invocation.reply("admin_sampling_rate_set", &[("rate", &rate)])
+}
+
+/// Whether the invoking user owns the application or is a member of the team owning it.
+pub(super) async fn is_owner(invocation: &Invocation<'_>) -> Result<bool, BroomError> {
+    let user_id = invocation.command.user.id;
+    let info = invocation
+        .context
+        .http
+        .get_current_application_info()
+        .await?;
+    let is_team_member = info
+        .team
+        .is_some_and(|team| team.members.iter().any(|member| member.user.id == user_id));
+
+    Ok(info.owner.is_some_and(|owner| owner.id == user_id) || is_team_member)
+}
@@ -0,0 +1,131 @@
+use serenity::{
+    builder::{
+        CreateCommandOption, CreateEmbed, CreateEmbedAuthor, CreateInteractionResponseMessage,
+    },
+    model::{application::CommandOptionType, mention::Mentionable, prelude::MessageId},
+};
+
+use super::{CommandResult, Invocation};
+use crate::{content::truncate, db::SharedDatabase, state::shared};
+
+/// Maximum length in characters of the description of an embed.
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+/// Maximum length in characters of the value of a field of an embed.
+const MAX_FIELD_LENGTH: usize = 1024;
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "archive",
+        "Review messages archived before the bot deleted them.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "view",
+            "Show an archived message.",
+        )
+        .add_sub_option(
+            // Message IDs exceed the range of integer options.
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "message_id",
+                "The ID of the deleted message.",
+            )
+            .required(true),
+        ),
+    )
+}
+
+pub async fn view(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(message_id) = invocation
+        .string_option("message_id")
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|id| *id != 0)
+        .map(MessageId::new)
+    else {
+        return invocation.reply("archive_invalid_message_id", &[]);
+    };
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    let Some(archived) = database
+        .archived_message(invocation.guild_id, message_id)
+        .await?
+    else {
+        return invocation.reply("archive_not_found", &[("id", &message_id)]);
+    };
+
+    let msg = &archived.message;
+    let author = CreateEmbedAuthor::new(format!("{} ({})", msg.author.name, msg.author.id))
+        .icon_url(msg.author.face());
+    let content = if msg.content.is_empty() {
+        invocation.translate("archive_no_content", &[])
+    } else {
+        truncate(&msg.content, MAX_DESCRIPTION_LENGTH)
+    };
+    let reason = invocation.translate(&format!("history_{}", archived.reason), &[]);
+    let mut embed = CreateEmbed::new()
+        .title(invocation.translate("archive_title", &[]))
+        .author(author)
+        .description(content)
+        .field(
+            invocation.translate("archive_channel", &[]),
+            msg.channel_id.mention().to_string(),
+            true,
+        )
+        .field(invocation.translate("archive_reason", &[]), reason, true)
+        .field(
+            invocation.translate("archive_posted_at", &[]),
+            format!("<t:{}:f>", msg.timestamp.unix_timestamp()),
+            true,
+        )
+        .field(
+            invocation.translate("archive_deleted_at", &[]),
+            format!("<t:{}:f>", archived.deleted_at),
+            true,
+        );
+    if !msg.attachments.is_empty() {
+        let urls = msg
+            .attachments
+            .iter()
+            .map(|attachment| attachment.url.as_str())
+            .collect::<Vec<_>>();
+        embed = embed.field(
+            invocation.translate("archive_attachments", &[]),
+            truncate(&urls.join("\n"), MAX_FIELD_LENGTH),
+            false,
+        );
+    }
+    if !msg.embeds.is_empty() {
+        // Embeds without a title or URL, e.g. of images, are still counted.
+        let embeds = msg
+            .embeds
+            .iter()
+            .map(|embed| {
+                embed
+                    .title
+                    .as_deref()
+                    .or(embed.url.as_deref())
+                    .unwrap_or("-")
+            })
+            .collect::<Vec<_>>();
+        embed = embed.field(
+            invocation.translate("archive_embeds", &[]),
+            truncate(&embeds.join("\n"), MAX_FIELD_LENGTH),
+            false,
+        );
+    }
+    if !msg.reactions.is_empty() {
+        let reactions = msg
+            .reactions
+            .iter()
+            .map(|reaction| format!("{} {}", reaction.reaction_type, reaction.count))
+            .collect::<Vec<_>>();
+        embed = embed.field(
+            invocation.translate("archive_reactions", &[]),
+            truncate(&reactions.join("  "), MAX_FIELD_LENGTH),
+            false,
+        );
+    }
+
+    Ok(CreateInteractionResponseMessage::new().embed(embed))
+}
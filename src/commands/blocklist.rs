@@ -0,0 +1,42 @@
+use serenity::{builder::CreateCommandOption, model::application::CommandOptionType};
+
+use super::{admin::is_owner, CommandResult, Invocation};
+use crate::{blocklist::DomainBlocklists, state::shared};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "blocklist",
+        "Manage the blocklist of malicious domains.",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "reload",
+        "Read the blocklist of malicious domains from its file again. Owner only.",
+    ))
+}
+
+/// Reads the blocklist shared by every guild from its file again, which is why only the owner of
+/// the bot may do so.
+pub async fn reload(invocation: &Invocation<'_>) -> CommandResult {
+    if !is_owner(invocation).await? {
+        return invocation.reply("admin_owner_only", &[]);
+    }
+
+    let blocklist = shared::<DomainBlocklists>(&*invocation.context.data.read().await)?;
+    match blocklist.reload() {
+        Ok(Some(count)) => {
+            tracing::info!(
+                "The blocklist was reloaded with {} domains by {}.",
+                count,
+                invocation.command.user.id
+            );
+            invocation.reply("blocklist_reloaded", &[("count", &count)])
+        }
+        Ok(None) => invocation.reply("blocklist_not_configured", &[]),
+        Err(e) => {
+            tracing::error!("There was an error while reloading the blocklist: {}", e);
+            invocation.reply("blocklist_reload_failed", &[])
+        }
+    }
+}
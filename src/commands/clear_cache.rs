@@ -0,0 +1,36 @@
+use serenity::{
+    builder::CreateCommandOption,
+    model::{application::CommandOptionType, mention::Mentionable},
+};
+
+use super::{CommandResult, Invocation};
+use crate::state::{shared, GuildStates};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "clearcache",
+        "Stop tracking the messages posted so far, e.g. after a false positive.",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::User,
+        "user",
+        "Only stop tracking the messages of this user.",
+    ))
+}
+
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let user_id = invocation.user_option("user").map(|user| user.id);
+
+    let guilds = shared::<GuildStates>(&*invocation.context.data.read().await)?;
+    let state = guilds.get(invocation.guild_id).await?;
+    let removed = state.clear_cache(user_id).await;
+
+    match user_id {
+        Some(user_id) => invocation.reply(
+            "clearcache_user",
+            &[("count", &removed), ("user", &user_id.mention())],
+        ),
+        None => invocation.reply("clearcache_guild", &[("count", &removed)]),
+    }
+}
@@ -0,0 +1,1111 @@
+use chrono::Utc;
+use serde_json::Value;
+use serenity::{
+    builder::{
+        CreateAutocompleteResponse, CreateCommandOption, CreateEmbed,
+        CreateInteractionResponseMessage,
+    },
+    model::{application::CommandOptionType, channel::ChannelType, mention::Mentionable},
+    utils::MessageBuilder,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    allowlist::MAX_ALLOWED_PREFIXES,
+    bridges::{self, DEFAULT_AUTHOR_PATTERN, MAX_TRUSTED_BRIDGES},
+    config::{DuplicateAction, GuildConfig, SettingError, ThresholdProfile},
+    i18n,
+    schedule::{self, WeeklyRange},
+    templates::{self, MAX_TEMPLATES},
+};
+
+/// Maximum number of suggestions Discord accepts in response to an autocomplete interaction, which
+/// is also the maximum number of choices of an option.
+const MAX_SUGGESTIONS: usize = 25;
+
+pub fn register() -> CreateCommandOption {
+    // There are more settings than an option may have choices, so they are suggested instead.
+    let setting = CreateCommandOption::new(
+        CommandOptionType::String,
+        "setting",
+        "The setting to change.",
+    )
+    .required(true)
+    .set_autocomplete(true);
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "config",
+        "Configure the bot for this server.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Change a setting.")
+            .add_sub_option(setting)
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "value",
+                    "The new value of the setting.",
+                )
+                .required(true),
+            ),
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "diff",
+        "Show the settings that differ from their defaults.",
+    ))
+    .add_sub_option(weekly_range_options(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "safe_hours",
+        "Manage the weekly time ranges during which duplicates are allowed.",
+    )))
+    .add_sub_option(weekly_range_options(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "quiet_hours",
+        "Manage the weekly time ranges during which duplicates are only reported until they end.",
+    )))
+    .add_sub_option(profile_options(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "profile",
+            "Manage the named threshold profiles that roles can be mapped to.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "name",
+            "The name of the profile.",
+        )),
+    ))
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "role_profile",
+            "Manage which threshold profile applies to members with a role.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Role,
+            "role",
+            "The role to map.",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "profile",
+            "The name of the profile the role is mapped to.",
+        )),
+    )
+    .add_sub_option(profile_options(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "channel_override",
+            "Manage the detection settings applying to a channel.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The channel to override the settings of.",
+            )
+            .channel_types(vec![ChannelType::Text, ChannelType::News, ChannelType::Stage]),
+        ),
+    ))
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "forum_exempt_tag",
+            "Manage the tags exempting forum posts from duplicate detection.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Channel, "forum", "The forum channel.")
+                .channel_types(vec![ChannelType::Forum]),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "tag",
+            "The name of the tag.",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "trusted_bridges",
+            "Manage the bots reposting messages from linked communities on behalf of others.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::User,
+            "bot",
+            "The bridge bot.",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "pattern",
+            "A regex with an author group and optionally a content group, defaults to **Author**: content.",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "prefix_allow",
+            "Manage the prefixes of messages that are never checked, e.g. code fences.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "prefix",
+            "The prefix, matched regardless of case.",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "honeypot",
+            "Manage the hidden channels whose posters are banned right away.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The honeypot channel.",
+            )
+            .channel_types(vec![ChannelType::Text, ChannelType::News, ChannelType::Stage]),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "exempt_category",
+            "Manage the categories whose channels are exempt from detection.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "category",
+                "The exempt category.",
+            )
+            .channel_types(vec![ChannelType::Category]),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "template",
+            "Manage the template messages users copy and post, e.g. bug report templates.",
+        )
+        .add_sub_option(action_option())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "message",
+            "A link to the template message.",
+        )),
+    )
+}
+
+/// Adds the options of all settings a threshold profile consists of, each of which is optional.
+fn profile_options(subcommand: CreateCommandOption) -> CreateCommandOption {
+    subcommand
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "min_length",
+                "The minimum length of messages to be tracked, measured as set by length_mode.",
+            )
+            .min_int_value(0),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "window_secs",
+                "The time in seconds within which reposts are considered duplicates.",
+            )
+            .min_int_value(1),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "occurrence_threshold",
+                "How often a message has to be posted to be considered a duplicate.",
+            )
+            .min_int_value(2),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "duplicate_action",
+                "What happens to duplicates.",
+            )
+            .add_string_choice("delete", "delete")
+            .add_string_choice("report", "report"),
+        )
+}
+
+/// Builds a threshold profile from the options added by [`profile_options`].
+fn profile_from_options(invocation: &Invocation<'_>) -> ThresholdProfile {
+    ThresholdProfile {
+        min_message_length: unsigned_option(invocation, "min_length"),
+        window_secs: unsigned_option(invocation, "window_secs"),
+        occurrence_threshold: unsigned_option(invocation, "occurrence_threshold"),
+        action: invocation
+            .string_option("duplicate_action")
+            .and_then(DuplicateAction::parse),
+    }
+}
+
+fn unsigned_option<T: TryFrom<i64>>(invocation: &Invocation<'_>, name: &str) -> Option<T> {
+    invocation
+        .integer_option(name)
+        .and_then(|value| value.try_into().ok())
+}
+
+/// Describes all settings of a threshold profile, including those falling back to the next layer.
+fn describe_profile(invocation: &Invocation<'_>, profile: &ThresholdProfile) -> String {
+    let default = invocation.translate("profile_default", &[]);
+    let describe = |value: Option<String>| value.unwrap_or_else(|| default.clone());
+
+    invocation.translate(
+        "profile_settings",
+        &[
+            (
+                "min_length",
+                &describe(profile.min_message_length.map(|value| value.to_string())),
+            ),
+            (
+                "window",
+                &describe(profile.window_secs.map(|value| value.to_string())),
+            ),
+            (
+                "threshold",
+                &describe(profile.occurrence_threshold.map(|value| value.to_string())),
+            ),
+            (
+                "action",
+                &describe(profile.action.map(|value| value.to_string())),
+            ),
+        ],
+    )
+}
+
+/// Settings holding a list are managed by a single subcommand with the action as its first option.
+/// Adds the options of a command managing weekly time ranges.
+fn weekly_range_options(command: CreateCommandOption) -> CreateCommandOption {
+    command
+        .add_sub_option(action_option())
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "weekday",
+                "The weekday the time range starts on.",
+            )
+            .add_string_choice("Monday", "Mon")
+            .add_string_choice("Tuesday", "Tue")
+            .add_string_choice("Wednesday", "Wed")
+            .add_string_choice("Thursday", "Thu")
+            .add_string_choice("Friday", "Fri")
+            .add_string_choice("Saturday", "Sat")
+            .add_string_choice("Sunday", "Sun"),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "range",
+            "The time range in the format HH:MM-HH:MM in the timezone of the server.",
+        ))
+}
+
+fn action_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "action",
+        "What to do with the list.",
+    )
+    .required(true)
+    .add_string_choice("add", "add")
+    .add_string_choice("remove", "remove")
+    .add_string_choice("list", "list")
+}
+
+/// Suggests the settings containing what has been typed so far.
+pub fn suggest_settings(typed: &str) -> CreateAutocompleteResponse {
+    let typed = typed.trim().to_ascii_lowercase();
+    GuildConfig::SETTINGS
+        .iter()
+        .filter(|setting| setting.contains(typed.as_str()))
+        .take(MAX_SUGGESTIONS)
+        .fold(CreateAutocompleteResponse::new(), |response, setting| {
+            response.add_string_choice(*setting, *setting)
+        })
+}
+
+pub async fn set(invocation: &Invocation<'_>) -> CommandResult {
+    let (Some(setting), Some(value)) = (
+        invocation.string_option("setting"),
+        invocation.string_option("value"),
+    ) else {
+        return invocation.reply("command_unexpected_error", &[]);
+    };
+
+    let result = invocation
+        .update_config(|config| config.set(setting, value))
+        .await?;
+
+    match result {
+        // Confirms in the new language so that the effect is immediately visible.
+        Ok(()) if setting == "language" => Ok(CreateInteractionResponseMessage::new().content(
+            i18n::translate(
+                value,
+                "config_updated",
+                &[("setting", &setting), ("value", &value)],
+            ),
+        )),
+        Ok(()) => invocation.reply(
+            "config_updated",
+            &[("setting", &setting), ("value", &value)],
+        ),
+        Err(SettingError::UnknownSetting) => {
+            invocation.reply("config_unknown_setting", &[("setting", &setting)])
+        }
+        Err(SettingError::InvalidValue) => match setting {
+            "language" => {
+                let languages = i18n::languages().collect::<Vec<_>>().join(", ");
+                invocation.reply(
+                    "config_unsupported_language",
+                    &[("language", &value), ("languages", &languages)],
+                )
+            }
+            "timezone" => invocation.reply("config_invalid_timezone", &[("timezone", &value)]),
+            _ => invocation.reply(
+                "config_invalid_value",
+                &[("setting", &setting), ("value", &value)],
+            ),
+        },
+    }
+}
+
+/// Lists the settings whose current values differ from the defaults. Collections that are empty
+/// by default are summarized by the number of their items, which may be far too many to list.
+pub async fn diff(invocation: &Invocation<'_>) -> CommandResult {
+    let (Ok(Value::Object(current)), Ok(Value::Object(defaults))) = (
+        serde_json::to_value(invocation.config().await?),
+        serde_json::to_value(GuildConfig::default()),
+    ) else {
+        return invocation.reply("command_unexpected_error", &[]);
+    };
+
+    let mut lines = Vec::new();
+    for (setting, value) in &current {
+        let default = defaults.get(setting).unwrap_or(&Value::Null);
+        if value == default {
+            continue;
+        }
+
+        let line = match (value, default) {
+            (Value::Array(items), Value::Array(defaults)) if defaults.is_empty() => invocation
+                .translate(
+                    "config_diff_count",
+                    &[("setting", setting), ("count", &items.len())],
+                ),
+            (Value::Object(items), Value::Object(defaults)) if defaults.is_empty() => invocation
+                .translate(
+                    "config_diff_count",
+                    &[("setting", setting), ("count", &items.len())],
+                ),
+            _ => invocation.translate(
+                "config_diff_entry",
+                &[
+                    ("setting", setting),
+                    ("value", &display_value(invocation, value)),
+                    ("default", &display_value(invocation, default)),
+                ],
+            ),
+        };
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        return invocation.reply("config_diff_none", &[]);
+    }
+
+    let embed = CreateEmbed::new()
+        .title(invocation.translate("config_diff_title", &[]))
+        .description(lines.join("\n"));
+
+    Ok(CreateInteractionResponseMessage::new().embed(embed))
+}
+
+/// Renders a setting the way it is given to `/broom config set`, i.e. strings without quotes.
+fn display_value(invocation: &Invocation<'_>, value: &Value) -> String {
+    match value {
+        Value::Null => invocation.translate("config_diff_unset", &[]),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+pub async fn safe_hours(invocation: &Invocation<'_>) -> CommandResult {
+    weekly_ranges(invocation, "safe_hours", |config| &mut config.safe_hours).await
+}
+
+pub async fn quiet_hours(invocation: &Invocation<'_>) -> CommandResult {
+    weekly_ranges(invocation, "quiet_hours", |config| &mut config.quiet_hours).await
+}
+
+/// Lists, adds or removes one of the lists of weekly time ranges, replying with the messages whose
+/// keys start with the given name.
+async fn weekly_ranges(
+    invocation: &Invocation<'_>,
+    name: &str,
+    ranges: fn(&mut GuildConfig) -> &mut Vec<WeeklyRange>,
+) -> CommandResult {
+    let key = |suffix: &str| format!("{}_{}", name, suffix);
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let mut config = invocation.config().await?;
+        let timezone = config.timezone();
+        let ranges = ranges(&mut config);
+        if ranges.is_empty() {
+            return invocation.reply(&key("empty"), &[]);
+        }
+
+        let now = Utc::now();
+        let mut content = MessageBuilder::new();
+        for range in ranges.iter() {
+            let (start, end, weekday) = range;
+            let next = schedule::next_occurrence(range, timezone, now);
+            content.push_line(invocation.translate(
+                &key("entry"),
+                &[
+                    ("weekday", weekday),
+                    ("start", &start.format("%H:%M")),
+                    ("end", &end.format("%H:%M")),
+                    ("next", &format!("<t:{}:F>", next.timestamp())),
+                ],
+            ));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let (Some(weekday), Some(range)) = (
+        invocation.string_option("weekday"),
+        invocation.string_option("range"),
+    ) else {
+        return invocation.reply(&key("missing_range"), &[]);
+    };
+    let (Some(weekday), Some((start, end))) = (
+        schedule::parse_weekday(weekday),
+        schedule::parse_range(range),
+    ) else {
+        return invocation.reply(&key("invalid_range"), &[("range", &range)]);
+    };
+    let entry = (start, end, weekday);
+
+    match action {
+        Some("add") => {
+            invocation
+                .update_config(|config| {
+                    let ranges = ranges(config);
+                    if !ranges.contains(&entry) {
+                        ranges.push(entry);
+                    }
+                })
+                .await?;
+            invocation.reply(&key("added"), &[("weekday", &weekday), ("range", &range)])
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| {
+                    let ranges = ranges(config);
+                    let count = ranges.len();
+                    ranges.retain(|existing| *existing != entry);
+                    ranges.len() < count
+                })
+                .await?;
+
+            if removed {
+                invocation.reply(&key("removed"), &[("weekday", &weekday), ("range", &range)])
+            } else {
+                invocation.reply(
+                    &key("not_found"),
+                    &[("weekday", &weekday), ("range", &range)],
+                )
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn profile(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.threshold_profiles.is_empty() {
+            return invocation.reply("profile_empty", &[]);
+        }
+
+        let mut profiles = config.threshold_profiles.iter().collect::<Vec<_>>();
+        profiles.sort_by_key(|(name, _)| *name);
+        let mut content = MessageBuilder::new();
+        for (name, profile) in profiles {
+            content.push_line(invocation.translate(
+                "profile_entry",
+                &[
+                    ("name", name),
+                    ("settings", &describe_profile(invocation, profile)),
+                ],
+            ));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(name) = invocation.string_option("name").map(str::trim) else {
+        return invocation.reply("profile_missing_name", &[]);
+    };
+
+    match action {
+        Some("add") => {
+            let profile = profile_from_options(invocation);
+            invocation
+                .update_config(|config| config.threshold_profiles.insert(name.to_string(), profile))
+                .await?;
+            invocation.reply("profile_added", &[("name", &name)])
+        }
+        Some("remove") => {
+            // Roles mapped to the profile would silently fall back to the defaults otherwise.
+            let removed = invocation
+                .update_config(|config| {
+                    config.role_profiles.retain(|_, profile| profile != name);
+                    config.threshold_profiles.remove(name).is_some()
+                })
+                .await?;
+
+            if removed {
+                invocation.reply("profile_removed", &[("name", &name)])
+            } else {
+                invocation.reply("profile_not_found", &[("name", &name)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn role_profile(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.role_profiles.is_empty() {
+            return invocation.reply("role_profile_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for (role_id, profile) in &config.role_profiles {
+            content.push_line(invocation.translate(
+                "role_profile_entry",
+                &[("role", &role_id.mention()), ("profile", profile)],
+            ));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(role) = invocation.role_option("role") else {
+        return invocation.reply("role_profile_missing_role", &[]);
+    };
+    let role_mention = role.id.mention();
+
+    match action {
+        Some("add") => {
+            let Some(profile) = invocation.string_option("profile").map(str::trim) else {
+                return invocation.reply("profile_missing_name", &[]);
+            };
+            let added = invocation
+                .update_config(|config| {
+                    let exists = config.threshold_profiles.contains_key(profile);
+                    if exists {
+                        config.role_profiles.insert(role.id, profile.to_string());
+                    }
+                    exists
+                })
+                .await?;
+
+            if added {
+                invocation.reply(
+                    "role_profile_added",
+                    &[("role", &role_mention), ("profile", &profile)],
+                )
+            } else {
+                invocation.reply("profile_not_found", &[("name", &profile)])
+            }
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.role_profiles.remove(&role.id).is_some())
+                .await?;
+
+            if removed {
+                invocation.reply("role_profile_removed", &[("role", &role_mention)])
+            } else {
+                invocation.reply("role_profile_not_found", &[("role", &role_mention)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn channel_override(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.channel_overrides.is_empty() {
+            return invocation.reply("channel_override_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for (channel_id, profile) in &config.channel_overrides {
+            content.push_line(invocation.translate(
+                "channel_override_entry",
+                &[
+                    ("channel", &channel_id.mention()),
+                    ("settings", &describe_profile(invocation, profile)),
+                ],
+            ));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(channel) = invocation.channel_option("channel") else {
+        return invocation.reply("channel_override_missing_channel", &[]);
+    };
+    let channel_mention = channel.id.mention();
+
+    match action {
+        Some("add") => {
+            let profile = profile_from_options(invocation);
+            invocation
+                .update_config(|config| config.channel_overrides.insert(channel.id, profile))
+                .await?;
+            invocation.reply("channel_override_added", &[("channel", &channel_mention)])
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.channel_overrides.remove(&channel.id).is_some())
+                .await?;
+
+            if removed {
+                invocation.reply("channel_override_removed", &[("channel", &channel_mention)])
+            } else {
+                invocation.reply(
+                    "channel_override_not_found",
+                    &[("channel", &channel_mention)],
+                )
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn forum_exempt_tag(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.forum_exempt_tags.is_empty() {
+            return invocation.reply("forum_exempt_tag_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for (forum_id, tags) in &config.forum_exempt_tags {
+            content.push_line(invocation.translate(
+                "forum_exempt_tag_entry",
+                &[("forum", &forum_id.mention()), ("tags", &tags.join(", "))],
+            ));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let (Some(forum), Some(tag)) = (
+        invocation.channel_option("forum"),
+        invocation.string_option("tag").map(str::trim),
+    ) else {
+        return invocation.reply("forum_exempt_tag_missing_tag", &[]);
+    };
+    let forum_mention = forum.id.mention();
+
+    match action {
+        Some("add") => {
+            // Forums are usually cached, in which case typos in the tag name can be caught.
+            let available_tags = invocation
+                .context
+                .cache
+                .guild(invocation.guild_id)
+                .and_then(|guild| guild.channels.get(&forum.id).cloned())
+                .map(|forum| forum.available_tags);
+            if let Some(available_tags) = available_tags {
+                if !available_tags
+                    .iter()
+                    .any(|available| available.name.eq_ignore_ascii_case(tag))
+                {
+                    let names = available_tags
+                        .iter()
+                        .map(|available| available.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    return invocation.reply(
+                        "forum_exempt_tag_unknown",
+                        &[("tag", &tag), ("forum", &forum_mention), ("tags", &names)],
+                    );
+                }
+            }
+
+            invocation
+                .update_config(|config| {
+                    let tags = config.forum_exempt_tags.entry(forum.id).or_default();
+                    if !tags
+                        .iter()
+                        .any(|existing| existing.eq_ignore_ascii_case(tag))
+                    {
+                        tags.push(tag.to_string());
+                    }
+                })
+                .await?;
+            invocation.reply(
+                "forum_exempt_tag_added",
+                &[("tag", &tag), ("forum", &forum_mention)],
+            )
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| {
+                    let Some(tags) = config.forum_exempt_tags.get_mut(&forum.id) else {
+                        return false;
+                    };
+                    let count = tags.len();
+                    tags.retain(|existing| !existing.eq_ignore_ascii_case(tag));
+                    let removed = tags.len() < count;
+                    if tags.is_empty() {
+                        config.forum_exempt_tags.remove(&forum.id);
+                    }
+                    removed
+                })
+                .await?;
+
+            if removed {
+                invocation.reply(
+                    "forum_exempt_tag_removed",
+                    &[("tag", &tag), ("forum", &forum_mention)],
+                )
+            } else {
+                invocation.reply(
+                    "forum_exempt_tag_not_found",
+                    &[("tag", &tag), ("forum", &forum_mention)],
+                )
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn trusted_bridges(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.trusted_bridges.is_empty() {
+            return invocation.reply("trusted_bridge_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for (bot_id, pattern) in &config.trusted_bridges {
+            content
+                .push(format!("{}: ", bot_id.mention()))
+                .push_mono_line_safe(pattern);
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(bot) = invocation.user_option("bot") else {
+        return invocation.reply("trusted_bridge_missing_bot", &[]);
+    };
+    let bot_mention = bot.id.mention();
+
+    match action {
+        Some("add") => {
+            let pattern = invocation
+                .string_option("pattern")
+                .unwrap_or(DEFAULT_AUTHOR_PATTERN);
+            if bridges::compile(pattern).is_none() {
+                return invocation.reply(
+                    "trusted_bridge_invalid_pattern",
+                    &[("pattern", &format!("`{}`", pattern))],
+                );
+            }
+
+            let added = invocation
+                .update_config(|config| {
+                    if !config.trusted_bridges.contains_key(&bot.id)
+                        && config.trusted_bridges.len() >= MAX_TRUSTED_BRIDGES
+                    {
+                        return false;
+                    }
+                    config.trusted_bridges.insert(bot.id, pattern.to_string());
+                    true
+                })
+                .await?;
+
+            if added {
+                invocation.reply(
+                    "trusted_bridge_added",
+                    &[
+                        ("bot", &bot_mention),
+                        ("pattern", &format!("`{}`", pattern)),
+                    ],
+                )
+            } else {
+                invocation.reply(
+                    "trusted_bridge_limit_reached",
+                    &[("limit", &MAX_TRUSTED_BRIDGES)],
+                )
+            }
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.trusted_bridges.remove(&bot.id).is_some())
+                .await?;
+
+            if removed {
+                invocation.reply("trusted_bridge_removed", &[("bot", &bot_mention)])
+            } else {
+                invocation.reply("trusted_bridge_not_found", &[("bot", &bot_mention)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn prefix_allow(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.content_prefix_allowlist.is_empty() {
+            return invocation.reply("prefix_allow_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for prefix in &config.content_prefix_allowlist {
+            content.push_line_safe(prefix);
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(prefix) = invocation
+        .string_option("prefix")
+        .map(str::trim)
+        .filter(|prefix| !prefix.is_empty())
+    else {
+        return invocation.reply("prefix_allow_missing_prefix", &[]);
+    };
+    // Escaped rather than quoted, since the default prefix consists of backticks.
+    let quoted = MessageBuilder::new().push_safe(prefix).build();
+
+    match action {
+        Some("add") => {
+            let added = invocation
+                .update_config(|config| {
+                    let prefixes = &mut config.content_prefix_allowlist;
+                    if prefixes
+                        .iter()
+                        .any(|existing| existing.eq_ignore_ascii_case(prefix))
+                    {
+                        return true;
+                    }
+                    if prefixes.len() >= MAX_ALLOWED_PREFIXES {
+                        return false;
+                    }
+                    prefixes.push(prefix.to_string());
+                    true
+                })
+                .await?;
+
+            if added {
+                invocation.reply("prefix_allow_added", &[("prefix", &quoted)])
+            } else {
+                invocation.reply(
+                    "prefix_allow_limit_reached",
+                    &[("limit", &MAX_ALLOWED_PREFIXES)],
+                )
+            }
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| {
+                    let prefixes = &mut config.content_prefix_allowlist;
+                    let count = prefixes.len();
+                    prefixes.retain(|existing| !existing.eq_ignore_ascii_case(prefix));
+                    prefixes.len() < count
+                })
+                .await?;
+
+            if removed {
+                invocation.reply("prefix_allow_removed", &[("prefix", &quoted)])
+            } else {
+                invocation.reply("prefix_allow_not_found", &[("prefix", &quoted)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn exempt_category(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.exempt_categories.is_empty() {
+            return invocation.reply("exempt_category_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for category_id in &config.exempt_categories {
+            content.mention(category_id).push_line("");
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(category) = invocation.channel_option("category") else {
+        return invocation.reply("exempt_category_missing_category", &[]);
+    };
+    let category_mention = category.id.mention();
+
+    match action {
+        Some("add") => {
+            invocation
+                .update_config(|config| config.exempt_categories.insert(category.id))
+                .await?;
+            invocation.reply("exempt_category_added", &[("category", &category_mention)])
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.exempt_categories.remove(&category.id))
+                .await?;
+
+            if removed {
+                invocation.reply(
+                    "exempt_category_removed",
+                    &[("category", &category_mention)],
+                )
+            } else {
+                invocation.reply(
+                    "exempt_category_not_found",
+                    &[("category", &category_mention)],
+                )
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn honeypot(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.honeypot_channels.is_empty() {
+            return invocation.reply("honeypot_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for channel_id in &config.honeypot_channels {
+            content.mention(channel_id).push_line("");
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some(channel) = invocation.channel_option("channel") else {
+        return invocation.reply("honeypot_missing_channel", &[]);
+    };
+    let channel_mention = channel.id.mention();
+
+    match action {
+        Some("add") => {
+            invocation
+                .update_config(|config| config.honeypot_channels.insert(channel.id))
+                .await?;
+            invocation.reply("honeypot_added", &[("channel", &channel_mention)])
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.honeypot_channels.remove(&channel.id))
+                .await?;
+
+            if removed {
+                invocation.reply("honeypot_removed", &[("channel", &channel_mention)])
+            } else {
+                invocation.reply("honeypot_not_found", &[("channel", &channel_mention)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+pub async fn template(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.template_message_ids.is_empty() {
+            return invocation.reply("template_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for (message_id, channel_id) in &config.template_message_ids {
+            content.push_line(message_id.link(*channel_id, Some(invocation.guild_id)));
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let Some((channel_id, message_id)) = invocation.string_option("message").and_then(|value| {
+        templates::parse_message(&invocation.context.cache, invocation.guild_id, value)
+    }) else {
+        return invocation.reply("template_invalid_message", &[]);
+    };
+    let link = message_id.link(channel_id, Some(invocation.guild_id));
+
+    let key = match action {
+        Some("add") => {
+            let added = invocation
+                .update_config(|config| {
+                    let templates = &mut config.template_message_ids;
+                    if !templates.contains_key(&message_id) && templates.len() >= MAX_TEMPLATES {
+                        return false;
+                    }
+                    templates.insert(message_id, channel_id);
+                    true
+                })
+                .await?;
+            if !added {
+                return invocation.reply("template_limit_reached", &[("limit", &MAX_TEMPLATES)]);
+            }
+            "template_added"
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| config.template_message_ids.remove(&message_id))
+                .await?;
+            if removed.is_none() {
+                return invocation.reply("template_not_found", &[("message", &link)]);
+            }
+            "template_removed"
+        }
+        _ => return invocation.reply("command_unexpected_error", &[]),
+    };
+
+    let state = invocation.guilds().await?.get(invocation.guild_id).await?;
+    templates::refresh(&invocation.context.http, invocation.guild_id, &state).await;
+    invocation.reply(key, &[("message", &link)])
+}
@@ -0,0 +1,261 @@
+use serenity::{
+    builder::{CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage},
+    model::{application::CommandOptionType, mention::Mentionable, prelude::MessageId},
+    utils::MessageBuilder,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    actions::MessageContext,
+    custom_commands::{
+        self, CustomAction, CustomCommand, MAX_CUSTOM_COMMANDS, MAX_DESCRIPTION_LENGTH,
+    },
+    db::SharedDatabase,
+    state::shared,
+    templates,
+};
+
+pub fn register() -> CreateCommandOption {
+    let action = CustomAction::ALL.into_iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "action",
+            "What the command does.",
+        )
+        .required(true),
+        |option, action| option.add_string_choice(action.as_str(), action.as_str()),
+    );
+    let name = || {
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "name",
+            "The name of the command, in lowercase.",
+        )
+        .required(true)
+    };
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "custom_cmd",
+        "Manage the commands all members of the server can use.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "add",
+            "Add a command for all members, or change an existing one.",
+        )
+        .add_sub_option(name())
+        .add_sub_option(action)
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "description",
+                "What members see in the command picker.",
+            )
+            .max_length(MAX_DESCRIPTION_LENGTH as u16),
+        ),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a command.")
+            .add_sub_option(name()),
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "list",
+        "List the commands for all members.",
+    ))
+}
+
+pub async fn add(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(name) = invocation
+        .string_option("name")
+        .map(|name| name.trim().to_string())
+        .filter(|name| custom_commands::is_valid_name(name))
+    else {
+        return invocation.reply("custom_cmd_invalid_name", &[]);
+    };
+    let Some(action) = invocation
+        .string_option("action")
+        .and_then(CustomAction::parse)
+    else {
+        return invocation.reply("command_unexpected_error", &[]);
+    };
+    let command = CustomCommand {
+        action,
+        description: invocation
+            .string_option("description")
+            .unwrap_or_default()
+            .to_string(),
+    };
+
+    let commands = invocation
+        .update_config(|config| {
+            let commands = &mut config.custom_commands;
+            if !commands.contains_key(&name) && commands.len() >= MAX_CUSTOM_COMMANDS {
+                return None;
+            }
+            commands.insert(name.clone(), command);
+            Some(commands.clone())
+        })
+        .await?;
+    let Some(commands) = commands else {
+        return invocation.reply(
+            "custom_cmd_limit_reached",
+            &[("limit", &MAX_CUSTOM_COMMANDS)],
+        );
+    };
+
+    if let Err(e) =
+        custom_commands::sync(&invocation.context.http, invocation.guild_id, &commands).await
+    {
+        tracing::error!(
+            "There was an error while registering the custom commands of a guild: {:?}",
+            e
+        );
+        return invocation.reply("custom_cmd_sync_failed", &[]);
+    }
+    invocation.reply("custom_cmd_added", &[("name", &name), ("action", &action)])
+}
+
+pub async fn remove(invocation: &Invocation<'_>) -> CommandResult {
+    let name = invocation.string_option("name").unwrap_or_default().trim();
+    let (removed, commands) = invocation
+        .update_config(|config| {
+            let removed = config.custom_commands.remove(name);
+            (removed, config.custom_commands.clone())
+        })
+        .await?;
+    if removed.is_none() {
+        return invocation.reply("custom_cmd_not_found", &[("name", &name)]);
+    }
+
+    if let Err(e) =
+        custom_commands::sync(&invocation.context.http, invocation.guild_id, &commands).await
+    {
+        tracing::error!(
+            "There was an error while registering the custom commands of a guild: {:?}",
+            e
+        );
+        return invocation.reply("custom_cmd_sync_failed", &[]);
+    }
+    invocation.reply("custom_cmd_removed", &[("name", &name)])
+}
+
+pub async fn list(invocation: &Invocation<'_>) -> CommandResult {
+    let config = invocation.config().await?;
+    if config.custom_commands.is_empty() {
+        return invocation.reply("custom_cmd_empty", &[]);
+    }
+
+    let mut commands = config.custom_commands.iter().collect::<Vec<_>>();
+    commands.sort_by_key(|(name, _)| name.as_str());
+    let mut content = MessageBuilder::new();
+    for (name, command) in commands {
+        content.push_line(format!("`/{}`: `{}`", name, command.action));
+    }
+
+    Ok(CreateInteractionResponseMessage::new().content(content.build()))
+}
+
+/// Runs the custom command of the guild the invocation is named after, on behalf of any member.
+pub async fn invoke(invocation: &Invocation<'_>) -> CommandResult {
+    let name = invocation.command.data.name.as_str();
+    let config = invocation.config().await?;
+    let Some(command) = config.custom_commands.get(name) else {
+        return invocation.reply("custom_cmd_unknown", &[]);
+    };
+    tracing::info!(
+        "User {} invoked the custom command /{} ({}) in guild {}.",
+        invocation.command.user.id,
+        name,
+        command.action,
+        invocation.guild_id
+    );
+
+    match command.action {
+        CustomAction::ReportToAuditChannel => {
+            let Some((channel_id, message_id)) =
+                invocation.string_option("message").and_then(|value| {
+                    templates::parse_message(&invocation.context.cache, invocation.guild_id, value)
+                })
+            else {
+                return invocation.reply("custom_cmd_report_invalid_message", &[]);
+            };
+            if config.audit_channel_id.is_none() {
+                return invocation.reply("custom_cmd_report_unavailable", &[]);
+            }
+
+            let mut embed = CreateEmbed::new()
+                .title(invocation.translate("custom_cmd_report_title", &[]))
+                .description(invocation.translate(
+                    "custom_cmd_report",
+                    &[
+                        ("reporter", &invocation.command.user.id.mention()),
+                        ("channel", &channel_id.mention()),
+                        (
+                            "link",
+                            &message_id.link(channel_id, Some(invocation.guild_id)),
+                        ),
+                    ],
+                ));
+            if let Some(reason) = invocation
+                .string_option("reason")
+                .filter(|reason| !reason.trim().is_empty())
+            {
+                embed = embed.field(
+                    invocation.translate("custom_cmd_report_reason", &[]),
+                    MessageBuilder::new().push_safe(reason).build(),
+                    false,
+                );
+            }
+            // Members ask moderators to have a look, so the report is posted even in shadow mode.
+            MessageContext::new(invocation.context)
+                .actions
+                .post_audit_log(&config, embed)
+                .await;
+
+            invocation.reply("custom_cmd_reported", &[])
+        }
+        CustomAction::CheckMessageId => {
+            let Some(message_id) = invocation
+                .string_option("message_id")
+                .and_then(|value| value.trim().parse().ok())
+                .filter(|id| *id != 0)
+                .map(MessageId::new)
+            else {
+                return invocation.reply("archive_invalid_message_id", &[]);
+            };
+            let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+            // Members may only learn about their own messages.
+            let archived = database
+                .archived_message(invocation.guild_id, message_id)
+                .await?
+                .filter(|archived| archived.message.author.id == invocation.command.user.id);
+            let Some(archived) = archived else {
+                return invocation.reply("custom_cmd_check_not_found", &[("id", &message_id)]);
+            };
+
+            let reason = invocation.translate(&format!("history_{}", archived.reason), &[]);
+            invocation.reply(
+                "custom_cmd_check_deleted",
+                &[
+                    ("id", &message_id),
+                    ("reason", &reason),
+                    ("time", &format!("<t:{}:R>", archived.deleted_at)),
+                ],
+            )
+        }
+        CustomAction::SelfOptoutDms => {
+            let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+            let user_id = invocation.command.user.id;
+            let opted_out = !database.dm_opted_out(user_id).await?;
+            database.set_dm_opt_out(user_id, opted_out).await?;
+
+            match opted_out {
+                true => invocation.reply("custom_cmd_dms_disabled", &[]),
+                false => invocation.reply("custom_cmd_dms_enabled", &[]),
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use serenity::{
+    builder::CreateCommandOption,
+    model::{application::CommandOptionType, mention::Mentionable},
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    db::{unix_timestamp, SharedDatabase},
+    history::{HistoryAction, HistoryEntry},
+    state::shared,
+};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "escalation",
+        "Manage the bans users have been warned about.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "cancel",
+            "Cancel the pending ban of a user.",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "The user to cancel the ban of.",
+            )
+            .required(true),
+        ),
+    )
+}
+
+pub async fn cancel(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(user) = invocation.user_option("user") else {
+        return invocation.reply("escalation_missing_user", &[]);
+    };
+
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    if !database
+        .cancel_pending_escalation(invocation.guild_id, user.id)
+        .await?
+    {
+        return invocation.reply("escalation_not_pending", &[("user", &user.id.mention())]);
+    }
+
+    let mut entry = HistoryEntry::new(user.id, HistoryAction::BanCancelled, unix_timestamp());
+    entry.moderator_id = Some(invocation.command.user.id);
+    database.record_action(invocation.guild_id, &entry).await?;
+
+    invocation.reply("escalation_cancelled", &[("user", &user.id.mention())])
+}
@@ -0,0 +1,125 @@
+use serenity::{
+    builder::{CreateCommandOption, CreateInteractionResponseMessage},
+    model::{application::CommandOptionType, prelude::GuildId},
+    utils::MessageBuilder,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    error::BroomError,
+    ignore::{IgnorePattern, PatternKind, MAX_IGNORE_PATTERNS},
+    state::Guilds,
+};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "ignore",
+        "Manage the patterns of messages that are never checked for duplicates.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "action",
+            "What to do with the list.",
+        )
+        .required(true)
+        .add_string_choice("add", "add")
+        .add_string_choice("remove", "remove")
+        .add_string_choice("list", "list"),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "kind",
+            "How messages are matched against the pattern.",
+        )
+        .add_string_choice("exact", "exact")
+        .add_string_choice("prefix", "prefix")
+        .add_string_choice("regex", "regex"),
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::String,
+        "pattern",
+        "The pattern, matched case-insensitively with all whitespace collapsed.",
+    ))
+}
+
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let action = invocation.string_option("action");
+    if action == Some("list") {
+        let config = invocation.config().await?;
+        if config.ignore_patterns.is_empty() {
+            return invocation.reply("ignore_empty", &[]);
+        }
+
+        let mut content = MessageBuilder::new();
+        for IgnorePattern { kind, pattern } in &config.ignore_patterns {
+            content.push(format!("{}: ", kind)).push_line_safe(pattern);
+        }
+
+        return Ok(CreateInteractionResponseMessage::new().content(content.build()));
+    }
+
+    let (Some(kind), Some(pattern)) = (
+        invocation
+            .string_option("kind")
+            .and_then(PatternKind::parse),
+        invocation.string_option("pattern"),
+    ) else {
+        return invocation.reply("ignore_missing_pattern", &[]);
+    };
+    let Some(entry) = IgnorePattern::new(kind, pattern) else {
+        return invocation.reply("ignore_invalid_regex", &[("pattern", &pattern)]);
+    };
+
+    match action {
+        Some("add") => {
+            let guilds = invocation.guilds().await?;
+            let added = add_pattern(&guilds, invocation.guild_id, entry).await?;
+
+            if added {
+                invocation.reply("ignore_added", &[("pattern", &pattern)])
+            } else {
+                invocation.reply("ignore_limit_reached", &[("limit", &MAX_IGNORE_PATTERNS)])
+            }
+        }
+        Some("remove") => {
+            let removed = invocation
+                .update_config(|config| {
+                    let count = config.ignore_patterns.len();
+                    config.ignore_patterns.retain(|existing| *existing != entry);
+                    config.ignore_patterns.len() < count
+                })
+                .await?;
+
+            if removed {
+                invocation.reply("ignore_removed", &[("pattern", &pattern)])
+            } else {
+                invocation.reply("ignore_not_found", &[("pattern", &pattern)])
+            }
+        }
+        _ => invocation.reply("command_unexpected_error", &[]),
+    }
+}
+
+/// Adds a validated pattern to the ignore list of the given guild. Returns `false` if the list is
+/// full, while patterns that are on it already count as added.
+pub(super) async fn add_pattern(
+    guilds: &Guilds,
+    guild_id: GuildId,
+    entry: IgnorePattern,
+) -> Result<bool, BroomError> {
+    guilds
+        .update_config(guild_id, |config| {
+            if config.ignore_patterns.contains(&entry) {
+                return true;
+            }
+            if config.ignore_patterns.len() >= MAX_IGNORE_PATTERNS {
+                return false;
+            }
+            config.ignore_patterns.push(entry);
+            true
+        })
+        .await
+}
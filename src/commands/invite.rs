@@ -0,0 +1,23 @@
+use serenity::{builder::CreateCommandOption, model::application::CommandOptionType};
+
+use super::{CommandResult, Invocation};
+use crate::permissions::REQUIRED_PERMISSIONS;
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "invite",
+        "Get a link to add the bot to another server.",
+    )
+}
+
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let url = format!(
+        "https://discord.com/oauth2/authorize?client_id={}&scope=bot%20applications.commands&permissions={}",
+        invocation.command.application_id,
+        REQUIRED_PERMISSIONS.bits()
+    );
+    let link = format!("[{}]({})", invocation.translate("invite_link", &[]), url);
+
+    invocation.reply("invite", &[("link", &link)])
+}
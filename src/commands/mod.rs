@@ -0,0 +1,419 @@
+//! The `/broom` slash command and its subcommands.
+
+use std::{fmt::Display, sync::Arc};
+
+use serenity::{
+    builder::{
+        CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage,
+        EditInteractionResponse,
+    },
+    client::Context,
+    model::{
+        application::{CommandInteraction, ResolvedOption, ResolvedValue},
+        channel::PartialChannel,
+        guild::Role,
+        permissions::Permissions,
+        prelude::GuildId,
+        user::User,
+    },
+    utils::MessageBuilder,
+};
+
+pub use self::reset_guild::{
+    handle_component as handle_reset_guild, CUSTOM_ID_PREFIX as RESET_GUILD_CUSTOM_ID_PREFIX,
+};
+#[cfg(feature = "ml_suggestions")]
+pub use self::suggest_filters::{
+    handle_component as handle_suggest_filters,
+    CUSTOM_ID_PREFIX as SUGGEST_FILTERS_CUSTOM_ID_PREFIX,
+};
+use crate::{
+    config::GuildConfig,
+    error::BroomError,
+    i18n,
+    state::{shared, GuildStates, Guilds},
+};
+
+mod admin;
+mod archive;
+mod blocklist;
+mod clear_cache;
+mod config;
+mod custom_cmd;
+mod escalation;
+mod ignore;
+mod invite;
+mod preferences;
+mod probation;
+mod reset_guild;
+mod stats;
+mod strikes;
+#[cfg(feature = "ml_suggestions")]
+mod suggest_filters;
+mod test_dm;
+
+type CommandResult = Result<CreateInteractionResponseMessage, BroomError>;
+/// The result of a subcommand whose response was deferred, see [`defer`].
+type DeferredResult = Result<EditInteractionResponse, BroomError>;
+
+/// Everything a subcommand needs to know about its invocation.
+struct Invocation<'a> {
+    context: &'a Context,
+    command: &'a CommandInteraction,
+    options: &'a [ResolvedOption<'a>],
+    guild_id: GuildId,
+    language: String,
+}
+
+impl Invocation<'_> {
+    /// Translates a message into the language of the guild the command was invoked in.
+    fn translate(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        i18n::translate(&self.language, key, args)
+    }
+
+    fn reply(&self, key: &str, args: &[(&str, &dyn Display)]) -> CommandResult {
+        Ok(CreateInteractionResponseMessage::new().content(self.translate(key, args)))
+    }
+
+    /// Subcommands have different requirements, so the permissions of the invoking member are
+    /// checked individually for each of them instead of restricting the whole command.
+    fn has_permission(&self, permission: Permissions) -> bool {
+        self.command
+            .member
+            .as_ref()
+            .and_then(|member| member.permissions)
+            .is_some_and(|permissions| {
+                permissions.administrator() || permissions.contains(permission)
+            })
+    }
+
+    fn user_option(&self, name: &str) -> Option<&User> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::User(user, _) if option.name == name => Some(user),
+            _ => None,
+        })
+    }
+
+    fn role_option(&self, name: &str) -> Option<&Role> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::Role(role) if option.name == name => Some(role),
+            _ => None,
+        })
+    }
+
+    fn channel_option(&self, name: &str) -> Option<&PartialChannel> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) if option.name == name => Some(channel),
+            _ => None,
+        })
+    }
+
+    async fn guilds(&self) -> Result<Arc<Guilds>, BroomError> {
+        shared::<GuildStates>(&*self.context.data.read().await)
+    }
+
+    /// Returns a snapshot of the configuration of the guild the command was invoked in.
+    async fn config(&self) -> Result<GuildConfig, BroomError> {
+        let state = self.guilds().await?.get(self.guild_id).await?;
+        let config = state.config.read().await.clone();
+
+        Ok(config)
+    }
+
+    async fn update_config<T>(
+        &self,
+        update: impl FnOnce(&mut GuildConfig) -> T,
+    ) -> Result<T, BroomError> {
+        self.guilds()
+            .await?
+            .update_config(self.guild_id, update)
+            .await
+    }
+
+    fn string_option(&self, name: &str) -> Option<&str> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::String(value) if option.name == name => Some(value),
+            _ => None,
+        })
+    }
+
+    fn integer_option(&self, name: &str) -> Option<i64> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(value) if option.name == name => Some(value),
+            _ => None,
+        })
+    }
+
+    fn number_option(&self, name: &str) -> Option<f64> {
+        self.options.iter().find_map(|option| match option.value {
+            ResolvedValue::Number(value) if option.name == name => Some(value),
+            _ => None,
+        })
+    }
+}
+
+pub fn register() -> CreateCommand {
+    let command = CreateCommand::new("broom")
+        .description("Manage the duplicate message detection.")
+        .dm_permission(false)
+        .add_option(stats::register())
+        .add_option(clear_cache::register())
+        .add_option(test_dm::register())
+        .add_option(config::register())
+        .add_option(ignore::register())
+        .add_option(admin::register())
+        .add_option(reset_guild::register())
+        .add_option(invite::register())
+        .add_option(preferences::register())
+        .add_option(archive::register())
+        .add_option(strikes::register())
+        .add_option(probation::register())
+        .add_option(escalation::register())
+        .add_option(blocklist::register())
+        .add_option(custom_cmd::register());
+    #[cfg(feature = "ml_suggestions")]
+    let command = command.add_option(suggest_filters::register());
+
+    command
+}
+
+pub async fn handle(context: &Context, command: &CommandInteraction) {
+    let Some(guild_id) = command.guild_id else {
+        return;
+    };
+
+    // Subcommands are either invoked directly or as part of a group, e.g. `/broom config set`.
+    let options = command.data.options();
+    let Some(ResolvedOption { name, value, .. }) = options.first() else {
+        return;
+    };
+    let (group, name, options) = match value {
+        ResolvedValue::SubCommand(options) => (None, *name, options),
+        ResolvedValue::SubCommandGroup(options) => match options.first() {
+            Some(ResolvedOption {
+                name: subcommand,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) => (Some(*name), *subcommand, options),
+            _ => return,
+        },
+        _ => return,
+    };
+
+    let invocation = Invocation {
+        context,
+        command,
+        options,
+        guild_id,
+        language: language_or_default(context, guild_id).await,
+    };
+
+    let result = match required_permission(group, name) {
+        Some(permission) if !invocation.has_permission(permission) => {
+            invocation.reply("command_missing_permission", &[("permission", &permission)])
+        }
+        _ => match (group, name) {
+            (Some("stats"), "show") => stats::show(&invocation).await,
+            (Some("stats"), "export_raw") => {
+                if defer(&invocation).await {
+                    let result = stats::export_raw(&invocation).await;
+                    edit_response(&invocation, result).await;
+                }
+                return;
+            }
+            (None, "clearcache") => clear_cache::run(&invocation).await,
+            (None, "test_dm") => test_dm::run(&invocation).await,
+            (None, "ignore") => ignore::run(&invocation).await,
+            (None, "reset_guild") => reset_guild::run(&invocation).await,
+            (None, "invite") => invite::run(&invocation).await,
+            #[cfg(feature = "ml_suggestions")]
+            (None, "suggest_filters") => suggest_filters::run(&invocation).await,
+            (Some("admin"), "status") => admin::status(&invocation).await,
+            (Some("admin"), "maintenance") => admin::maintenance(&invocation).await,
+            (Some("admin"), "sampling_rate") => admin::sampling_rate(&invocation).await,
+            (Some("archive"), "view") => archive::view(&invocation).await,
+            (Some("strikes"), "list") => strikes::list(&invocation).await,
+            (Some("probation"), "lift") => probation::lift(&invocation).await,
+            (Some("escalation"), "cancel") => escalation::cancel(&invocation).await,
+            (Some("blocklist"), "reload") => blocklist::reload(&invocation).await,
+            (Some("custom_cmd"), "add") => custom_cmd::add(&invocation).await,
+            (Some("custom_cmd"), "remove") => custom_cmd::remove(&invocation).await,
+            (Some("custom_cmd"), "list") => custom_cmd::list(&invocation).await,
+            (Some("preferences"), "dm_format") => preferences::dm_format(&invocation).await,
+            (Some("config"), "set") => config::set(&invocation).await,
+            (Some("config"), "diff") => config::diff(&invocation).await,
+            (Some("config"), "safe_hours") => config::safe_hours(&invocation).await,
+            (Some("config"), "quiet_hours") => config::quiet_hours(&invocation).await,
+            (Some("config"), "profile") => config::profile(&invocation).await,
+            (Some("config"), "role_profile") => config::role_profile(&invocation).await,
+            (Some("config"), "channel_override") => config::channel_override(&invocation).await,
+            (Some("config"), "forum_exempt_tag") => config::forum_exempt_tag(&invocation).await,
+            (Some("config"), "trusted_bridges") => config::trusted_bridges(&invocation).await,
+            (Some("config"), "prefix_allow") => config::prefix_allow(&invocation).await,
+            (Some("config"), "honeypot") => config::honeypot(&invocation).await,
+            (Some("config"), "exempt_category") => config::exempt_category(&invocation).await,
+            (Some("config"), "template") => config::template(&invocation).await,
+            _ => return,
+        },
+    };
+
+    respond(&invocation, result).await;
+}
+
+/// Runs a custom command a guild defined for its members, which is open to all of them.
+pub async fn handle_custom(context: &Context, command: &CommandInteraction) {
+    let Some(guild_id) = command.guild_id else {
+        return;
+    };
+
+    let options = command.data.options();
+    let invocation = Invocation {
+        context,
+        command,
+        options: &options,
+        guild_id,
+        language: language_or_default(context, guild_id).await,
+    };
+    let result = custom_cmd::invoke(&invocation).await;
+    respond(&invocation, result).await;
+}
+
+/// Replies to the invocation with the result of the subcommand, ephemerally.
+async fn respond(invocation: &Invocation<'_>, result: CommandResult) {
+    let message = result.unwrap_or_else(|e| {
+        CreateInteractionResponseMessage::new().content(describe_error(invocation, e))
+    });
+
+    let response = CreateInteractionResponse::Message(message.ephemeral(true));
+    if let Err(e) = invocation
+        .command
+        .create_response(&invocation.context.http, response)
+        .await
+    {
+        tracing::error!(
+            "There was an error while attempting to respond to a command: {:?}",
+            e
+        );
+    }
+}
+
+/// Acknowledges the invocation ephemerally, for subcommands that may take longer than Discord
+/// waits for a response. Returns whether the invocation could be acknowledged, after which the
+/// response has to be given by [`edit_response`].
+async fn defer(invocation: &Invocation<'_>) -> bool {
+    let response =
+        CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new().ephemeral(true));
+    if let Err(e) = invocation
+        .command
+        .create_response(&invocation.context.http, response)
+        .await
+    {
+        tracing::error!(
+            "There was an error while attempting to defer the response to a command: {:?}",
+            e
+        );
+        return false;
+    }
+
+    true
+}
+
+/// Replaces the deferred response to the invocation with the result of the subcommand.
+async fn edit_response(invocation: &Invocation<'_>, result: DeferredResult) {
+    let response = result
+        .unwrap_or_else(|e| EditInteractionResponse::new().content(describe_error(invocation, e)));
+    if let Err(e) = invocation
+        .command
+        .edit_response(&invocation.context.http, response)
+        .await
+    {
+        tracing::error!(
+            "There was an error while attempting to respond to a command: {:?}",
+            e
+        );
+    }
+}
+
+/// Describes the error a subcommand failed with to the invoking member. Errors other than
+/// violated limits are logged rather than shown.
+fn describe_error(invocation: &Invocation<'_>, error: BroomError) -> String {
+    match error {
+        BroomError::InvalidGuildConfig(error) => {
+            let mut content = MessageBuilder::new();
+            content.push_line(invocation.translate("config_limits_violated", &[]));
+            for violation in &error.violations {
+                content.push_line(invocation.translate(
+                    "config_limit_violation",
+                    &[
+                        ("setting", &violation.setting),
+                        ("value", &violation.value),
+                        ("minimum", &violation.minimum),
+                    ],
+                ));
+            }
+            content.build()
+        }
+        e => {
+            tracing::error!("There was an error while executing a command: {}", e);
+            invocation.translate("command_unexpected_error", &[])
+        }
+    }
+}
+
+/// Returns the language of the given guild, or the default one if it cannot be looked up.
+async fn language_or_default(context: &Context, guild_id: GuildId) -> String {
+    match guild_language(context, guild_id).await {
+        Ok(language) => language,
+        Err(e) => {
+            tracing::error!(
+                "There was an error while looking up the language of a guild: {}",
+                e
+            );
+            i18n::DEFAULT_LANGUAGE.to_string()
+        }
+    }
+}
+
+/// Returns the permission a member needs to invoke the given subcommand of the given group, if
+/// any. Most groups require the same permission for all of their subcommands.
+fn required_permission(group: Option<&str>, subcommand: &str) -> Option<Permissions> {
+    match (group, subcommand) {
+        (None, "clearcache" | "test_dm") => Some(Permissions::MANAGE_MESSAGES),
+        (Some("config" | "custom_cmd"), _) | (None, "ignore") | (None, "suggest_filters") => {
+            Some(Permissions::MANAGE_GUILD)
+        }
+        (Some("strikes" | "probation" | "escalation"), _) => Some(Permissions::BAN_MEMBERS),
+        (Some("admin" | "archive" | "blocklist"), _)
+        | (None, "reset_guild")
+        | (Some("stats"), "export_raw") => Some(Permissions::ADMINISTRATOR),
+        _ => None,
+    }
+}
+
+/// Suggests values for the option of a subcommand that is being typed.
+pub async fn autocomplete(context: &Context, command: &CommandInteraction) {
+    let Some(option) = command.data.autocomplete() else {
+        return;
+    };
+    let response = match option.name {
+        "setting" => config::suggest_settings(option.value),
+        _ => return,
+    };
+
+    let response = CreateInteractionResponse::Autocomplete(response);
+    if let Err(e) = command.create_response(&context.http, response).await {
+        tracing::error!(
+            "There was an error while attempting to suggest values for an option: {:?}",
+            e
+        );
+    }
+}
+
+async fn guild_language(context: &Context, guild_id: GuildId) -> Result<String, BroomError> {
+    let state = shared::<GuildStates>(&*context.data.read().await)?
+        .get(guild_id)
+        .await?;
+    let language = state.config.read().await.language.clone();
+
+    Ok(language)
+}
@@ -0,0 +1,42 @@
+use serenity::{builder::CreateCommandOption, model::application::CommandOptionType};
+
+use super::{CommandResult, Invocation};
+use crate::{db::SharedDatabase, preferences::DmFormat, state::shared};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "preferences",
+        "Change how the bot treats you in every server.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "dm_format",
+            "Choose how the messages the bot sends you are formatted.",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "format",
+                "Plain text suits screen readers better than embeds.",
+            )
+            .required(true)
+            .add_string_choice("plain", "plain")
+            .add_string_choice("embed", "embed"),
+        ),
+    )
+}
+
+pub async fn dm_format(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(format) = invocation.string_option("format").and_then(DmFormat::parse) else {
+        return invocation.reply("preferences_dm_format_invalid", &[]);
+    };
+
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    database
+        .set_dm_format(invocation.command.user.id, format)
+        .await?;
+
+    invocation.reply("preferences_dm_format_set", &[("format", &format)])
+}
@@ -0,0 +1,54 @@
+use serenity::{
+    builder::CreateCommandOption,
+    model::{application::CommandOptionType, mention::Mentionable},
+};
+
+use super::{CommandResult, Invocation};
+use crate::{db::SharedDatabase, probation, state::shared};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "probation",
+        "Manage users on probation, who are held to stricter settings.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "lift",
+            "End the probation of a user.",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "The user to end the probation of.",
+            )
+            .required(true),
+        ),
+    )
+}
+
+pub async fn lift(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(user) = invocation.user_option("user") else {
+        return invocation.reply("probation_missing_user", &[]);
+    };
+
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    let state = invocation.guilds().await?.get(invocation.guild_id).await?;
+    let lifted = probation::lift(
+        &database,
+        &state,
+        invocation.guild_id,
+        user.id,
+        invocation.command.user.id,
+    )
+    .await?;
+
+    let key = if lifted {
+        "probation_lifted"
+    } else {
+        "probation_not_on_probation"
+    };
+    invocation.reply(key, &[("user", &user.id.mention())])
+}
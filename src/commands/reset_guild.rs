@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateCommandOption, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage,
+    },
+    client::Context,
+    model::{
+        application::{ButtonStyle, CommandOptionType, ComponentInteraction},
+        mention::Mentionable,
+        permissions::Permissions,
+    },
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    counters::SharedCounters,
+    custom_commands,
+    error::BroomError,
+    i18n, monitor,
+    state::{shared, GuildStates},
+};
+
+/// Prefix of the custom IDs of the buttons confirming or cancelling a reset.
+pub const CUSTOM_ID_PREFIX: &str = "broom:reset_guild:";
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "reset_guild",
+        "Permanently delete all data of this server, including its configuration.",
+    )
+}
+
+/// Asks for a confirmation before anything is deleted.
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let embed = CreateEmbed::new()
+        .title(invocation.translate("reset_guild_confirm_title", &[]))
+        .description(invocation.translate("reset_guild_confirm", &[]));
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}confirm", CUSTOM_ID_PREFIX))
+            .label(invocation.translate("reset_guild_confirm_button", &[]))
+            .style(ButtonStyle::Danger),
+        CreateButton::new(format!("{}cancel", CUSTOM_ID_PREFIX))
+            .label(invocation.translate("reset_guild_cancel_button", &[]))
+            .style(ButtonStyle::Secondary),
+    ]);
+
+    Ok(CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(vec![buttons]))
+}
+
+/// Handles a click on one of the buttons of a confirmation.
+pub async fn handle_component(context: &Context, component: &ComponentInteraction) {
+    if let Err(e) = handle_confirmation(context, component).await {
+        tracing::error!(
+            "There was an error while handling the confirmation of a reset: {}",
+            e
+        );
+    }
+}
+
+async fn handle_confirmation(
+    context: &Context,
+    component: &ComponentInteraction,
+) -> Result<(), BroomError> {
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    let (guilds, counters) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SharedCounters>(&data_read)?,
+        )
+    };
+    // The configuration is reset as well, so everything is reported using the previous one.
+    let config = guilds.get(guild_id).await?.config.read().await.clone();
+    let language = &config.language;
+    let update = |content: String| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .embeds(Vec::new())
+                .components(Vec::new()),
+        )
+    };
+
+    if !component.data.custom_id.ends_with(":confirm") {
+        let content = i18n::translate(language, "reset_guild_cancelled", &[]);
+        component.create_response(context, update(content)).await?;
+        return Ok(());
+    }
+    if !component
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.administrator())
+    {
+        let content = i18n::translate(
+            language,
+            "command_missing_permission",
+            &[("permission", &Permissions::ADMINISTRATOR)],
+        );
+        component.create_response(context, update(content)).await?;
+        return Ok(());
+    }
+
+    let deleted = guilds.reset(guild_id).await?;
+    counters.forget(guild_id).await;
+    if !config.custom_commands.is_empty() {
+        if let Err(e) = custom_commands::sync(&context.http, guild_id, &HashMap::new()).await {
+            tracing::error!(
+                "There was an error while removing the custom commands of a guild: {:?}",
+                e
+            );
+        }
+    }
+    tracing::info!("Deleted all data of guild {} on request.", guild_id);
+
+    let mut description = i18n::translate(
+        language,
+        "reset_guild_done",
+        &[("moderator", &component.user.id.mention())],
+    );
+    for (table, count) in deleted {
+        description.push_str(&format!("\n`{}`: {}", table, count));
+    }
+    let content = i18n::translate(language, "reset_guild_done_title", &[]);
+    component.create_response(context, update(content)).await?;
+
+    // The audit channel is reset along with everything else, but moderators following it are
+    // still informed there.
+    let channel_id = config.audit_channel_id.unwrap_or(component.channel_id);
+    let embed = CreateEmbed::new()
+        .title(i18n::translate(language, "reset_guild_done_title", &[]))
+        .description(description);
+    let result = channel_id
+        .send_message(context, CreateMessage::new().embed(embed))
+        .await;
+    monitor::record(context, &result).await;
+    result?;
+
+    Ok(())
+}
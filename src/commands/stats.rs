@@ -0,0 +1,325 @@
+use std::{collections::HashMap, sync::atomic::Ordering};
+
+use chrono::{Days, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde_json::json;
+use serenity::{
+    builder::{
+        CreateAttachment, CreateCommandOption, CreateInteractionResponseMessage,
+        EditInteractionResponse,
+    },
+    model::{
+        application::CommandOptionType,
+        channel::Channel,
+        prelude::{ChannelId, UserId},
+    },
+    utils::MessageBuilder,
+};
+
+use super::{CommandResult, DeferredResult, Invocation};
+use crate::{
+    counters::{ActionCounts, GuildAction, SharedCounters},
+    db::SharedDatabase,
+    monitor, schedule,
+    state::shared,
+    stats::SharedStats,
+};
+
+/// Maximum size in bytes of an export, which is the largest file Discord accepts by default.
+const MAX_EXPORT_SIZE: usize = 25 * 1024 * 1024;
+/// Number of actions read from the database at once while exporting.
+const EXPORT_PAGE_SIZE: u32 = 1000;
+
+pub fn register() -> CreateCommandOption {
+    let date =
+        |name, description| CreateCommandOption::new(CommandOptionType::String, name, description);
+
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "stats",
+        "Show statistics about the moderation of this server.",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "show",
+        "Show statistics about the tracked messages.",
+    ))
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "export_raw",
+            "Export every action taken in this server as a JSONL file.",
+        )
+        .add_sub_option(date(
+            "start",
+            "The first day to export as YYYY-MM-DD in the timezone of the server.",
+        ))
+        .add_sub_option(date(
+            "end",
+            "The last day to export as YYYY-MM-DD in the timezone of the server.",
+        )),
+    )
+}
+
+pub async fn show(invocation: &Invocation<'_>) -> CommandResult {
+    let (stats, counters) = {
+        let data_read = invocation.context.data.read().await;
+        (
+            shared::<SharedStats>(&data_read)?,
+            shared::<SharedCounters>(&data_read)?,
+        )
+    };
+
+    // Days start at midnight in the timezone of the guild rather than in UTC.
+    let now = Utc::now();
+    let timezone = invocation.config().await?.timezone();
+    let start_of_today = now
+        .with_timezone(&timezone)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(timezone).earliest())
+        .map_or(now.timestamp(), |midnight| midnight.timestamp());
+    let last_week = (now - Duration::days(7)).timestamp();
+
+    let mut content = MessageBuilder::new();
+    for (period, since) in [
+        ("stats_period_today", Some(start_of_today)),
+        ("stats_period_week", Some(last_week)),
+        ("stats_period_all_time", None),
+    ] {
+        let counts = counters.counts(invocation.guild_id, since).await?;
+        content.push_line(describe_counts(invocation, period, &counts));
+    }
+
+    content
+        .push_line(invocation.translate(
+            "stats_expired_entries",
+            &[("count", &stats.expired_entries.load(Ordering::Relaxed))],
+        ))
+        .push_line(invocation.translate(
+            "stats_evicted_entries",
+            &[("count", &stats.evicted_entries.load(Ordering::Relaxed))],
+        ));
+    if let Some(lookups) = stats.cache_lookups(invocation.guild_id) {
+        let rate = lookups.detection_rate().unwrap_or_default();
+        content.push_line(invocation.translate(
+            "stats_cache_lookups",
+            &[
+                ("hits", &lookups.hits.load(Ordering::Relaxed)),
+                ("misses", &lookups.misses.load(Ordering::Relaxed)),
+                ("rate", &format!("{:.1}", rate * 100.0)),
+            ],
+        ));
+    }
+    if let Some(prune) = stats.last_prune(invocation.guild_id) {
+        content.push_line(invocation.translate(
+            "stats_last_prune",
+            &[
+                ("count", &prune.rows),
+                ("time", &format!("<t:{}:R>", prune.pruned_at)),
+            ],
+        ));
+    }
+
+    Ok(CreateInteractionResponseMessage::new().content(content.build()))
+}
+
+fn describe_counts(invocation: &Invocation<'_>, period: &str, counts: &ActionCounts) -> String {
+    invocation.translate(
+        "stats_actions",
+        &[
+            ("period", &invocation.translate(period, &[])),
+            ("detected", &counts.get(GuildAction::Detected)),
+            ("deleted", &counts.get(GuildAction::Deleted)),
+            ("reported", &counts.get(GuildAction::Reported)),
+            ("dm_failed", &counts.get(GuildAction::DmFailed)),
+        ],
+    )
+}
+
+/// Exports the history of all users of the guild within the given days, one JSON object per line
+/// with the names of the users and channels resolved. Exports exceeding the size Discord accepts
+/// are truncated, in which case no further actions are read.
+pub async fn export_raw(invocation: &Invocation<'_>) -> DeferredResult {
+    let timezone = invocation.config().await?.timezone();
+    let (Some(since), Some(until)) = (
+        day_option(invocation, "start", timezone, 0),
+        day_option(invocation, "end", timezone, 1),
+    ) else {
+        return Ok(EditInteractionResponse::new()
+            .content(invocation.translate("stats_export_invalid_date", &[])));
+    };
+
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    let guild_name = guild_name(invocation).await;
+    let mut names = Names::default();
+    let mut file = Vec::new();
+    let mut exported = 0;
+    let mut truncated = false;
+    let mut last = None;
+    'pages: loop {
+        let after = last.as_ref().map(|(id, entry)| (*id, entry));
+        let page = database
+            .guild_actions(invocation.guild_id, since, until, after, EXPORT_PAGE_SIZE)
+            .await?;
+        let complete = page.len() < EXPORT_PAGE_SIZE as usize;
+        for (id, entry) in &page {
+            let username = names.user(invocation, entry.user_id).await;
+            let moderator_name = match entry.moderator_id {
+                Some(moderator_id) => names.user(invocation, moderator_id).await,
+                None => None,
+            };
+            let channel_name = match entry.channel_id {
+                Some(channel_id) => names.channel(invocation, channel_id).await,
+                None => None,
+            };
+            // Snowflakes are exported as strings like Discord does, since they exceed the precision
+            // of numbers in JavaScript.
+            let line = json!({
+                "id": id,
+                "guild_id": invocation.guild_id.to_string(),
+                "guild_name": guild_name,
+                "user_id": entry.user_id.to_string(),
+                "username": username,
+                "action": entry.action.as_str(),
+                "channel_id": entry.channel_id.map(|channel_id| channel_id.to_string()),
+                "channel_name": channel_name,
+                "moderator_id": entry.moderator_id.map(|user_id| user_id.to_string()),
+                "moderator_name": moderator_name,
+                "created_at": entry.created_at,
+            })
+            .to_string();
+            if file.len() + line.len() + 1 > MAX_EXPORT_SIZE {
+                tracing::warn!(
+                    "Truncated the export of guild {} after {} actions.",
+                    invocation.guild_id,
+                    exported
+                );
+                truncated = true;
+                break 'pages;
+            }
+            file.extend_from_slice(line.as_bytes());
+            file.push(b'\n');
+            exported += 1;
+        }
+
+        if complete {
+            break;
+        }
+        last = page.into_iter().last();
+    }
+
+    let key = if truncated {
+        "stats_export_truncated"
+    } else {
+        "stats_export_done"
+    };
+    let filename = format!("broom-actions-{}.jsonl", invocation.guild_id);
+
+    Ok(EditInteractionResponse::new()
+        .content(invocation.translate(key, &[("count", &exported)]))
+        .new_attachment(CreateAttachment::bytes(file, filename)))
+}
+
+/// Returns the timestamp at which the day given by the option with the given name starts, moved
+/// by the given number of days, or `Some(None)` if the option was not given.
+fn day_option(
+    invocation: &Invocation<'_>,
+    name: &str,
+    timezone: Tz,
+    offset: u64,
+) -> Option<Option<i64>> {
+    let Some(value) = invocation.string_option(name) else {
+        return Some(None);
+    };
+    let date = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .ok()?
+        .checked_add_days(Days::new(offset))?;
+
+    schedule::start_of_day(timezone, date).map(|start| Some(start.timestamp()))
+}
+
+async fn guild_name(invocation: &Invocation<'_>) -> Option<String> {
+    let cached = invocation
+        .context
+        .cache
+        .guild(invocation.guild_id)
+        .map(|guild| guild.name.clone());
+    if cached.is_some() {
+        return cached;
+    }
+
+    let result = invocation
+        .guild_id
+        .to_partial_guild(invocation.context)
+        .await;
+    monitor::record(invocation.context, &result).await;
+    result.ok().map(|guild| guild.name)
+}
+
+/// Names of the users and channels of an export, resolved from the cache or else from the API
+/// once each. Names that cannot be resolved, e.g. of deleted channels, are exported as `null`.
+#[derive(Default)]
+struct Names {
+    users: HashMap<UserId, Option<String>>,
+    channels: HashMap<ChannelId, Option<String>>,
+}
+
+impl Names {
+    async fn user(&mut self, invocation: &Invocation<'_>, user_id: UserId) -> Option<String> {
+        if let Some(name) = self.users.get(&user_id) {
+            return name.clone();
+        }
+
+        let cached = invocation
+            .context
+            .cache
+            .user(user_id)
+            .map(|user| user.name.clone());
+        let name = match cached {
+            Some(name) => Some(name),
+            None => {
+                let result = invocation.context.http.get_user(user_id).await;
+                monitor::record(invocation.context, &result).await;
+                result.ok().map(|user| user.name)
+            }
+        };
+        self.users.insert(user_id, name.clone());
+
+        name
+    }
+
+    async fn channel(
+        &mut self,
+        invocation: &Invocation<'_>,
+        channel_id: ChannelId,
+    ) -> Option<String> {
+        if let Some(name) = self.channels.get(&channel_id) {
+            return name.clone();
+        }
+
+        let cached = invocation
+            .context
+            .cache
+            .guild(invocation.guild_id)
+            .and_then(|guild| {
+                let channel = guild.channels.get(&channel_id);
+                let thread = || guild.threads.iter().find(|thread| thread.id == channel_id);
+                channel.or_else(thread).map(|channel| channel.name.clone())
+            });
+        let name = match cached {
+            Some(name) => Some(name),
+            None => {
+                let result = channel_id.to_channel(invocation.context).await;
+                monitor::record(invocation.context, &result).await;
+                match result {
+                    Ok(Channel::Guild(channel)) => Some(channel.name),
+                    _ => None,
+                }
+            }
+        };
+        self.channels.insert(channel_id, name.clone());
+
+        name
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use serenity::{
+    builder::{CreateCommandOption, CreateInteractionResponseMessage},
+    model::{application::CommandOptionType, mention::Mentionable},
+    utils::MessageBuilder,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{
+    db::{unix_timestamp, SharedDatabase},
+    history::HistoryAction,
+    state::shared,
+};
+
+/// Maximum number of users listed, which keeps the reply within the length limit of messages.
+const MAX_LISTED_USERS: u32 = 25;
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "strikes",
+        "Review the users with deleted duplicates.",
+    )
+    .add_sub_option(CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "list",
+        "List the users with the most strikes and those on probation.",
+    ))
+}
+
+pub async fn list(invocation: &Invocation<'_>) -> CommandResult {
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    let config = invocation.config().await?;
+    let since = unix_timestamp() - config.strike_window_secs as i64;
+    let strikes = database
+        .count_actions_by_user(
+            invocation.guild_id,
+            HistoryAction::Deleted,
+            since,
+            MAX_LISTED_USERS,
+        )
+        .await?;
+    let probations = database
+        .probations(invocation.guild_id)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    if strikes.is_empty() && probations.is_empty() {
+        return invocation.reply("strikes_none", &[]);
+    }
+
+    let mut content = MessageBuilder::new();
+    content.push_line(
+        invocation.translate("strikes_header", &[("since", &format!("<t:{}:R>", since))]),
+    );
+    // Users on probation are listed even once their strikes left the window.
+    let mut without_strikes = probations
+        .iter()
+        .filter(|(user_id, _)| !strikes.iter().any(|(id, _)| id == *user_id))
+        .map(|(user_id, started_at)| (*user_id, *started_at))
+        .collect::<Vec<_>>();
+    without_strikes.sort_by_key(|(_, started_at)| *started_at);
+    let users = strikes
+        .iter()
+        .copied()
+        .chain(without_strikes.into_iter().map(|(user_id, _)| (user_id, 0)));
+    for (user_id, count) in users.take(MAX_LISTED_USERS as usize) {
+        let entry = match probations.get(&user_id) {
+            Some(started_at) => invocation.translate(
+                "strikes_entry_probation",
+                &[
+                    ("user", &user_id.mention()),
+                    ("count", &count),
+                    ("since", &format!("<t:{}:R>", started_at)),
+                ],
+            ),
+            None => invocation.translate(
+                "strikes_entry",
+                &[("user", &user_id.mention()), ("count", &count)],
+            ),
+        };
+        content.push_line(entry);
+    }
+
+    Ok(CreateInteractionResponseMessage::new().content(content.build()))
+}
@@ -0,0 +1,271 @@
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateCommandOption, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage,
+    },
+    client::Context,
+    model::{
+        application::{ButtonStyle, CommandOptionType, ComponentInteraction},
+        permissions::Permissions,
+    },
+    utils::MessageBuilder,
+};
+
+use super::{ignore::add_pattern, CommandResult, Invocation};
+use crate::{
+    content::truncate,
+    db::{unix_timestamp, SharedDatabase},
+    error::BroomError,
+    i18n,
+    ignore::{IgnorePattern, PatternKind, MAX_IGNORE_PATTERNS},
+    privacy::ContentPolicies,
+    state::{shared, GuildStates},
+    suggestions::{
+        self, SuggestionSession, SuggestionSessions, ANALYZED_DAYS, MAX_ANALYZED_SAMPLES,
+    },
+};
+
+/// Prefix of the custom IDs of the buttons attached to suggestions.
+pub const CUSTOM_ID_PREFIX: &str = "broom:suggest_filters:";
+/// Maximum length in characters of the value of a field of an embed.
+const MAX_FIELD_LENGTH: usize = 1024;
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "suggest_filters",
+        "Suggest ignore patterns for contents that were detected as duplicates over and over again.",
+    )
+}
+
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let (database, policy, sessions) = {
+        let data_read = invocation.context.data.read().await;
+        (
+            shared::<SharedDatabase>(&data_read)?,
+            shared::<ContentPolicies>(&data_read)?,
+            shared::<SuggestionSessions>(&data_read)?,
+        )
+    };
+    if policy.hash_only() {
+        return invocation.reply("suggest_filters_hashed", &[]);
+    }
+
+    let since = unix_timestamp() - ANALYZED_DAYS * 24 * 60 * 60;
+    let contents = database
+        .detection_sample_contents(
+            invocation.guild_id,
+            "duplicate",
+            since,
+            MAX_ANALYZED_SAMPLES,
+        )
+        .await?;
+    let state = invocation.guilds().await?.get(invocation.guild_id).await?;
+    let suggestions = suggestions::suggest(&contents, &*state.ignore_list.read().await);
+    if suggestions.is_empty() {
+        return invocation.reply(
+            "suggest_filters_none",
+            &[("count", &contents.len()), ("days", &ANALYZED_DAYS)],
+        );
+    }
+
+    let session_id = invocation.command.id.get();
+    let session = SuggestionSession {
+        guild_id: invocation.guild_id,
+        suggestions,
+    };
+    let (embed, components) = render(&invocation.language, session_id, &session, 0);
+    sessions.insert(session_id, session).await;
+
+    Ok(CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(components))
+}
+
+/// Renders the suggestion on the given page along with the buttons to act on it.
+fn render(
+    language: &str,
+    session_id: u64,
+    session: &SuggestionSession,
+    page: usize,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let suggestion = &session.suggestions[page];
+    let embed = CreateEmbed::new()
+        .title(i18n::translate(
+            language,
+            "suggest_filters_title",
+            &[("page", &(page + 1)), ("count", &session.suggestions.len())],
+        ))
+        .description(
+            MessageBuilder::new()
+                .push_codeblock_safe(&suggestion.pattern, None)
+                .build(),
+        )
+        .field(
+            i18n::translate(language, "suggest_filters_matches", &[]),
+            suggestion.matches.to_string(),
+            true,
+        )
+        .field(
+            i18n::translate(language, "suggest_filters_example", &[]),
+            MessageBuilder::new()
+                .push_safe(truncate(&suggestion.example, MAX_FIELD_LENGTH - 16))
+                .build(),
+            false,
+        );
+    let button = |action: &str, key: &str| {
+        CreateButton::new(format!(
+            "{}{}:{}:{}",
+            CUSTOM_ID_PREFIX, session_id, page, action
+        ))
+        .label(i18n::translate(language, key, &[]))
+    };
+    let buttons = CreateActionRow::Buttons(vec![
+        button("previous", "suggest_filters_previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        button("next", "suggest_filters_next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 == session.suggestions.len()),
+        button("add", "suggest_filters_add").style(ButtonStyle::Success),
+        button("dismiss", "suggest_filters_dismiss").style(ButtonStyle::Danger),
+    ]);
+
+    (embed, vec![buttons])
+}
+
+/// Handles a click on one of the buttons of a suggestion.
+pub async fn handle_component(context: &Context, component: &ComponentInteraction) {
+    if let Err(e) = handle_click(context, component).await {
+        tracing::error!(
+            "There was an error while handling a click on a filter suggestion: {}",
+            e
+        );
+    }
+}
+
+async fn handle_click(
+    context: &Context,
+    component: &ComponentInteraction,
+) -> Result<(), BroomError> {
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    let Some((session_id, page, action)) = component
+        .data
+        .custom_id
+        .strip_prefix(CUSTOM_ID_PREFIX)
+        .and_then(|id| {
+            let mut parts = id.splitn(3, ':');
+            let session_id = parts.next()?.parse::<u64>().ok()?;
+            let page = parts.next()?.parse::<usize>().ok()?;
+            Some((session_id, page, parts.next()?))
+        })
+    else {
+        return Ok(());
+    };
+    let (guilds, sessions) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SuggestionSessions>(&data_read)?,
+        )
+    };
+    let language = guilds
+        .get(guild_id)
+        .await?
+        .config
+        .read()
+        .await
+        .language
+        .clone();
+    let update = |content: String| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .embeds(Vec::new())
+                .components(Vec::new()),
+        )
+    };
+
+    let Some(mut session) = sessions
+        .get(&session_id)
+        .await
+        .filter(|session| session.guild_id == guild_id && !session.suggestions.is_empty())
+    else {
+        let content = i18n::translate(&language, "suggest_filters_expired", &[]);
+        component.create_response(context, update(content)).await?;
+        return Ok(());
+    };
+    if !component
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| {
+            permissions.administrator() || permissions.contains(Permissions::MANAGE_GUILD)
+        })
+    {
+        let content = i18n::translate(
+            &language,
+            "command_missing_permission",
+            &[("permission", &Permissions::MANAGE_GUILD)],
+        );
+        component.create_response(context, update(content)).await?;
+        return Ok(());
+    }
+
+    let mut page = page.min(session.suggestions.len() - 1);
+    let mut note = String::new();
+    match action {
+        "previous" => page = page.saturating_sub(1),
+        "next" => page += 1,
+        "add" => {
+            let pattern = session.suggestions[page].pattern.clone();
+            // Suggestions go through the same validation as patterns added by moderators.
+            note = match IgnorePattern::new(PatternKind::Regex, &pattern) {
+                Some(entry) => {
+                    if add_pattern(&guilds, guild_id, entry).await? {
+                        session.suggestions.remove(page);
+                        i18n::translate(&language, "ignore_added", &[("pattern", &pattern)])
+                    } else {
+                        i18n::translate(
+                            &language,
+                            "ignore_limit_reached",
+                            &[("limit", &MAX_IGNORE_PATTERNS)],
+                        )
+                    }
+                }
+                None => {
+                    session.suggestions.remove(page);
+                    i18n::translate(&language, "ignore_invalid_regex", &[("pattern", &pattern)])
+                }
+            };
+        }
+        "dismiss" => {
+            session.suggestions.remove(page);
+        }
+        _ => return Ok(()),
+    }
+
+    if session.suggestions.is_empty() {
+        sessions.invalidate(&session_id).await;
+        if note.is_empty() {
+            note = i18n::translate(&language, "suggest_filters_done", &[]);
+        }
+        component.create_response(context, update(note)).await?;
+        return Ok(());
+    }
+
+    page = page.min(session.suggestions.len() - 1);
+    let (embed, components) = render(&language, session_id, &session, page);
+    sessions.insert(session_id, session).await;
+    let response = CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(note)
+            .embed(embed)
+            .components(components),
+    );
+    component.create_response(context, response).await?;
+
+    Ok(())
+}
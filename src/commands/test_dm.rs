@@ -0,0 +1,62 @@
+use serenity::{
+    builder::{CreateCommandOption, CreateMessage},
+    http::HttpError,
+    model::application::CommandOptionType,
+    Error,
+};
+
+use super::{CommandResult, Invocation};
+use crate::{db::SharedDatabase, monitor, state::shared};
+
+pub fn register() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "test_dm",
+        "Check whether direct messages can be delivered to a user.",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::User,
+            "user",
+            "The user to send a test message to.",
+        )
+        .required(true),
+    )
+}
+
+pub async fn run(invocation: &Invocation<'_>) -> CommandResult {
+    let Some(user) = invocation.user_option("user") else {
+        return invocation.reply("test_dm_missing_user", &[]);
+    };
+
+    let guild_name = invocation
+        .guild_id
+        .name(&invocation.context.cache)
+        .unwrap_or_else(|| invocation.guild_id.to_string());
+    let message =
+        CreateMessage::new().content(invocation.translate("dm_test", &[("guild", &guild_name)]));
+    let result = user.dm(invocation.context, message).await;
+    monitor::record(invocation.context, &result).await;
+
+    let database = shared::<SharedDatabase>(&*invocation.context.data.read().await)?;
+    if let Err(e) = database.set_dm_reachable(user.id, result.is_ok()).await {
+        tracing::error!(
+            "There was an error while attempting to record the DM reachability of a user: {:?}",
+            e
+        );
+    }
+
+    match result {
+        Ok(_) => invocation.reply("test_dm_delivered", &[]),
+        Err(e) => invocation.reply("test_dm_failed", &[("code", &discord_error_code(&e))]),
+    }
+}
+
+/// Extracts the JSON error code returned by Discord, falling back to the whole error for anything
+/// that did not originate from an unsuccessful request.
+fn discord_error_code(error: &Error) -> String {
+    match error {
+        Error::Http(HttpError::UnsuccessfulRequest(response)) => response.error.code.to_string(),
+        _ => error.to_string(),
+    }
+}
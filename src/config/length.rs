@@ -0,0 +1,203 @@
+//! How the length of messages is measured, which decides whether they are long enough to be
+//! tracked.
+
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Default minimum number of bytes of messages to be tracked.
+const MIN_MESSAGE_LENGTH: usize = 50;
+/// Default minimum number of words of messages to be tracked, which is about as long as the
+/// default minimum length in English.
+const MIN_MESSAGE_WORDS: usize = 8;
+
+/// The unit the length of messages is measured in along with the minimum length of messages to be
+/// tracked. Words suit languages whose words are much longer or shorter than in English, e.g.
+/// German or Chinese, where the same number of characters carries a very different amount of
+/// content.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", from = "StoredLengthMode")]
+pub enum LengthMode {
+    Characters(usize),
+    Words(usize),
+}
+
+impl Default for LengthMode {
+    fn default() -> Self {
+        Self::Characters(MIN_MESSAGE_LENGTH)
+    }
+}
+
+impl LengthMode {
+    /// Returns the mode with the given unit and its default minimum length, or `None` if the unit
+    /// is unknown.
+    pub fn parse(unit: &str) -> Option<Self> {
+        match unit.trim().to_ascii_lowercase().as_str() {
+            "characters" => Some(Self::Characters(MIN_MESSAGE_LENGTH)),
+            "words" => Some(Self::Words(MIN_MESSAGE_WORDS)),
+            _ => None,
+        }
+    }
+
+    pub fn minimum(self) -> usize {
+        match self {
+            Self::Characters(minimum) | Self::Words(minimum) => minimum,
+        }
+    }
+
+    /// Whether both modes measure lengths in the same unit, regardless of their minimum lengths.
+    pub fn same_unit(self, other: Self) -> bool {
+        mem::discriminant(&self) == mem::discriminant(&other)
+    }
+
+    /// Returns the mode with the same unit but the given minimum length.
+    pub fn with_minimum(self, minimum: usize) -> Self {
+        match self {
+            Self::Characters(_) => Self::Characters(minimum),
+            Self::Words(_) => Self::Words(minimum),
+        }
+    }
+
+    /// Measures the given text in the unit of this mode. Characters are counted in bytes as they
+    /// always have been, so that existing minimum lengths keep their meaning.
+    pub fn measure(self, text: &str) -> usize {
+        match self {
+            Self::Characters(_) => text.len(),
+            Self::Words(_) => text.unicode_words().count(),
+        }
+    }
+}
+
+/// A length mode as persisted, which was a plain number of characters before words were
+/// supported.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredLengthMode {
+    Characters(usize),
+    Mode(TaggedLengthMode),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TaggedLengthMode {
+    Characters(usize),
+    Words(usize),
+}
+
+impl From<StoredLengthMode> for LengthMode {
+    fn from(stored: StoredLengthMode) -> Self {
+        match stored {
+            StoredLengthMode::Characters(minimum)
+            | StoredLengthMode::Mode(TaggedLengthMode::Characters(minimum)) => {
+                Self::Characters(minimum)
+            }
+            StoredLengthMode::Mode(TaggedLengthMode::Words(minimum)) => Self::Words(minimum),
+        }
+    }
+}
+
+/// A range of message lengths with its own window, e.g. to count reposts of long messages over a
+/// longer time than reposts of short ones. Both bounds are inclusive and measured in the unit of
+/// the length mode.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LengthBand {
+    pub min: usize,
+    pub max: usize,
+    pub window_secs: u64,
+}
+
+impl LengthBand {
+    /// Parses a band given as `min-max:window_secs`, e.g. `0-20:30`.
+    fn parse(value: &str) -> Option<Self> {
+        let (range, window_secs) = value.split_once(':')?;
+        let (min, max) = range.split_once('-')?;
+        let band = Self {
+            min: min.trim().parse().ok()?,
+            max: max.trim().parse().ok()?,
+            window_secs: window_secs.trim().parse().ok()?,
+        };
+
+        (band.min <= band.max).then_some(band)
+    }
+
+    pub fn contains(&self, length: usize) -> bool {
+        (self.min..=self.max).contains(&length)
+    }
+}
+
+/// Parses bands separated by whitespace, e.g. `0-20:30 21-200:120`, sorted by their lengths.
+/// Returns `None` if any of them is invalid or overlaps another one, since a message could not be
+/// assigned to a single band otherwise.
+pub fn parse_length_bands(value: &str) -> Option<Vec<LengthBand>> {
+    let mut bands = value
+        .split_whitespace()
+        .map(LengthBand::parse)
+        .collect::<Option<Vec<_>>>()?;
+    bands.sort_by_key(|band| band.min);
+    if bands.windows(2).any(|pair| pair[1].min <= pair[0].max) {
+        return None;
+    }
+
+    Some(bands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the window of the band the given length falls into, if any.
+    fn window(bands: &[LengthBand], length: usize) -> Option<u64> {
+        bands
+            .iter()
+            .find(|band| band.contains(length))
+            .map(|band| band.window_secs)
+    }
+
+    #[test]
+    fn assigns_lengths_to_short_medium_and_long_bands() {
+        let bands = parse_length_bands("501-2000:600 0-50:30  51-300:120").unwrap();
+
+        assert_eq!(
+            bands.iter().map(|band| band.min).collect::<Vec<_>>(),
+            [0, 51, 501]
+        );
+        for (length, expected) in [
+            (0, Some(30)),
+            (50, Some(30)),
+            (51, Some(120)),
+            (300, Some(120)),
+            (301, None),
+            (500, None),
+            (501, Some(600)),
+            (2000, Some(600)),
+            (2001, None),
+        ] {
+            assert_eq!(window(&bands, length), expected, "length {}", length);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_or_overlapping_bands() {
+        for value in [
+            "0-50:30 50-100:60",
+            "0-100:30 20-40:60",
+            "50-0:30",
+            "0-50",
+            "0:30",
+            "a-50:30",
+            "0-50:-1",
+        ] {
+            assert_eq!(parse_length_bands(value), None, "{}", value);
+        }
+        assert_eq!(parse_length_bands(""), Some(Vec::new()));
+        assert_eq!(
+            parse_length_bands("7-7:1"),
+            Some(vec![LengthBand {
+                min: 7,
+                max: 7,
+                window_secs: 1,
+            }])
+        );
+    }
+}
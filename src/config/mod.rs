@@ -0,0 +1,774 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use chrono::{DateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::{ChannelId, MessageId, RoleId, UserId};
+
+use self::length::parse_length_bands;
+pub use self::{
+    length::{LengthBand, LengthMode},
+    profiles::{
+        DetectionScope, DmOverflowAction, DuplicateAction, HoneypotAction, NewAccountPolicy,
+        NsfwChannelPolicy, ProfilePrecedence, ThresholdProfile,
+    },
+    validation::{ConfigLimits, ConfigValidationError},
+};
+use crate::{
+    custom_commands::CustomCommand, db::unix_timestamp, i18n, ignore::IgnorePattern,
+    preferences::DmFormat, schedule, verbose::VERBOSE_DEBUG_DURATION_IN_SECS,
+};
+
+mod length;
+mod profiles;
+mod validation;
+
+/// Time in seconds until a message is automatically evicted from the tracking cache.
+const TIME_TO_IDLE_IN_SECS: u64 = 120;
+/// Maximum number of messages tracked per guild before the least recently used ones are evicted.
+const MAX_TRACKED_MESSAGES: u64 = 10_000;
+/// Number of times a message has to be posted within the window to be considered a duplicate.
+const OCCURRENCE_THRESHOLD: usize = 2;
+/// Time in seconds within which deleted duplicates count towards proposing a ban.
+const STRIKE_WINDOW_IN_SECS: u64 = 7 * 24 * 60 * 60;
+/// Time in seconds after which a proposal to ban a user can no longer be decided on.
+const BAN_PROPOSAL_EXPIRY_IN_SECS: u64 = 24 * 60 * 60;
+/// Days of messages of a banned user that are deleted along with the ban.
+const BAN_DELETE_MESSAGE_DAYS: u8 = 1;
+/// Maximum number of bits in which the perceptual hashes of images that look alike differ.
+const PHASH_DISTANCE_THRESHOLD: u32 = 10;
+/// Maximum time in seconds a user may be warned for before they are banned.
+const MAX_BAN_WARNING_IN_SECS: u64 = 24 * 60 * 60;
+/// Number of deleted duplicates within the strike window at which users are put on probation.
+const PROBATION_THRESHOLD: u64 = 5;
+/// Days without strikes after which the probation of a user ends.
+const PROBATION_EXPIRY_DAYS: u16 = 30;
+/// Number of times an author may mention the same user within the window before moderators are
+/// alerted.
+const REPEATED_MENTION_THRESHOLD: usize = 5;
+/// Time in seconds within which repeated mentions of the same user are counted.
+const REPEATED_MENTION_WINDOW_IN_SECS: u64 = 60;
+/// Number of distinct users above which posting the same content within the window is considered
+/// a coordinated attack.
+const COORDINATED_ATTACK_THRESHOLD: usize = 10;
+/// Time in seconds within which the users posting the same content are counted.
+const COORDINATED_ATTACK_WINDOW_IN_SECS: u64 = 60;
+/// Time in seconds between deleting a duplicate and messaging its author.
+const DM_DELAY_IN_SECS: u8 = 2;
+/// Number of times an invite has to be posted within the window to be considered a duplicate.
+const INVITE_OCCURRENCE_THRESHOLD: usize = 2;
+/// Time in seconds within which a repost of an invite is considered a duplicate.
+const INVITE_WINDOW_IN_SECS: u64 = 10 * 60;
+/// Age in days below which accounts of joining members might be alt accounts.
+const ALT_ACCOUNT_AGE_THRESHOLD_DAYS: u16 = 7;
+/// Days within which a ban makes joining members with new accounts suspicious.
+const BAN_LOOKBACK_DAYS: u16 = 30;
+/// Time in seconds possible alt accounts are watched for.
+const ALT_ACCOUNT_WATCH_IN_SECS: u64 = 3 * 24 * 60 * 60;
+/// Days deleted messages are archived for.
+const ARCHIVE_RETENTION_DAYS: u16 = 30;
+/// Days recorded actions and detection samples are kept for.
+const EVENT_RETENTION_DAYS: u32 = 90;
+/// Days after joining a guild during which no actions are taken.
+const BOOTSTRAP_DAYS: u16 = 7;
+/// Share of checked messages that are explained in the debug channel in verbose mode.
+const VERBOSE_SAMPLE_RATE: f64 = 0.1;
+/// Number of most recent messages of an author a message is compared against under the recent
+/// messages scope.
+const WINDOW_SIZE: usize = 5;
+/// Length in characters below which messages starting with a bot command prefix are exempt.
+const BOT_COMMAND_MAX_LENGTH: usize = 50;
+/// Prefix of code snippets, which are allowed by default.
+const CODE_FENCE: &str = "```";
+
+/// Settings that can be configured for each guild individually. Settings missing from a persisted
+/// configuration, e.g. because they were added later on, fall back to their defaults.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    /// Time in seconds within which a repost of a tracked message is considered a duplicate. This
+    /// is used as the `time_to_idle` of the tracking cache, i.e. every repost restarts the window.
+    pub time_to_idle_secs: u64,
+    /// Time in seconds after which a tracked message is evicted no matter how often it is
+    /// accessed. This is used as the `time_to_live` of the tracking cache and serves as a hard
+    /// ceiling for memory usage. It only affects which reposts are considered duplicates under the
+    /// `recent_messages` scope, where it is the maximum age of the most recent messages.
+    pub max_entry_age_secs: u64,
+    /// How the length of messages is measured and the minimum length of messages to be tracked.
+    /// Anything shorter than this is ignored entirely. Minimum lengths of threshold profiles and
+    /// channel overrides are measured the same way. Configurations persisted before words were
+    /// supported stored a plain number of characters as `min_message_length`.
+    #[serde(alias = "min_message_length")]
+    pub length_mode: LengthMode,
+    /// Windows for messages whose length falls into a band, measured by the length mode, instead
+    /// of the time to idle. Bands never overlap, and messages outside of all of them use the time
+    /// to idle. Threshold profiles and channel overrides defining a window take precedence.
+    pub length_bands: Vec<LengthBand>,
+    /// Maximum number of messages tracked at once. Reaching this limit evicts tracked messages
+    /// before their window has elapsed, which means the limit is too small for the guild.
+    pub max_tracked_messages: u64,
+    /// Number of times a message has to be posted within the window to be considered a duplicate.
+    pub occurrence_threshold: usize,
+    /// What happens to duplicates once they have been detected.
+    pub duplicate_action: DuplicateAction,
+    /// Whether reposts are counted within the window or within the current day in the timezone of
+    /// the guild. Messages tracked for the current day are evicted at midnight rather than after
+    /// the window.
+    pub detection_scope: DetectionScope,
+    /// Number of most recent messages of an author a message is compared against under the recent
+    /// messages scope.
+    pub window_size: usize,
+    /// Number of times the author of a duplicate is warned before it is deleted, each time they
+    /// reach the occurrence threshold with the same content. Disabled if zero.
+    pub warning_count_before_action: u8,
+    /// Whether a trailing counter such as the `3` of `bump 3` is ignored when comparing messages.
+    /// Disabled by default since some communities use meaningful numeric suffixes.
+    pub strip_trailing_counter: bool,
+    /// Whether all copies of a duplicate posted within the window are deleted once the threshold
+    /// is reached, including the original, rather than only the copies from then on.
+    pub retroactive_delete: bool,
+    /// Whether replies are exempt from duplicate detection, since replying to the same message in
+    /// several channels usually provides context rather than spam.
+    pub ignore_replies: bool,
+    /// Whether only replies to messages of other users are exempt if replies are, since replying
+    /// to oneself could still be spam.
+    pub ignore_replies_to_same_author: bool,
+    /// Whether messages consisting only of attachments are exempt from duplicate detection, e.g.
+    /// in channels meant for sharing images. Otherwise they are tracked by the hashes of the
+    /// contents of their attachments, which are downloaded to that end.
+    pub skip_media_only: bool,
+    /// Whether the titles and descriptions of embeds count towards the length of a message, so
+    /// that bare links with substantial previews are long enough to be tracked. Previews that
+    /// Discord only adds once the message was posted are not seen.
+    pub include_embed_in_length: bool,
+    /// Whether messages with embeds are tracked by the titles and descriptions of their embeds
+    /// instead of their text, so that links to the same video are duplicates regardless of the
+    /// text around them.
+    pub include_embed_in_content: bool,
+    /// Whether images that look alike are detected as duplicates even if they were resized,
+    /// slightly cropped or watermarked. Requires the `perceptual_hashing` feature.
+    pub detect_similar_images: bool,
+    /// Maximum number of bits in which the perceptual hashes of two images may differ for them
+    /// to be considered alike, at most 64.
+    pub phash_distance_threshold: u32,
+    /// Prefixes of commands of other bots, e.g. `!` for `!rank`. Messages starting with one of
+    /// them that are shorter than the maximum length are exempt from all detectors, since such
+    /// commands are expected to be repeated across channels.
+    pub bot_command_prefixes: Vec<String>,
+    /// Length in characters below which messages starting with a bot command prefix are exempt.
+    pub bot_command_max_length: usize,
+    /// Prefixes of messages that are exempt from all detectors regardless of their length, e.g.
+    /// the code fence starting code snippets that are shared in several help channels. Matched
+    /// case-insensitively after leading whitespace.
+    pub content_prefix_allowlist: Vec<String>,
+    /// Template messages users are instructed to copy and post, along with the channels they were
+    /// posted in. Messages matching the content of a template exactly are exempt from all
+    /// detectors.
+    pub template_message_ids: HashMap<MessageId, ChannelId>,
+    /// Slash commands exposed to all members of the guild by their names, each backed by one of
+    /// a fixed menu of actions.
+    pub custom_commands: HashMap<String, CustomCommand>,
+    /// Whether fingerprints of removed duplicates are shared with other guilds that opted in, and
+    /// contents removed in several of them are removed on first sight.
+    pub share_fingerprints: bool,
+    /// Whether messages linking to a domain on the blocklist of the deployment are deleted on
+    /// first sight, regardless of the detection settings and warnings.
+    pub blocked_link_detection: bool,
+    /// Whether messages containing a Discord invite are tracked by the invite code regardless of
+    /// their length and of the detection settings, using the stricter invite settings instead.
+    pub invite_detection: bool,
+    /// Number of times an invite has to be posted within the invite window to be considered a
+    /// duplicate, which may be a single time to act on every invite.
+    pub invite_occurrence_threshold: usize,
+    /// Time in seconds within which a repost of an invite is considered a duplicate.
+    pub invite_window_secs: u64,
+    /// Time in seconds an author of a duplicate invite is timed out for, if at all.
+    pub invite_timeout_secs: Option<u64>,
+    /// Whether invites to the guild itself are exempt from invite detection and tracked like any
+    /// other message instead.
+    pub invite_exempt_own_guild: bool,
+    /// Age in seconds below which accounts are treated according to the new account policy.
+    /// Disabled if zero.
+    pub min_account_age_secs: u64,
+    /// How messages of accounts younger than the minimum account age are treated.
+    pub new_account_action: NewAccountPolicy,
+    /// Age in days below which the account of a joining member might be an alt account of a
+    /// banned user, if at all. Such members are watched and moderators are alerted, but only if
+    /// the guild banned an account within the ban lookback.
+    pub alt_account_age_threshold_days: Option<u16>,
+    /// Days within which a ban approved through a ban proposal makes joining members with new
+    /// accounts suspicious.
+    pub ban_lookback_days: u16,
+    /// Time in seconds possible alt accounts are held to the zero tolerance settings for.
+    pub alt_account_watch_secs: u64,
+    /// How messages in channels marked as NSFW are treated.
+    pub nsfw_channel_behavior: NsfwChannelPolicy,
+    /// Hidden channels that only spam bots and new accounts without roles can see. Messages posted
+    /// in them are acted on right away without being checked by any detector, unless their author
+    /// is a bot or a moderator.
+    pub honeypot_channels: HashSet<ChannelId>,
+    /// What happens to authors of messages posted in a honeypot channel.
+    pub honeypot_action: HoneypotAction,
+    /// Categories whose channels and threads are exempt from all detectors, e.g. a category of
+    /// channels for bot commands.
+    pub exempt_categories: HashSet<ChannelId>,
+    /// Whether the first message a user posts in the guild is not checked, since it cannot be a
+    /// duplicate of anything they posted there before. Users are only remembered while this is
+    /// enabled, so everyone's next message is exempt once it gets enabled.
+    pub exempt_first_message: bool,
+    /// Whether reports of duplicates and known spam that were not deleted, including warnings,
+    /// come with buttons to delete them or to dismiss them as false positives.
+    pub report_buttons: bool,
+    /// Share of messages that are checked, between 0 and 1, which reduces the load in guilds
+    /// with a lot of traffic. Reposts of contents that are tracked already are always checked, so
+    /// that spam is caught once any copy of it was sampled.
+    pub message_sampling_rate: f64,
+    /// Whether a message is no longer checked by further detectors once one of them detected
+    /// something, rather than taking action on everything detected.
+    pub stop_at_first_detection: bool,
+    /// Named sets of detection settings that roles can be mapped to.
+    pub threshold_profiles: HashMap<String, ThresholdProfile>,
+    /// The threshold profile applying to members with a role, by role.
+    pub role_profiles: HashMap<RoleId, String>,
+    /// Which settings win when a member has several roles mapped to different profiles.
+    pub profile_precedence: ProfilePrecedence,
+    /// Detection settings applying to messages posted in a channel, by channel.
+    pub channel_overrides: HashMap<ChannelId, ThresholdProfile>,
+    /// Language code of the catalog used for all messages sent on behalf of the guild.
+    pub language: String,
+    /// Whether the messages sent to authors of deleted duplicates are written in their own
+    /// language rather than the language of the guild, if a catalog for it is bundled.
+    pub dm_user_locale: bool,
+    /// Time in seconds between deleting a duplicate and messaging its author, so that they notice
+    /// the deletion before being told about it.
+    pub dm_delay_secs: u8,
+    /// How the messages sent to authors are formatted unless they chose a format themselves.
+    pub dm_format: DmFormat,
+    /// What happens to messages to authors that are dropped since too many actions are queued.
+    pub dm_queue_overflow_action: DmOverflowAction,
+    /// IANA name of the timezone all times configured for the guild are given in.
+    pub timezone: String,
+    /// Weekly time ranges during which duplicates are intentionally allowed, e.g. for scheduled
+    /// events that are announced in several channels at once.
+    pub safe_hours: Vec<(NaiveTime, NaiveTime, Weekday)>,
+    /// Weekly time ranges during which moderators are unavailable. Detections are reported, but
+    /// messages are neither deleted nor are their authors messaged until they end.
+    pub quiet_hours: Vec<(NaiveTime, NaiveTime, Weekday)>,
+    /// Patterns of messages that are legitimately repeated and therefore never tracked.
+    pub ignore_patterns: Vec<IgnorePattern>,
+    /// Names of tags exempting posts in a forum from duplicate detection, by forum.
+    pub forum_exempt_tags: HashMap<ChannelId, Vec<String>>,
+    /// Patterns extracting the original author from messages reposted by bridge bots, by the user
+    /// ID of the bot.
+    pub trusted_bridges: HashMap<UserId, String>,
+    /// Channel that moderators are informed in about all actions taken by the bot.
+    pub audit_channel_id: Option<ChannelId>,
+    /// Whether deleted duplicates are posted to the audit channel along with links to the channels
+    /// they were posted in.
+    pub audit_deletions: bool,
+    /// Whether routine detections are handled silently without posting to the audit channel, i.e.
+    /// everything but timeouts, bans and reports. Actions are still recorded and authors are
+    /// still messaged.
+    pub shadow_mode: bool,
+    /// Channel in which checked messages are explained in verbose mode, which is disabled once
+    /// `verbose_debug_expires_at` passed.
+    pub verbose_debug_channel_id: Option<ChannelId>,
+    /// Timestamp at which verbose mode disables itself, set whenever the debug channel is.
+    pub verbose_debug_expires_at: Option<i64>,
+    /// Share of checked messages between 0 and 1 that are explained in verbose mode.
+    pub verbose_sample_rate: f64,
+    /// Whether messages are archived in full before the bot deletes them, so that moderators can
+    /// review them later with `/broom archive view`.
+    pub archive_before_delete: bool,
+    /// Days after which archived messages are purged.
+    pub archive_retention_days: u16,
+    /// Days after which recorded actions and detection samples are pruned, which also limits how
+    /// far back the history of a user and exports reach.
+    pub event_retention_days: u32,
+    /// Days after the bot joined the guild during which duplicates are only logged, since the
+    /// cache is cold and channels are commonly being set up. Disabled if zero.
+    pub bootstrap_days: u16,
+    /// Number of deleted duplicates within the strike window at which moderators are asked to ban
+    /// the author, if at all. Bans are never executed without the approval of a moderator.
+    pub ban_proposal_threshold: Option<u64>,
+    /// Time in seconds within which deleted duplicates count towards proposing a ban.
+    pub strike_window_secs: u64,
+    /// Time in seconds after which a proposal to ban a user can no longer be decided on.
+    pub ban_proposal_expiry_secs: u64,
+    /// Days of messages of a banned user that are deleted along with the ban, at most 7.
+    pub ban_delete_message_days: u8,
+    /// Time in seconds a user is warned for before a ban approved through a ban proposal is
+    /// executed, during which moderators may cancel it, if at all.
+    pub ban_proposal_warning_secs: Option<u64>,
+    /// Time in seconds a user who posted in the honeypot channel is warned for before they are
+    /// banned, if at all. Only spammers can see the channel, so they are banned right away by
+    /// default.
+    pub honeypot_warning_secs: Option<u64>,
+    /// Number of deleted duplicates within the strike window at which users are put on probation,
+    /// if at all. Their messages are then checked under zero tolerance and they are messaged
+    /// without delay.
+    pub probation_threshold: Option<u64>,
+    /// Days without strikes after which the probation of a user ends on its own.
+    pub probation_expiry_days: u16,
+    /// Number of times an author may mention the same user within the window before moderators
+    /// are alerted.
+    pub repeated_mention_threshold: usize,
+    /// Time in seconds within which repeated mentions of the same user are counted.
+    pub repeated_mention_window_secs: u64,
+    /// Time in seconds an author repeatedly mentioning the same user is timed out for, if at all.
+    pub repeated_mention_timeout_secs: Option<u64>,
+    /// Whether moderators are alerted when many users post the same content within a short time,
+    /// e.g. during a raid.
+    pub coordinated_attack_detection: bool,
+    /// Number of distinct users above which posting the same content within the window is
+    /// considered a coordinated attack.
+    pub coordinated_attack_threshold: usize,
+    /// Time in seconds within which the users posting the same content are counted.
+    pub coordinated_attack_window_secs: u64,
+    /// Whether the verification level of the guild is raised to the highest one once a
+    /// coordinated attack is detected, so that only members with a verified phone number can
+    /// post until moderators lower it again.
+    pub coordinated_attack_lockdown: bool,
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        Self {
+            time_to_idle_secs: TIME_TO_IDLE_IN_SECS,
+            max_entry_age_secs: 2 * TIME_TO_IDLE_IN_SECS,
+            length_mode: LengthMode::default(),
+            length_bands: Vec::new(),
+            max_tracked_messages: MAX_TRACKED_MESSAGES,
+            occurrence_threshold: OCCURRENCE_THRESHOLD,
+            duplicate_action: DuplicateAction::Delete,
+            detection_scope: DetectionScope::default(),
+            window_size: WINDOW_SIZE,
+            warning_count_before_action: 0,
+            strip_trailing_counter: false,
+            retroactive_delete: false,
+            ignore_replies: false,
+            ignore_replies_to_same_author: false,
+            skip_media_only: true,
+            include_embed_in_length: false,
+            include_embed_in_content: false,
+            detect_similar_images: false,
+            phash_distance_threshold: PHASH_DISTANCE_THRESHOLD,
+            bot_command_prefixes: Vec::new(),
+            bot_command_max_length: BOT_COMMAND_MAX_LENGTH,
+            content_prefix_allowlist: vec![CODE_FENCE.to_string()],
+            template_message_ids: HashMap::new(),
+            custom_commands: HashMap::new(),
+            share_fingerprints: false,
+            blocked_link_detection: false,
+            invite_detection: false,
+            invite_occurrence_threshold: INVITE_OCCURRENCE_THRESHOLD,
+            invite_window_secs: INVITE_WINDOW_IN_SECS,
+            invite_timeout_secs: None,
+            invite_exempt_own_guild: true,
+            min_account_age_secs: 0,
+            new_account_action: NewAccountPolicy::default(),
+            alt_account_age_threshold_days: Some(ALT_ACCOUNT_AGE_THRESHOLD_DAYS),
+            ban_lookback_days: BAN_LOOKBACK_DAYS,
+            alt_account_watch_secs: ALT_ACCOUNT_WATCH_IN_SECS,
+            nsfw_channel_behavior: NsfwChannelPolicy::default(),
+            honeypot_channels: HashSet::new(),
+            exempt_categories: HashSet::new(),
+            exempt_first_message: false,
+            report_buttons: false,
+            message_sampling_rate: 1.0,
+            honeypot_action: HoneypotAction::default(),
+            stop_at_first_detection: false,
+            threshold_profiles: HashMap::new(),
+            role_profiles: HashMap::new(),
+            profile_precedence: ProfilePrecedence::default(),
+            channel_overrides: HashMap::new(),
+            language: i18n::DEFAULT_LANGUAGE.to_string(),
+            dm_user_locale: false,
+            dm_delay_secs: DM_DELAY_IN_SECS,
+            dm_format: DmFormat::default(),
+            dm_queue_overflow_action: DmOverflowAction::default(),
+            timezone: "UTC".to_string(),
+            safe_hours: Vec::new(),
+            quiet_hours: Vec::new(),
+            ignore_patterns: Vec::new(),
+            forum_exempt_tags: HashMap::new(),
+            trusted_bridges: HashMap::new(),
+            audit_channel_id: None,
+            audit_deletions: true,
+            shadow_mode: false,
+            verbose_debug_channel_id: None,
+            verbose_debug_expires_at: None,
+            verbose_sample_rate: VERBOSE_SAMPLE_RATE,
+            archive_before_delete: false,
+            archive_retention_days: ARCHIVE_RETENTION_DAYS,
+            event_retention_days: EVENT_RETENTION_DAYS,
+            bootstrap_days: BOOTSTRAP_DAYS,
+            ban_proposal_threshold: None,
+            strike_window_secs: STRIKE_WINDOW_IN_SECS,
+            ban_proposal_expiry_secs: BAN_PROPOSAL_EXPIRY_IN_SECS,
+            ban_delete_message_days: BAN_DELETE_MESSAGE_DAYS,
+            ban_proposal_warning_secs: None,
+            honeypot_warning_secs: None,
+            probation_threshold: Some(PROBATION_THRESHOLD),
+            probation_expiry_days: PROBATION_EXPIRY_DAYS,
+            repeated_mention_threshold: REPEATED_MENTION_THRESHOLD,
+            repeated_mention_window_secs: REPEATED_MENTION_WINDOW_IN_SECS,
+            repeated_mention_timeout_secs: None,
+            coordinated_attack_detection: false,
+            coordinated_attack_threshold: COORDINATED_ATTACK_THRESHOLD,
+            coordinated_attack_window_secs: COORDINATED_ATTACK_WINDOW_IN_SECS,
+            coordinated_attack_lockdown: false,
+        }
+    }
+}
+
+/// Why a value could not be applied to a setting.
+#[derive(Debug)]
+pub enum SettingError {
+    UnknownSetting,
+    InvalidValue,
+}
+
+impl GuildConfig {
+    /// Names of all settings that can be changed with [`GuildConfig::set`].
+    pub const SETTINGS: &'static [&'static str] = &[
+        "language",
+        "dm_user_locale",
+        "dm_delay_secs",
+        "dm_format",
+        "dm_queue_overflow_action",
+        "timezone",
+        "length_mode",
+        "min_message_length",
+        "length_bands",
+        "max_entry_age_secs",
+        "occurrence_threshold",
+        "duplicate_action",
+        "detection_scope",
+        "window_size",
+        "warning_count_before_action",
+        "retroactive_delete",
+        "strip_trailing_counter",
+        "ignore_replies",
+        "ignore_replies_to_same_author",
+        "skip_media_only",
+        "exempt_first_message",
+        "include_embed_in_length",
+        "include_embed_in_content",
+        "detect_similar_images",
+        "phash_distance_threshold",
+        "bot_command_prefixes",
+        "bot_command_max_length",
+        "share_fingerprints",
+        "blocked_link_detection",
+        "invite_detection",
+        "invite_occurrence_threshold",
+        "invite_window_secs",
+        "invite_timeout_secs",
+        "invite_exempt_own_guild",
+        "min_account_age_secs",
+        "new_account_action",
+        "alt_account_age_threshold_days",
+        "ban_lookback_days",
+        "alt_account_watch_secs",
+        "nsfw_channel_behavior",
+        "honeypot_action",
+        "message_sampling_rate",
+        "stop_at_first_detection",
+        "profile_precedence",
+        "audit_channel",
+        "audit_deletions",
+        "report_buttons",
+        "shadow_mode",
+        "verbose_debug_channel",
+        "verbose_sample_rate",
+        "archive_before_delete",
+        "archive_retention_days",
+        "event_retention_days",
+        "bootstrap_days",
+        "repeated_mention_threshold",
+        "repeated_mention_window_secs",
+        "repeated_mention_timeout_secs",
+        "coordinated_attack_detection",
+        "coordinated_attack_threshold",
+        "coordinated_attack_window_secs",
+        "coordinated_attack_lockdown",
+        "ban_proposal_threshold",
+        "strike_window_secs",
+        "ban_proposal_expiry_secs",
+        "ban_delete_message_days",
+        "ban_proposal_warning_secs",
+        "honeypot_warning_secs",
+        "probation_threshold",
+        "probation_expiry_days",
+    ];
+
+    /// Parses the given value and applies it to the setting with the given name. Optional settings
+    /// are cleared with the value `none`.
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<(), SettingError> {
+        match setting {
+            "language" if i18n::is_supported(value) => self.language = value.to_string(),
+            "dm_user_locale" => self.dm_user_locale = parse(value)?,
+            "dm_delay_secs" => self.dm_delay_secs = parse(value)?,
+            "dm_format" => {
+                self.dm_format = DmFormat::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "dm_queue_overflow_action" => {
+                self.dm_queue_overflow_action =
+                    DmOverflowAction::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "timezone" => {
+                let timezone = schedule::parse_timezone(value).ok_or(SettingError::InvalidValue)?;
+                self.timezone = timezone.name().to_string();
+            }
+            "length_mode" => {
+                let mode = LengthMode::parse(value).ok_or(SettingError::InvalidValue)?;
+                // Keep the minimum length unless it was given in the other unit.
+                if !mode.same_unit(self.length_mode) {
+                    self.length_mode = mode;
+                }
+            }
+            "min_message_length" => self.length_mode = self.length_mode.with_minimum(parse(value)?),
+            "length_bands" => {
+                self.length_bands = parse_optional(value, |value| {
+                    parse_length_bands(value).ok_or(SettingError::InvalidValue)
+                })?
+                .unwrap_or_default()
+            }
+            "max_entry_age_secs" => self.max_entry_age_secs = parse(value)?,
+            "occurrence_threshold" => self.occurrence_threshold = parse_threshold(value)?,
+            "duplicate_action" => {
+                self.duplicate_action =
+                    DuplicateAction::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "detection_scope" => {
+                self.detection_scope =
+                    DetectionScope::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "window_size" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                size => self.window_size = size,
+            },
+            "warning_count_before_action" => self.warning_count_before_action = parse(value)?,
+            "retroactive_delete" => self.retroactive_delete = parse(value)?,
+            "strip_trailing_counter" => self.strip_trailing_counter = parse(value)?,
+            "ignore_replies" => self.ignore_replies = parse(value)?,
+            "ignore_replies_to_same_author" => self.ignore_replies_to_same_author = parse(value)?,
+            "skip_media_only" => self.skip_media_only = parse(value)?,
+            "exempt_first_message" => self.exempt_first_message = parse(value)?,
+            "include_embed_in_length" => self.include_embed_in_length = parse(value)?,
+            "include_embed_in_content" => self.include_embed_in_content = parse(value)?,
+            "detect_similar_images" => self.detect_similar_images = parse(value)?,
+            "phash_distance_threshold" => match parse(value)? {
+                distance @ 0..=64 => self.phash_distance_threshold = distance,
+                _ => return Err(SettingError::InvalidValue),
+            },
+            // Prefixes are separated by whitespace, which no prefix can contain.
+            "bot_command_prefixes" => {
+                self.bot_command_prefixes = parse_optional(value, |value| {
+                    Ok(value.split_whitespace().map(str::to_string).collect())
+                })?
+                .unwrap_or_default()
+            }
+            "bot_command_max_length" => self.bot_command_max_length = parse(value)?,
+            "share_fingerprints" => self.share_fingerprints = parse(value)?,
+            "blocked_link_detection" => self.blocked_link_detection = parse(value)?,
+            "invite_detection" => self.invite_detection = parse(value)?,
+            "invite_occurrence_threshold" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                threshold => self.invite_occurrence_threshold = threshold,
+            },
+            "invite_window_secs" => self.invite_window_secs = parse(value)?,
+            "invite_timeout_secs" => self.invite_timeout_secs = parse_optional(value, parse)?,
+            "invite_exempt_own_guild" => self.invite_exempt_own_guild = parse(value)?,
+            "min_account_age_secs" => self.min_account_age_secs = parse(value)?,
+            "new_account_action" => {
+                self.new_account_action =
+                    NewAccountPolicy::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "alt_account_age_threshold_days" => {
+                self.alt_account_age_threshold_days =
+                    parse_optional(value, |value| match parse(value)? {
+                        0 => Err(SettingError::InvalidValue),
+                        days => Ok(days),
+                    })?
+            }
+            "ban_lookback_days" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                days => self.ban_lookback_days = days,
+            },
+            "alt_account_watch_secs" => self.alt_account_watch_secs = parse(value)?,
+            "nsfw_channel_behavior" => {
+                self.nsfw_channel_behavior =
+                    NsfwChannelPolicy::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "honeypot_action" => {
+                self.honeypot_action =
+                    HoneypotAction::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "message_sampling_rate" => match parse(value)? {
+                rate if (0.0..=1.0).contains(&rate) => self.message_sampling_rate = rate,
+                _ => return Err(SettingError::InvalidValue),
+            },
+            "stop_at_first_detection" => self.stop_at_first_detection = parse(value)?,
+            "profile_precedence" => {
+                self.profile_precedence =
+                    ProfilePrecedence::parse(value).ok_or(SettingError::InvalidValue)?
+            }
+            "audit_channel" => self.audit_channel_id = parse_optional(value, parse_channel)?,
+            "audit_deletions" => self.audit_deletions = parse(value)?,
+            "report_buttons" => self.report_buttons = parse(value)?,
+            "shadow_mode" => self.shadow_mode = parse(value)?,
+            "verbose_debug_channel" => {
+                self.verbose_debug_channel_id = parse_optional(value, parse_channel)?;
+                self.verbose_debug_expires_at = self
+                    .verbose_debug_channel_id
+                    .map(|_| unix_timestamp() + VERBOSE_DEBUG_DURATION_IN_SECS);
+            }
+            "verbose_sample_rate" => match parse(value)? {
+                rate if (0.0..=1.0).contains(&rate) => self.verbose_sample_rate = rate,
+                _ => return Err(SettingError::InvalidValue),
+            },
+            "archive_before_delete" => self.archive_before_delete = parse(value)?,
+            "archive_retention_days" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                days => self.archive_retention_days = days,
+            },
+            "event_retention_days" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                days => self.event_retention_days = days,
+            },
+            "bootstrap_days" => self.bootstrap_days = parse(value)?,
+            "repeated_mention_threshold" => self.repeated_mention_threshold = parse(value)?,
+            "repeated_mention_window_secs" => self.repeated_mention_window_secs = parse(value)?,
+            "repeated_mention_timeout_secs" => {
+                self.repeated_mention_timeout_secs = parse_optional(value, parse)?
+            }
+            "coordinated_attack_detection" => self.coordinated_attack_detection = parse(value)?,
+            "coordinated_attack_threshold" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                threshold => self.coordinated_attack_threshold = threshold,
+            },
+            "coordinated_attack_window_secs" => self.coordinated_attack_window_secs = parse(value)?,
+            "coordinated_attack_lockdown" => self.coordinated_attack_lockdown = parse(value)?,
+            "ban_proposal_threshold" => {
+                self.ban_proposal_threshold = parse_optional(value, |value| match parse(value)? {
+                    0 => Err(SettingError::InvalidValue),
+                    threshold => Ok(threshold),
+                })?
+            }
+            "strike_window_secs" => self.strike_window_secs = parse(value)?,
+            "ban_proposal_expiry_secs" => self.ban_proposal_expiry_secs = parse(value)?,
+            "ban_delete_message_days" => match parse(value)? {
+                days @ 0..=7 => self.ban_delete_message_days = days,
+                _ => return Err(SettingError::InvalidValue),
+            },
+            "ban_proposal_warning_secs" => {
+                self.ban_proposal_warning_secs = parse_optional(value, parse_warning_secs)?
+            }
+            "honeypot_warning_secs" => {
+                self.honeypot_warning_secs = parse_optional(value, parse_warning_secs)?
+            }
+            "probation_threshold" => {
+                self.probation_threshold = parse_optional(value, |value| match parse(value)? {
+                    0 => Err(SettingError::InvalidValue),
+                    threshold => Ok(threshold),
+                })?
+            }
+            "probation_expiry_days" => match parse(value)? {
+                0 => return Err(SettingError::InvalidValue),
+                days => self.probation_expiry_days = days,
+            },
+            _ if Self::SETTINGS.contains(&setting) => return Err(SettingError::InvalidValue),
+            _ => return Err(SettingError::UnknownSetting),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configured timezone, falling back to UTC should it have become invalid, e.g.
+    /// after a timezone was removed from the database.
+    pub fn timezone(&self) -> Tz {
+        schedule::parse_timezone(&self.timezone).unwrap_or(Tz::UTC)
+    }
+
+    /// Whether the given content is a short command of another bot.
+    pub fn is_bot_command(&self, content: &str) -> bool {
+        let content = content.trim();
+        content.chars().count() < self.bot_command_max_length
+            && self
+                .bot_command_prefixes
+                .iter()
+                .any(|prefix| content.starts_with(prefix.as_str()))
+    }
+
+    pub fn in_safe_hours(&self, now: DateTime<Utc>) -> bool {
+        let timezone = self.timezone();
+        self.safe_hours
+            .iter()
+            .any(|range| schedule::contains(range, timezone, now))
+    }
+
+    pub fn in_quiet_hours(&self, now: DateTime<Utc>) -> bool {
+        let timezone = self.timezone();
+        self.quiet_hours
+            .iter()
+            .any(|range| schedule::contains(range, timezone, now))
+    }
+
+    /// Returns the timestamp at which the bootstrap period of a guild joined at the given
+    /// timestamp ends.
+    pub fn bootstrap_ends_at(&self, joined_at: i64) -> i64 {
+        joined_at + i64::from(self.bootstrap_days) * 24 * 60 * 60
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Result<T, SettingError> {
+    value.trim().parse().map_err(|_| SettingError::InvalidValue)
+}
+
+/// Parses an occurrence threshold, which has to be at least two since a single message can never be
+/// a duplicate.
+pub fn parse_threshold(value: &str) -> Result<usize, SettingError> {
+    match parse(value)? {
+        threshold if threshold >= 2 => Ok(threshold),
+        _ => Err(SettingError::InvalidValue),
+    }
+}
+
+fn parse_optional<T>(
+    value: &str,
+    parse: impl FnOnce(&str) -> Result<T, SettingError>,
+) -> Result<Option<T>, SettingError> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        Ok(None)
+    } else {
+        parse(value).map(Some)
+    }
+}
+
+/// Parses the time in seconds users are warned for before they are banned. Disabling the warning
+/// is done with `none` rather than zero.
+fn parse_warning_secs(value: &str) -> Result<u64, SettingError> {
+    match parse(value)? {
+        secs @ 1..=MAX_BAN_WARNING_IN_SECS => Ok(secs),
+        _ => Err(SettingError::InvalidValue),
+    }
+}
+
+/// Parses a channel given either as a mention such as `<#123>` or as its plain ID.
+fn parse_channel(value: &str) -> Result<ChannelId, SettingError> {
+    let value = value.trim();
+    let id = value
+        .strip_prefix("<#")
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value);
+
+    match id.parse() {
+        Ok(id) if id != 0 => Ok(ChannelId::new(id)),
+        _ => Err(SettingError::InvalidValue),
+    }
+}
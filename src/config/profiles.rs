@@ -0,0 +1,452 @@
+//! Resolution of the duplicate detection settings that apply to a single message.
+//!
+//! Settings are resolved field by field in the following order, using the first layer that
+//! defines a field:
+//!
+//! 1. The threshold profile of the author, combined from the profiles of all their mapped roles.
+//! 2. The override of the channel the message was posted in.
+//! 3. The defaults of the guild, where the window depends on the length band of the message.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::{ChannelId, RoleId, UserId};
+
+use super::GuildConfig;
+
+/// Occurrence threshold under zero tolerance, which is the lowest at which a message can be a
+/// duplicate.
+const ZERO_TOLERANCE_THRESHOLD: usize = 2;
+/// Factor the window is multiplied by in NSFW channels under the stricter policy.
+const NSFW_WINDOW_FACTOR: u64 = 2;
+
+/// What happens to a message once it has been posted often enough to be considered a duplicate.
+/// Variants are ordered from the strictest to the most lenient one.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    /// Deletes the message and informs its author.
+    Delete,
+    /// Only reports the message in the audit channel.
+    Report,
+}
+
+impl DuplicateAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "delete" => Some(Self::Delete),
+            "report" => Some(Self::Report),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DuplicateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delete => f.write_str("delete"),
+            Self::Report => f.write_str("report"),
+        }
+    }
+}
+
+/// Which settings win when the roles of an author are mapped to several profiles.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfilePrecedence {
+    #[default]
+    MostLenient,
+    MostStrict,
+}
+
+impl ProfilePrecedence {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "most_lenient" => Some(Self::MostLenient),
+            "most_strict" => Some(Self::MostStrict),
+            _ => None,
+        }
+    }
+}
+
+/// How messages of accounts younger than the minimum account age are treated.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewAccountPolicy {
+    /// Exempts them from duplicate detection, since new users may not know the rules yet.
+    Skip,
+    #[default]
+    Normal,
+    /// Tracks their messages regardless of length and considers the first repost a duplicate,
+    /// since throwaway accounts are commonly created for spamming.
+    ZeroTolerance,
+}
+
+impl NewAccountPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Some(Self::Skip),
+            "normal" => Some(Self::Normal),
+            "zero_tolerance" => Some(Self::ZeroTolerance),
+            _ => None,
+        }
+    }
+}
+
+/// How messages in channels marked as NSFW are treated.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NsfwChannelPolicy {
+    #[default]
+    Same,
+    /// Exempts them from duplicate detection, e.g. where adult content is allowed to repeat.
+    Skip,
+    /// Counts reposts over a longer window, since rapid spam is more harmful there.
+    Stricter,
+}
+
+impl NsfwChannelPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "same" => Some(Self::Same),
+            "skip" => Some(Self::Skip),
+            "stricter" => Some(Self::Stricter),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to the author of a message posted in a honeypot channel.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoneypotAction {
+    /// Deletes the message and bans its author right away, without asking moderators first.
+    #[default]
+    Ban,
+    /// Only deletes the message.
+    Delete,
+}
+
+impl HoneypotAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ban" => Some(Self::Ban),
+            "delete" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when a message to an author is dropped since too many actions of the guild are
+/// queued.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DmOverflowAction {
+    /// Only counts the message as undeliverable.
+    #[default]
+    Drop,
+    /// Posts a condensed version of the message to the audit channel, mentioning the author.
+    AuditChannelFallback,
+    /// Logs a condensed version of the message for the operator.
+    LogOnly,
+}
+
+impl DmOverflowAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "drop" => Some(Self::Drop),
+            "audit_channel_fallback" => Some(Self::AuditChannelFallback),
+            "log_only" => Some(Self::LogOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Which messages count towards duplicates.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionScope {
+    /// Counts every message posted within the window.
+    #[default]
+    AllMessages,
+    /// Counts every message posted on the same day in the timezone of the guild regardless of the
+    /// window, e.g. in daily check-in channels where a repost means checking in twice.
+    DailyFirstOnly,
+    /// Counts every message that is among the most recent messages of its author regardless of
+    /// the window, as long as they were posted within the maximum entry age, e.g. to catch the
+    /// same question asked in several channels in the middle of a conversation.
+    RecentMessages,
+}
+
+impl DetectionScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "all_messages" => Some(Self::AllMessages),
+            "daily_first_only" => Some(Self::DailyFirstOnly),
+            "recent_messages" => Some(Self::RecentMessages),
+            _ => None,
+        }
+    }
+}
+
+/// A set of detection settings, each of which falls back to the next layer if absent.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ThresholdProfile {
+    pub min_message_length: Option<usize>,
+    pub window_secs: Option<u64>,
+    pub occurrence_threshold: Option<usize>,
+    pub action: Option<DuplicateAction>,
+}
+
+impl ThresholdProfile {
+    /// Combines two profiles field by field, picking the more lenient or stricter value of fields
+    /// defined by both of them. Longer minimum lengths, shorter windows and higher thresholds are
+    /// more lenient since they cause fewer messages to be considered duplicates.
+    fn combine(self, other: &Self, precedence: ProfilePrecedence) -> Self {
+        let lenient = precedence == ProfilePrecedence::MostLenient;
+        Self {
+            min_message_length: pick(self.min_message_length, other.min_message_length, lenient),
+            window_secs: pick(self.window_secs, other.window_secs, !lenient),
+            occurrence_threshold: pick(
+                self.occurrence_threshold,
+                other.occurrence_threshold,
+                lenient,
+            ),
+            action: pick(self.action, other.action, lenient),
+        }
+    }
+}
+
+/// Picks the larger of both values if `larger` is set, otherwise the smaller one.
+fn pick<T: Ord>(a: Option<T>, b: Option<T>, larger: bool) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) if larger => Some(a.max(b)),
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The detection settings that apply to a single message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DetectionSettings {
+    pub min_message_length: usize,
+    pub window_secs: u64,
+    pub occurrence_threshold: usize,
+    pub action: DuplicateAction,
+}
+
+impl GuildConfig {
+    /// Resolves the settings for a message of the given length posted in the given channel by an
+    /// author with the given roles, in the order documented in this module.
+    pub fn detection_settings(
+        &self,
+        roles: &[RoleId],
+        channel_id: ChannelId,
+        length: usize,
+    ) -> DetectionSettings {
+        let role_profile = roles
+            .iter()
+            .filter_map(|role_id| self.role_profiles.get(role_id))
+            .filter_map(|name| self.threshold_profiles.get(name))
+            .fold(ThresholdProfile::default(), |combined, profile| {
+                combined.combine(profile, self.profile_precedence)
+            });
+        let channel_override = self.channel_overrides.get(&channel_id);
+        let layers = [Some(&role_profile), channel_override];
+        let layers = layers.iter().flatten();
+
+        DetectionSettings {
+            min_message_length: layers
+                .clone()
+                .find_map(|layer| layer.min_message_length)
+                .unwrap_or(self.length_mode.minimum()),
+            window_secs: layers
+                .clone()
+                .find_map(|layer| layer.window_secs)
+                .or_else(|| {
+                    self.length_bands
+                        .iter()
+                        .find(|band| band.contains(length))
+                        .map(|band| band.window_secs)
+                })
+                .unwrap_or(self.time_to_idle_secs),
+            occurrence_threshold: layers
+                .clone()
+                .find_map(|layer| layer.occurrence_threshold)
+                .unwrap_or(self.occurrence_threshold),
+            action: layers
+                .clone()
+                .find_map(|layer| layer.action)
+                .unwrap_or(self.duplicate_action),
+        }
+    }
+
+    /// Returns the settings for a message containing an invite, which only take the action from
+    /// the given settings resolved for the message.
+    pub fn invite_settings(&self, settings: DetectionSettings) -> DetectionSettings {
+        DetectionSettings {
+            min_message_length: 0,
+            window_secs: self.invite_window_secs,
+            occurrence_threshold: self.invite_occurrence_threshold,
+            action: settings.action,
+        }
+    }
+
+    /// Returns the settings for a message of a new account under zero tolerance, which only take
+    /// the window and action from the given settings resolved for the message.
+    pub fn zero_tolerance_settings(&self, settings: DetectionSettings) -> DetectionSettings {
+        DetectionSettings {
+            min_message_length: 0,
+            occurrence_threshold: ZERO_TOLERANCE_THRESHOLD,
+            ..settings
+        }
+    }
+
+    /// Returns the settings for a message in an NSFW channel under the stricter policy, which
+    /// multiply the window of the given settings resolved for the message.
+    pub fn nsfw_settings(&self, settings: DetectionSettings) -> DetectionSettings {
+        DetectionSettings {
+            window_secs: settings.window_secs.saturating_mul(NSFW_WINDOW_FACTOR),
+            ..settings
+        }
+    }
+
+    /// Whether the account of the given user is younger than the minimum account age at the given
+    /// timestamp. Account ages are derived from the creation time encoded in the user ID.
+    pub fn is_new_account(&self, user_id: UserId, now: i64) -> bool {
+        let age = now - user_id.created_at().unix_timestamp();
+        self.min_account_age_secs > 0 && age < self.min_account_age_secs as i64
+    }
+
+    /// Returns the longest window any message could be checked against, which is how long
+    /// messages have to be tracked for.
+    pub fn max_window_secs(&self) -> u64 {
+        let invite_window_secs = self.invite_detection.then_some(self.invite_window_secs);
+        let max_window_secs = self
+            .threshold_profiles
+            .values()
+            .chain(self.channel_overrides.values())
+            .filter_map(|profile| profile.window_secs)
+            .chain(self.length_bands.iter().map(|band| band.window_secs))
+            .chain(invite_window_secs)
+            .fold(self.time_to_idle_secs, u64::max);
+
+        // Windows are multiplied in NSFW channels under the stricter policy.
+        match self.nsfw_channel_behavior {
+            NsfwChannelPolicy::Stricter => max_window_secs.saturating_mul(NSFW_WINDOW_FACTOR),
+            NsfwChannelPolicy::Same | NsfwChannelPolicy::Skip => max_window_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LengthBand;
+
+    const LENIENT: RoleId = RoleId::new(1);
+    const STRICT: RoleId = RoleId::new(2);
+    const PARTIAL: RoleId = RoleId::new(3);
+    const OVERRIDDEN: ChannelId = ChannelId::new(10);
+    const OTHER: ChannelId = ChannelId::new(11);
+
+    fn config(precedence: ProfilePrecedence) -> GuildConfig {
+        let profile =
+            |min_message_length, window_secs, occurrence_threshold, action| ThresholdProfile {
+                min_message_length,
+                window_secs,
+                occurrence_threshold,
+                action,
+            };
+
+        GuildConfig {
+            profile_precedence: precedence,
+            threshold_profiles: [
+                (
+                    "lenient".to_string(),
+                    profile(Some(100), Some(60), Some(5), Some(DuplicateAction::Report)),
+                ),
+                (
+                    "strict".to_string(),
+                    profile(None, Some(600), Some(3), None),
+                ),
+                ("partial".to_string(), profile(None, None, Some(4), None)),
+            ]
+            .into(),
+            role_profiles: [
+                (LENIENT, "lenient".to_string()),
+                (STRICT, "strict".to_string()),
+                (PARTIAL, "partial".to_string()),
+            ]
+            .into(),
+            channel_overrides: [(OVERRIDDEN, profile(Some(10), Some(900), Some(7), None))].into(),
+            length_bands: vec![
+                LengthBand {
+                    min: 0,
+                    max: 20,
+                    window_secs: 30,
+                },
+                LengthBand {
+                    min: 21,
+                    max: 200,
+                    window_secs: 300,
+                },
+            ],
+            ..GuildConfig::default()
+        }
+    }
+
+    /// What a case is named, the precedence of profiles, the roles of the author, the channel and
+    /// the length of the message, and the minimum length, window, threshold and action expected.
+    type Case<'a> = (
+        &'a str,
+        ProfilePrecedence,
+        &'a [RoleId],
+        ChannelId,
+        usize,
+        (usize, u64, usize, DuplicateAction),
+    );
+
+    #[test]
+    fn resolves_settings_in_order() {
+        use DuplicateAction::{Delete, Report};
+        use ProfilePrecedence::{MostLenient, MostStrict};
+
+        let defaults = GuildConfig::default();
+        let (min, window, threshold) = (
+            defaults.length_mode.minimum(),
+            defaults.time_to_idle_secs,
+            defaults.occurrence_threshold,
+        );
+        #[rustfmt::skip]
+        let cases: &[Case] = &[
+            ("guild defaults outside of bands", MostLenient, &[], OTHER, 300, (min, window, threshold, Delete)),
+            ("band of short messages", MostLenient, &[], OTHER, 20, (min, 30, threshold, Delete)),
+            ("band of long messages", MostLenient, &[], OTHER, 21, (min, 300, threshold, Delete)),
+            ("unmapped role", MostLenient, &[RoleId::new(42)], OTHER, 300, (min, window, threshold, Delete)),
+            ("channel over guild and band", MostLenient, &[], OVERRIDDEN, 20, (10, 900, 7, Delete)),
+            ("role over channel", MostLenient, &[LENIENT], OVERRIDDEN, 20, (100, 60, 5, Report)),
+            ("role over channel field by field", MostLenient, &[PARTIAL], OVERRIDDEN, 20, (10, 900, 4, Delete)),
+            ("role over band", MostLenient, &[STRICT], OTHER, 20, (min, 600, 3, Delete)),
+            ("most lenient of roles", MostLenient, &[LENIENT, STRICT], OTHER, 20, (100, 60, 5, Report)),
+            ("most strict of roles", MostStrict, &[LENIENT, STRICT], OTHER, 20, (100, 600, 3, Report)),
+            ("most strict of three roles", MostStrict, &[PARTIAL, LENIENT, STRICT], OVERRIDDEN, 20, (100, 600, 3, Report)),
+        ];
+
+        for (name, precedence, roles, channel_id, length, expected) in cases {
+            let (min_message_length, window_secs, occurrence_threshold, action) = *expected;
+            assert_eq!(
+                config(*precedence).detection_settings(roles, *channel_id, *length),
+                DetectionSettings {
+                    min_message_length,
+                    window_secs,
+                    occurrence_threshold,
+                    action,
+                },
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Lower bounds for settings that would effectively disable duplicate detection, e.g. a window of
+//! zero seconds. Guild administrators cannot go below these bounds, only the operator of the bot
+//! can change them for their deployment.
+
+use std::{env, fmt, str::FromStr};
+
+use super::{GuildConfig, ThresholdProfile};
+use crate::error::BroomError;
+
+/// Default minimum of all windows in seconds.
+const MIN_WINDOW_IN_SECS: u64 = 10;
+/// Default minimum of all minimum message lengths.
+const MIN_MESSAGE_LENGTH: usize = 1;
+/// Default minimum of all occurrence thresholds.
+const MIN_OCCURRENCE_THRESHOLD: usize = 2;
+
+/// The lower bounds guild configurations are validated against.
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigLimits {
+    pub min_window_secs: u64,
+    pub min_message_length: usize,
+    pub min_occurrence_threshold: usize,
+}
+
+impl Default for ConfigLimits {
+    fn default() -> Self {
+        Self {
+            min_window_secs: MIN_WINDOW_IN_SECS,
+            min_message_length: MIN_MESSAGE_LENGTH,
+            min_occurrence_threshold: MIN_OCCURRENCE_THRESHOLD,
+        }
+    }
+}
+
+impl ConfigLimits {
+    /// Reads the limits from the `MIN_WINDOW_SECS`, `MIN_MESSAGE_LENGTH` and
+    /// `MIN_OCCURRENCE_THRESHOLD` environment variables, falling back to the defaults for unset
+    /// ones.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let defaults = Self::default();
+
+        Ok(Self {
+            min_window_secs: env_or("MIN_WINDOW_SECS", defaults.min_window_secs)?,
+            min_message_length: env_or("MIN_MESSAGE_LENGTH", defaults.min_message_length)?,
+            min_occurrence_threshold: env_or(
+                "MIN_OCCURRENCE_THRESHOLD",
+                defaults.min_occurrence_threshold,
+            )?,
+        })
+    }
+}
+
+fn env_or<T: FromStr>(name: &'static str, default: T) -> Result<T, BroomError> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig(name)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A setting whose value is below the limit configured by the operator.
+#[derive(Debug)]
+pub struct Violation {
+    /// Path of the setting, e.g. `threshold_profiles.regular.window_secs`.
+    pub setting: String,
+    pub value: u64,
+    pub minimum: u64,
+}
+
+/// All violated limits of a configuration.
+#[derive(Debug)]
+pub struct ConfigValidationError {
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for ConfigValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, violation) in self.violations.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(
+                f,
+                "{} is {} but has to be at least {}",
+                violation.setting, violation.value, violation.minimum
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigValidationError {}
+
+impl GuildConfig {
+    /// Checks the guild defaults, all threshold profiles, length bands and channel overrides
+    /// against the given limits, and that tracked messages are kept for at least the window of the
+    /// guild.
+    pub fn validate(&self, limits: &ConfigLimits) -> Result<(), ConfigValidationError> {
+        let mut violations = Vec::new();
+        let defaults = ThresholdProfile {
+            min_message_length: Some(self.length_mode.minimum()),
+            window_secs: Some(self.time_to_idle_secs),
+            occurrence_threshold: Some(self.occurrence_threshold),
+            action: None,
+        };
+        validate_profile(&defaults, "", limits, &mut violations);
+        if self.max_entry_age_secs < self.time_to_idle_secs {
+            violations.push(Violation {
+                setting: "max_entry_age_secs".to_string(),
+                value: self.max_entry_age_secs,
+                minimum: self.time_to_idle_secs,
+            });
+        }
+        if self.invite_detection {
+            // Invites may be acted on at their first occurrence, so only their window is limited.
+            let invites = ThresholdProfile {
+                window_secs: Some(self.invite_window_secs),
+                ..ThresholdProfile::default()
+            };
+            validate_profile(&invites, "invite_", limits, &mut violations);
+        }
+
+        let mut profiles = self.threshold_profiles.iter().collect::<Vec<_>>();
+        profiles.sort_by_key(|(name, _)| *name);
+        for (name, profile) in profiles {
+            let prefix = format!("threshold_profiles.{}.", name);
+            validate_profile(profile, &prefix, limits, &mut violations);
+        }
+        for band in &self.length_bands {
+            let prefix = format!("length_bands.{}-{}.", band.min, band.max);
+            let profile = ThresholdProfile {
+                window_secs: Some(band.window_secs),
+                ..ThresholdProfile::default()
+            };
+            validate_profile(&profile, &prefix, limits, &mut violations);
+        }
+        for (channel_id, profile) in &self.channel_overrides {
+            let prefix = format!("channel_overrides.{}.", channel_id);
+            validate_profile(profile, &prefix, limits, &mut violations);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigValidationError { violations })
+        }
+    }
+}
+
+fn validate_profile(
+    profile: &ThresholdProfile,
+    prefix: &str,
+    limits: &ConfigLimits,
+    violations: &mut Vec<Violation>,
+) {
+    let mut check = |setting: &str, value: Option<u64>, minimum: u64| {
+        if let Some(value) = value.filter(|value| *value < minimum) {
+            violations.push(Violation {
+                setting: format!("{}{}", prefix, setting),
+                value,
+                minimum,
+            });
+        }
+    };
+
+    check(
+        "min_message_length",
+        profile.min_message_length.map(|value| value as u64),
+        limits.min_message_length as u64,
+    );
+    check("window_secs", profile.window_secs, limits.min_window_secs);
+    check(
+        "occurrence_threshold",
+        profile.occurrence_threshold.map(|value| value as u64),
+        limits.min_occurrence_threshold as u64,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_tracked_messages_for_at_least_the_window() {
+        let limits = ConfigLimits::default();
+        let mut config = GuildConfig::default();
+        assert!(config.validate(&limits).is_ok());
+
+        let window_secs = config.time_to_idle_secs;
+        config
+            .set("max_entry_age_secs", &window_secs.to_string())
+            .unwrap();
+        assert!(config.validate(&limits).is_ok());
+
+        config
+            .set("max_entry_age_secs", &(window_secs - 1).to_string())
+            .unwrap();
+        let violations = config.validate(&limits).unwrap_err().violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].setting, "max_entry_age_secs");
+        assert_eq!(violations[0].minimum, window_secs);
+    }
+}
@@ -0,0 +1,346 @@
+//! Extraction of the content that is checked for duplicates from a message.
+
+use serenity::model::channel::{Attachment, Message};
+use sha2::{Digest, Sha256};
+
+/// The content of a message that is tracked and compared against earlier messages.
+pub struct TrackedContent {
+    pub text: String,
+    /// Whether the content was forwarded from another message rather than posted directly.
+    pub forwarded: bool,
+    /// The attachments the content only consists of, if it does, in which case its length is
+    /// meaningless. Their text is only known once they are hashed by `hash_attachments`.
+    pub attachments: Vec<Attachment>,
+}
+
+/// Returns the content of the given message, or `None` if it has nothing that could be tracked or
+/// only consists of attachments that are to be skipped.
+///
+/// Forwarded messages arrive with an empty content and carry the forwarded content as snapshots
+/// instead. Forwarding a forward snapshots the original content again, so no nesting has to be
+/// handled. The content is attributed to the forwarder since they are the one cross-posting it.
+pub fn tracked_content(msg: &Message, skip_media_only: bool) -> Option<TrackedContent> {
+    if msg.message_snapshots.is_empty() {
+        if !msg.attachments.is_empty() && msg.content.trim().is_empty() {
+            if skip_media_only {
+                return None;
+            }
+            return Some(TrackedContent {
+                text: String::new(),
+                forwarded: false,
+                attachments: msg.attachments.clone(),
+            });
+        }
+
+        return Some(TrackedContent {
+            text: msg.content.clone(),
+            forwarded: false,
+            attachments: Vec::new(),
+        });
+    }
+
+    let text = msg
+        .message_snapshots
+        .iter()
+        .map(|snapshot| snapshot.content.as_str())
+        .filter(|content| !content.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !text.is_empty() {
+        return Some(TrackedContent {
+            text,
+            forwarded: true,
+            attachments: Vec::new(),
+        });
+    }
+
+    let attachments = msg
+        .message_snapshots
+        .iter()
+        .flat_map(|snapshot| snapshot.attachments.iter().cloned())
+        .collect::<Vec<_>>();
+    if attachments.is_empty() {
+        return None;
+    }
+
+    Some(TrackedContent {
+        text: String::new(),
+        forwarded: true,
+        attachments,
+    })
+}
+
+/// Returns the titles and descriptions of the embeds of the given message, e.g. the title of a
+/// linked video, or an empty string if it has none.
+pub fn embed_text(msg: &Message) -> String {
+    msg.embeds
+        .iter()
+        .flat_map(|embed| [embed.title.as_deref(), embed.description.as_deref()])
+        .flatten()
+        .filter(|text| !text.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Maximum number of characters of a trailing token that is considered a counter.
+const MAX_COUNTER_LENGTH: usize = 6;
+
+/// Maximum size in bytes of an attachment that is downloaded to be hashed.
+const MAX_HASHED_ATTACHMENT_SIZE: u32 = 8 * 1024 * 1024;
+
+impl TrackedContent {
+    /// Whether the content only consists of attachments.
+    pub fn attachments_only(&self) -> bool {
+        !self.attachments.is_empty()
+    }
+
+    /// Downloads the attachments the content consists of and tracks it by their SHA-256 hashes,
+    /// since every repost or forward receives new attachment URLs and identifiers even though the
+    /// files are the same. Returns `false` if any of them could not be hashed, e.g. as it is too
+    /// large, in which case the content must not be tracked at all: different files commonly share
+    /// their names and sizes, e.g. screenshots named `image.png`.
+    pub async fn hash_attachments(&mut self) -> bool {
+        let mut hashes = Vec::with_capacity(self.attachments.len());
+        for attachment in &self.attachments {
+            match attachment_hash(attachment).await {
+                Some(hash) => hashes.push(hash),
+                None => return false,
+            }
+        }
+
+        self.text = format!("attachments:{}", hashes.join(","));
+        true
+    }
+
+    /// Strips a trailing counter such as the `3` of `bump 3` or the `+2` of `LFG +2`, so that
+    /// sequences of otherwise identical messages are tracked as the same content. Single symbols
+    /// such as punctuation or emoji are stripped as well. Nothing is stripped if the remaining
+    /// text would no longer be long enough to be tracked.
+    pub fn strip_trailing_counter(&mut self, long_enough: impl Fn(&str) -> bool) {
+        if self.attachments_only() {
+            return;
+        }
+
+        let trimmed = self.text.trim_end();
+        let Some((rest, token)) = trimmed.rsplit_once(char::is_whitespace) else {
+            return;
+        };
+        let rest = rest.trim_end();
+        if is_counter(token) && long_enough(rest) {
+            self.text = rest.to_string();
+        }
+    }
+}
+
+/// Shortens the given text to at most the given number of characters, marking that it was cut.
+pub fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+
+    let mut truncated = text.chars().take(max_length - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Whether the token is a number, optionally prefixed with `+` or `#`, or a single symbol.
+fn is_counter(token: &str) -> bool {
+    let digits = token.strip_prefix(['+', '#']).unwrap_or(token);
+    let is_number = !digits.is_empty()
+        && digits.len() <= MAX_COUNTER_LENGTH
+        && digits.chars().all(|c| c.is_ascii_digit());
+    // Emoji may consist of several code points such as skin tone modifiers.
+    let is_symbol = token.chars().count() <= 4 && !token.chars().any(char::is_alphanumeric);
+
+    is_number || is_symbol
+}
+
+/// Downloads the given attachment and returns the SHA-256 hash of its content, or `None` if it is
+/// too large or could not be downloaded.
+async fn attachment_hash(attachment: &Attachment) -> Option<String> {
+    if attachment.size > MAX_HASHED_ATTACHMENT_SIZE {
+        return None;
+    }
+    match attachment.download().await {
+        Ok(bytes) => Some(format!("{:x}", Sha256::digest(&bytes))),
+        Err(e) => {
+            tracing::warn!(
+                "Could not download attachment {} to hash it: {:?}",
+                attachment.id,
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn attachment(size: u32) -> serde_json::Value {
+        json!({
+            "id": "1",
+            "filename": "image.png",
+            "size": size,
+            "url": "https://cdn.discordapp.com/attachments/1/1/image.png",
+            "proxy_url": "https://media.discordapp.net/attachments/1/1/image.png",
+        })
+    }
+
+    fn snapshot(content: &str, attachments: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "message": {
+                "content": content,
+                "timestamp": "2026-01-01T00:00:00Z",
+                "mentions": [],
+                "attachments": attachments,
+                "embeds": [],
+                "type": 0,
+            }
+        })
+    }
+
+    fn forward(snapshots: Vec<serde_json::Value>) -> Message {
+        let mut msg = json!(message("", Vec::new()));
+        msg["message_snapshots"] = json!(snapshots);
+
+        serde_json::from_value(msg).unwrap()
+    }
+
+    fn message(content: &str, attachments: Vec<serde_json::Value>) -> Message {
+        serde_json::from_value(json!({
+            "id": "2",
+            "channel_id": "3",
+            "author": { "id": "4", "username": "author", "discriminator": "0" },
+            "content": content,
+            "timestamp": "2026-01-01T00:00:00Z",
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": attachments,
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn does_not_track_attachments_too_large_to_hash() {
+        let msg = message("", vec![attachment(MAX_HASHED_ATTACHMENT_SIZE + 1)]);
+        let mut content = tracked_content(&msg, false).unwrap();
+
+        assert!(content.attachments_only());
+        assert!(!content.hash_attachments().await);
+        assert!(content.text.is_empty());
+    }
+
+    #[test]
+    fn tracks_text() {
+        let content = tracked_content(&message("hello there", Vec::new()), true).unwrap();
+
+        assert_eq!(content.text, "hello there");
+        assert!(!content.forwarded);
+        assert!(!content.attachments_only());
+    }
+
+    #[test]
+    fn tracks_attachments_only_unless_skipped() {
+        let msg = message(" ", vec![attachment(10)]);
+
+        assert!(tracked_content(&msg, true).is_none());
+        let content = tracked_content(&msg, false).unwrap();
+        assert!(content.attachments_only());
+        assert!(!content.forwarded);
+    }
+
+    #[test]
+    fn tracks_forwarded_text() {
+        let msg = forward(vec![snapshot("hello there", Vec::new())]);
+        let content = tracked_content(&msg, true).unwrap();
+
+        assert_eq!(content.text, "hello there");
+        assert!(content.forwarded);
+        assert!(!content.attachments_only());
+    }
+
+    #[test]
+    fn tracks_forwarded_attachments_only() {
+        let msg = forward(vec![snapshot("", vec![attachment(10), attachment(20)])]);
+        let content = tracked_content(&msg, true).unwrap();
+
+        assert!(content.forwarded);
+        assert_eq!(content.attachments.len(), 2);
+        assert!(tracked_content(&forward(vec![snapshot("", Vec::new())]), false).is_none());
+    }
+
+    #[test]
+    fn tracks_forwards_of_forwards_like_the_first_forward() {
+        // Forwarding a forward snapshots the original content rather than the empty forward, and
+        // snapshots carry no snapshots of their own.
+        let first = forward(vec![snapshot("hello there", Vec::new())]);
+        let mut nested = snapshot("hello there", Vec::new());
+        nested["message"]["message_snapshots"] = json!([snapshot("hello there", Vec::new())]);
+        let second = forward(vec![nested]);
+
+        assert_eq!(
+            tracked_content(&first, true).unwrap().text,
+            tracked_content(&second, true).unwrap().text
+        );
+        let msg = forward(vec![
+            snapshot("hello", Vec::new()),
+            snapshot("there", Vec::new()),
+        ]);
+        assert_eq!(tracked_content(&msg, true).unwrap().text, "hello\nthere");
+    }
+
+    fn stripped(text: &str, min_length: usize) -> String {
+        let mut content = tracked_content(&message(text, Vec::new()), true).unwrap();
+        content.strip_trailing_counter(|rest| rest.chars().count() > min_length);
+
+        content.text
+    }
+
+    #[test]
+    fn strips_trailing_counters() {
+        for text in ["bump 1", "bump 2", "bump 3 ", "bump #4"] {
+            assert_eq!(stripped(text, 3), "bump");
+        }
+        for text in ["LFG +1", "LFG +2", "LFG !", "LFG \u{1F44D}\u{1F3FD}"] {
+            assert_eq!(stripped(text, 2), "LFG");
+        }
+    }
+
+    #[test]
+    fn strips_years_only_where_the_rest_matches() {
+        assert_eq!(
+            stripped("see you in 2025", 5),
+            stripped("see you in 2026", 5)
+        );
+        assert_ne!(
+            stripped("see you in 2025", 5),
+            stripped("see them in 2025", 5)
+        );
+        assert_eq!(stripped("see you in 2025", 5), "see you in");
+    }
+
+    #[test]
+    fn keeps_texts_that_would_become_too_short() {
+        assert_eq!(stripped("bump 1", 4), "bump 1");
+        assert_eq!(stripped("1", 0), "1");
+        assert_eq!(stripped("version 1234567", 3), "version 1234567");
+        assert_eq!(stripped("bump one", 3), "bump one");
+    }
+
+    #[test]
+    fn keeps_attachments_only() {
+        let mut content = tracked_content(&message("", vec![attachment(10)]), false).unwrap();
+        content.strip_trailing_counter(|_| true);
+
+        assert!(content.text.is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! Per-guild counters of the actions taken, bucketed by hour and persisted in the database.
+//!
+//! Counting happens in memory and is flushed periodically, so that handling a message never waits
+//! for a database write.
+
+use std::{collections::HashMap, mem, sync::Arc, time::Duration};
+
+use serenity::{model::prelude::GuildId, prelude::TypeMapKey};
+use tokio::sync::Mutex;
+
+use crate::db::{unix_timestamp, Database};
+
+/// Interval in seconds in which the buffered counts are written to the database.
+const FLUSH_INTERVAL_IN_SECS: u64 = 30;
+/// Time in seconds after which hourly buckets are pruned. The totals are kept indefinitely.
+const RETENTION_IN_SECS: i64 = 90 * 24 * 60 * 60;
+const HOUR_IN_SECS: i64 = 60 * 60;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GuildAction {
+    /// A duplicate was detected and acted upon, no matter the action.
+    Detected,
+    Deleted,
+    /// A duplicate was only reported to the moderators.
+    Reported,
+    /// The author of a deleted duplicate could not be messaged.
+    DmFailed,
+}
+
+impl GuildAction {
+    pub const ALL: [GuildAction; 4] = [
+        GuildAction::Detected,
+        GuildAction::Deleted,
+        GuildAction::Reported,
+        GuildAction::DmFailed,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Detected => "detected",
+            Self::Deleted => "deleted",
+            Self::Reported => "reported",
+            Self::DmFailed => "dm_failed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+    }
+}
+
+/// The number of times each action was taken within some period.
+#[derive(Debug, Default)]
+pub struct ActionCounts(HashMap<GuildAction, u64>);
+
+impl ActionCounts {
+    pub fn get(&self, action: GuildAction) -> u64 {
+        self.0.get(&action).copied().unwrap_or(0)
+    }
+}
+
+pub struct ActionCounters {
+    pending: Mutex<HashMap<(GuildId, i64, GuildAction), u64>>,
+    database: Arc<Database>,
+}
+
+impl ActionCounters {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            database,
+        }
+    }
+
+    /// Counts an action in the bucket of the current hour.
+    pub async fn record(&self, guild_id: GuildId, action: GuildAction) {
+        self.record_at(guild_id, action, unix_timestamp()).await;
+    }
+
+    async fn record_at(&self, guild_id: GuildId, action: GuildAction, timestamp: i64) {
+        let hour = bucket(timestamp);
+        *self
+            .pending
+            .lock()
+            .await
+            .entry((guild_id, hour, action))
+            .or_default() += 1;
+    }
+
+    /// Discards all buffered counts of the given guild, e.g. since all of its data was deleted.
+    pub async fn forget(&self, guild_id: GuildId) {
+        self.pending
+            .lock()
+            .await
+            .retain(|(pending_guild_id, _, _), _| *pending_guild_id != guild_id);
+    }
+
+    /// Writes all buffered counts to the database. Counts that could not be written are buffered
+    /// again so that they are retried with the next flush.
+    pub async fn flush(&self) {
+        let pending = mem::take(&mut *self.pending.lock().await);
+        if pending.is_empty() {
+            return;
+        }
+
+        let counts = pending
+            .iter()
+            .map(|((guild_id, hour, action), count)| {
+                (*guild_id, *hour, action.as_str(), *count as i64)
+            })
+            .collect::<Vec<_>>();
+        if let Err(e) = self.database.add_action_counts(&counts).await {
+            tracing::error!(
+                "There was an error while attempting to persist the action counters: {:?}",
+                e
+            );
+
+            let mut buffered = self.pending.lock().await;
+            for (key, count) in pending {
+                *buffered.entry(key).or_default() += count;
+            }
+        }
+    }
+
+    /// Flushes the buffered counts and prunes expired buckets periodically until the process
+    /// exits.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_IN_SECS));
+        loop {
+            interval.tick().await;
+            self.flush().await;
+
+            if let Err(e) = self
+                .database
+                .prune_action_counts(unix_timestamp() - RETENTION_IN_SECS)
+                .await
+            {
+                tracing::error!(
+                    "There was an error while attempting to prune the action counters: {:?}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Returns the counts of the given guild since the given timestamp, or in total if none is
+    /// given. Buffered counts are flushed first so that they are included.
+    pub async fn counts(
+        &self,
+        guild_id: GuildId,
+        since: Option<i64>,
+    ) -> Result<ActionCounts, sqlx::Error> {
+        self.flush().await;
+        let since = since.map(bucket);
+        let counts = self
+            .database
+            .action_counts(guild_id, since)
+            .await?
+            .into_iter()
+            .filter_map(|(action, count)| Some((GuildAction::parse(&action)?, count as u64)))
+            .collect();
+
+        Ok(ActionCounts(counts))
+    }
+}
+
+/// Returns the start of the hour the given timestamp falls into.
+fn bucket(timestamp: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(HOUR_IN_SECS)
+}
+
+pub struct SharedCounters;
+
+impl TypeMapKey for SharedCounters {
+    type Value = Arc<ActionCounters>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(1);
+    const OTHER_GUILD: GuildId = GuildId::new(2);
+
+    async fn counters() -> (ActionCounters, Arc<Database>) {
+        let database = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+
+        (ActionCounters::new(database.clone()), database)
+    }
+
+    #[test]
+    fn buckets_timestamps_by_hour() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(HOUR_IN_SECS - 1), 0);
+        assert_eq!(bucket(HOUR_IN_SECS), HOUR_IN_SECS);
+        assert_eq!(bucket(-1), -HOUR_IN_SECS);
+    }
+
+    #[tokio::test]
+    async fn buffers_counts_until_they_are_flushed() {
+        let (counters, database) = counters().await;
+        counters.record(GUILD, GuildAction::Deleted).await;
+        counters.record(GUILD, GuildAction::Deleted).await;
+
+        assert!(database
+            .action_counts(GUILD, None)
+            .await
+            .unwrap()
+            .is_empty());
+        counters.flush().await;
+        assert_eq!(
+            database.action_counts(GUILD, None).await.unwrap(),
+            [("deleted".to_string(), 2)]
+        );
+        // Flushing again does not count anything twice.
+        counters.flush().await;
+        assert_eq!(
+            counters
+                .counts(GUILD, None)
+                .await
+                .unwrap()
+                .get(GuildAction::Deleted),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn includes_buffered_counts() {
+        let (counters, _) = counters().await;
+        counters.record(GUILD, GuildAction::Reported).await;
+        counters.record(OTHER_GUILD, GuildAction::Reported).await;
+
+        let counts = counters.counts(GUILD, None).await.unwrap();
+        assert_eq!(counts.get(GuildAction::Reported), 1);
+        assert_eq!(counts.get(GuildAction::Deleted), 0);
+    }
+
+    #[tokio::test]
+    async fn rolls_over_at_hour_boundaries() {
+        let (counters, _) = counters().await;
+        let hour = 1_000 * HOUR_IN_SECS;
+        counters
+            .record_at(GUILD, GuildAction::Detected, hour - 1)
+            .await;
+        counters.record_at(GUILD, GuildAction::Detected, hour).await;
+        counters
+            .record_at(GUILD, GuildAction::Detected, hour + HOUR_IN_SECS - 1)
+            .await;
+
+        let counters = &counters;
+        let count = |since| async move {
+            counters
+                .counts(GUILD, since)
+                .await
+                .unwrap()
+                .get(GuildAction::Detected)
+        };
+        assert_eq!(count(None).await, 3);
+        assert_eq!(count(Some(hour - 1)).await, 3);
+        assert_eq!(count(Some(hour)).await, 2);
+        // Counts are bucketed by hour, so a timestamp within the hour includes all of it.
+        assert_eq!(count(Some(hour + 1)).await, 2);
+        assert_eq!(count(Some(hour + HOUR_IN_SECS)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn forgets_buffered_counts_of_a_guild() {
+        let (counters, _) = counters().await;
+        counters.record(GUILD, GuildAction::Deleted).await;
+        counters.record(OTHER_GUILD, GuildAction::Deleted).await;
+        counters.forget(GUILD).await;
+
+        let counters = &counters;
+        let count = |guild_id| async move {
+            counters
+                .counts(guild_id, None)
+                .await
+                .unwrap()
+                .get(GuildAction::Deleted)
+        };
+        assert_eq!(count(GUILD).await, 0);
+        assert_eq!(count(OTHER_GUILD).await, 1);
+    }
+}
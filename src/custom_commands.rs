@@ -0,0 +1,141 @@
+//! Slash commands guilds define for their members, each backed by one of a fixed menu of actions,
+//! e.g. reporting a message to the moderators. Members cannot run anything beyond these actions,
+//! so the commands are safe to expose to everyone rather than only to moderators.
+
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::{CreateCommand, CreateCommandOption},
+    http::Http,
+    model::{application::CommandOptionType, prelude::GuildId},
+};
+
+/// Maximum number of custom commands per guild.
+pub const MAX_CUSTOM_COMMANDS: usize = 10;
+/// Maximum length in characters of the name of a command, as Discord allows it.
+const MAX_NAME_LENGTH: usize = 32;
+/// Maximum length in characters of the description of a command, as Discord allows it.
+pub const MAX_DESCRIPTION_LENGTH: usize = 100;
+/// Maximum length in characters of the reason members give for a report.
+const MAX_REASON_LENGTH: u16 = 500;
+
+/// What a custom command does when a member invokes it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomAction {
+    /// Posts a link to a message to the audit channel for moderators to review.
+    ReportToAuditChannel,
+    /// Tells the member whether and why the bot deleted one of their messages.
+    CheckMessageId,
+    /// Stops or resumes messages from the bot to the member about their deleted messages.
+    SelfOptoutDms,
+}
+
+impl CustomAction {
+    pub const ALL: [CustomAction; 3] = [
+        CustomAction::ReportToAuditChannel,
+        CustomAction::CheckMessageId,
+        CustomAction::SelfOptoutDms,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ReportToAuditChannel => "report_to_audit_channel",
+            Self::CheckMessageId => "check_message_id",
+            Self::SelfOptoutDms => "self_optout_dms",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value.trim())
+    }
+
+    /// Description of commands whose guild did not describe them.
+    fn default_description(self) -> &'static str {
+        match self {
+            Self::ReportToAuditChannel => "Report a message to the moderators.",
+            Self::CheckMessageId => "Check whether the bot deleted a message of yours, and why.",
+            Self::SelfOptoutDms => "Stop or resume messages from the bot about deleted messages.",
+        }
+    }
+}
+
+impl fmt::Display for CustomAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CustomCommand {
+    pub action: CustomAction,
+    /// Shown to members in the command picker, or the default of the action if empty.
+    pub description: String,
+}
+
+/// Whether the given name is valid for a command, which Discord requires to be lowercase. `broom`
+/// is taken by the commands of the bot itself.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.chars().count())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        && name != "broom"
+}
+
+fn build(name: &str, command: &CustomCommand) -> CreateCommand {
+    let description = match command.description.trim() {
+        "" => command.action.default_description(),
+        description => description,
+    };
+    let builder = CreateCommand::new(name).description(description);
+
+    match command.action {
+        CustomAction::ReportToAuditChannel => builder
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "message",
+                    "A link to the message.",
+                )
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "reason",
+                    "What is wrong with the message.",
+                )
+                .max_length(MAX_REASON_LENGTH),
+            ),
+        CustomAction::CheckMessageId => builder.add_option(
+            // Message IDs exceed the range of integer options.
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "message_id",
+                "The ID of your message.",
+            )
+            .required(true),
+        ),
+        CustomAction::SelfOptoutDms => builder,
+    }
+}
+
+/// Replaces the commands registered for the given guild with its custom commands, which removes
+/// all of them if it has none.
+pub async fn sync(
+    http: &Http,
+    guild_id: GuildId,
+    commands: &HashMap<String, CustomCommand>,
+) -> Result<(), serenity::Error> {
+    let commands = commands
+        .iter()
+        .map(|(name, command)| build(name, command))
+        .collect::<Vec<_>>();
+    guild_id.set_commands(http, commands).await?;
+
+    Ok(())
+}
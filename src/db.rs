@@ -0,0 +1,1213 @@
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serenity::{
+    model::{
+        channel::Message,
+        prelude::{ChannelId, GuildId, MessageId, UserId},
+    },
+    prelude::TypeMapKey,
+};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow},
+    types::Json,
+    Row, SqlitePool,
+};
+
+use crate::{
+    archive::ArchivedMessage,
+    config::GuildConfig,
+    detectors::Detection,
+    history::{HistoryAction, HistoryEntry},
+    preferences::DmFormat,
+    quiet_hours::DeferredAction,
+    sampling::DetectionSample,
+};
+
+/// Time in seconds after which a recorded DM reachability is no longer trusted, since users may
+/// change their privacy settings at any time.
+const DM_REACHABILITY_TTL_IN_SECS: i64 = 24 * 60 * 60;
+/// Result codes of SQLite for a database that is locked by another connection.
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+/// Statements deleting all rows of a guild, by table. The DM reachability and preferences are
+/// recorded per user and the shared fingerprints do not belong to any single guild, so neither of
+/// them is included. The join of the bot is kept as well, since a reset does not make the bot any
+/// newer to the guild.
+const DELETE_GUILD_STATEMENTS: &[(&str, &str)] = &[
+    (
+        "guild_configs",
+        "DELETE FROM guild_configs WHERE guild_id = ?",
+    ),
+    (
+        "guild_action_counts",
+        "DELETE FROM guild_action_counts WHERE guild_id = ?",
+    ),
+    (
+        "guild_action_totals",
+        "DELETE FROM guild_action_totals WHERE guild_id = ?",
+    ),
+    (
+        "action_history",
+        "DELETE FROM action_history WHERE guild_id = ?",
+    ),
+    (
+        "ban_proposals",
+        "DELETE FROM ban_proposals WHERE guild_id = ?",
+    ),
+    (
+        "detection_samples",
+        "DELETE FROM detection_samples WHERE guild_id = ?",
+    ),
+    (
+        "deleted_messages",
+        "DELETE FROM deleted_messages WHERE guild_id = ?",
+    ),
+    (
+        "user_strike_records",
+        "DELETE FROM user_strike_records WHERE guild_id = ?",
+    ),
+    (
+        "deferred_actions",
+        "DELETE FROM deferred_actions WHERE guild_id = ?",
+    ),
+    (
+        "pending_escalations",
+        "DELETE FROM pending_escalations WHERE guild_id = ?",
+    ),
+    ("known_users", "DELETE FROM known_users WHERE guild_id = ?"),
+    (
+        "perceptual_hashes",
+        "DELETE FROM perceptual_hashes WHERE guild_id = ?",
+    ),
+];
+
+pub struct Database {
+    pool: SqlitePool,
+}
+
+impl Database {
+    /// Opens the SQLite database at the given URL, creating it if necessary, and applies all
+    /// pending migrations.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        sqlx::migrate!().run(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Records whether a direct message could be delivered to the given user.
+    pub async fn set_dm_reachable(
+        &self,
+        user_id: UserId,
+        reachable: bool,
+    ) -> Result<(), sqlx::Error> {
+        let now = unix_timestamp();
+        sqlx::query("DELETE FROM dm_reachability WHERE checked_at <= ?")
+            .bind(now - DM_REACHABILITY_TTL_IN_SECS)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO dm_reachability (user_id, dm_reachable, checked_at) VALUES (?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET dm_reachable = excluded.dm_reachable, checked_at = excluded.checked_at",
+        )
+        .bind(user_id.get() as i64)
+        .bind(reachable)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns whether direct messages could be delivered to the given user when this was last
+    /// checked within the past 24 hours, or `None` if that is unknown.
+    pub async fn dm_reachable(&self, user_id: UserId) -> Result<Option<bool>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT dm_reachable FROM dm_reachability WHERE user_id = ? AND checked_at > ?",
+        )
+        .bind(user_id.get() as i64)
+        .bind(unix_timestamp() - DM_REACHABILITY_TTL_IN_SECS)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.get("dm_reachable")))
+    }
+
+    /// Records how the given user wants direct messages to be formatted.
+    pub async fn set_dm_format(
+        &self,
+        user_id: UserId,
+        format: DmFormat,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_preferences (user_id, dm_format) VALUES (?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET dm_format = excluded.dm_format",
+        )
+        .bind(user_id.get() as i64)
+        .bind(format.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns how the given user wants direct messages to be formatted, or `None` if they never
+    /// said so.
+    pub async fn dm_format(&self, user_id: UserId) -> Result<Option<DmFormat>, sqlx::Error> {
+        let row = sqlx::query("SELECT dm_format FROM user_preferences WHERE user_id = ?")
+            .bind(user_id.get() as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.and_then(|row| DmFormat::parse(row.get("dm_format"))))
+    }
+
+    /// Records whether the given user opted out of messages about their deleted messages.
+    pub async fn set_dm_opt_out(
+        &self,
+        user_id: UserId,
+        opted_out: bool,
+    ) -> Result<(), sqlx::Error> {
+        let query = if opted_out {
+            sqlx::query("INSERT OR IGNORE INTO dm_opt_outs (user_id, opted_out_at) VALUES (?, ?)")
+                .bind(user_id.get() as i64)
+                .bind(unix_timestamp())
+        } else {
+            sqlx::query("DELETE FROM dm_opt_outs WHERE user_id = ?").bind(user_id.get() as i64)
+        };
+        query.execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    /// Whether the given user opted out of messages about their deleted messages.
+    pub async fn dm_opted_out(&self, user_id: UserId) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT 1 FROM dm_opt_outs WHERE user_id = ?")
+            .bind(user_id.get() as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    pub async fn guild_config(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Option<GuildConfig>, sqlx::Error> {
+        let row = sqlx::query("SELECT config FROM guild_configs WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.get::<Json<GuildConfig>, _>("config").0))
+    }
+
+    pub async fn set_guild_config(
+        &self,
+        guild_id: GuildId,
+        config: &GuildConfig,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO guild_configs (guild_id, config) VALUES (?, ?)
+            ON CONFLICT (guild_id) DO UPDATE SET config = excluded.config",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(Json(config))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds the given counts to their hourly buckets and to the totals of their guilds.
+    pub async fn add_action_counts(
+        &self,
+        counts: &[(GuildId, i64, &str, i64)],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        for (guild_id, hour, action, count) in counts {
+            sqlx::query(
+                "INSERT INTO guild_action_counts (guild_id, hour, action, count) VALUES (?, ?, ?, ?)
+                ON CONFLICT (guild_id, hour, action) DO UPDATE SET count = count + excluded.count",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(hour)
+            .bind(action)
+            .bind(count)
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query(
+                "INSERT INTO guild_action_totals (guild_id, action, count) VALUES (?, ?, ?)
+                ON CONFLICT (guild_id, action) DO UPDATE SET count = count + excluded.count",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(action)
+            .bind(count)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await
+    }
+
+    /// Deletes all hourly buckets starting before the given timestamp.
+    pub async fn prune_action_counts(&self, before: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM guild_action_counts WHERE hour < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the number of times each action was taken in the given guild since the given
+    /// timestamp, or in total if none is given.
+    pub async fn action_counts(
+        &self,
+        guild_id: GuildId,
+        since: Option<i64>,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let query = match since {
+            Some(since) => sqlx::query(
+                "SELECT action, SUM(count) AS count FROM guild_action_counts
+                WHERE guild_id = ? AND hour >= ? GROUP BY action",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(since),
+            None => sqlx::query("SELECT action, count FROM guild_action_totals WHERE guild_id = ?")
+                .bind(guild_id.get() as i64),
+        };
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("action"), row.get("count")))
+            .collect())
+    }
+
+    pub async fn record_action(
+        &self,
+        guild_id: GuildId,
+        entry: &HistoryEntry,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO action_history (guild_id, user_id, action, channel_id, moderator_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(entry.user_id.get() as i64)
+        .bind(entry.action.as_str())
+        .bind(entry.channel_id.map(|channel_id| channel_id.get() as i64))
+        .bind(entry.moderator_id.map(|user_id| user_id.get() as i64))
+        .bind(entry.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the most recent entries of the history of the given user, newest first.
+    pub async fn recent_actions(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        limit: u32,
+    ) -> Result<Vec<HistoryEntry>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT action, channel_id, moderator_id, created_at FROM action_history
+            WHERE guild_id = ? AND user_id = ? ORDER BY created_at DESC, id DESC LIMIT ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(HistoryEntry {
+                    user_id,
+                    action: HistoryAction::parse(row.get("action"))?,
+                    channel_id: row
+                        .get::<Option<i64>, _>("channel_id")
+                        .map(|id| ChannelId::new(id as u64)),
+                    moderator_id: row
+                        .get::<Option<i64>, _>("moderator_id")
+                        .map(|id| UserId::new(id as u64)),
+                    created_at: row.get("created_at"),
+                })
+            })
+            .collect())
+    }
+
+    /// Returns at most the given number of entries of the history of all users of the given guild
+    /// created within the given range of timestamps along with their IDs, oldest first. Pages
+    /// continue after the given entry of the previous page, if any.
+    pub async fn guild_actions(
+        &self,
+        guild_id: GuildId,
+        since: Option<i64>,
+        until: Option<i64>,
+        after: Option<(i64, &HistoryEntry)>,
+        limit: u32,
+    ) -> Result<Vec<(i64, HistoryEntry)>, sqlx::Error> {
+        let (after_id, after_created_at) =
+            after.map_or((i64::MIN, i64::MIN), |(id, entry)| (id, entry.created_at));
+        let rows = sqlx::query(
+            "SELECT id, user_id, action, channel_id, moderator_id, created_at FROM action_history
+            WHERE guild_id = ? AND created_at >= ? AND created_at < ?
+                AND (created_at > ? OR (created_at = ? AND id > ?))
+            ORDER BY created_at, id LIMIT ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(since.unwrap_or(i64::MIN))
+        .bind(until.unwrap_or(i64::MAX))
+        .bind(after_created_at)
+        .bind(after_created_at)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let entry = HistoryEntry {
+                    user_id: UserId::new(row.get::<i64, _>("user_id") as u64),
+                    action: HistoryAction::parse(row.get("action"))?,
+                    channel_id: row
+                        .get::<Option<i64>, _>("channel_id")
+                        .map(|id| ChannelId::new(id as u64)),
+                    moderator_id: row
+                        .get::<Option<i64>, _>("moderator_id")
+                        .map(|id| UserId::new(id as u64)),
+                    created_at: row.get("created_at"),
+                };
+                Some((row.get("id"), entry))
+            })
+            .collect())
+    }
+
+    /// Returns how often the given action was taken against the given user since the given
+    /// timestamp.
+    pub async fn count_actions(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        action: HistoryAction,
+        since: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count FROM action_history
+            WHERE guild_id = ? AND user_id = ? AND action = ? AND created_at >= ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(action.as_str())
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get::<i64, _>("count") as u64)
+    }
+
+    /// Returns how often the given action was taken against any user since the given timestamp.
+    pub async fn count_guild_actions(
+        &self,
+        guild_id: GuildId,
+        action: HistoryAction,
+        since: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS count FROM action_history
+            WHERE guild_id = ? AND action = ? AND created_at >= ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(action.as_str())
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get::<i64, _>("count") as u64)
+    }
+
+    /// Returns the users the given action was taken against most often since the given timestamp
+    /// along with how often, most often first.
+    pub async fn count_actions_by_user(
+        &self,
+        guild_id: GuildId,
+        action: HistoryAction,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<(UserId, u64)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT user_id, COUNT(*) AS count FROM action_history
+            WHERE guild_id = ? AND action = ? AND created_at >= ?
+            GROUP BY user_id ORDER BY count DESC LIMIT ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(action.as_str())
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let user_id = UserId::new(row.get::<i64, _>("user_id") as u64);
+                (user_id, row.get::<i64, _>("count") as u64)
+            })
+            .collect())
+    }
+
+    pub async fn create_ban_proposal(&self, proposal: &BanProposal) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO ban_proposals (message_id, channel_id, guild_id, user_id, expires_at)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(proposal.message_id.get() as i64)
+        .bind(proposal.channel_id.get() as i64)
+        .bind(proposal.guild_id.get() as i64)
+        .bind(proposal.user_id.get() as i64)
+        .bind(proposal.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the undecided proposal posted as the given message, if any.
+    pub async fn open_ban_proposal(
+        &self,
+        message_id: MessageId,
+    ) -> Result<Option<BanProposal>, sqlx::Error> {
+        let row =
+            sqlx::query("SELECT * FROM ban_proposals WHERE message_id = ? AND outcome IS NULL")
+                .bind(message_id.get() as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.as_ref().map(ban_proposal))
+    }
+
+    /// Whether a proposal to ban the given user is awaiting a decision, including expired ones
+    /// that have not been closed yet.
+    pub async fn has_open_ban_proposal(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT 1 FROM ban_proposals WHERE guild_id = ? AND user_id = ? AND outcome IS NULL",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Returns all undecided proposals that expired before the given timestamp.
+    pub async fn expired_ban_proposals(&self, now: i64) -> Result<Vec<BanProposal>, sqlx::Error> {
+        let rows =
+            sqlx::query("SELECT * FROM ban_proposals WHERE outcome IS NULL AND expires_at <= ?")
+                .bind(now)
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(rows.iter().map(ban_proposal).collect())
+    }
+
+    /// Records the outcome of a proposal. Returns `false` if it had already been decided on, e.g.
+    /// by another moderator clicking at the same time, in which case nothing is changed.
+    pub async fn resolve_ban_proposal(
+        &self,
+        message_id: MessageId,
+        outcome: HistoryAction,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE ban_proposals SET outcome = ? WHERE message_id = ? AND outcome IS NULL",
+        )
+        .bind(outcome.as_str())
+        .bind(message_id.get() as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Returns the users who posted in the given guild while its first messages were exempt.
+    pub async fn known_users(&self, guild_id: GuildId) -> Result<Vec<UserId>, sqlx::Error> {
+        let rows = sqlx::query("SELECT user_id FROM known_users WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| UserId::new(row.get::<i64, _>("user_id") as u64))
+            .collect())
+    }
+
+    pub async fn record_known_user(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO known_users (guild_id, user_id) VALUES (?, ?)")
+            .bind(guild_id.get() as i64)
+            .bind(user_id.get() as i64)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "perceptual_hashing")]
+    pub async fn record_perceptual_hash(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        phash: u64,
+        first_seen: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO perceptual_hashes (guild_id, user_id, phash, first_seen) VALUES (?, ?, ?, ?)",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(phash as i64)
+        .bind(first_seen)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the perceptual hashes of the images the given user posted in the given guild since
+    /// the given timestamp.
+    #[cfg(feature = "perceptual_hashing")]
+    pub async fn recent_perceptual_hashes(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        since: i64,
+    ) -> Result<Vec<u64>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT phash FROM perceptual_hashes
+            WHERE guild_id = ? AND user_id = ? AND first_seen >= ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row.get::<i64, _>("phash") as u64)
+            .collect())
+    }
+
+    /// Schedules the given ban. Returns `false` if a ban of the user is pending already, in which
+    /// case nothing is changed.
+    pub async fn create_pending_escalation(
+        &self,
+        escalation: &PendingEscalation,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO pending_escalations (guild_id, user_id, moderator_id, reason, fire_at)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(escalation.guild_id.get() as i64)
+        .bind(escalation.user_id.get() as i64)
+        .bind(escalation.moderator_id.map(|user_id| user_id.get() as i64))
+        .bind(&escalation.reason)
+        .bind(escalation.fire_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Cancels the pending ban of the given user. Returns `false` if none was pending, e.g. since
+    /// it was executed already.
+    pub async fn cancel_pending_escalation(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM pending_escalations WHERE guild_id = ? AND user_id = ?")
+                .bind(guild_id.get() as i64)
+                .bind(user_id.get() as i64)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Removes and returns all pending bans due at the given timestamp, so that bans cancelled in
+    /// the meantime are not returned and no other task executes them as well.
+    pub async fn take_due_escalations(
+        &self,
+        now: i64,
+    ) -> Result<Vec<PendingEscalation>, sqlx::Error> {
+        let rows = sqlx::query("DELETE FROM pending_escalations WHERE fire_at <= ? RETURNING *")
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(pending_escalation).collect())
+    }
+
+    /// Deletes all rows of the given guild within a single transaction and returns the number of
+    /// deleted rows by table.
+    pub async fn delete_guild_data(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<(&'static str, u64)>, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        let mut deleted = Vec::new();
+        for (table, statement) in DELETE_GUILD_STATEMENTS {
+            let result = sqlx::query(*statement)
+                .bind(guild_id.get() as i64)
+                .execute(&mut *transaction)
+                .await?;
+            deleted.push((*table, result.rows_affected()));
+        }
+        transaction.commit().await?;
+
+        Ok(deleted)
+    }
+
+    /// Records when the bot joined the given guild, replacing an earlier join.
+    pub async fn record_guild_join(
+        &self,
+        guild_id: GuildId,
+        joined_at: i64,
+        bootstrap_completed: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO guild_joins (guild_id, joined_at, bootstrap_completed) VALUES (?, ?, ?)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET joined_at = excluded.joined_at, bootstrap_completed = excluded.bootstrap_completed",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(joined_at)
+        .bind(bootstrap_completed)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns when the bot joined the given guild and whether its bootstrap period is over.
+    pub async fn guild_join(&self, guild_id: GuildId) -> Result<Option<(i64, bool)>, sqlx::Error> {
+        let row = sqlx::query("SELECT * FROM guild_joins WHERE guild_id = ?")
+            .bind(guild_id.get() as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| (row.get("joined_at"), row.get("bootstrap_completed"))))
+    }
+
+    /// Returns the guilds whose bootstrap period has not been completed yet along with when the
+    /// bot joined them.
+    pub async fn bootstrapping_guilds(&self) -> Result<Vec<(GuildId, i64)>, sqlx::Error> {
+        let rows = sqlx::query("SELECT * FROM guild_joins WHERE bootstrap_completed = 0")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let guild_id = GuildId::new(row.get::<i64, _>("guild_id") as u64);
+                (guild_id, row.get("joined_at"))
+            })
+            .collect())
+    }
+
+    /// Marks the bootstrap period of the given guild as completed. Returns `false` if it already
+    /// was, in which case moderators must not be told again.
+    pub async fn complete_bootstrap(&self, guild_id: GuildId) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE guild_joins SET bootstrap_completed = 1
+            WHERE guild_id = ? AND bootstrap_completed = 0",
+        )
+        .bind(guild_id.get() as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Puts the given user on probation in the given guild as of the given timestamp.
+    pub async fn start_probation(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        started_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_strike_records (guild_id, user_id, on_probation, probation_started_at)
+            VALUES (?, ?, 1, ?)
+            ON CONFLICT (guild_id, user_id) DO UPDATE
+            SET on_probation = 1, probation_started_at = excluded.probation_started_at",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .bind(started_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ends the probation of the given user in the given guild. Returns `false` if they were not
+    /// on probation.
+    pub async fn end_probation(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE user_strike_records SET on_probation = 0
+            WHERE guild_id = ? AND user_id = ? AND on_probation = 1",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.get() as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Returns the users on probation in the given guild along with when their probation started.
+    pub async fn probations(&self, guild_id: GuildId) -> Result<Vec<(UserId, i64)>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT * FROM user_strike_records WHERE guild_id = ? AND on_probation = 1",
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let user_id = UserId::new(row.get::<i64, _>("user_id") as u64);
+                (user_id, row.get("probation_started_at"))
+            })
+            .collect())
+    }
+
+    /// Returns the users on probation in any guild along with when their probation started.
+    pub async fn all_probations(&self) -> Result<Vec<(GuildId, UserId, i64)>, sqlx::Error> {
+        let rows = sqlx::query("SELECT * FROM user_strike_records WHERE on_probation = 1")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    GuildId::new(row.get::<i64, _>("guild_id") as u64),
+                    UserId::new(row.get::<i64, _>("user_id") as u64),
+                    row.get("probation_started_at"),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns all shared fingerprints that have not expired at the given timestamp.
+    pub async fn scam_fingerprints(
+        &self,
+        now: i64,
+    ) -> Result<Vec<(String, Vec<GuildId>, i64)>, sqlx::Error> {
+        let rows = sqlx::query("SELECT * FROM scam_fingerprints WHERE expires_at > ?")
+            .bind(now)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let guild_ids = row.get::<Json<Vec<GuildId>>, _>("guild_ids").0;
+                (row.get("fingerprint"), guild_ids, row.get("expires_at"))
+            })
+            .collect())
+    }
+
+    pub async fn set_scam_fingerprint(
+        &self,
+        fingerprint: &str,
+        guild_ids: &[GuildId],
+        expires_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO scam_fingerprints (fingerprint, guild_ids, expires_at) VALUES (?, ?, ?)
+            ON CONFLICT (fingerprint) DO UPDATE SET guild_ids = excluded.guild_ids, expires_at = excluded.expires_at",
+        )
+        .bind(fingerprint)
+        .bind(Json(guild_ids))
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn record_detection_sample(
+        &self,
+        sample: &DetectionSample,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO detection_samples (guild_id, channel_id, user_id, detection, content, similarity, config, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(sample.guild_id.get() as i64)
+        .bind(sample.channel_id.get() as i64)
+        .bind(sample.user_id.get() as i64)
+        .bind(sample.detection)
+        .bind(&sample.content)
+        .bind(sample.similarity)
+        .bind(Json(&sample.config))
+        .bind(sample.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the contents of the most recent samples of the given detection in the given guild
+    /// since the given time, newest first.
+    #[cfg(feature = "ml_suggestions")]
+    pub async fn detection_sample_contents(
+        &self,
+        guild_id: GuildId,
+        detection: &str,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT content FROM detection_samples
+            WHERE guild_id = ? AND detection = ? AND created_at >= ?
+            ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(detection)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| row.get::<String, _>("content"))
+            .collect())
+    }
+
+    pub async fn record_deferred_action(
+        &self,
+        guild_id: GuildId,
+        action: &DeferredAction,
+        deferred_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO deferred_actions (guild_id, channel_id, message_id, detection, deferred_at)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(action.channel_id.get() as i64)
+        .bind(action.message_id.get() as i64)
+        .bind(Json(&action.detection))
+        .bind(deferred_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the IDs of all guilds with deferred actions.
+    pub async fn guilds_with_deferred_actions(&self) -> Result<Vec<GuildId>, sqlx::Error> {
+        let rows = sqlx::query("SELECT DISTINCT guild_id FROM deferred_actions")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| GuildId::new(row.get::<i64, _>("guild_id") as u64))
+            .collect())
+    }
+
+    /// Removes and returns the deferred actions of the given guild in the order they were deferred
+    /// in, so that no other task takes them as well.
+    pub async fn take_deferred_actions(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<DeferredAction>, sqlx::Error> {
+        let rows = sqlx::query(
+            "DELETE FROM deferred_actions WHERE guild_id = ?
+            RETURNING id, channel_id, message_id, detection",
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut actions = rows
+            .iter()
+            .map(|row| {
+                Ok((
+                    row.get::<i64, _>("id"),
+                    DeferredAction {
+                        channel_id: ChannelId::new(row.get::<i64, _>("channel_id") as u64),
+                        message_id: MessageId::new(row.get::<i64, _>("message_id") as u64),
+                        detection: row.try_get::<Json<Detection>, _>("detection")?.0,
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+        // The order of the rows returned by a deletion is undefined.
+        actions.sort_by_key(|(id, _)| *id);
+
+        Ok(actions.into_iter().map(|(_, action)| action).collect())
+    }
+
+    /// Archives a message that is about to be deleted for the given reason.
+    pub async fn archive_message(
+        &self,
+        guild_id: GuildId,
+        msg: &Message,
+        reason: HistoryAction,
+        deleted_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO deleted_messages (message_id, guild_id, reason, message, deleted_at)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(msg.id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .bind(reason.as_str())
+        .bind(Json(msg))
+        .bind(deleted_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns the archived message with the given ID if it was deleted in the given guild.
+    pub async fn archived_message(
+        &self,
+        guild_id: GuildId,
+        message_id: MessageId,
+    ) -> Result<Option<ArchivedMessage>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT reason, message, deleted_at FROM deleted_messages WHERE message_id = ? AND guild_id = ?",
+        )
+        .bind(message_id.get() as i64)
+        .bind(guild_id.get() as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        Ok(Some(ArchivedMessage {
+            message: row.try_get::<Json<Message>, _>("message")?.0,
+            reason: row.get("reason"),
+            deleted_at: row.get("deleted_at"),
+        }))
+    }
+
+    /// Returns the IDs of all guilds with archived messages.
+    pub async fn archiving_guilds(&self) -> Result<Vec<GuildId>, sqlx::Error> {
+        let rows = sqlx::query("SELECT DISTINCT guild_id FROM deleted_messages")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| GuildId::new(row.get::<i64, _>("guild_id") as u64))
+            .collect())
+    }
+
+    /// Deletes the archived messages of the given guild that were deleted before the given
+    /// timestamp and returns how many there were.
+    pub async fn purge_archived_messages(
+        &self,
+        guild_id: GuildId,
+        before: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM deleted_messages WHERE guild_id = ? AND deleted_at < ?")
+                .bind(guild_id.get() as i64)
+                .bind(before)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Returns the IDs of all guilds with recorded actions, detection samples or perceptual hashes.
+    pub async fn guilds_with_events(&self) -> Result<Vec<GuildId>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT guild_id FROM action_history UNION SELECT guild_id FROM detection_samples
+            UNION SELECT guild_id FROM perceptual_hashes",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| GuildId::new(row.get::<i64, _>("guild_id") as u64))
+            .collect())
+    }
+
+    /// Deletes the actions, detection samples and perceptual hashes of the given guild that were
+    /// recorded before the given timestamp and returns how many there were.
+    pub async fn prune_events(&self, guild_id: GuildId, before: i64) -> Result<u64, sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+        let mut pruned = 0;
+        for statement in [
+            "DELETE FROM action_history WHERE guild_id = ? AND created_at < ?",
+            "DELETE FROM detection_samples WHERE guild_id = ? AND created_at < ?",
+            "DELETE FROM perceptual_hashes WHERE guild_id = ? AND first_seen < ?",
+        ] {
+            pruned += sqlx::query(statement)
+                .bind(guild_id.get() as i64)
+                .bind(before)
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+
+        Ok(pruned)
+    }
+
+    /// Deletes all shared fingerprints that expired before the given timestamp.
+    pub async fn prune_scam_fingerprints(&self, now: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM scam_fingerprints WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A proposal to ban a user, posted to the audit channel for moderators to decide on.
+#[derive(Clone, Debug)]
+pub struct BanProposal {
+    pub message_id: MessageId,
+    pub channel_id: ChannelId,
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub expires_at: i64,
+}
+
+fn ban_proposal(row: &SqliteRow) -> BanProposal {
+    BanProposal {
+        message_id: MessageId::new(row.get::<i64, _>("message_id") as u64),
+        channel_id: ChannelId::new(row.get::<i64, _>("channel_id") as u64),
+        guild_id: GuildId::new(row.get::<i64, _>("guild_id") as u64),
+        user_id: UserId::new(row.get::<i64, _>("user_id") as u64),
+        expires_at: row.get("expires_at"),
+    }
+}
+
+/// A ban of a user who was warned about it, executed once the warning window passed.
+#[derive(Clone, Debug)]
+pub struct PendingEscalation {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    /// The moderator that approved the ban, if it was not decided on by the bot on its own.
+    pub moderator_id: Option<UserId>,
+    pub reason: String,
+    pub fire_at: i64,
+}
+
+fn pending_escalation(row: &SqliteRow) -> PendingEscalation {
+    PendingEscalation {
+        guild_id: GuildId::new(row.get::<i64, _>("guild_id") as u64),
+        user_id: UserId::new(row.get::<i64, _>("user_id") as u64),
+        moderator_id: row
+            .get::<Option<i64>, _>("moderator_id")
+            .map(|id| UserId::new(id as u64)),
+        reason: row.get("reason"),
+        fire_at: row.get("fire_at"),
+    }
+}
+
+pub fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// Whether the error is due to the database being locked by another connection, in which case the
+/// statement can be retried later.
+pub fn is_locked(error: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(error) = error else {
+        return false;
+    };
+
+    // Extended result codes carry the primary result code in their lowest byte.
+    error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+pub struct SharedDatabase;
+
+impl TypeMapKey for SharedDatabase {
+    type Value = Arc<Database>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pages_guild_actions_oldest_first() {
+        let database = Database::connect("sqlite::memory:").await.unwrap();
+        let guild_id = GuildId::new(1);
+        for (user_id, created_at) in [(1, 20), (2, 10), (3, 10), (4, 10), (5, 30), (6, 40)] {
+            let entry = HistoryEntry::new(UserId::new(user_id), HistoryAction::Deleted, created_at);
+            database.record_action(guild_id, &entry).await.unwrap();
+        }
+        let other = HistoryEntry::new(UserId::new(7), HistoryAction::Deleted, 10);
+        database
+            .record_action(GuildId::new(2), &other)
+            .await
+            .unwrap();
+
+        let mut users = Vec::new();
+        let mut last = None;
+        loop {
+            let after = last.as_ref().map(|(id, entry)| (*id, entry));
+            let page = database
+                .guild_actions(guild_id, None, Some(40), after, 2)
+                .await
+                .unwrap();
+            users.extend(page.iter().map(|(_, entry)| entry.user_id.get()));
+            if page.len() < 2 {
+                break;
+            }
+            last = page.into_iter().last();
+        }
+
+        assert_eq!(users, [2, 3, 4, 1, 5]);
+    }
+}
@@ -0,0 +1,136 @@
+//! Detection of coordinated attacks, in which many users post the same content within a short
+//! time, e.g. during a raid. Unlike duplicates, every single author may only post it once, so the
+//! content is counted across authors and moderators are alerted about the attack as a whole.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serenity::model::{channel::Message, prelude::UserId};
+
+use super::{Detection, Detector, GuildView};
+use crate::{actions::MessageContext, error::BroomError, ignore::normalize};
+
+/// Interval in seconds in which contents that have left their window are purged.
+const SWEEP_INTERVAL_IN_SECS: u64 = 60;
+
+/// Recent posts of each normalized content in a guild, along with who posted them.
+pub struct ContentFrequency {
+    posts: Mutex<HashMap<String, Vec<(UserId, Instant)>>>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl ContentFrequency {
+    pub fn new() -> Self {
+        Self {
+            posts: Mutex::new(HashMap::new()),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Records that the given user posted the given normalized content and returns the number of
+    /// distinct users who posted it within the window if there are more than `threshold`. The
+    /// posts of a returned content are reset so that each attack is only reported once.
+    pub fn record(
+        &self,
+        content: String,
+        user_id: UserId,
+        threshold: usize,
+        window: Duration,
+        now: Instant,
+    ) -> Option<usize> {
+        let mut posts = self.posts.lock().unwrap_or_else(|e| e.into_inner());
+        self.sweep(&mut posts, window, now);
+
+        let authors = posts.entry(content).or_default();
+        // Only the latest post of each author is kept, so that every author counts once.
+        authors.retain(|(author, posted_at)| {
+            *author != user_id && now.duration_since(*posted_at) <= window
+        });
+        authors.push((user_id, now));
+        if authors.len() <= threshold {
+            return None;
+        }
+
+        let count = authors.len();
+        authors.clear();
+
+        Some(count)
+    }
+
+    /// Removes all contents that have not been posted within the window.
+    fn sweep(
+        &self,
+        posts: &mut HashMap<String, Vec<(UserId, Instant)>>,
+        window: Duration,
+        now: Instant,
+    ) {
+        let mut last_sweep = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*last_sweep) < Duration::from_secs(SWEEP_INTERVAL_IN_SECS) {
+            return;
+        }
+
+        posts.retain(|_, authors| {
+            authors
+                .last()
+                .is_some_and(|(_, posted_at)| now.duration_since(*posted_at) <= window)
+        });
+        *last_sweep = now;
+    }
+}
+
+/// Checks messages against the [`ContentFrequency`] of their guild. It only alerts moderators, so
+/// the other detectors still act on the individual authors.
+pub struct CoordinatedAttackDetector;
+
+#[serenity::async_trait]
+impl Detector for CoordinatedAttackDetector {
+    fn name(&self) -> &'static str {
+        "coordinated_attacks"
+    }
+
+    async fn check(
+        &self,
+        _context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError> {
+        let (state, config) = (guild.state, guild.config);
+        if !config.coordinated_attack_detection {
+            return Ok(None);
+        }
+        if !guild.sampled {
+            return Ok(guild.skip("not sampled"));
+        }
+        let content = normalize(&msg.content);
+        let roles = msg
+            .member
+            .as_ref()
+            .map(|member| member.roles.as_slice())
+            .unwrap_or_default();
+        let length = config.length_mode.measure(&content);
+        // Short contents such as greetings are commonly posted by many users at once.
+        if length
+            <= config
+                .detection_settings(roles, msg.channel_id, length)
+                .min_message_length
+        {
+            return Ok(guild.skip("shorter than the minimum length"));
+        }
+        if state.ignore_list.read().await.matches(&content) {
+            return Ok(guild.skip("ignore pattern"));
+        }
+
+        let users = state.content_frequency.record(
+            content,
+            msg.author.id,
+            config.coordinated_attack_threshold,
+            Duration::from_secs(config.coordinated_attack_window_secs),
+            Instant::now(),
+        );
+
+        Ok(users.map(|users| Detection::CoordinatedAttack { users }))
+    }
+}
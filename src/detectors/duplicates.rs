@@ -0,0 +1,386 @@
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serenity::model::{channel::Message, prelude::GuildId};
+
+use super::{Detection, Detector, Duplicate, GuildView};
+use crate::{
+    actions::MessageContext,
+    config::{DetectionScope, DuplicateAction, GuildConfig, NewAccountPolicy, NsfwChannelPolicy},
+    content::{self, tracked_content},
+    db::unix_timestamp,
+    error::BroomError,
+    fingerprints::FingerprintRegistries,
+    invites, schedule,
+    state::{shared, CacheEntry},
+    stats::SharedStats,
+};
+
+/// Detects messages posted several times within the window, and contents that other guilds
+/// sharing fingerprints removed.
+pub struct DuplicateDetector;
+
+#[serenity::async_trait]
+impl Detector for DuplicateDetector {
+    fn name(&self) -> &'static str {
+        "duplicates"
+    }
+
+    async fn check(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError> {
+        let (guild_id, state, config) = (guild.guild_id, guild.state, guild.config);
+        if is_ignored_reply(msg, config) {
+            return Ok(guild.skip("ignored reply"));
+        }
+        let new_account = config.is_new_account(msg.author.id, unix_timestamp());
+        if new_account && config.new_account_action == NewAccountPolicy::Skip {
+            return Ok(guild.skip("new account"));
+        }
+        let nsfw = context
+            .actions
+            .channel_nsfw(guild_id, msg.channel_id)
+            .unwrap_or(false);
+        if nsfw && config.nsfw_channel_behavior == NsfwChannelPolicy::Skip {
+            return Ok(guild.skip("NSFW channel"));
+        }
+        let Some(mut content) = tracked_content(msg, config.skip_media_only) else {
+            return Ok(guild.skip("no trackable content"));
+        };
+        if content.attachments_only() {
+            // Attachments are only tracked by their hashes, and downloading them is what sampling
+            // saves, so reposts of them cannot be recognized in unsampled messages.
+            if !guild.sampled {
+                return Ok(guild.skip("not sampled"));
+            }
+            if !content.hash_attachments().await {
+                return Ok(guild.skip("unhashable attachments"));
+            }
+        }
+        let bridged_author = if config.trusted_bridges.contains_key(&msg.author.id) {
+            let bridge_list = state.bridge_list.read().await;
+            let Some(bridged) = bridge_list.attribute(msg.author.id, &content.text, &msg.embeds)
+            else {
+                // Attributing the message to the bridge would flag it for relaying everything.
+                return Ok(guild.skip("unattributed bridge message"));
+            };
+            content.text = bridged.content;
+            Some(bridged.author)
+        } else {
+            None
+        };
+        if state.ignore_list.read().await.matches(&content.text) {
+            return Ok(guild.skip("ignore pattern"));
+        }
+        let roles = msg
+            .member
+            .as_ref()
+            .map(|member| member.roles.as_slice())
+            .unwrap_or_default();
+        // Bridges commonly relay content in embeds, which is attributed to its author already.
+        let embed_text = if bridged_author.is_none() && !content.attachments_only() {
+            content::embed_text(msg)
+        } else {
+            String::new()
+        };
+        let mut length = config.length_mode.measure(&content.text);
+        if config.include_embed_in_length {
+            length += config.length_mode.measure(&embed_text);
+        }
+        let mut settings = config.detection_settings(roles, msg.channel_id, length);
+        if new_account && config.new_account_action == NewAccountPolicy::ZeroTolerance {
+            settings = config.zero_tolerance_settings(settings);
+        }
+        // Users on probation and possible alt accounts are held to the same settings as new
+        // accounts under zero tolerance.
+        if state.probations.read().await.contains(&msg.author.id)
+            || state.is_watched(msg.author.id, unix_timestamp()).await
+        {
+            settings = config.zero_tolerance_settings(settings);
+        }
+        if nsfw && config.nsfw_channel_behavior == NsfwChannelPolicy::Stricter {
+            settings = config.nsfw_settings(settings);
+        }
+        // Resolving invites is deferred for unsampled messages until they repost a tracked one.
+        let invite_code = match tracked_invite(&content.text, config) {
+            Some(code)
+                if guild.sampled && exempt_invite(context, guild_id, code, config).await? =>
+            {
+                None
+            }
+            code => code.map(str::to_string),
+        };
+        if let Some(code) = &invite_code {
+            // Invites are short and commonly posted with varying text, so only the code is tracked.
+            content.text = format!("invite:{}", code);
+            settings = config.invite_settings(settings);
+        } else {
+            let length_mode = config.length_mode;
+            if !content.attachments_only() && length <= settings.min_message_length {
+                return Ok(guild.skip("shorter than the minimum length"));
+            }
+            if config.include_embed_in_content && !embed_text.is_empty() {
+                content.text = format!("embed:{}", embed_text);
+            } else if config.strip_trailing_counter {
+                content.strip_trailing_counter(|text| {
+                    length_mode.measure(text) > settings.min_message_length
+                });
+            }
+        }
+        let now = Instant::now();
+
+        // Bridged messages are tracked by their original author, who has no user ID of their own.
+        let mut key = match &bridged_author {
+            Some(author) => (
+                msg.author.id,
+                format!("bridged:{}:{}", author, content.text),
+            ),
+            None => (msg.author.id, content.text.clone()),
+        };
+        if config.detection_scope == DetectionScope::DailyFirstOnly {
+            // Reposts on the next day are tracked separately, even before the old entry is
+            // evicted.
+            let today = schedule::local_date(config.timezone(), Utc::now());
+            key.1 = format!("{}:{}", today, key.1);
+        }
+        let previous = state.tracked(&key).await;
+        // Known contents are tracked like any other while no actions are taken.
+        let known_scam = if config.share_fingerprints && guild.preview.is_none() {
+            let fingerprints = shared::<FingerprintRegistries>(&*context.data.read().await)?;
+            fingerprints.seen_elsewhere(guild_id, &content.text)
+        } else {
+            None
+        };
+        // Skipping messages that were not sampled before any requests are made for them is what
+        // saves the load. Reposts of tracked contents are checked regardless, so that spam is
+        // caught once any copy of it was sampled.
+        if !guild.sampled && previous.is_none() && known_scam.is_none() {
+            return Ok(guild.skip("not sampled"));
+        }
+        if let (false, Some(code)) = (guild.sampled, &invite_code) {
+            if exempt_invite(context, guild_id, code, config).await? {
+                return Ok(guild.skip("invite to the own guild"));
+            }
+        }
+        if has_exempt_tag(context, msg, config).await? {
+            return Ok(guild.skip("exempt forum tag"));
+        }
+        if let Some(other_guilds) = known_scam {
+            return Ok(Some(Detection::KnownScam {
+                action: settings.action,
+                other_guilds,
+            }));
+        }
+
+        if config.in_safe_hours(Utc::now()) {
+            return Ok(guild.skip("safe hours"));
+        }
+
+        // Whether an earlier copy counts regardless of the window, or `None` if it counts within
+        // it.
+        let within_scope = match config.detection_scope {
+            DetectionScope::AllMessages => None,
+            DetectionScope::DailyFirstOnly => Some(true),
+            DetectionScope::RecentMessages => {
+                let max_age = Duration::from_secs(config.max_entry_age_secs);
+                let recent = state
+                    .push_recent_message(msg.author.id, &key.1, now, config.window_size, max_age)
+                    .await;
+                Some(recent)
+            }
+        };
+        let lookups = shared::<SharedStats>(&*context.data.read().await)?
+            .record_lookup(guild_id, previous.is_some());
+        if let Some(rate) = lookups.detection_rate() {
+            tracing::debug!("Detection rate of guild {}: {:.3}", guild_id, rate);
+        }
+        let warnings = previous.as_ref().map_or(0, |previous| previous.warnings);
+        let (occurrences, mut earlier_messages, first_channel_id) = match previous {
+            Some(previous)
+                if within_scope.unwrap_or_else(|| {
+                    now.checked_duration_since(previous.last_seen)
+                        .is_some_and(|duration| duration.as_secs() <= settings.window_secs)
+                }) =>
+            {
+                (
+                    previous.occurrences + 1,
+                    previous.messages,
+                    previous.first_channel_id,
+                )
+            }
+            _ => (1, Vec::new(), msg.channel_id),
+        };
+        let threshold_reached = occurrences >= settings.occurrence_threshold;
+        let retroactive = config.retroactive_delete && settings.action == DuplicateAction::Delete;
+        // Warnings are only counted once they are sent, and a bridge cannot heed them.
+        let warn = threshold_reached
+            && settings.action == DuplicateAction::Delete
+            && warnings < config.warning_count_before_action
+            && bridged_author.is_none()
+            && guild.preview.is_none();
+        {
+            // Earlier copies are only remembered until they are deleted along with this one.
+            let mut messages = Vec::new();
+            if retroactive && (!threshold_reached || warn) {
+                messages = earlier_messages.clone();
+                messages.push((msg.channel_id, msg.id));
+            }
+            let entry = CacheEntry {
+                last_seen: now,
+                occurrences,
+                messages,
+                first_channel_id,
+                warnings: warnings + u8::from(warn),
+            };
+            state.track(key, entry).await;
+        }
+        if let Some(trace) = guild.trace {
+            trace.record_occurrences(occurrences, settings.occurrence_threshold);
+        }
+
+        if !threshold_reached {
+            return Ok(None);
+        }
+        if !retroactive || warn {
+            earlier_messages.clear();
+        }
+
+        Ok(Some(Detection::Duplicate(Duplicate {
+            text: content.text,
+            forwarded: content.forwarded,
+            occurrences,
+            action: settings.action,
+            earlier_messages,
+            warn,
+            first_channel_id,
+            invite: invite_code.is_some(),
+            bridged: bridged_author.is_some(),
+        })))
+    }
+}
+
+/// Whether the message is a reply that is exempt from duplicate detection.
+fn is_ignored_reply(msg: &Message, config: &GuildConfig) -> bool {
+    let Some(referenced) = msg.referenced_message.as_ref() else {
+        return false;
+    };
+
+    config.ignore_replies
+        && !(config.ignore_replies_to_same_author && referenced.author.id == msg.author.id)
+}
+
+/// Returns the code of the invite the message is tracked by, if invite detection is enabled and
+/// the message contains an invite.
+fn tracked_invite<'a>(text: &'a str, config: &GuildConfig) -> Option<&'a str> {
+    if !config.invite_detection {
+        return None;
+    }
+
+    invites::invite_code(text)
+}
+
+/// Whether the invite is exempt from detection because it leads to the guild itself.
+async fn exempt_invite(
+    context: &MessageContext,
+    guild_id: GuildId,
+    code: &str,
+    config: &GuildConfig,
+) -> Result<bool, BroomError> {
+    if !config.invite_exempt_own_guild {
+        return Ok(false);
+    }
+
+    context.actions.invite_leads_to(code, guild_id).await
+}
+
+/// Whether the message was posted in a forum post with a tag that exempts it from detection.
+async fn has_exempt_tag(
+    context: &MessageContext,
+    msg: &Message,
+    config: &GuildConfig,
+) -> Result<bool, BroomError> {
+    if config.forum_exempt_tags.is_empty() {
+        return Ok(false);
+    }
+
+    let Some(tags) = context.actions.thread_tags(msg.channel_id).await? else {
+        return Ok(false);
+    };
+    let Some(exempt_tags) = config.forum_exempt_tags.get(&tags.forum_id) else {
+        return Ok(false);
+    };
+
+    Ok(tags.tag_names.iter().any(|name| {
+        exempt_tags
+            .iter()
+            .any(|exempt| exempt.eq_ignore_ascii_case(name))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serenity::prelude::{RwLock, TypeMap};
+
+    use super::*;
+    use crate::actions::RecordedActions;
+
+    const GUILD: GuildId = GuildId::new(1);
+
+    async fn invite(content: &str, config: &GuildConfig) -> Option<String> {
+        let actions = RecordedActions {
+            invites: [
+                ("own".to_string(), GUILD),
+                ("other".to_string(), GuildId::new(2)),
+            ]
+            .into(),
+            ..RecordedActions::default()
+        };
+        let context = MessageContext {
+            data: Arc::new(RwLock::new(TypeMap::new())),
+            actions: Arc::new(actions),
+        };
+        let code = tracked_invite(content, config)?;
+        if exempt_invite(&context, GUILD, code, config).await.unwrap() {
+            return None;
+        }
+
+        Some(code.to_string())
+    }
+
+    #[tokio::test]
+    async fn tracks_invites_only_if_enabled() {
+        let mut config = GuildConfig::default();
+        assert_eq!(invite("discord.gg/other", &config).await, None);
+
+        config.invite_detection = true;
+        assert_eq!(
+            invite("join discord.gg/other", &config).await.as_deref(),
+            Some("other")
+        );
+        assert_eq!(invite("no invite here", &config).await, None);
+    }
+
+    #[tokio::test]
+    async fn exempts_invites_to_the_own_guild_if_configured() {
+        let mut config = GuildConfig {
+            invite_detection: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(invite("discord.gg/own", &config).await, None);
+        assert_eq!(
+            invite("discord.gg/unknown", &config).await.as_deref(),
+            Some("unknown")
+        );
+
+        config.invite_exempt_own_guild = false;
+        assert_eq!(
+            invite("discord.gg/own", &config).await.as_deref(),
+            Some("own")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Detection of images that are reposted with small changes, e.g. resized, slightly cropped or
+//! watermarked, which comparing attachments by the hashes of their contents misses. Images are
+//! compared by their perceptual hashes, which only differ in a few bits for images that look alike.
+
+use img_hash::{image, HasherConfig};
+use serenity::model::channel::{Attachment, Message};
+
+use super::{Detection, Detector, Duplicate, GuildView};
+use crate::{
+    actions::MessageContext,
+    db::{unix_timestamp, SharedDatabase},
+    error::BroomError,
+    state::shared,
+};
+
+/// Maximum size in bytes of an image that is downloaded to be hashed.
+const MAX_IMAGE_SIZE: u32 = 8 * 1024 * 1024;
+/// Maximum number of images of a single message that are hashed.
+const MAX_HASHED_IMAGES: usize = 4;
+
+pub struct SimilarImageDetector;
+
+#[serenity::async_trait]
+impl Detector for SimilarImageDetector {
+    fn name(&self) -> &'static str {
+        "similar_images"
+    }
+
+    async fn check(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError> {
+        let config = guild.config;
+        if !config.detect_similar_images {
+            return Ok(None);
+        }
+        let images = msg
+            .attachments
+            .iter()
+            .filter(|attachment| {
+                attachment
+                    .content_type
+                    .as_deref()
+                    .is_some_and(|content_type| content_type.starts_with("image/"))
+                    && attachment.size <= MAX_IMAGE_SIZE
+            })
+            .take(MAX_HASHED_IMAGES)
+            .collect::<Vec<_>>();
+        if images.is_empty() {
+            return Ok(guild.skip("no images"));
+        }
+        // Downloading images is far more expensive than tracking texts, so unsampled messages are
+        // never checked.
+        if !guild.sampled {
+            return Ok(guild.skip("not sampled"));
+        }
+
+        let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+        let roles = msg
+            .member
+            .as_ref()
+            .map(|member| member.roles.as_slice())
+            .unwrap_or_default();
+        // Images have no length, so length bands do not apply.
+        let settings = config.detection_settings(roles, msg.channel_id, 0);
+        let now = unix_timestamp();
+        let recent = database
+            .recent_perceptual_hashes(
+                guild.guild_id,
+                msg.author.id,
+                now - settings.window_secs as i64,
+            )
+            .await?;
+
+        let mut most_similar = None;
+        for attachment in images {
+            let Some(hash) = perceptual_hash(attachment).await else {
+                continue;
+            };
+            let occurrences = recent
+                .iter()
+                .filter(|recent| (*recent ^ hash).count_ones() <= config.phash_distance_threshold)
+                .count()
+                + 1;
+            database
+                .record_perceptual_hash(guild.guild_id, msg.author.id, hash, now)
+                .await?;
+            if most_similar.is_none_or(|(_, most)| occurrences > most) {
+                most_similar = Some((hash, occurrences));
+            }
+        }
+
+        let Some((hash, occurrences)) = most_similar else {
+            return Ok(guild.skip("no decodable images"));
+        };
+        if let Some(trace) = guild.trace {
+            trace.record_occurrences(occurrences, settings.occurrence_threshold);
+        }
+        if occurrences < settings.occurrence_threshold {
+            return Ok(None);
+        }
+
+        // Similar images are acted on like duplicates, but their earlier copies are not known.
+        Ok(Some(Detection::Duplicate(Duplicate {
+            text: format!("image:{:016x}", hash),
+            forwarded: false,
+            occurrences,
+            action: settings.action,
+            earlier_messages: Vec::new(),
+            warn: false,
+            first_channel_id: msg.channel_id,
+            invite: false,
+            bridged: false,
+        })))
+    }
+}
+
+/// Downloads the given image and returns its perceptual hash, or `None` if it could not be
+/// downloaded or decoded.
+async fn perceptual_hash(attachment: &Attachment) -> Option<u64> {
+    let bytes = match attachment.download().await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!(
+                "Could not download attachment {} to hash it: {:?}",
+                attachment.id,
+                e
+            );
+            return None;
+        }
+    };
+
+    // Decoding and hashing images is CPU bound, so it does not hold up handling other messages.
+    tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory(&bytes).ok()?;
+        let hash = HasherConfig::new().to_hasher().hash_image(&image);
+
+        Some(u64::from_be_bytes(hash.as_bytes().try_into().ok()?))
+    })
+    .await
+    .ok()
+    .flatten()
+}
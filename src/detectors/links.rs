@@ -0,0 +1,37 @@
+//! Detection of links to domains on the blocklist of the deployment, which are deleted on first
+//! sight regardless of the detection settings of the guild.
+
+use serenity::model::channel::Message;
+
+use super::{Detection, Detector, GuildView};
+use crate::{
+    actions::MessageContext, blocklist::DomainBlocklists, error::BroomError, state::shared,
+};
+
+pub struct BlockedLinkDetector;
+
+#[serenity::async_trait]
+impl Detector for BlockedLinkDetector {
+    fn name(&self) -> &'static str {
+        "blocked_links"
+    }
+
+    async fn check(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError> {
+        if !guild.config.blocked_link_detection {
+            return Ok(None);
+        }
+
+        // Checking links is cheap, so unsampled messages are checked as well.
+        let blocklist = shared::<DomainBlocklists>(&*context.data.read().await)?;
+        let Some(host) = blocklist.blocked_host(&msg.content) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Detection::BlockedLink { host }))
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    model::{
+        channel::Message,
+        prelude::{GuildId, UserId},
+    },
+    prelude::TypeMapKey,
+};
+
+use super::{Detection, Detector, GuildView};
+use crate::{actions::MessageContext, error::BroomError, state::shared};
+
+/// Interval in seconds in which mentions that have left their window are purged.
+const SWEEP_INTERVAL_IN_SECS: u64 = 60;
+
+/// Recent mentions of a single target by a single author.
+struct MentionHistory {
+    window: Duration,
+    mentions: Vec<Instant>,
+}
+
+/// Detects an author repeatedly mentioning the same user across several messages, which is a
+/// common harassment pattern that never hits a per-message mention limit.
+pub struct RepeatedMentionDetector {
+    histories: Mutex<HashMap<(GuildId, UserId, UserId), MentionHistory>>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl RepeatedMentionDetector {
+    pub fn new() -> Self {
+        Self {
+            histories: Mutex::new(HashMap::new()),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Records the user mentions of the given message and returns all targets that were mentioned
+    /// by its author more than `threshold` times within the window. The history of every returned
+    /// target is reset so that each burst is only reported once.
+    ///
+    /// Only explicit user mentions are counted, `@everyone` and `@here` are tracked separately by
+    /// Discord and never appear among the mentioned users.
+    pub fn check(
+        &self,
+        guild_id: GuildId,
+        msg: &Message,
+        threshold: usize,
+        window: Duration,
+        now: Instant,
+    ) -> Vec<(UserId, usize)> {
+        let targets: HashSet<_> = msg
+            .mentions
+            .iter()
+            .map(|user| user.id)
+            .filter(|user_id| *user_id != msg.author.id)
+            .collect();
+        if targets.is_empty() {
+            return Vec::new();
+        }
+
+        let mut histories = self.histories.lock().unwrap_or_else(|e| e.into_inner());
+        self.sweep(&mut histories, now);
+
+        let mut exceeded = Vec::new();
+        for target in targets {
+            let history = histories
+                .entry((guild_id, msg.author.id, target))
+                .or_insert_with(|| MentionHistory {
+                    window,
+                    mentions: Vec::new(),
+                });
+            history.window = window;
+            history
+                .mentions
+                .retain(|mention| now.duration_since(*mention) <= window);
+            history.mentions.push(now);
+
+            if history.mentions.len() > threshold {
+                exceeded.push((target, history.mentions.len()));
+                history.mentions.clear();
+            }
+        }
+
+        exceeded
+    }
+
+    /// Removes the histories of all pairs that have not mentioned each other within their window.
+    fn sweep(
+        &self,
+        histories: &mut HashMap<(GuildId, UserId, UserId), MentionHistory>,
+        now: Instant,
+    ) {
+        let mut last_sweep = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*last_sweep) < Duration::from_secs(SWEEP_INTERVAL_IN_SECS) {
+            return;
+        }
+
+        histories.retain(|_, history| {
+            history
+                .mentions
+                .last()
+                .is_some_and(|mention| now.duration_since(*mention) <= history.window)
+        });
+        *last_sweep = now;
+    }
+}
+
+/// Checks messages with the [`RepeatedMentionDetector`] shared by all guilds.
+pub struct RepeatedMentions;
+
+#[serenity::async_trait]
+impl Detector for RepeatedMentions {
+    fn name(&self) -> &'static str {
+        "repeated_mentions"
+    }
+
+    async fn check(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError> {
+        if !guild.sampled {
+            return Ok(guild.skip("not sampled"));
+        }
+        let detector = shared::<RepeatedMentionDetectors>(&*context.data.read().await)?;
+        let exceeded = detector.check(
+            guild.guild_id,
+            msg,
+            guild.config.repeated_mention_threshold,
+            Duration::from_secs(guild.config.repeated_mention_window_secs),
+            Instant::now(),
+        );
+
+        Ok((!exceeded.is_empty()).then_some(Detection::RepeatedMentions(exceeded)))
+    }
+}
+
+pub struct RepeatedMentionDetectors;
+
+impl TypeMapKey for RepeatedMentionDetectors {
+    type Value = Arc<RepeatedMentionDetector>;
+}
@@ -0,0 +1,144 @@
+//! Detectors for abusive behavior, each of which checks messages for one kind of it independently
+//! of the others. The handler takes action on whatever they detect.
+
+use serde::{Deserialize, Serialize};
+use serenity::model::{
+    channel::Message,
+    prelude::{ChannelId, GuildId, MessageId, UserId},
+};
+
+#[cfg(feature = "perceptual_hashing")]
+pub use self::images::SimilarImageDetector;
+pub use self::{
+    coordinated::{ContentFrequency, CoordinatedAttackDetector},
+    duplicates::DuplicateDetector,
+    links::BlockedLinkDetector,
+    mentions::{RepeatedMentionDetector, RepeatedMentionDetectors, RepeatedMentions},
+};
+use crate::{
+    actions::MessageContext,
+    config::{DuplicateAction, GuildConfig},
+    error::BroomError,
+    state::GuildState,
+    verbose::DebugTrace,
+};
+
+mod coordinated;
+mod duplicates;
+#[cfg(feature = "perceptual_hashing")]
+mod images;
+mod links;
+mod mentions;
+
+/// The guild a message was posted in, as seen by every detector checking the message.
+pub struct GuildView<'a> {
+    pub guild_id: GuildId,
+    pub state: &'a GuildState,
+    /// Snapshot of the configuration, so that all detectors check the message against the same
+    /// one.
+    pub config: &'a GuildConfig,
+    /// Why no actions are taken, if they are not. Messages are still tracked in the meantime, so
+    /// that detectors are warmed up once actions are taken again.
+    pub preview: Option<&'static str>,
+    /// Whether the message was sampled for checking. Messages that were not are only checked for
+    /// reposts of contents that are tracked already.
+    pub sampled: bool,
+    /// Where detectors record why they skipped the message in verbose mode.
+    pub trace: Option<&'a DebugTrace>,
+}
+
+impl GuildView<'_> {
+    /// Records why the message is skipped in verbose mode. Returns nothing, which is what the
+    /// detector found.
+    pub fn skip(&self, reason: &'static str) -> Option<Detection> {
+        if let Some(trace) = self.trace {
+            trace.skip(reason);
+        }
+        None
+    }
+}
+
+/// What a detector found in a message.
+#[derive(Deserialize, Serialize)]
+pub enum Detection {
+    Duplicate(Duplicate),
+    /// The content was removed in the given number of other guilds sharing fingerprints.
+    KnownScam {
+        action: DuplicateAction,
+        other_guilds: usize,
+    },
+    /// The users the author mentioned too often within the window, along with how often.
+    RepeatedMentions(Vec<(UserId, usize)>),
+    /// The message was posted in a honeypot channel, which is found without any detector.
+    Honeypot,
+    /// The message links to the given host, which is on the blocklist of the deployment.
+    BlockedLink {
+        host: String,
+    },
+    /// More than the given number of distinct users posted the same content within the window.
+    CoordinatedAttack {
+        users: usize,
+    },
+}
+
+impl Detection {
+    /// Short name of what was detected, as it is shown in verbose mode.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Duplicate(_) => "duplicate",
+            Self::KnownScam { .. } => "known_scam",
+            Self::RepeatedMentions(_) => "repeated_mentions",
+            Self::Honeypot => "honeypot",
+            Self::BlockedLink { .. } => "blocked_link",
+            Self::CoordinatedAttack { .. } => "coordinated_attack",
+        }
+    }
+
+    /// What was detected, as it is logged.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Duplicate(_) => "a duplicate",
+            Self::KnownScam { .. } => "a known scam",
+            Self::RepeatedMentions(_) => "repeated mentions",
+            Self::Honeypot => "a message in a honeypot channel",
+            Self::BlockedLink { .. } => "a link to a blocked domain",
+            Self::CoordinatedAttack { .. } => "a coordinated attack",
+        }
+    }
+}
+
+/// A message that was posted often enough within the window to be considered a duplicate.
+#[derive(Deserialize, Serialize)]
+pub struct Duplicate {
+    /// The content the message was tracked by.
+    pub text: String,
+    pub forwarded: bool,
+    pub occurrences: usize,
+    pub action: DuplicateAction,
+    /// Copies posted before the threshold was reached that are to be deleted along with it.
+    pub earlier_messages: Vec<(ChannelId, MessageId)>,
+    /// Whether the author is only warned instead, since they were not warned as often as the
+    /// guild requires before taking action on this content.
+    pub warn: bool,
+    /// Channel the message was first posted in within the window.
+    pub first_channel_id: ChannelId,
+    /// Whether the message was tracked by the invite it contains.
+    pub invite: bool,
+    /// Whether the message was relayed by a trusted bridge.
+    pub bridged: bool,
+}
+
+#[serenity::async_trait]
+pub trait Detector: Send + Sync {
+    /// Name of the detector as it is shown in verbose mode.
+    fn name(&self) -> &'static str;
+
+    /// Checks the given message and records whatever later checks depend on, returning what was
+    /// detected, if anything.
+    async fn check(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+    ) -> Result<Option<Detection>, BroomError>;
+}
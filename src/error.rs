@@ -0,0 +1,56 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    io,
+    path::PathBuf,
+};
+
+use thiserror::Error;
+
+use crate::config::ConfigValidationError;
+
+#[derive(Error)]
+pub enum BroomError {
+    #[error("Could not find the {0} environment variable.")]
+    MissingConfig(&'static str),
+    #[error("The {0} environment variable is invalid.")]
+    InvalidConfig(&'static str),
+    /// An environment variable was set for a feature that this build was compiled without.
+    #[cfg(not(feature = "sentry"))]
+    #[error("The {0} environment variable requires a build with the {1} feature.")]
+    UnsupportedConfig(&'static str, &'static str),
+    /// A change to the configuration of a guild was rejected since it violates the limits set by
+    /// the operator.
+    #[error("The configuration violates the limits of this deployment: {0}")]
+    InvalidGuildConfig(#[from] ConfigValidationError),
+    #[error("There was an error while communicating with Discord: {0}")]
+    DiscordClientError(Box<serenity::Error>),
+    #[error("There was an error while accessing the database: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+    #[error("Could not read the token from {path}: {1}", path = .0.display())]
+    TokenFileError(PathBuf, io::Error),
+    #[error("Could not read the domain blocklist from {path}: {1}", path = .0.display())]
+    BlocklistFileError(PathBuf, io::Error),
+    #[error("Could not write to the log directory {path}: {1}", path = .0.display())]
+    LogDirectoryError(PathBuf, io::Error),
+    #[error("Could not read messages from stdin: {0}")]
+    StdinError(io::Error),
+    /// A value that should have been inserted into the `TypeMap` during startup is missing, which
+    /// is a programming error rather than something an operator can fix.
+    #[error("Expected {0} in TypeMap.")]
+    MissingState(&'static str),
+}
+
+/// Serenity's error is boxed since it is considerably larger than all other variants.
+impl From<serenity::Error> for BroomError {
+    fn from(error: serenity::Error) -> Self {
+        Self::DiscordClientError(Box::new(error))
+    }
+}
+
+/// Returning an error from `main` prints its `Debug` representation, so this forwards to the
+/// human-readable message instead of dumping the variant structure.
+impl Debug for BroomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
@@ -0,0 +1,108 @@
+//! Reporting of errors in the moderation path to Sentry, which keeps their context in deployments
+//! whose logs are not aggregated.
+//!
+//! Errors are only reported in builds with the `sentry` feature that are given a `SENTRY_DSN`.
+//! Providing a DSN to a build without the feature fails at startup rather than silently dropping
+//! every error.
+
+use std::{env, fmt::Debug};
+
+use serenity::model::prelude::{GuildId, UserId};
+
+use crate::error::BroomError;
+
+/// Keeps reporting errors until it is dropped, which flushes the pending ones.
+pub struct ErrorTracking {
+    #[cfg(feature = "sentry")]
+    _guard: Option<sentry::ClientInitGuard>,
+}
+
+/// Connects to the Sentry project given by the `SENTRY_DSN` environment variable, if any.
+#[cfg(feature = "sentry")]
+pub fn init() -> Result<ErrorTracking, BroomError> {
+    let Ok(dsn) = env::var("SENTRY_DSN") else {
+        return Ok(ErrorTracking { _guard: None });
+    };
+    let dsn: sentry::types::Dsn = dsn
+        .trim()
+        .parse()
+        .map_err(|_| BroomError::InvalidConfig("SENTRY_DSN"))?;
+    let mut options = sentry::ClientOptions::new().traces_sample_rate(1.0);
+    options.release = sentry::release_name!();
+    let guard = sentry::init((dsn, options));
+
+    Ok(ErrorTracking {
+        _guard: Some(guard),
+    })
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn init() -> Result<ErrorTracking, BroomError> {
+    match env::var("SENTRY_DSN") {
+        Ok(_) => Err(BroomError::UnsupportedConfig("SENTRY_DSN", "sentry")),
+        Err(_) => Ok(ErrorTracking {}),
+    }
+}
+
+/// Reports an error that was handled by logging it, along with the guild and user it concerned.
+pub fn capture<E: Debug>(
+    description: &str,
+    error: &E,
+    guild_id: Option<GuildId>,
+    user_id: Option<UserId>,
+) {
+    #[cfg(feature = "sentry")]
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("error_type", std::any::type_name::<E>());
+            if let Some(guild_id) = guild_id {
+                scope.set_tag("guild_id", guild_id);
+            }
+            if let Some(user_id) = user_id {
+                scope.set_tag("user_id", user_id);
+            }
+        },
+        || {
+            sentry::capture_message(
+                &format!("{}: {:?}", description, error),
+                sentry::Level::Error,
+            )
+        },
+    );
+    #[cfg(not(feature = "sentry"))]
+    let _ = (description, error, guild_id, user_id);
+}
+
+/// A unit of work errors are attributed to, e.g. the lifetime of the Discord client.
+pub struct Transaction {
+    #[cfg(feature = "sentry")]
+    transaction: sentry::TransactionOrSpan,
+}
+
+impl Transaction {
+    /// Starts a transaction that errors captured on the current thread are attributed to until it
+    /// is finished.
+    pub fn start(name: &str, operation: &str) -> Self {
+        #[cfg(feature = "sentry")]
+        {
+            let context = sentry::TransactionContext::new(name, operation);
+            let transaction = sentry::TransactionOrSpan::from(sentry::start_transaction(context));
+            sentry::configure_scope(|scope| scope.set_span(Some(transaction.clone())));
+
+            Self { transaction }
+        }
+        #[cfg(not(feature = "sentry"))]
+        {
+            let _ = (name, operation);
+            Self {}
+        }
+    }
+
+    pub fn finish(self) {
+        #[cfg(feature = "sentry")]
+        {
+            sentry::configure_scope(|scope| scope.set_span(None));
+            self.transaction.finish();
+        }
+    }
+}
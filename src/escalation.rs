@@ -0,0 +1,760 @@
+//! Proposals to ban repeat offenders, which are only ever executed once a moderator approves them,
+//! and bans that users are warned about before they are executed, giving moderators a chance to
+//! intervene.
+
+use std::{
+    sync::{Arc, Once},
+    time::Duration,
+};
+
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, EditMessage,
+    },
+    client::Context,
+    http::Http,
+    model::{
+        application::{ButtonStyle, ComponentInteraction},
+        mention::Mentionable,
+        permissions::Permissions,
+        prelude::{GuildId, MessageId, User, UserId},
+    },
+};
+
+use crate::{
+    actions::MessageContext,
+    config::GuildConfig,
+    db::{unix_timestamp, BanProposal, Database, PendingEscalation, SharedDatabase},
+    error::BroomError,
+    error_tracking,
+    history::{HistoryAction, HistoryEntry},
+    i18n,
+    maintenance::MaintenanceModes,
+    monitor,
+    state::{shared, GuildStates, Guilds},
+};
+
+/// Prefix of the custom IDs of all buttons attached to proposals.
+pub const CUSTOM_ID_PREFIX: &str = "broom:ban_proposal:";
+/// Number of history entries shown in a proposal.
+const HISTORY_LIMIT: u32 = 10;
+/// Interval in seconds in which expired proposals are closed.
+const EXPIRY_INTERVAL_IN_SECS: u64 = 60;
+/// Interval in seconds in which pending bans whose warning window passed are executed.
+const PENDING_INTERVAL_IN_SECS: u64 = 1;
+
+static PENDING: Once = Once::new();
+
+/// Proposes to ban the given user if they reached the configured number of deleted duplicates
+/// within the strike window and no proposal for them is pending already.
+pub async fn check(
+    context: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    config: &GuildConfig,
+) -> Result<(), BroomError> {
+    let (Some(threshold), Some(channel_id)) =
+        (config.ban_proposal_threshold, config.audit_channel_id)
+    else {
+        return Ok(());
+    };
+
+    let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+    let now = unix_timestamp();
+    let strikes = database
+        .count_actions(
+            guild_id,
+            user_id,
+            HistoryAction::Deleted,
+            now - config.strike_window_secs as i64,
+        )
+        .await?;
+    if strikes < threshold || database.has_open_ban_proposal(guild_id, user_id).await? {
+        return Ok(());
+    }
+
+    let expires_at = now + config.ban_proposal_expiry_secs as i64;
+    let history = database
+        .recent_actions(guild_id, user_id, HISTORY_LIMIT)
+        .await?;
+    let history = history
+        .iter()
+        .map(|entry| describe_entry(&config.language, entry))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = CreateEmbed::new()
+        .title(i18n::translate(&config.language, "ban_proposal_title", &[]))
+        .description(i18n::translate(
+            &config.language,
+            "ban_proposal",
+            &[
+                ("user", &user_id.mention()),
+                ("count", &strikes),
+                ("expires", &format!("<t:{}:R>", expires_at)),
+            ],
+        ))
+        .field(
+            i18n::translate(&config.language, "ban_proposal_history", &[]),
+            history,
+            false,
+        );
+
+    let result = channel_id
+        .send_message(
+            context,
+            CreateMessage::new()
+                .embed(embed)
+                .components(buttons(&config.language, false)),
+        )
+        .await;
+    monitor::record(context, &result).await;
+    let message = result?;
+
+    database
+        .create_ban_proposal(&BanProposal {
+            message_id: message.id,
+            channel_id,
+            guild_id,
+            user_id,
+            expires_at,
+        })
+        .await?;
+    database
+        .record_action(
+            guild_id,
+            &HistoryEntry::new(user_id, HistoryAction::BanProposed, now),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn buttons(language: &str, disabled: bool) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}ban", CUSTOM_ID_PREFIX))
+            .label(i18n::translate(language, "ban_proposal_ban", &[]))
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+        CreateButton::new(format!("{}dismiss", CUSTOM_ID_PREFIX))
+            .label(i18n::translate(language, "ban_proposal_dismiss", &[]))
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled),
+    ])]
+}
+
+fn describe_entry(language: &str, entry: &HistoryEntry) -> String {
+    let action = i18n::translate(language, &format!("history_{}", entry.action.as_str()), &[]);
+    let mut description = format!("<t:{}:R> {}", entry.created_at, action);
+    if let Some(channel_id) = entry.channel_id {
+        description.push_str(&format!(" ({})", channel_id.mention()));
+    }
+    if let Some(moderator_id) = entry.moderator_id {
+        description.push_str(&format!(" ({})", moderator_id.mention()));
+    }
+
+    description
+}
+
+/// Handles a click on one of the buttons of a proposal.
+pub async fn handle_component(context: &Context, component: &ComponentInteraction) {
+    if let Err(e) = handle_decision(context, component).await {
+        tracing::error!(
+            "There was an error while handling a decision on a ban proposal: {}",
+            e
+        );
+    }
+}
+
+async fn handle_decision(
+    context: &Context,
+    component: &ComponentInteraction,
+) -> Result<(), BroomError> {
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    let guilds = shared::<GuildStates>(&*context.data.read().await)?;
+    let config = guilds.get(guild_id).await?.config.read().await.clone();
+    let permissions = component
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions);
+
+    let response = match decide(
+        &MessageContext::new(context),
+        guild_id,
+        component.message.id,
+        &component.data.custom_id,
+        &component.user,
+        permissions,
+        &config,
+    )
+    .await?
+    {
+        Response::Reply(key) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(i18n::translate(&config.language, key, &[]))
+                .ephemeral(true),
+        ),
+        Response::Update(content) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(buttons(&config.language, true)),
+        ),
+    };
+    component.create_response(context, response).await?;
+
+    Ok(())
+}
+
+/// How a decision on a proposal is answered.
+#[derive(Debug, PartialEq)]
+enum Response {
+    /// Tells only the moderator the message with the given key.
+    Reply(&'static str),
+    /// Replaces the proposal with the given outcome and disables its buttons.
+    Update(String),
+}
+
+/// Decides on the proposal posted as the given message on behalf of the given moderator, whose
+/// permissions are `None` if they are unknown.
+async fn decide(
+    context: &MessageContext,
+    guild_id: GuildId,
+    message_id: MessageId,
+    custom_id: &str,
+    moderator: &User,
+    permissions: Option<Permissions>,
+    config: &GuildConfig,
+) -> Result<Response, BroomError> {
+    let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+    let language = &config.language;
+    let Some(proposal) = database.open_ban_proposal(message_id).await? else {
+        return Ok(Response::Reply("ban_proposal_closed"));
+    };
+
+    let now = unix_timestamp();
+    let outcome = if now >= proposal.expires_at {
+        HistoryAction::BanExpired
+    } else if !permissions.is_some_and(|permissions| {
+        permissions.administrator() || permissions.contains(Permissions::BAN_MEMBERS)
+    }) {
+        return Ok(Response::Reply("ban_proposal_missing_permission"));
+    } else if custom_id.ends_with(":ban") {
+        HistoryAction::BanApproved
+    } else {
+        HistoryAction::BanDismissed
+    };
+
+    // Another moderator may have decided on the proposal in the meantime.
+    if !database
+        .resolve_ban_proposal(proposal.message_id, outcome)
+        .await?
+    {
+        return Ok(Response::Reply("ban_proposal_closed"));
+    }
+
+    let reason = i18n::translate(
+        language,
+        "ban_proposal_reason",
+        &[("moderator", &moderator.name)],
+    );
+    if let (HistoryAction::BanApproved, Some(warning_secs)) =
+        (outcome, config.ban_proposal_warning_secs)
+    {
+        let fire_at = warn_before_ban(
+            context,
+            guild_id,
+            proposal.user_id,
+            Some(moderator.id),
+            &reason,
+            warning_secs,
+            config,
+        )
+        .await?;
+        let content = match fire_at {
+            Some(fire_at) => i18n::translate(
+                language,
+                "ban_proposal_outcome_ban_pending",
+                &[
+                    ("user", &proposal.user_id.mention()),
+                    ("moderator", &moderator.id.mention()),
+                    ("time", &format!("<t:{}:R>", fire_at)),
+                ],
+            ),
+            None => i18n::translate(
+                language,
+                "audit_ban_already_pending",
+                &[("user", &proposal.user_id.mention())],
+            ),
+        };
+        return Ok(Response::Update(content));
+    }
+
+    let outcome = if outcome == HistoryAction::BanApproved {
+        match context
+            .actions
+            .ban(
+                guild_id,
+                proposal.user_id,
+                config.ban_delete_message_days,
+                &reason,
+            )
+            .await
+        {
+            Ok(()) => HistoryAction::BanApproved,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to ban a member: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not ban a member",
+                    &e,
+                    Some(guild_id),
+                    Some(proposal.user_id),
+                );
+                HistoryAction::BanFailed
+            }
+        }
+    } else {
+        outcome
+    };
+
+    let mut entry = HistoryEntry::new(proposal.user_id, outcome, now);
+    if outcome != HistoryAction::BanExpired {
+        entry.moderator_id = Some(moderator.id);
+    }
+    database.record_action(guild_id, &entry).await?;
+
+    Ok(Response::Update(i18n::translate(
+        language,
+        &format!("ban_proposal_outcome_{}", outcome.as_str()),
+        &[
+            ("user", &proposal.user_id.mention()),
+            ("moderator", &moderator.id.mention()),
+        ],
+    )))
+}
+
+/// Closes expired proposals periodically by disabling their buttons, until the process exits.
+pub async fn run_expiry(http: Arc<Http>, database: Arc<Database>, guilds: Arc<Guilds>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(EXPIRY_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = close_expired(&http, &database, &guilds).await {
+            tracing::error!(
+                "There was an error while attempting to close expired ban proposals: {}",
+                e
+            );
+        }
+    }
+}
+
+async fn close_expired(
+    http: &Http,
+    database: &Database,
+    guilds: &Guilds,
+) -> Result<(), BroomError> {
+    let now = unix_timestamp();
+    for proposal in database.expired_ban_proposals(now).await? {
+        if !database
+            .resolve_ban_proposal(proposal.message_id, HistoryAction::BanExpired)
+            .await?
+        {
+            continue;
+        }
+        database
+            .record_action(
+                proposal.guild_id,
+                &HistoryEntry::new(proposal.user_id, HistoryAction::BanExpired, now),
+            )
+            .await?;
+
+        let language = guilds
+            .get(proposal.guild_id)
+            .await?
+            .config
+            .read()
+            .await
+            .language
+            .clone();
+        let content = i18n::translate(
+            &language,
+            "ban_proposal_outcome_ban_expired",
+            &[("user", &proposal.user_id.mention())],
+        );
+        if let Err(e) = proposal
+            .channel_id
+            .edit_message(
+                http,
+                proposal.message_id,
+                EditMessage::new()
+                    .content(content)
+                    .components(buttons(&language, true)),
+            )
+            .await
+        {
+            tracing::error!(
+                "There was an error while attempting to close an expired ban proposal: {:?}",
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Warns the given user that they are about to be banned and schedules the ban for once the given
+/// number of seconds passed. Returns when the ban is executed, or `None` if one is pending for the
+/// user already.
+pub async fn warn_before_ban(
+    context: &MessageContext,
+    guild_id: GuildId,
+    user_id: UserId,
+    moderator_id: Option<UserId>,
+    reason: &str,
+    warning_secs: u64,
+    config: &GuildConfig,
+) -> Result<Option<i64>, BroomError> {
+    let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+    let fire_at = unix_timestamp() + warning_secs as i64;
+    let escalation = PendingEscalation {
+        guild_id,
+        user_id,
+        moderator_id,
+        reason: reason.to_string(),
+        fire_at,
+    };
+    if !database.create_pending_escalation(&escalation).await? {
+        return Ok(None);
+    }
+
+    let language = if config.dm_user_locale {
+        context
+            .actions
+            .user_language(user_id)
+            .await?
+            .unwrap_or(&config.language)
+    } else {
+        &config.language
+    };
+    let guild = context
+        .actions
+        .guild_name(guild_id)
+        .unwrap_or_else(|| guild_id.to_string());
+    let content = i18n::translate(
+        language,
+        "escalation_warning",
+        &[("guild", &guild), ("seconds", &warning_secs)],
+    );
+    // The ban is executed regardless of whether the user could be warned.
+    if let Err(e) = context
+        .actions
+        .direct_message(user_id, CreateMessage::new().content(content))
+        .await
+    {
+        tracing::info!(
+            "Could not warn user {} about being banned from guild {}: {:?}",
+            user_id,
+            guild_id,
+            e
+        );
+    }
+
+    Ok(Some(fire_at))
+}
+
+/// Starts executing pending bans unless it was started already, e.g. by another shard.
+pub fn start(context: &Context) {
+    PENDING.call_once(|| {
+        tokio::spawn(run_pending(context.clone()));
+    });
+}
+
+async fn run_pending(context: Context) {
+    let mut interval = tokio::time::interval(Duration::from_secs(PENDING_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = execute_due(&context).await {
+            tracing::error!(
+                "There was an error while attempting to execute pending bans: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Executes all pending bans whose warning window passed. Cancelled bans are deleted, so they are
+/// never taken from the database.
+async fn execute_due(context: &Context) -> Result<(), BroomError> {
+    let (guilds, database, maintenance) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SharedDatabase>(&data_read)?,
+            shared::<MaintenanceModes>(&data_read)?,
+        )
+    };
+    // Pending bans are kept until actions are taken again.
+    if maintenance.is_active() {
+        return Ok(());
+    }
+
+    let now = unix_timestamp();
+    let escalations = database.take_due_escalations(now).await?;
+    if escalations.is_empty() {
+        return Ok(());
+    }
+
+    let message_context = MessageContext::new(context);
+    for escalation in escalations {
+        let config = guilds
+            .get(escalation.guild_id)
+            .await?
+            .config
+            .read()
+            .await
+            .clone();
+        let language = &config.language;
+        let user = escalation.user_id.mention();
+        let result = message_context
+            .actions
+            .ban(
+                escalation.guild_id,
+                escalation.user_id,
+                config.ban_delete_message_days,
+                &escalation.reason,
+            )
+            .await;
+        let (outcome, description) = match result {
+            Ok(()) => (
+                HistoryAction::BanApproved,
+                i18n::translate(language, "escalation_executed", &[("user", &user)]),
+            ),
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to ban a member: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not ban a member",
+                    &e,
+                    Some(escalation.guild_id),
+                    Some(escalation.user_id),
+                );
+                (
+                    HistoryAction::BanFailed,
+                    i18n::translate(
+                        language,
+                        "escalation_failed",
+                        &[("user", &user), ("error", &e)],
+                    ),
+                )
+            }
+        };
+
+        // Only bans approved by moderators are part of the history, like before they were
+        // deferred.
+        if let Some(moderator_id) = escalation.moderator_id {
+            let mut entry = HistoryEntry::new(escalation.user_id, outcome, now);
+            entry.moderator_id = Some(moderator_id);
+            database.record_action(escalation.guild_id, &entry).await?;
+        }
+
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(language, "escalation_title", &[]))
+            .description(description);
+        message_context.actions.post_audit_log(&config, embed).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::{
+        model::prelude::ChannelId,
+        prelude::{RwLock, TypeMap},
+    };
+
+    use super::*;
+    use crate::actions::RecordedActions;
+
+    const GUILD: GuildId = GuildId::new(1);
+    const PROPOSAL: MessageId = MessageId::new(2);
+    const OFFENDER: UserId = UserId::new(3);
+
+    fn moderator() -> User {
+        serde_json::from_value(json!({
+            "id": "4",
+            "username": "moderator",
+            "discriminator": "0000",
+            "avatar": null,
+        }))
+        .unwrap()
+    }
+
+    /// Returns a context with a proposal to ban the offender expiring after the given number of
+    /// seconds, along with the actions taken.
+    async fn propose(expires_in_secs: i64) -> (MessageContext, Arc<RecordedActions>) {
+        let database = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+        database
+            .create_ban_proposal(&BanProposal {
+                message_id: PROPOSAL,
+                channel_id: ChannelId::new(5),
+                guild_id: GUILD,
+                user_id: OFFENDER,
+                expires_at: unix_timestamp() + expires_in_secs,
+            })
+            .await
+            .unwrap();
+        let mut data = TypeMap::new();
+        data.insert::<SharedDatabase>(database);
+        let actions = Arc::new(RecordedActions::default());
+        let context = MessageContext {
+            data: Arc::new(RwLock::new(data)),
+            actions: actions.clone(),
+        };
+
+        (context, actions)
+    }
+
+    async fn decide_on(
+        context: &MessageContext,
+        button: &str,
+        permissions: Option<Permissions>,
+    ) -> Response {
+        decide_with(context, button, permissions, &GuildConfig::default()).await
+    }
+
+    async fn decide_with(
+        context: &MessageContext,
+        button: &str,
+        permissions: Option<Permissions>,
+        config: &GuildConfig,
+    ) -> Response {
+        let custom_id = format!("{}{}", CUSTOM_ID_PREFIX, button);
+        decide(
+            context,
+            GUILD,
+            PROPOSAL,
+            &custom_id,
+            &moderator(),
+            permissions,
+            config,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn history(context: &MessageContext) -> Vec<(HistoryAction, Option<UserId>)> {
+        let database = shared::<SharedDatabase>(&*context.data.read().await).unwrap();
+        database
+            .recent_actions(GUILD, OFFENDER, HISTORY_LIMIT)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.action, entry.moderator_id))
+            .collect()
+    }
+
+    fn outcome(action: HistoryAction) -> Response {
+        Response::Update(i18n::translate(
+            "en",
+            &format!("ban_proposal_outcome_{}", action.as_str()),
+            &[
+                ("user", &OFFENDER.mention()),
+                ("moderator", &moderator().id.mention()),
+            ],
+        ))
+    }
+
+    #[tokio::test]
+    async fn bans_once_approved_by_a_moderator() {
+        let (context, actions) = propose(60).await;
+
+        assert_eq!(
+            decide_on(&context, "ban", Some(Permissions::BAN_MEMBERS)).await,
+            outcome(HistoryAction::BanApproved)
+        );
+        assert_eq!(*actions.bans.lock().unwrap(), [(GUILD, OFFENDER)]);
+        assert_eq!(
+            history(&context).await,
+            [(HistoryAction::BanApproved, Some(moderator().id))]
+        );
+
+        // Proposals are only ever decided on once.
+        assert_eq!(
+            decide_on(&context, "dismiss", Some(Permissions::ADMINISTRATOR)).await,
+            Response::Reply("ban_proposal_closed")
+        );
+        assert_eq!(actions.bans.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn warns_before_banning_if_configured() {
+        let (context, actions) = propose(60).await;
+        let config = GuildConfig {
+            ban_proposal_warning_secs: Some(30),
+            ..GuildConfig::default()
+        };
+
+        let Response::Update(content) =
+            decide_with(&context, "ban", Some(Permissions::BAN_MEMBERS), &config).await
+        else {
+            panic!("the proposal was not decided on");
+        };
+        assert!(content.contains("will be banned"));
+        assert!(actions.bans.lock().unwrap().is_empty());
+        assert_eq!(*actions.direct_messages.lock().unwrap(), [OFFENDER]);
+    }
+
+    #[tokio::test]
+    async fn dismisses_without_banning() {
+        let (context, actions) = propose(60).await;
+
+        assert_eq!(
+            decide_on(&context, "dismiss", Some(Permissions::ADMINISTRATOR)).await,
+            outcome(HistoryAction::BanDismissed)
+        );
+        assert!(actions.bans.lock().unwrap().is_empty());
+        assert_eq!(
+            history(&context).await,
+            [(HistoryAction::BanDismissed, Some(moderator().id))]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_decisions_of_members_who_cannot_ban() {
+        let (context, actions) = propose(60).await;
+
+        for permissions in [None, Some(Permissions::MANAGE_MESSAGES)] {
+            assert_eq!(
+                decide_on(&context, "ban", permissions).await,
+                Response::Reply("ban_proposal_missing_permission")
+            );
+        }
+        assert!(actions.bans.lock().unwrap().is_empty());
+        assert!(history(&context).await.is_empty());
+
+        // The proposal stays open for moderators.
+        assert_eq!(
+            decide_on(&context, "ban", Some(Permissions::BAN_MEMBERS)).await,
+            outcome(HistoryAction::BanApproved)
+        );
+    }
+
+    #[tokio::test]
+    async fn closes_expired_proposals_without_banning() {
+        let (context, actions) = propose(0).await;
+
+        assert_eq!(
+            decide_on(&context, "ban", Some(Permissions::BAN_MEMBERS)).await,
+            outcome(HistoryAction::BanExpired)
+        );
+        assert!(actions.bans.lock().unwrap().is_empty());
+        assert_eq!(history(&context).await, [(HistoryAction::BanExpired, None)]);
+    }
+}
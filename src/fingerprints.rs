@@ -0,0 +1,361 @@
+//! Fingerprints of contents removed in several guilds, e.g. scams posted to every server the bot
+//! is in within minutes, so that guilds sharing them can remove such contents on first sight.
+//!
+//! Only hashes of normalized contents and the IDs of the guilds that removed them cross the guild
+//! boundary, never the contents themselves.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serenity::{model::prelude::GuildId, prelude::TypeMapKey};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    db::{unix_timestamp, Database},
+    error::BroomError,
+    ignore,
+};
+
+/// Default number of distinct guilds that have to remove a content for it to become known.
+const DEFAULT_GUILD_THRESHOLD: usize = 3;
+/// Default time in seconds within which the guilds have to remove a content.
+const DEFAULT_WINDOW_IN_SECS: i64 = 60 * 60;
+/// Default time in seconds a content stays known after it was last removed by a guild.
+const DEFAULT_EXPIRY_IN_SECS: i64 = 7 * 24 * 60 * 60;
+/// Interval in seconds in which expired sightings and fingerprints are pruned.
+const PRUNE_INTERVAL_IN_SECS: u64 = 10 * 60;
+
+/// Returns the fingerprint of the given content, which is the same for contents that only differ
+/// in casing or whitespace.
+pub fn fingerprint(content: &str) -> String {
+    let digest = Sha256::digest(ignore::normalize(content).as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Operator settings deciding when contents become known.
+#[derive(Clone, Copy, Debug)]
+pub struct FingerprintSettings {
+    pub guild_threshold: usize,
+    pub window_secs: i64,
+    pub expiry_secs: i64,
+}
+
+impl FingerprintSettings {
+    /// Reads the settings from `FINGERPRINT_GUILD_THRESHOLD`, `FINGERPRINT_WINDOW_SECS` and
+    /// `FINGERPRINT_EXPIRY_SECS`, falling back to the defaults for those that are not set.
+    pub fn from_env() -> Result<Self, BroomError> {
+        Ok(Self {
+            guild_threshold: match read("FINGERPRINT_GUILD_THRESHOLD", DEFAULT_GUILD_THRESHOLD)? {
+                threshold if threshold >= 2 => threshold,
+                _ => return Err(BroomError::InvalidConfig("FINGERPRINT_GUILD_THRESHOLD")),
+            },
+            window_secs: read("FINGERPRINT_WINDOW_SECS", DEFAULT_WINDOW_IN_SECS)?,
+            expiry_secs: read("FINGERPRINT_EXPIRY_SECS", DEFAULT_EXPIRY_IN_SECS)?,
+        })
+    }
+}
+
+fn read<T: std::str::FromStr>(name: &'static str, default: T) -> Result<T, BroomError> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig(name)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A content that was removed in enough guilds to be removed on first sight.
+#[derive(Clone, Debug)]
+struct KnownFingerprint {
+    guild_ids: HashSet<GuildId>,
+    expires_at: i64,
+}
+
+/// The fingerprints of recently removed contents and of contents that became known.
+#[derive(Default)]
+struct Fingerprints {
+    /// When each guild last removed a content that is not known yet, by fingerprint.
+    sightings: HashMap<String, HashMap<GuildId, i64>>,
+    known: HashMap<String, KnownFingerprint>,
+}
+
+impl Fingerprints {
+    /// Records that the given guild removed a content. Returns the known fingerprint if the content
+    /// is known afterwards, in which case it has to be persisted.
+    fn record(
+        &mut self,
+        settings: &FingerprintSettings,
+        guild_id: GuildId,
+        fingerprint: &str,
+        now: i64,
+    ) -> Option<KnownFingerprint> {
+        let expires_at = now + settings.expiry_secs;
+        if let Some(known) = self.known.get_mut(fingerprint) {
+            known.guild_ids.insert(guild_id);
+            known.expires_at = expires_at;
+            return Some(known.clone());
+        }
+
+        let sightings = self.sightings.entry(fingerprint.to_string()).or_default();
+        sightings.insert(guild_id, now);
+        sightings.retain(|_, seen_at| now - *seen_at <= settings.window_secs);
+        if sightings.len() < settings.guild_threshold {
+            return None;
+        }
+
+        let guild_ids = sightings.keys().copied().collect();
+        self.sightings.remove(fingerprint);
+        let known = KnownFingerprint {
+            guild_ids,
+            expires_at,
+        };
+        self.known.insert(fingerprint.to_string(), known.clone());
+        Some(known)
+    }
+
+    /// Returns the number of other guilds that removed the content if it is known.
+    fn seen_elsewhere(&self, guild_id: GuildId, fingerprint: &str, now: i64) -> Option<usize> {
+        let known = self
+            .known
+            .get(fingerprint)
+            .filter(|known| known.expires_at > now)?;
+        let others = known.guild_ids.iter().filter(|id| **id != guild_id).count();
+
+        // A guild being the only one to have removed the content is not told about itself.
+        (others > 0).then_some(others)
+    }
+
+    fn prune(&mut self, settings: &FingerprintSettings, now: i64) {
+        for sightings in self.sightings.values_mut() {
+            sightings.retain(|_, seen_at| now - *seen_at <= settings.window_secs);
+        }
+        self.sightings.retain(|_, sightings| !sightings.is_empty());
+        self.known.retain(|_, known| known.expires_at > now);
+    }
+}
+
+pub struct FingerprintRegistry {
+    fingerprints: Mutex<Fingerprints>,
+    settings: FingerprintSettings,
+    database: Arc<Database>,
+}
+
+impl FingerprintRegistry {
+    /// Creates the registry with all known fingerprints that have not expired yet. Sightings are
+    /// only kept in memory since they are short-lived.
+    pub async fn load(
+        database: Arc<Database>,
+        settings: FingerprintSettings,
+    ) -> Result<Self, sqlx::Error> {
+        let known = database
+            .scam_fingerprints(unix_timestamp())
+            .await?
+            .into_iter()
+            .map(|(fingerprint, guild_ids, expires_at)| {
+                let guild_ids = guild_ids.into_iter().collect();
+                (
+                    fingerprint,
+                    KnownFingerprint {
+                        guild_ids,
+                        expires_at,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            fingerprints: Mutex::new(Fingerprints {
+                sightings: HashMap::new(),
+                known,
+            }),
+            settings,
+            database,
+        })
+    }
+
+    /// Records that the given guild removed the given content on its own.
+    pub async fn record(&self, guild_id: GuildId, content: &str) {
+        let fingerprint = fingerprint(content);
+        let update = {
+            let Ok(mut fingerprints) = self.fingerprints.lock() else {
+                return;
+            };
+            fingerprints
+                .record(&self.settings, guild_id, &fingerprint, unix_timestamp())
+                .map(|known| {
+                    (
+                        known.guild_ids.into_iter().collect::<Vec<_>>(),
+                        known.expires_at,
+                    )
+                })
+        };
+
+        let Some((guild_ids, expires_at)) = update else {
+            return;
+        };
+        if let Err(e) = self
+            .database
+            .set_scam_fingerprint(&fingerprint, &guild_ids, expires_at)
+            .await
+        {
+            tracing::error!(
+                "There was an error while attempting to persist a shared fingerprint: {:?}",
+                e
+            );
+        }
+    }
+
+    /// Returns the number of other guilds that removed the given content if it is known.
+    pub fn seen_elsewhere(&self, guild_id: GuildId, content: &str) -> Option<usize> {
+        let fingerprint = fingerprint(content);
+        self.fingerprints
+            .lock()
+            .ok()?
+            .seen_elsewhere(guild_id, &fingerprint, unix_timestamp())
+    }
+
+    /// Prunes expired sightings and fingerprints periodically until the process exits.
+    pub async fn run(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(PRUNE_INTERVAL_IN_SECS));
+        loop {
+            interval.tick().await;
+            let now = unix_timestamp();
+            if let Ok(mut fingerprints) = self.fingerprints.lock() {
+                fingerprints.prune(&self.settings, now);
+            }
+
+            if let Err(e) = self.database.prune_scam_fingerprints(now).await {
+                tracing::error!(
+                    "There was an error while attempting to prune the shared fingerprints: {:?}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+pub struct FingerprintRegistries;
+
+impl TypeMapKey for FingerprintRegistries {
+    type Value = Arc<FingerprintRegistry>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: FingerprintSettings = FingerprintSettings {
+        guild_threshold: 3,
+        window_secs: 60,
+        expiry_secs: 600,
+    };
+    const CONTENT: &str = "Free nitro at scam.example";
+
+    fn guild(id: u64) -> GuildId {
+        GuildId::new(id)
+    }
+
+    /// Records that the given guilds removed the content at the given times.
+    fn record(fingerprints: &mut Fingerprints, sightings: &[(u64, i64)]) -> bool {
+        let fingerprint = fingerprint(CONTENT);
+        sightings
+            .iter()
+            .map(|(guild_id, now)| {
+                fingerprints.record(&SETTINGS, guild(*guild_id), &fingerprint, *now)
+            })
+            .last()
+            .flatten()
+            .is_some()
+    }
+
+    #[test]
+    fn ignores_casing_and_whitespace() {
+        assert_eq!(
+            fingerprint(CONTENT),
+            fingerprint("  free NITRO at\nscam.example ")
+        );
+        assert_ne!(
+            fingerprint(CONTENT),
+            fingerprint("Free nitro at other.example")
+        );
+    }
+
+    #[test]
+    fn becomes_known_once_removed_in_enough_guilds() {
+        let mut fingerprints = Fingerprints::default();
+
+        assert!(!record(&mut fingerprints, &[(1, 0), (2, 10)]));
+        assert_eq!(
+            fingerprints.seen_elsewhere(guild(4), &fingerprint(CONTENT), 10),
+            None
+        );
+        assert!(record(&mut fingerprints, &[(3, 20)]));
+        assert_eq!(
+            fingerprints.seen_elsewhere(guild(4), &fingerprint(CONTENT), 20),
+            Some(3)
+        );
+        // Guilds are not told about their own removals.
+        assert_eq!(
+            fingerprints.seen_elsewhere(guild(1), &fingerprint(CONTENT), 20),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn counts_each_guild_once() {
+        let mut fingerprints = Fingerprints::default();
+
+        assert!(!record(
+            &mut fingerprints,
+            &[(1, 0), (1, 1), (2, 2), (2, 3)]
+        ));
+        assert!(record(&mut fingerprints, &[(3, 4)]));
+    }
+
+    #[test]
+    fn only_counts_removals_within_the_window() {
+        let mut fingerprints = Fingerprints::default();
+
+        assert!(!record(&mut fingerprints, &[(1, 0), (2, 30), (3, 61)]));
+        assert!(record(&mut fingerprints, &[(4, 90)]));
+    }
+
+    #[test]
+    fn forgets_known_contents_once_expired() {
+        let mut fingerprints = Fingerprints::default();
+        let fingerprint = fingerprint(CONTENT);
+        record(&mut fingerprints, &[(1, 0), (2, 0), (3, 0)]);
+
+        // Every further removal extends the expiry.
+        assert!(record(&mut fingerprints, &[(4, 300)]));
+        assert_eq!(
+            fingerprints.seen_elsewhere(guild(5), &fingerprint, 899),
+            Some(4)
+        );
+        assert_eq!(
+            fingerprints.seen_elsewhere(guild(5), &fingerprint, 900),
+            None
+        );
+        fingerprints.prune(&SETTINGS, 900);
+        assert!(fingerprints.known.is_empty());
+    }
+
+    #[tokio::test]
+    async fn loads_persisted_known_contents() {
+        let database = Arc::new(Database::connect("sqlite::memory:").await.unwrap());
+        let registry = FingerprintRegistry::load(database.clone(), SETTINGS)
+            .await
+            .unwrap();
+        for guild_id in 1..=3 {
+            registry.record(guild(guild_id), CONTENT).await;
+        }
+
+        let registry = FingerprintRegistry::load(database, SETTINGS).await.unwrap();
+        assert_eq!(registry.seen_elsewhere(guild(4), CONTENT), Some(3));
+    }
+}
@@ -0,0 +1,119 @@
+//! Lookup of the tags applied to forum posts, which can exempt them from duplicate detection.
+
+use std::{sync::Arc, time::Duration};
+
+use moka::future::Cache;
+use serenity::{
+    client::Context,
+    model::{
+        channel::{Channel, ChannelType},
+        prelude::ChannelId,
+    },
+    prelude::TypeMapKey,
+};
+
+use crate::monitor;
+
+/// Time in seconds the tags of a thread are cached for, which is how long it takes until changes
+/// to them are picked up.
+const TIME_TO_LIVE_IN_SECS: u64 = 60;
+/// Maximum number of channels whose tags are cached at once.
+const MAX_CACHED_CHANNELS: u64 = 10_000;
+
+/// The forum a post was made in and the names of the tags applied to it.
+#[derive(Clone, Debug)]
+pub struct ThreadTags {
+    pub forum_id: ChannelId,
+    pub tag_names: Vec<String>,
+}
+
+/// Caches the tags of forum posts, since looking them up requires fetching the thread from the
+/// Discord API. Channels that are not forum posts are cached as well so that messages posted in
+/// them do not cause an API request each.
+pub struct ThreadTagCache {
+    cache: Cache<ChannelId, Option<ThreadTags>>,
+}
+
+impl ThreadTagCache {
+    pub fn new() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(MAX_CACHED_CHANNELS)
+                .time_to_live(Duration::from_secs(TIME_TO_LIVE_IN_SECS))
+                .build(),
+        }
+    }
+
+    /// Returns the tags of the forum post with the given ID, or `None` if the channel is not a
+    /// forum post. Errors while fetching are logged and not cached.
+    pub async fn get(&self, context: &Context, channel_id: ChannelId) -> Option<ThreadTags> {
+        if let Some(tags) = self.cache.get(&channel_id).await {
+            return tags;
+        }
+
+        let result = fetch(context, channel_id).await;
+        monitor::record(context, &result).await;
+        match result {
+            Ok(tags) => {
+                self.cache.insert(channel_id, tags.clone()).await;
+                tags
+            }
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to fetch the tags of a thread: {:?}",
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+async fn fetch(
+    context: &Context,
+    channel_id: ChannelId,
+) -> Result<Option<ThreadTags>, serenity::Error> {
+    let Channel::Guild(thread) = channel_id.to_channel(context).await? else {
+        return Ok(None);
+    };
+    let Some(forum_id) = thread
+        .parent_id
+        .filter(|_| thread.thread_metadata.is_some())
+    else {
+        return Ok(None);
+    };
+
+    // Forums are part of the guild and therefore usually cached, unlike their threads.
+    let cached_forum = context
+        .cache
+        .guild(thread.guild_id)
+        .and_then(|guild| guild.channels.get(&forum_id).cloned());
+    let forum = match cached_forum {
+        Some(forum) => forum,
+        None => match forum_id.to_channel(context).await? {
+            Channel::Guild(forum) => forum,
+            _ => return Ok(None),
+        },
+    };
+    if forum.kind != ChannelType::Forum {
+        return Ok(None);
+    }
+
+    let tag_names = forum
+        .available_tags
+        .iter()
+        .filter(|tag| thread.applied_tags.contains(&tag.id))
+        .map(|tag| tag.name.clone())
+        .collect();
+
+    Ok(Some(ThreadTags {
+        forum_id,
+        tag_names,
+    }))
+}
+
+pub struct ThreadTagCaches;
+
+impl TypeMapKey for ThreadTagCaches {
+    type Value = Arc<ThreadTagCache>;
+}
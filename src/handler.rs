@@ -0,0 +1,1892 @@
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    client::{Context, EventHandler},
+    gateway::{ConnectionStage, ShardStageUpdateEvent},
+    model::{
+        application::{Command, Interaction},
+        channel::{ChannelType, Message},
+        gateway::Ready,
+        guild::{Guild, Member},
+        mention::Mentionable,
+        permissions::Permissions,
+        prelude::{ChannelId, GuildId, MessageId, UserId},
+        Timestamp,
+    },
+    utils::MessageBuilder,
+};
+
+use crate::{
+    actions::{self, DiscordActions, MessageContext},
+    alt_accounts, commands,
+    config::{DmOverflowAction, DuplicateAction, GuildConfig, HoneypotAction},
+    content,
+    counters::{ActionCounters, GuildAction, SharedCounters},
+    custom_commands,
+    db::{unix_timestamp, Database, SharedDatabase},
+    detectors::{BlockedLinkDetector, Detection, Detector, Duplicate, GuildView},
+    error::BroomError,
+    error_tracking, escalation,
+    fingerprints::FingerprintRegistries,
+    history::{HistoryAction, HistoryEntry},
+    i18n,
+    locale::UserLocaleCaches,
+    lock::ActionLocks,
+    maintenance::MaintenanceModes,
+    moderation,
+    permissions::READ_PERMISSIONS,
+    preferences::DmFormat,
+    privacy::ContentPolicies,
+    probation,
+    quiet_hours::{self, DeferredAction},
+    ratelimit::ActionRateLimiters,
+    reports,
+    sampling::{DetectionSample, Samplers},
+    shards::ShardMonitors,
+    state::{shared, GuildStates, SharedGlobalState},
+    templates,
+    verbose::{self, Check, DebugTrace, Outcome},
+};
+
+pub struct Handler;
+
+/// Maximum length in characters of a dropped message to an author as it is reported.
+const MAX_DROPPED_DM_LENGTH: usize = 300;
+
+/// Why a message is deleted, which decides what its author is told.
+#[derive(Clone, Copy, Debug)]
+pub enum Violation {
+    Duplicate {
+        forwarded: bool,
+    },
+    /// The content was removed in other guilds sharing fingerprints.
+    KnownScam,
+    /// The message links to a domain on the blocklist of the deployment.
+    BlockedLink,
+}
+
+impl Violation {
+    fn dm_reason(self) -> &'static str {
+        match self {
+            Self::Duplicate { forwarded: false } => "dm_duplicate_reason",
+            Self::Duplicate { forwarded: true } => "dm_forward_reason",
+            Self::KnownScam => "dm_known_scam_reason",
+            Self::BlockedLink => "dm_blocked_link_reason",
+        }
+    }
+
+    fn history_action(self) -> HistoryAction {
+        match self {
+            Self::Duplicate { .. } => HistoryAction::Deleted,
+            Self::KnownScam => HistoryAction::KnownScamDeleted,
+            Self::BlockedLink => HistoryAction::BlockedLinkDeleted,
+        }
+    }
+}
+
+impl Handler {
+    /// Checks a message with every detector and takes action on whatever they detect. Errors are
+    /// returned rather than logged, since the headless mode reports them along with the message
+    /// they occurred for.
+    pub async fn handle_message(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+    ) -> Result<(), BroomError> {
+        let Some(guild_id) = msg.guild_id else {
+            return Ok(());
+        };
+
+        let (guilds, maintenance, rate_limiter, global) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<GuildStates>(&data_read)?,
+                shared::<MaintenanceModes>(&data_read)?,
+                shared::<ActionRateLimiters>(&data_read)?,
+                shared::<SharedGlobalState>(&data_read)?,
+            )
+        };
+        if global.in_grace_period(guild_id).await {
+            return Ok(());
+        }
+        let context = &context.rate_limited(guild_id, rate_limiter);
+
+        let state = guilds.get(guild_id).await?;
+        // Guilds of unknown size are checked, since the cache may not have been filled yet.
+        if let Some(member_count) = context.actions.guild_member_count(guild_id) {
+            let too_small = global.is_too_small(member_count);
+            if state.too_small.swap(too_small, Ordering::Relaxed) != too_small {
+                let change = if too_small {
+                    "deactivated"
+                } else {
+                    "activated"
+                };
+                tracing::info!(
+                    "Detection {} in guild {} with {} members.",
+                    change,
+                    guild_id,
+                    member_count
+                );
+            }
+            if too_small {
+                return Ok(());
+            }
+        }
+        let config = state.config.read().await.clone();
+        let debug_channel = verbose::debug_channel(&*context.actions, &guilds, guild_id, &config)
+            .await?
+            .filter(|_| rand::random::<f64>() < config.verbose_sample_rate);
+        // Messages are still tracked while no actions are taken, so that the cache is warm once
+        // they are taken again.
+        let bootstrapping = state
+            .join
+            .read()
+            .await
+            .is_some_and(|join| join.bootstrapping(&config, unix_timestamp()));
+        let preview = if maintenance.is_active() {
+            Some("maintenance")
+        } else if bootstrapping {
+            Some("the bootstrap period")
+        } else {
+            None
+        };
+        let rate = config.message_sampling_rate;
+        let sampled = rate >= 1.0 || rand::random::<f64>() < rate;
+        tracing::trace!(
+            "Message {} in guild {} sampled for checking: {}",
+            msg.id,
+            guild_id,
+            sampled
+        );
+        let trace = debug_channel.map(|_| DebugTrace::default());
+        let guild = GuildView {
+            guild_id,
+            state: &state,
+            config: &config,
+            preview,
+            sampled,
+            trace: trace.as_ref(),
+        };
+
+        // Anything posted in a honeypot channel is acted on, whatever it contains.
+        if config.honeypot_channels.contains(&msg.channel_id) {
+            if is_honeypot_exempt(context, guild_id, msg) {
+                let check = Check::skipped("exempt from the honeypot");
+                verbose::explain(&context.actions, debug_channel, &state, &config, msg, check)
+                    .await;
+                return Ok(());
+            }
+            let check = Check {
+                outcomes: &[("honeypot", Outcome::Hit("honeypot"))],
+                preview,
+                ..Check::default()
+            };
+            verbose::explain(&context.actions, debug_channel, &state, &config, msg, check).await;
+            return self
+                .take_action(context, msg, &guild, Detection::Honeypot)
+                .await;
+        }
+        if context
+            .actions
+            .channel_category(guild_id, msg.channel_id)
+            .is_some_and(|category_id| config.exempt_categories.contains(&category_id))
+        {
+            let check = Check::skipped("exempt category");
+            verbose::explain(&context.actions, debug_channel, &state, &config, msg, check).await;
+            return Ok(());
+        }
+
+        // Blocked links are deleted regardless of what else the message is, so neither the
+        // exemptions below nor any other detector apply to them.
+        let blocked_links = BlockedLinkDetector;
+        let detection = blocked_links.check(context, msg, &guild).await?;
+        let mut outcomes = Vec::new();
+        if trace.is_some() {
+            let outcome = match &detection {
+                Some(detection) => Outcome::Hit(detection.kind()),
+                None => Outcome::Miss,
+            };
+            outcomes.push((blocked_links.name(), outcome));
+        }
+        if let Some(detection) = detection {
+            let check = Check {
+                outcomes: &outcomes,
+                preview,
+                ..Check::default()
+            };
+            verbose::explain(&context.actions, debug_channel, &state, &config, msg, check).await;
+            return self.take_action(context, msg, &guild, detection).await;
+        }
+
+        let skipped = if config.is_bot_command(&msg.content) {
+            Some("bot command")
+        } else if state.prefix_allowlist.read().await.matches(&msg.content) {
+            Some("allowed prefix")
+        } else if state.templates.read().await.matches(&msg.content) {
+            Some("template message")
+        } else if config.exempt_first_message
+            && guilds.record_known_user(guild_id, msg.author.id).await?
+        {
+            Some("first message in the guild")
+        } else {
+            None
+        };
+        if let Some(reason) = skipped {
+            let check = Check::skipped(reason);
+            verbose::explain(&context.actions, debug_channel, &state, &config, msg, check).await;
+            return Ok(());
+        }
+        for detector in state.detectors() {
+            let detection = detector.check(context, msg, &guild).await?;
+            if let Some(trace) = &trace {
+                let outcome = match &detection {
+                    Some(detection) => Outcome::Hit(detection.kind()),
+                    None => match trace.take_skip_reason() {
+                        Some(reason) => Outcome::Skipped(reason),
+                        None => Outcome::Miss,
+                    },
+                };
+                outcomes.push((detector.name(), outcome));
+            }
+            let Some(detection) = detection else {
+                continue;
+            };
+            // Coordinated attacks are about all of their authors, so each of them is still checked
+            // on their own.
+            let stops = !matches!(detection, Detection::CoordinatedAttack { .. });
+            self.take_action(context, msg, &guild, detection).await?;
+            if config.stop_at_first_detection && stops {
+                break;
+            }
+        }
+        let check = Check {
+            skipped: None,
+            outcomes: &outcomes,
+            preview,
+            trace: trace.as_ref(),
+        };
+        verbose::explain(&context.actions, debug_channel, &state, &config, msg, check).await;
+
+        Ok(())
+    }
+
+    /// Takes action on what a detector found in a message, unless no actions are taken at the
+    /// moment.
+    async fn take_action(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+        detection: Detection,
+    ) -> Result<(), BroomError> {
+        let (guild_id, config) = (guild.guild_id, guild.config);
+        self.sample_detection(context, msg, guild, &detection)
+            .await?;
+        if let Some(reason) = guild.preview {
+            tracing::info!(
+                "Not taking action on {} by {} in guild {} during {}.",
+                detection.description(),
+                msg.author.id,
+                guild_id,
+                reason
+            );
+            return Ok(());
+        }
+        if config.in_quiet_hours(Utc::now()) && quiet_hours::is_deferred(&detection) {
+            return self
+                .defer_action(context, guild_id, msg, config, detection)
+                .await;
+        }
+
+        self.act_on(context, guild_id, msg, config, detection).await
+    }
+
+    /// Acts on a detection right away, e.g. once the quiet hours it was deferred during ended.
+    pub async fn act_on(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        detection: Detection,
+    ) -> Result<(), BroomError> {
+        match detection {
+            Detection::Duplicate(duplicate) => {
+                self.handle_duplicate(context, guild_id, msg, config, duplicate)
+                    .await
+            }
+            Detection::KnownScam {
+                action,
+                other_guilds,
+            } => {
+                self.handle_known_scam(context, guild_id, msg, config, action, other_guilds)
+                    .await
+            }
+            Detection::RepeatedMentions(exceeded) => {
+                self.alert_repeated_mentions(context, guild_id, msg, config, exceeded)
+                    .await;
+                Ok(())
+            }
+            Detection::Honeypot => self.handle_honeypot(context, guild_id, msg, config).await,
+            Detection::BlockedLink { host } => {
+                self.handle_blocked_link(context, guild_id, msg, config, host)
+                    .await
+            }
+            Detection::CoordinatedAttack { users } => {
+                self.alert_coordinated_attack(context, guild_id, msg, config, users)
+                    .await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Stores a detection during quiet hours to act on it once they end, and tells moderators about
+    /// it in the meantime.
+    async fn defer_action(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        detection: Detection,
+    ) -> Result<(), BroomError> {
+        let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+        let action = DeferredAction {
+            channel_id: msg.channel_id,
+            message_id: msg.id,
+            detection,
+        };
+        database
+            .record_deferred_action(guild_id, &action, unix_timestamp())
+            .await?;
+        tracing::info!(
+            "Deferred acting on message {} by {} in guild {} until its quiet hours end.",
+            msg.id,
+            msg.author.id,
+            guild_id
+        );
+
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_deferred_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_deferred",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("channel", &msg.channel_id.mention()),
+                    ("link", &msg.link()),
+                ],
+            ));
+        post_routine_audit_log(&*context.actions, config, embed).await;
+
+        Ok(())
+    }
+
+    /// Stores the detection along with the message for offline analysis, if it is sampled. Samples
+    /// are taken regardless of whether actions are taken, since both help tuning the settings.
+    async fn sample_detection(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        guild: &GuildView<'_>,
+        detection: &Detection,
+    ) -> Result<(), BroomError> {
+        let (sampler, database, policy) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<Samplers>(&data_read)?,
+                shared::<SharedDatabase>(&data_read)?,
+                shared::<ContentPolicies>(&data_read)?,
+            )
+        };
+        if !sampler.sample() {
+            return Ok(());
+        }
+
+        // Duplicates are exact reposts of the normalized content they were tracked by.
+        let (detection, similarity) = match detection {
+            Detection::Duplicate(_) => ("duplicate", Some(1.0)),
+            Detection::KnownScam { .. } => ("known_scam", Some(1.0)),
+            Detection::RepeatedMentions(_) => ("repeated_mentions", None),
+            Detection::Honeypot => ("honeypot", None),
+            Detection::BlockedLink { .. } => ("blocked_link", None),
+            Detection::CoordinatedAttack { .. } => ("coordinated_attack", None),
+        };
+        let sample = DetectionSample {
+            guild_id: guild.guild_id,
+            channel_id: msg.channel_id,
+            user_id: msg.author.id,
+            detection,
+            content: policy.minimize(&msg.content),
+            similarity,
+            config: guild.config.clone(),
+            created_at: unix_timestamp(),
+        };
+        if let Err(e) = database.record_detection_sample(&sample).await {
+            tracing::error!(
+                "There was an error while attempting to record a detection sample: {:?}",
+                e
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn handle_duplicate(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        duplicate: Duplicate,
+    ) -> Result<(), BroomError> {
+        if !can_read(context, guild_id, msg.channel_id) {
+            return Ok(());
+        }
+        let (action_lock, counters, fingerprints) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<ActionLocks>(&data_read)?,
+                shared::<SharedCounters>(&data_read)?,
+                shared::<FingerprintRegistries>(&data_read)?,
+            )
+        };
+
+        // Another replica that detected the same duplicate is already taking action.
+        let Some(guard) = action_lock.acquire(msg.guild_id, msg.id).await else {
+            return Ok(());
+        };
+
+        // Errors must not keep the lock from being released, which would keep other replicas
+        // from acting on the user until it expires.
+        let result = async {
+            counters.record(guild_id, GuildAction::Detected).await;
+            self.check_cross_guild(context, guild_id, msg, config)
+                .await?;
+            if duplicate.warn {
+                return self
+                    .warn_author(context, guild_id, msg, config, &duplicate, &counters)
+                    .await;
+            }
+            let result = match duplicate.action {
+                DuplicateAction::Delete => {
+                    for (channel_id, message_id) in duplicate.earlier_messages {
+                        self.delete_earlier_copy(context, msg, config, channel_id, message_id)
+                            .await;
+                        counters.record(guild_id, GuildAction::Deleted).await;
+                    }
+                    let violation = Violation::Duplicate {
+                        forwarded: duplicate.forwarded,
+                    };
+                    let deleted = self
+                        .delete_duplicate(context, guild_id, msg, config, violation, &counters)
+                        .await;
+                    if let Ok(true) = deleted {
+                        if config.audit_deletions {
+                            self.report_deletion(
+                                context,
+                                msg,
+                                config,
+                                duplicate.occurrences,
+                                guild_id,
+                                duplicate.first_channel_id,
+                            )
+                            .await;
+                        }
+                        if config.share_fingerprints {
+                            fingerprints.record(guild_id, &duplicate.text).await;
+                        }
+                    }
+                    deleted.map(|_| ())
+                }
+                DuplicateAction::Report => {
+                    self.report_duplicate(context, msg, config, &duplicate, guild_id)
+                        .await;
+                    counters.record(guild_id, GuildAction::Reported).await;
+                    let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+                    self.record_action(&database, guild_id, msg, HistoryAction::Reported)
+                        .await;
+                    Ok(())
+                }
+            };
+            let timeout = config
+                .invite_timeout_secs
+                .filter(|_| duplicate.invite && !duplicate.bridged);
+            if let Some(duration) = timeout {
+                self.time_out_invite_author(
+                    context,
+                    guild_id,
+                    msg,
+                    config,
+                    duplicate.occurrences,
+                    duration,
+                )
+                .await;
+            }
+
+            result
+        }
+        .await;
+        action_lock.release(guard).await;
+
+        result
+    }
+
+    async fn time_out_invite_author(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        occurrences: usize,
+        duration: u64,
+    ) {
+        let reason = i18n::translate(&config.language, "audit_invite_spam_title", &[]);
+        let mut description = i18n::translate(
+            &config.language,
+            "audit_invite_spam",
+            &[
+                ("author", &msg.author.id.mention()),
+                ("count", &occurrences),
+                ("channel", &msg.channel_id.mention()),
+            ],
+        );
+        description.push('\n');
+        description.push_str(
+            &self
+                .time_out(context, guild_id, msg.author.id, config, duration, &reason)
+                .await,
+        );
+
+        let embed = CreateEmbed::new().title(reason).description(description);
+        context.actions.post_audit_log(config, embed).await;
+    }
+
+    /// Alerts moderators about many users posting the same content at once, and locks the guild
+    /// down if it wants to. The alert is posted even in shadow mode, since attacks are rare and
+    /// urgent.
+    async fn alert_coordinated_attack(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        users: usize,
+    ) {
+        let title = i18n::translate(&config.language, "audit_coordinated_attack_title", &[]);
+        let mut description = i18n::translate(
+            &config.language,
+            "audit_coordinated_attack",
+            &[
+                ("count", &users),
+                ("window", &config.coordinated_attack_window_secs),
+                ("author", &msg.author.id.mention()),
+                ("channel", &msg.channel_id.mention()),
+                ("link", &msg.link()),
+            ],
+        );
+        if config.coordinated_attack_lockdown {
+            let note = match context.actions.lock_down(guild_id, &title).await {
+                Ok(()) => {
+                    tracing::warn!(
+                        "Locked down guild {} after {} users posted the same content.",
+                        guild_id,
+                        users
+                    );
+                    i18n::translate(&config.language, "audit_locked_down", &[])
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "There was an error while attempting to lock down a guild: {:?}",
+                        e
+                    );
+                    error_tracking::capture(
+                        "Could not lock down a guild",
+                        &e,
+                        Some(guild_id),
+                        None,
+                    );
+                    i18n::translate(&config.language, "audit_lockdown_failed", &[("error", &e)])
+                }
+            };
+            description.push('\n');
+            description.push_str(&note);
+        }
+
+        let embed = CreateEmbed::new().title(title).description(description);
+        context.actions.post_audit_log(config, embed).await;
+    }
+
+    /// Times out the given member and returns a note for the audit channel on whether it
+    /// succeeded.
+    async fn time_out(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        user_id: UserId,
+        config: &GuildConfig,
+        duration: u64,
+        reason: &str,
+    ) -> String {
+        match context
+            .actions
+            .timeout(guild_id, user_id, duration, reason)
+            .await
+        {
+            Ok(()) => i18n::translate(
+                &config.language,
+                "audit_timed_out",
+                &[("user", &user_id.mention()), ("duration", &duration)],
+            ),
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to time out a member: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not time out a member",
+                    &e,
+                    Some(guild_id),
+                    Some(user_id),
+                );
+                i18n::translate(
+                    &config.language,
+                    "audit_timeout_failed",
+                    &[("user", &user_id.mention()), ("error", &e)],
+                )
+            }
+        }
+    }
+
+    /// Takes action on a message whose content other guilds sharing fingerprints removed,
+    /// regardless of how often it was posted.
+    async fn handle_known_scam(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        action: DuplicateAction,
+        other_guilds: usize,
+    ) -> Result<(), BroomError> {
+        if !can_read(context, guild_id, msg.channel_id) {
+            return Ok(());
+        }
+        let (action_lock, counters) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<ActionLocks>(&data_read)?,
+                shared::<SharedCounters>(&data_read)?,
+            )
+        };
+        let Some(guard) = action_lock.acquire(msg.guild_id, msg.id).await else {
+            return Ok(());
+        };
+
+        let result = async {
+            counters.record(guild_id, GuildAction::Detected).await;
+            self.check_cross_guild(context, guild_id, msg, config)
+                .await?;
+            let key = match action {
+                DuplicateAction::Delete => "audit_known_scam_deleted",
+                DuplicateAction::Report => "audit_known_scam_reported",
+            };
+            let description = i18n::translate(
+                &config.language,
+                key,
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("channel", &msg.channel_id.mention()),
+                    ("count", &other_guilds),
+                    ("link", &msg.link()),
+                ],
+            );
+            match action {
+                DuplicateAction::Delete => {
+                    let violation = Violation::KnownScam;
+                    let deleted = self
+                        .delete_duplicate(context, guild_id, msg, config, violation, &counters)
+                        .await;
+                    if let Ok(true) = deleted {
+                        self.post_known_scam(context, msg, config, description, false)
+                            .await;
+                    }
+                    deleted.map(|_| ())
+                }
+                DuplicateAction::Report => {
+                    self.post_known_scam(context, msg, config, description, true)
+                        .await;
+                    counters.record(guild_id, GuildAction::Reported).await;
+                    let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+                    self.record_action(&database, guild_id, msg, HistoryAction::Reported)
+                        .await;
+                    Ok(())
+                }
+            }
+        }
+        .await;
+        action_lock.release(guard).await;
+
+        result
+    }
+
+    /// Deletes a message linking to a blocked domain right away, without waiting for reposts or
+    /// warning its author first, since a single click on it is enough to do harm.
+    async fn handle_blocked_link(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        host: String,
+    ) -> Result<(), BroomError> {
+        if !can_read(context, guild_id, msg.channel_id) {
+            return Ok(());
+        }
+        let (action_lock, counters) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<ActionLocks>(&data_read)?,
+                shared::<SharedCounters>(&data_read)?,
+            )
+        };
+        let Some(guard) = action_lock.acquire(msg.guild_id, msg.id).await else {
+            return Ok(());
+        };
+
+        counters.record(guild_id, GuildAction::Detected).await;
+        let deleted = self
+            .delete_duplicate(
+                context,
+                guild_id,
+                msg,
+                config,
+                Violation::BlockedLink,
+                &counters,
+            )
+            .await;
+        if let Ok(true) = deleted {
+            let embed = CreateEmbed::new()
+                .title(i18n::translate(
+                    &config.language,
+                    "audit_blocked_link_title",
+                    &[],
+                ))
+                .description(i18n::translate(
+                    &config.language,
+                    "audit_blocked_link_deleted",
+                    &[
+                        ("author", &msg.author.id.mention()),
+                        ("channel", &msg.channel_id.mention()),
+                        ("host", &host),
+                    ],
+                ));
+            post_routine_audit_log(&*context.actions, config, embed).await;
+        }
+
+        action_lock.release(guard).await;
+
+        deleted.map(|_| ())
+    }
+
+    /// Deletes a message posted in a honeypot channel and bans its author unless the guild only
+    /// deletes such messages. Its author is not messaged, since only spammers can see the channel,
+    /// unless the guild warns them before banning them.
+    async fn handle_honeypot(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        let (action_lock, counters, database) = {
+            let data_read = context.data.read().await;
+            (
+                shared::<ActionLocks>(&data_read)?,
+                shared::<SharedCounters>(&data_read)?,
+                shared::<SharedDatabase>(&data_read)?,
+            )
+        };
+        let Some(guard) = action_lock.acquire(msg.guild_id, msg.id).await else {
+            return Ok(());
+        };
+
+        counters.record(guild_id, GuildAction::Detected).await;
+        self.record_action(&database, guild_id, msg, HistoryAction::HoneypotTriggered)
+            .await;
+        let title = i18n::translate(&config.language, "audit_honeypot_title", &[]);
+        let mut description = i18n::translate(
+            &config.language,
+            "audit_honeypot",
+            &[
+                ("author", &msg.author.id.mention()),
+                ("channel", &msg.channel_id.mention()),
+            ],
+        );
+        self.archive_message(
+            context,
+            &database,
+            guild_id,
+            msg,
+            config,
+            HistoryAction::HoneypotTriggered,
+        )
+        .await;
+        match context.actions.delete_message(msg.channel_id, msg.id).await {
+            Ok(()) => counters.record(guild_id, GuildAction::Deleted).await,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to delete a message in a honeypot channel: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not delete a message in a honeypot channel",
+                    &e,
+                    Some(guild_id),
+                    Some(msg.author.id),
+                );
+            }
+        }
+        if let (HoneypotAction::Ban, Some(warning_secs)) =
+            (config.honeypot_action, config.honeypot_warning_secs)
+        {
+            let fire_at = escalation::warn_before_ban(
+                context,
+                guild_id,
+                msg.author.id,
+                None,
+                &title,
+                warning_secs,
+                config,
+            )
+            .await;
+            let note = match fire_at {
+                Ok(Some(fire_at)) => i18n::translate(
+                    &config.language,
+                    "audit_ban_pending",
+                    &[
+                        ("user", &msg.author.id.mention()),
+                        ("time", &format!("<t:{}:R>", fire_at)),
+                    ],
+                ),
+                Ok(None) => i18n::translate(
+                    &config.language,
+                    "audit_ban_already_pending",
+                    &[("user", &msg.author.id.mention())],
+                ),
+                Err(e) => {
+                    tracing::error!(
+                        "There was an error while attempting to schedule a ban: {}",
+                        e
+                    );
+                    i18n::translate(
+                        &config.language,
+                        "audit_honeypot_ban_failed",
+                        &[("user", &msg.author.id.mention()), ("error", &e)],
+                    )
+                }
+            };
+            description.push('\n');
+            description.push_str(&note);
+        } else if config.honeypot_action == HoneypotAction::Ban {
+            let result = context
+                .actions
+                .ban(
+                    guild_id,
+                    msg.author.id,
+                    config.ban_delete_message_days,
+                    &title,
+                )
+                .await;
+            let note = match result {
+                Ok(()) => i18n::translate(
+                    &config.language,
+                    "audit_honeypot_banned",
+                    &[("user", &msg.author.id.mention())],
+                ),
+                Err(e) => {
+                    tracing::error!(
+                        "There was an error while attempting to ban a member: {:?}",
+                        e
+                    );
+                    error_tracking::capture(
+                        "Could not ban a member",
+                        &e,
+                        Some(guild_id),
+                        Some(msg.author.id),
+                    );
+                    i18n::translate(
+                        &config.language,
+                        "audit_honeypot_ban_failed",
+                        &[("user", &msg.author.id.mention()), ("error", &e)],
+                    )
+                }
+            };
+            description.push('\n');
+            description.push_str(&note);
+        }
+
+        let embed = CreateEmbed::new().title(title).description(description);
+        if config.honeypot_action == HoneypotAction::Ban {
+            context.actions.post_audit_log(config, embed).await;
+        } else {
+            post_routine_audit_log(&*context.actions, config, embed).await;
+        }
+        action_lock.release(guard).await;
+
+        Ok(())
+    }
+
+    async fn post_known_scam(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        config: &GuildConfig,
+        description: String,
+        reported: bool,
+    ) {
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_known_scam_title",
+                &[],
+            ))
+            .description(description);
+        if !reported {
+            post_routine_audit_log(&*context.actions, config, embed).await;
+        } else if !config.shadow_mode {
+            post_report(&*context.actions, config, embed, msg, Violation::KnownScam).await;
+        }
+    }
+
+    /// Deletes a message that was reported rather than deleted when it was detected, on behalf of a
+    /// moderator. Returns whether the message was deleted.
+    pub async fn delete_reported(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        violation: Violation,
+    ) -> Result<bool, BroomError> {
+        let counters = shared::<SharedCounters>(&*context.data.read().await)?;
+
+        self.delete_duplicate(context, guild_id, msg, config, violation, &counters)
+            .await
+    }
+
+    /// Deletes a message and messages its author about it. Returns whether the message was
+    /// deleted, which might have failed e.g. due to missing permissions.
+    async fn delete_duplicate(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        violation: Violation,
+        counters: &Arc<ActionCounters>,
+    ) -> Result<bool, BroomError> {
+        let language = self.dm_language(context, msg.author.id, config).await?;
+        // Messages in the text chat of a stage are easily mistaken for messages in a text channel.
+        let stage =
+            context.actions.channel_type(guild_id, msg.channel_id) == Some(ChannelType::Stage);
+        let dm_intro = match context.actions.guild_name(guild_id) {
+            Some(guild_name) => {
+                let key = if stage {
+                    "dm_deleted_in_guild_stage"
+                } else {
+                    "dm_deleted_in_guild"
+                };
+                i18n::translate(language, key, &[("guild", &guild_name)])
+            }
+            None => i18n::translate(language, "dm_deleted", &[]),
+        };
+
+        let dm_content = MessageBuilder::new()
+            .push(dm_intro)
+            .push(" ")
+            .push(i18n::translate(language, violation.dm_reason(), &[]))
+            .build();
+
+        let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+        let dm_reachable = self.dm_reachable(&database, msg.author.id).await;
+        let dm_title = i18n::translate(language, "dm_deleted_title", &[]);
+        let dropped_dm = content::truncate(&dm_content, MAX_DROPPED_DM_LENGTH);
+        let dm = self
+            .format_dm(&database, msg.author.id, config, dm_title, dm_content)
+            .await;
+
+        self.archive_message(
+            context,
+            &database,
+            guild_id,
+            msg,
+            config,
+            violation.history_action(),
+        )
+        .await;
+        if let Err(e) = context.actions.delete_message(msg.channel_id, msg.id).await {
+            tracing::error!(
+                "There was an error while attempting to delete a duplicate message: {:?}",
+                e
+            );
+            error_tracking::capture(
+                "Could not delete a duplicate",
+                &e,
+                Some(guild_id),
+                Some(msg.author.id),
+            );
+            return Ok(false);
+        }
+
+        counters.record(guild_id, GuildAction::Deleted).await;
+        self.record_action(&database, guild_id, msg, violation.history_action())
+            .await;
+        // A bridge relays the messages of others, so neither messaging nor banning it helps.
+        if config.trusted_bridges.contains_key(&msg.author.id) {
+            return Ok(true);
+        }
+        if let Err(e) = context
+            .actions
+            .propose_ban(guild_id, msg.author.id, config)
+            .await
+        {
+            tracing::error!(
+                "There was an error while attempting to propose a ban: {}",
+                e
+            );
+            error_tracking::capture(
+                "Could not propose a ban",
+                &e,
+                Some(guild_id),
+                Some(msg.author.id),
+            );
+        }
+        let state = shared::<GuildStates>(&*context.data.read().await)?
+            .get(guild_id)
+            .await?;
+        if let Err(e) = probation::check(&database, &state, guild_id, msg.author.id, config).await {
+            tracing::error!(
+                "There was an error while attempting to put a user on probation: {}",
+                e
+            );
+        }
+        if !dm_reachable {
+            tracing::info!("Skipped messaging the author of a deleted message since they are known to be unreachable.");
+            return Ok(true);
+        }
+
+        // Messaging the author right away may notify them before they noticed the deletion, so the
+        // message is delayed without holding up the handling of further messages.
+        let actions = context.actions.clone();
+        let author_id = msg.author.id;
+        let counters = counters.clone();
+        let config = config.clone();
+        // Users on probation are told right away, since they should know what they did by now.
+        let delay = if state.probations.read().await.contains(&author_id) {
+            Duration::ZERO
+        } else {
+            Duration::from_secs(config.dm_delay_secs.into())
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = actions.direct_message(author_id, dm).await {
+                if actions::is_dropped(&e) {
+                    report_dropped_dm(&*actions, &config, guild_id, author_id, &dropped_dm).await;
+                }
+                tracing::error!(
+                    "There was an error while attempting to message an author of a deleted message: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not message an author of a deleted message",
+                    &e,
+                    Some(guild_id),
+                    Some(author_id),
+                );
+                counters.record(guild_id, GuildAction::DmFailed).await;
+            }
+        });
+
+        Ok(true)
+    }
+
+    /// Warns the author of a duplicate instead of deleting it and informs moderators about it.
+    async fn warn_author(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        duplicate: &Duplicate,
+        counters: &Arc<ActionCounters>,
+    ) -> Result<(), BroomError> {
+        let database = shared::<SharedDatabase>(&*context.data.read().await)?;
+        self.record_action(&database, guild_id, msg, HistoryAction::Warned)
+            .await;
+
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_duplicate_warned_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_duplicate_warned",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("count", &duplicate.occurrences),
+                    ("channel", &msg.channel_id.mention()),
+                    ("link", &msg.link()),
+                ],
+            ));
+        let embed = moderation::channel_links(
+            embed,
+            &config.language,
+            guild_id,
+            msg.channel_id,
+            duplicate.first_channel_id,
+            context.actions.channel_type(guild_id, msg.channel_id),
+        );
+        if !config.shadow_mode {
+            let violation = Violation::Duplicate {
+                forwarded: duplicate.forwarded,
+            };
+            post_report(&*context.actions, config, embed, msg, violation).await;
+        }
+
+        if !self.dm_reachable(&database, msg.author.id).await {
+            tracing::info!(
+                "Skipped warning the author of a duplicate since they are known to be unreachable."
+            );
+            return Ok(());
+        }
+        let language = self.dm_language(context, msg.author.id, config).await?;
+        let content = match context.actions.guild_name(guild_id) {
+            Some(guild_name) => {
+                i18n::translate(language, "dm_warning_in_guild", &[("guild", &guild_name)])
+            }
+            None => i18n::translate(language, "dm_warning", &[]),
+        };
+        let title = i18n::translate(language, "dm_warning_title", &[]);
+        let dropped_dm = content::truncate(&content, MAX_DROPPED_DM_LENGTH);
+        let dm = self
+            .format_dm(&database, msg.author.id, config, title, content)
+            .await;
+        if let Err(e) = context.actions.direct_message(msg.author.id, dm).await {
+            if actions::is_dropped(&e) {
+                report_dropped_dm(
+                    &*context.actions,
+                    config,
+                    guild_id,
+                    msg.author.id,
+                    &dropped_dm,
+                )
+                .await;
+            }
+            tracing::error!(
+                "There was an error while attempting to warn an author of a duplicate: {:?}",
+                e
+            );
+            error_tracking::capture(
+                "Could not warn an author of a duplicate",
+                &e,
+                Some(guild_id),
+                Some(msg.author.id),
+            );
+            counters.record(guild_id, GuildAction::DmFailed).await;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the given author can be messaged, assuming they can unless they are known not to or
+    /// opted out of it.
+    async fn dm_reachable(&self, database: &Database, user_id: UserId) -> bool {
+        match database.dm_opted_out(user_id).await {
+            Ok(true) => return false,
+            Ok(false) => {}
+            Err(e) => tracing::error!(
+                "There was an error while attempting to look up whether a user opted out of messages: {:?}",
+                e
+            ),
+        }
+        match database.dm_reachable(user_id).await {
+            Ok(reachable) => reachable.unwrap_or(true),
+            Err(e) => {
+                tracing::error!("There was an error while attempting to look up the DM reachability of a user: {:?}", e);
+                error_tracking::capture(
+                    "Could not look up the DM reachability of a user",
+                    &e,
+                    None,
+                    Some(user_id),
+                );
+                true
+            }
+        }
+    }
+
+    /// Formats a message to an author as they chose, or as the guild does by default.
+    async fn format_dm(
+        &self,
+        database: &Database,
+        user_id: UserId,
+        config: &GuildConfig,
+        title: String,
+        content: String,
+    ) -> CreateMessage {
+        let dm_format = match database.dm_format(user_id).await {
+            Ok(format) => format.unwrap_or(config.dm_format),
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to look up the DM format of a user: {:?}",
+                    e
+                );
+                error_tracking::capture(
+                    "Could not look up the DM format of a user",
+                    &e,
+                    None,
+                    Some(user_id),
+                );
+                config.dm_format
+            }
+        };
+
+        match dm_format {
+            DmFormat::PlainText => CreateMessage::new().content(content),
+            DmFormat::Embed => {
+                CreateMessage::new().embed(CreateEmbed::new().title(title).description(content))
+            }
+        }
+    }
+
+    /// Returns the language of the messages sent to the given author, which is their own if the
+    /// guild opted into it and their locale is known.
+    async fn dm_language<'a>(
+        &self,
+        context: &MessageContext,
+        user_id: UserId,
+        config: &'a GuildConfig,
+    ) -> Result<&'a str, BroomError> {
+        if !config.dm_user_locale {
+            return Ok(&config.language);
+        }
+
+        let language = context.actions.user_language(user_id).await?;
+
+        Ok(language.unwrap_or(&config.language))
+    }
+
+    async fn record_action(
+        &self,
+        database: &Database,
+        guild_id: GuildId,
+        msg: &Message,
+        action: HistoryAction,
+    ) {
+        let mut entry = HistoryEntry::new(msg.author.id, action, unix_timestamp());
+        entry.channel_id = Some(msg.channel_id);
+        if let Err(e) = database.record_action(guild_id, &entry).await {
+            tracing::error!(
+                "There was an error while attempting to record an action: {:?}",
+                e
+            );
+            error_tracking::capture(
+                "Could not record an action",
+                &e,
+                Some(guild_id),
+                Some(msg.author.id),
+            );
+        }
+    }
+
+    /// Archives a message that is about to be deleted, if the guild enabled archiving.
+    async fn archive_message(
+        &self,
+        context: &MessageContext,
+        database: &Database,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        reason: HistoryAction,
+    ) {
+        if !config.archive_before_delete {
+            return;
+        }
+
+        let result = match shared::<ContentPolicies>(&*context.data.read().await) {
+            Ok(policy) => database
+                .archive_message(
+                    guild_id,
+                    &policy.minimize_message(msg),
+                    reason,
+                    unix_timestamp(),
+                )
+                .await
+                .map_err(BroomError::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!(
+                "There was an error while attempting to archive a message: {:?}",
+                e
+            );
+            error_tracking::capture(
+                "Could not archive a message",
+                &e,
+                Some(guild_id),
+                Some(msg.author.id),
+            );
+        }
+    }
+
+    /// Deletes an earlier copy of a duplicate that was posted before the threshold was reached and
+    /// informs moderators about it, since its author is not messaged a second time.
+    async fn delete_earlier_copy(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        config: &GuildConfig,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) {
+        if let Err(e) = context.actions.delete_message(channel_id, message_id).await {
+            tracing::error!(
+                "There was an error while attempting to delete an earlier copy of a duplicate: {:?}",
+                e
+            );
+            error_tracking::capture(
+                "Could not delete an earlier copy of a duplicate",
+                &e,
+                msg.guild_id,
+                Some(msg.author.id),
+            );
+            return;
+        }
+
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_retroactive_delete_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_retroactive_delete",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("channel", &channel_id.mention()),
+                    ("message", &message_id),
+                ],
+            ));
+        post_routine_audit_log(&*context.actions, config, embed).await;
+    }
+
+    /// Informs moderators about a deleted duplicate.
+    async fn report_deletion(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        config: &GuildConfig,
+        occurrences: usize,
+        guild_id: GuildId,
+        first_channel_id: ChannelId,
+    ) {
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_duplicate_deleted_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_duplicate_deleted",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("count", &occurrences),
+                    ("channel", &msg.channel_id.mention()),
+                ],
+            ));
+        let embed = moderation::channel_links(
+            embed,
+            &config.language,
+            guild_id,
+            msg.channel_id,
+            first_channel_id,
+            context.actions.channel_type(guild_id, msg.channel_id),
+        );
+        post_routine_audit_log(&*context.actions, config, embed).await;
+    }
+
+    /// Informs moderators about a duplicate without taking any action on it.
+    async fn report_duplicate(
+        &self,
+        context: &MessageContext,
+        msg: &Message,
+        config: &GuildConfig,
+        duplicate: &Duplicate,
+        guild_id: GuildId,
+    ) {
+        let embed = CreateEmbed::new()
+            .title(i18n::translate(
+                &config.language,
+                "audit_duplicate_title",
+                &[],
+            ))
+            .description(i18n::translate(
+                &config.language,
+                "audit_duplicate",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("count", &duplicate.occurrences),
+                    ("channel", &msg.channel_id.mention()),
+                    ("link", &msg.link()),
+                ],
+            ));
+        let embed = moderation::channel_links(
+            embed,
+            &config.language,
+            guild_id,
+            msg.channel_id,
+            duplicate.first_channel_id,
+            context.actions.channel_type(guild_id, msg.channel_id),
+        );
+        let violation = Violation::Duplicate {
+            forwarded: duplicate.forwarded,
+        };
+        post_report(&*context.actions, config, embed, msg, violation).await;
+    }
+
+    /// Alerts the operator about authors triggering detections in several guilds within the
+    /// global cooldown, who are likely spamming every server they are in. Guilds are never told
+    /// about other guilds.
+    async fn check_cross_guild(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+    ) -> Result<(), BroomError> {
+        // Bridges relay the messages of many authors in every guild they are in.
+        if config.trusted_bridges.contains_key(&msg.author.id) {
+            return Ok(());
+        }
+        let global = shared::<SharedGlobalState>(&*context.data.read().await)?;
+        let Some((previous_guild_id, elapsed)) = global
+            .record_detection(msg.author.id, guild_id, Instant::now())
+            .await
+        else {
+            return Ok(());
+        };
+
+        tracing::info!(
+            "User {} triggered a detection in guild {} {} seconds after one in guild {}.",
+            msg.author.id,
+            guild_id,
+            elapsed.as_secs(),
+            previous_guild_id
+        );
+        let guild_name = |guild_id: GuildId| {
+            context
+                .actions
+                .guild_name(guild_id)
+                .map(|name| format!("{} ({})", name, guild_id))
+                .unwrap_or_else(|| guild_id.to_string())
+        };
+        let embed = CreateEmbed::new()
+            .title("Cross-guild detection")
+            .description(format!(
+                "{} ({}) triggered a detection in {} {} seconds after one in {}.",
+                msg.author.id.mention(),
+                msg.author.id,
+                guild_name(guild_id),
+                elapsed.as_secs(),
+                guild_name(previous_guild_id)
+            ))
+            .timestamp(Timestamp::now());
+        context.actions.post_operator_alert(embed).await;
+
+        Ok(())
+    }
+
+    /// Alerts moderators about an author repeatedly mentioning the given users.
+    async fn alert_repeated_mentions(
+        &self,
+        context: &MessageContext,
+        guild_id: GuildId,
+        msg: &Message,
+        config: &GuildConfig,
+        exceeded: Vec<(UserId, usize)>,
+    ) {
+        for (target, count) in exceeded {
+            let mut description = i18n::translate(
+                &config.language,
+                "audit_repeated_mentions",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("target", &target.mention()),
+                    ("count", &count),
+                    ("window", &config.repeated_mention_window_secs),
+                ],
+            );
+
+            if let Some(duration) = config.repeated_mention_timeout_secs {
+                let reason =
+                    i18n::translate(&config.language, "audit_repeated_mentions_title", &[]);
+                let note = self
+                    .time_out(context, guild_id, msg.author.id, config, duration, &reason)
+                    .await;
+                description.push('\n');
+                description.push_str(&note);
+            }
+
+            let embed = CreateEmbed::new()
+                .title(i18n::translate(
+                    &config.language,
+                    "audit_repeated_mentions_title",
+                    &[],
+                ))
+                .description(description);
+            if config.repeated_mention_timeout_secs.is_some() {
+                context.actions.post_audit_log(config, embed).await;
+            } else {
+                post_routine_audit_log(&*context.actions, config, embed).await;
+            }
+        }
+    }
+}
+
+/// Posts an embed about a routine detection to the audit channel, unless the guild is in shadow
+/// mode and only wants to hear about escalations.
+async fn post_routine_audit_log(
+    actions: &dyn DiscordActions,
+    config: &GuildConfig,
+    embed: CreateEmbed,
+) {
+    if config.shadow_mode {
+        return;
+    }
+
+    actions.post_audit_log(config, embed).await;
+}
+
+/// Posts an embed about a message that was detected but not deleted to the audit channel, along
+/// with buttons to delete it or to dismiss the report if the guild enabled them.
+async fn post_report(
+    actions: &dyn DiscordActions,
+    config: &GuildConfig,
+    embed: CreateEmbed,
+    msg: &Message,
+    violation: Violation,
+) {
+    if !config.report_buttons {
+        actions.post_audit_log(config, embed).await;
+        return;
+    }
+
+    let buttons = reports::buttons(
+        &config.language,
+        violation,
+        msg.channel_id,
+        msg.id,
+        msg.author.id,
+    );
+    actions.post_audit_report(config, embed, buttons).await;
+}
+
+/// Reports a message to an author that was dropped since too many actions of the guild are queued,
+/// so that moderators still learn about the detection if the guild opted into it.
+async fn report_dropped_dm(
+    actions: &dyn DiscordActions,
+    config: &GuildConfig,
+    guild_id: GuildId,
+    author_id: UserId,
+    content: &str,
+) {
+    match config.dm_queue_overflow_action {
+        DmOverflowAction::Drop => {}
+        DmOverflowAction::LogOnly => tracing::warn!(
+            "Dropped a message to user {} in guild {} since too many actions are queued: {}",
+            author_id,
+            guild_id,
+            content
+        ),
+        DmOverflowAction::AuditChannelFallback => {
+            let embed = CreateEmbed::new()
+                .title(i18n::translate(
+                    &config.language,
+                    "audit_dm_dropped_title",
+                    &[],
+                ))
+                .description(i18n::translate(
+                    &config.language,
+                    "audit_dm_dropped",
+                    &[("user", &author_id.mention()), ("content", &content)],
+                ));
+            actions.post_audit_log(config, embed).await;
+        }
+    }
+}
+
+/// Whether the author of a message posted in a honeypot channel is exempt from its action, which
+/// bots and moderators are. Authors whose permissions are unknown are exempt as well, since banning
+/// a moderator by mistake is worse than letting a spammer go.
+fn is_honeypot_exempt(context: &MessageContext, guild_id: GuildId, msg: &Message) -> bool {
+    if msg.author.bot || msg.webhook_id.is_some() {
+        return true;
+    }
+    let Some(member) = &msg.member else {
+        return true;
+    };
+
+    let exempt = context
+        .actions
+        .member_permissions(guild_id, msg.author.id, member)
+        .is_none_or(|permissions| {
+            permissions.administrator() || permissions.contains(Permissions::MANAGE_MESSAGES)
+        });
+    if exempt {
+        tracing::info!(
+            "Not taking action on a message by {} in the honeypot channel {} of guild {} since they may be a moderator.",
+            msg.author.id,
+            msg.channel_id,
+            guild_id
+        );
+    }
+
+    exempt
+}
+
+/// Whether the bot can read the given channel, warning if it cannot. Permissions may have changed
+/// since the channel was configured, e.g. by restrictive overwrites making it private, in which
+/// case actions would fail or be based on an incomplete view of the channel.
+fn can_read(context: &MessageContext, guild_id: GuildId, channel_id: ChannelId) -> bool {
+    let Some(permissions) = context.actions.channel_permissions(guild_id, channel_id) else {
+        return true;
+    };
+    let missing = READ_PERMISSIONS.difference(permissions);
+    if missing.is_empty() {
+        return true;
+    }
+
+    tracing::warn!(
+        "Not taking action on a duplicate in channel {} of guild {} since the bot lacks the {} permissions there.",
+        channel_id,
+        guild_id,
+        missing
+    );
+    false
+}
+
+#[serenity::async_trait]
+impl EventHandler for Handler {
+    async fn message(&self, context: Context, msg: Message) {
+        if let Err(e) = self
+            .handle_message(&MessageContext::new(&context), &msg)
+            .await
+        {
+            tracing::error!("There was an error while handling a message: {}", e);
+            error_tracking::capture(
+                "Could not handle a message",
+                &e,
+                msg.guild_id,
+                Some(msg.author.id),
+            );
+        }
+    }
+
+    async fn ready(&self, context: Context, data: Ready) {
+        tracing::info!("{} is connected and running.", data.user.name);
+        quiet_hours::start(&context);
+        escalation::start(&context);
+
+        if let Err(e) = Command::create_global_command(&context.http, commands::register()).await {
+            tracing::error!(
+                "There was an error while attempting to register the commands: {:?}",
+                e
+            );
+        }
+    }
+
+    async fn shard_stage_update(&self, context: Context, event: ShardStageUpdateEvent) {
+        if event.new == ConnectionStage::Disconnected || event.old == ConnectionStage::Connected {
+            tracing::warn!(
+                "Shard {} changed from {:?} to {:?}.",
+                event.shard_id,
+                event.old,
+                event.new
+            );
+        } else {
+            tracing::info!(
+                "Shard {} changed from {:?} to {:?}.",
+                event.shard_id,
+                event.old,
+                event.new
+            );
+        }
+
+        let monitor = match shared::<ShardMonitors>(&*context.data.read().await) {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                tracing::error!("There was an error while tracking a shard: {}", e);
+                return;
+            }
+        };
+        let Some(downtime) = monitor.update(&event) else {
+            return;
+        };
+        tracing::info!(
+            "Shard {} reconnected after {} seconds.",
+            event.shard_id,
+            downtime.as_secs()
+        );
+        if !monitor.exceeds_acceptable(downtime) {
+            return;
+        }
+
+        let embed = CreateEmbed::new()
+            .title("Shard reconnected")
+            .description(format!(
+                "Shard {} was disconnected from the gateway for {} seconds.",
+                event.shard_id,
+                downtime.as_secs()
+            ))
+            .timestamp(Timestamp::now());
+        MessageContext::new(&context)
+            .actions
+            .post_operator_alert(embed)
+            .await;
+    }
+
+    async fn guild_create(&self, context: Context, guild: Guild, _is_new: Option<bool>) {
+        match shared::<SharedGlobalState>(&*context.data.read().await) {
+            Ok(global) => global.start_grace_period(guild.id).await,
+            Err(e) => tracing::error!(
+                "There was an error while attempting to start the grace period of a guild: {}",
+                e
+            ),
+        }
+
+        let guilds = match shared::<GuildStates>(&*context.data.read().await) {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to record joining a guild: {}",
+                    e
+                );
+                return;
+            }
+        };
+        if let Err(e) = guilds
+            .record_join(guild.id, guild.joined_at.unix_timestamp())
+            .await
+        {
+            tracing::error!(
+                "There was an error while attempting to record joining a guild: {}",
+                e
+            );
+        }
+
+        match guilds.get(guild.id).await {
+            Ok(state) => {
+                templates::refresh(&context.http, guild.id, &state).await;
+                // Custom commands stay registered, but the configuration may have been restored
+                // from a backup in the meantime.
+                let commands = state.config.read().await.custom_commands.clone();
+                if !commands.is_empty() {
+                    if let Err(e) = custom_commands::sync(&context.http, guild.id, &commands).await
+                    {
+                        tracing::error!(
+                            "There was an error while registering the custom commands of a guild: {:?}",
+                            e
+                        );
+                    }
+                }
+            }
+            Err(e) => tracing::error!(
+                "There was an error while attempting to fetch the templates of a guild: {}",
+                e
+            ),
+        }
+    }
+
+    async fn guild_member_addition(&self, context: Context, member: Member) {
+        if let Err(e) = alt_accounts::check(&context, &member).await {
+            tracing::error!(
+                "There was an error while checking a joining member for alt accounts: {}",
+                e
+            );
+        }
+    }
+
+    async fn interaction_create(&self, context: Context, interaction: Interaction) {
+        let user_locale = match &interaction {
+            Interaction::Command(command) => Some((command.user.id, &command.locale)),
+            Interaction::Component(component) => Some((component.user.id, &component.locale)),
+            _ => None,
+        };
+        if let Some((user_id, locale)) = user_locale {
+            match shared::<UserLocaleCaches>(&*context.data.read().await) {
+                Ok(locales) => locales.remember(user_id, locale).await,
+                Err(e) => {
+                    tracing::error!("There was an error while handling an interaction: {}", e)
+                }
+            }
+        }
+
+        match interaction {
+            Interaction::Command(command) if command.data.name == "broom" => {
+                commands::handle(&context, &command).await;
+            }
+            // Every other command is one of the custom commands of the guild.
+            Interaction::Command(command) => commands::handle_custom(&context, &command).await,
+            Interaction::Autocomplete(command) if command.data.name == "broom" => {
+                commands::autocomplete(&context, &command).await;
+            }
+            Interaction::Component(component)
+                if component
+                    .data
+                    .custom_id
+                    .starts_with(escalation::CUSTOM_ID_PREFIX) =>
+            {
+                escalation::handle_component(&context, &component).await;
+            }
+            Interaction::Component(component)
+                if component
+                    .data
+                    .custom_id
+                    .starts_with(reports::CUSTOM_ID_PREFIX) =>
+            {
+                reports::handle_component(&context, &component).await;
+            }
+            Interaction::Component(component)
+                if component
+                    .data
+                    .custom_id
+                    .starts_with(commands::RESET_GUILD_CUSTOM_ID_PREFIX) =>
+            {
+                commands::handle_reset_guild(&context, &component).await;
+            }
+            #[cfg(feature = "ml_suggestions")]
+            Interaction::Component(component)
+                if component
+                    .data
+                    .custom_id
+                    .starts_with(commands::SUGGEST_FILTERS_CUSTOM_ID_PREFIX) =>
+            {
+                commands::handle_suggest_filters(&context, &component).await;
+            }
+            _ => {}
+        }
+    }
+}
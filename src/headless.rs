@@ -0,0 +1,115 @@
+//! Handling of messages read from stdin without connecting to Discord, e.g. to try out a
+//! configuration against a recorded spam wave. Every line is a message as serialized by Discord,
+//! and the actions that would be taken are printed to stdout as JSON lines, see
+//! [`PrintedActions`].
+
+use std::{collections::HashSet, env, sync::Arc, time::Duration};
+
+use serenity::{
+    model::prelude::Message,
+    prelude::{RwLock, TypeMap},
+};
+use tokio::io::{self, AsyncBufReadExt, BufReader};
+
+use crate::{
+    actions::{MessageContext, PrintedActions},
+    blocklist::{DomainBlocklist, DomainBlocklists},
+    config::ConfigLimits,
+    counters::{ActionCounters, SharedCounters},
+    db::{Database, SharedDatabase},
+    detectors::{RepeatedMentionDetector, RepeatedMentionDetectors},
+    error::BroomError,
+    fingerprints::{FingerprintRegistries, FingerprintRegistry, FingerprintSettings},
+    handler::Handler,
+    lock::{ActionLock, ActionLocks},
+    maintenance::{Maintenance, MaintenanceModes},
+    privacy::{ContentPolicies, ContentPolicy},
+    ratelimit::{ActionRateLimiter, ActionRateLimiters, RateLimitSettings},
+    sampling::{Sampler, Samplers},
+    state::{write_batch_interval_from_env, GlobalState, GuildStates, Guilds, SharedGlobalState},
+    stats::{SharedStats, Stats},
+};
+
+/// Grace period in milliseconds after the longest delay of direct messages, so that those sent
+/// last are printed before exiting.
+const DIRECT_MESSAGE_GRACE_IN_MILLIS: u64 = 500;
+
+/// Handles every message read from stdin until it is closed. The database defaults to an
+/// in-memory one, so that a run starts with default configurations unless `DATABASE_URL` points
+/// to a prepared one.
+pub async fn run() -> Result<(), BroomError> {
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+    let database = Arc::new(Database::connect(&database_url).await?);
+    let stats = Arc::new(Stats::default());
+    let counters = Arc::new(ActionCounters::new(database.clone()));
+    let limits = ConfigLimits::from_env()?;
+    let fingerprints = Arc::new(
+        FingerprintRegistry::load(database.clone(), FingerprintSettings::from_env()?).await?,
+    );
+    let guilds = Arc::new(Guilds::new(
+        stats.clone(),
+        database.clone(),
+        limits,
+        write_batch_interval_from_env()?,
+    ));
+    tokio::spawn(guilds.clone().run_write_batches());
+    let rate_limiter = Arc::new(ActionRateLimiter::new(RateLimitSettings::from_env()?));
+    tokio::spawn(rate_limiter.clone().run());
+
+    let mut data = TypeMap::new();
+    data.insert::<GuildStates>(guilds.clone());
+    data.insert::<ActionLocks>(Arc::new(ActionLock::unshared()));
+    data.insert::<SharedStats>(stats);
+    data.insert::<SharedDatabase>(database);
+    data.insert::<SharedCounters>(counters.clone());
+    data.insert::<RepeatedMentionDetectors>(Arc::new(RepeatedMentionDetector::new()));
+    data.insert::<FingerprintRegistries>(fingerprints);
+    data.insert::<MaintenanceModes>(Arc::new(Maintenance::from_env()?));
+    data.insert::<ActionRateLimiters>(rate_limiter);
+    data.insert::<SharedGlobalState>(Arc::new(GlobalState::from_env()?));
+    data.insert::<Samplers>(Arc::new(Sampler::from_env()?));
+    data.insert::<ContentPolicies>(Arc::new(ContentPolicy::from_env()?));
+    data.insert::<DomainBlocklists>(Arc::new(DomainBlocklist::from_env()?));
+    let context = MessageContext {
+        data: Arc::new(RwLock::new(data)),
+        actions: Arc::new(PrintedActions),
+    };
+
+    let mut guild_ids = HashSet::new();
+    let mut lines = BufReader::new(io::stdin()).lines();
+    while let Some(line) = lines.next_line().await.map_err(BroomError::StdinError)? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let msg = match serde_json::from_str::<Message>(&line) {
+            Ok(msg) => msg,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to parse a message: {:?}",
+                    e
+                );
+                continue;
+            }
+        };
+        guild_ids.extend(msg.guild_id);
+        if let Err(e) = Handler.handle_message(&context, &msg).await {
+            tracing::error!(
+                "There was an error while attempting to handle message {}: {:?}",
+                msg.id,
+                e
+            );
+        }
+    }
+
+    // Direct messages are sent after a delay, so they are waited for before exiting.
+    let mut delay = Duration::ZERO;
+    for guild_id in guild_ids {
+        let config = guilds.get(guild_id).await?.config.read().await.clone();
+        delay = delay.max(Duration::from_secs(config.dm_delay_secs.into()));
+    }
+    tokio::time::sleep(delay + Duration::from_millis(DIRECT_MESSAGE_GRACE_IN_MILLIS)).await;
+    counters.flush().await;
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! The history of actions taken against users, persisted in the database.
+
+use serenity::model::prelude::{ChannelId, UserId};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HistoryAction {
+    /// A duplicate of the user was deleted.
+    Deleted,
+    /// A duplicate of the user was reported to the moderators.
+    Reported,
+    /// The user was warned instead of their duplicate being deleted.
+    Warned,
+    /// A message of the user was deleted since other guilds removed the same content.
+    KnownScamDeleted,
+    /// A message of the user was deleted since it linked to a blocked domain.
+    BlockedLinkDeleted,
+    BanProposed,
+    BanApproved,
+    BanDismissed,
+    BanExpired,
+    /// A moderator approved a ban, but executing it failed.
+    BanFailed,
+    /// A moderator cancelled a ban within the window the user was warned for.
+    BanCancelled,
+    /// A moderator dismissed a report about a message of the user as a false positive.
+    ReportDismissed,
+    /// The user posted in a honeypot channel.
+    HoneypotTriggered,
+    /// The user reached the probation threshold.
+    ProbationStarted,
+    /// A moderator lifted the probation of the user.
+    ProbationLifted,
+    /// The user behaved long enough for their probation to end.
+    ProbationExpired,
+}
+
+impl HistoryAction {
+    const ALL: [HistoryAction; 16] = [
+        HistoryAction::Deleted,
+        HistoryAction::Reported,
+        HistoryAction::Warned,
+        HistoryAction::KnownScamDeleted,
+        HistoryAction::BlockedLinkDeleted,
+        HistoryAction::BanProposed,
+        HistoryAction::BanApproved,
+        HistoryAction::BanDismissed,
+        HistoryAction::BanExpired,
+        HistoryAction::BanFailed,
+        HistoryAction::BanCancelled,
+        HistoryAction::ReportDismissed,
+        HistoryAction::HoneypotTriggered,
+        HistoryAction::ProbationStarted,
+        HistoryAction::ProbationLifted,
+        HistoryAction::ProbationExpired,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Deleted => "deleted",
+            Self::Reported => "reported",
+            Self::Warned => "warned",
+            Self::KnownScamDeleted => "known_scam_deleted",
+            Self::BlockedLinkDeleted => "blocked_link_deleted",
+            Self::BanProposed => "ban_proposed",
+            Self::BanApproved => "ban_approved",
+            Self::BanDismissed => "ban_dismissed",
+            Self::BanExpired => "ban_expired",
+            Self::BanFailed => "ban_failed",
+            Self::BanCancelled => "ban_cancelled",
+            Self::ReportDismissed => "report_dismissed",
+            Self::HoneypotTriggered => "honeypot_triggered",
+            Self::ProbationStarted => "probation_started",
+            Self::ProbationLifted => "probation_lifted",
+            Self::ProbationExpired => "probation_expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+    }
+}
+
+/// A single entry of the history of a user.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub user_id: UserId,
+    pub action: HistoryAction,
+    /// The channel the action was taken in, if it concerned a message.
+    pub channel_id: Option<ChannelId>,
+    /// The moderator that decided on the action, if it was not taken by the bot on its own.
+    pub moderator_id: Option<UserId>,
+    /// Unix timestamp of when the action was taken.
+    pub created_at: i64,
+}
+
+impl HistoryEntry {
+    pub fn new(user_id: UserId, action: HistoryAction, created_at: i64) -> Self {
+        Self {
+            user_id,
+            action,
+            channel_id: None,
+            moderator_id: None,
+            created_at,
+        }
+    }
+}
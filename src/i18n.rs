@@ -0,0 +1,169 @@
+//! Translations of all messages sent to users, bundled into the binary as TOML catalogs.
+//!
+//! Messages refer to their arguments by name, e.g. `{guild}`, so that translations are free to
+//! place them in whatever order the language requires.
+
+use std::{collections::HashMap, fmt::Display, sync::LazyLock};
+
+/// Language used whenever a guild has not configured one or a catalog lacks a message.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+const CATALOG_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.toml")),
+    ("de", include_str!("../locales/de.toml")),
+];
+
+/// Messages by their key, by the language they are in.
+type Catalogs = HashMap<&'static str, HashMap<String, String>>;
+
+static CATALOGS: LazyLock<Catalogs> = LazyLock::new(|| {
+    CATALOG_SOURCES
+        .iter()
+        .map(|(language, source)| {
+            let catalog = toml::from_str(source).unwrap_or_else(|e| {
+                panic!("The bundled catalog for {} is invalid: {}", language, e)
+            });
+            (*language, catalog)
+        })
+        .collect()
+});
+
+/// Returns the codes of all languages with a bundled catalog.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    CATALOG_SOURCES.iter().map(|(language, _)| *language)
+}
+
+pub fn is_supported(language: &str) -> bool {
+    CATALOGS.contains_key(language)
+}
+
+/// Returns the language of the bundled catalog matching a Discord locale such as `en-US` or `de`,
+/// preferring a catalog for the exact region over one for the language as a whole.
+pub fn language_for_locale(locale: &str) -> Option<&'static str> {
+    let locale = locale.to_ascii_lowercase();
+    let primary = locale.split('-').next().unwrap_or_default();
+
+    [locale.as_str(), primary]
+        .iter()
+        .find_map(|candidate| languages().find(|language| language == candidate))
+}
+
+/// Looks up the message with the given key in the catalog of the given language, falling back to
+/// the default language and finally to the key itself, and substitutes all named arguments.
+pub fn translate(language: &str, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    substitute(template(&CATALOGS, language, key), args)
+}
+
+/// Substitutes the named arguments in a single pass, so that placeholders within their values are
+/// kept as they are.
+fn substitute(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let argument = placeholder.find('}').and_then(|end| {
+            args.iter()
+                .find(|(name, _)| *name == &placeholder[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match argument {
+            Some((end, value)) => {
+                message.push_str(&value.to_string());
+                rest = &placeholder[end + 1..];
+            }
+            None => {
+                message.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    message.push_str(rest);
+
+    message
+}
+
+fn template<'a>(catalogs: &'a Catalogs, language: &str, key: &'a str) -> &'a str {
+    [language, DEFAULT_LANGUAGE]
+        .into_iter()
+        .find_map(|language| catalogs.get(language)?.get(key))
+        .map_or(key, String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalogs() -> Catalogs {
+        let catalog = |messages: &[(&str, &str)]| {
+            messages
+                .iter()
+                .map(|(key, message)| (key.to_string(), message.to_string()))
+                .collect()
+        };
+
+        HashMap::from([
+            ("en", catalog(&[("both", "Hello"), ("only_en", "Goodbye")])),
+            ("de", catalog(&[("both", "Hallo")])),
+        ])
+    }
+
+    #[test]
+    fn falls_back_to_the_default_language() {
+        let catalogs = catalogs();
+
+        assert_eq!(template(&catalogs, "de", "both"), "Hallo");
+        assert_eq!(template(&catalogs, "de", "only_en"), "Goodbye");
+        assert_eq!(template(&catalogs, "fr", "both"), "Hello");
+    }
+
+    #[test]
+    fn falls_back_to_the_key() {
+        let catalogs = catalogs();
+
+        assert_eq!(template(&catalogs, "de", "missing"), "missing");
+        assert_eq!(translate("de", "missing", &[("name", &"x")]), "missing");
+    }
+
+    #[test]
+    fn bundled_catalogs_are_valid_and_complete() {
+        let default = &CATALOGS[DEFAULT_LANGUAGE];
+        for language in languages() {
+            let catalog = &CATALOGS[language];
+            for key in default.keys() {
+                assert!(catalog.contains_key(key), "{} lacks {}", language, key);
+            }
+            for key in catalog.keys() {
+                assert!(
+                    default.contains_key(key),
+                    "{} has unknown {}",
+                    language,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn substitutes_arguments() {
+        assert_eq!(
+            translate("en", "custom_cmd_removed", &[("name", &"report")]),
+            "Removed the command `/report`."
+        );
+    }
+
+    #[test]
+    fn keeps_placeholders_within_arguments() {
+        assert_eq!(
+            substitute(
+                "{first} and {second}",
+                &[("first", &"{second}"), ("second", &"x")]
+            ),
+            "{second} and x"
+        );
+        assert_eq!(
+            substitute("{unknown} {first}", &[("first", &"{")]),
+            "{unknown} {"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Patterns exempting commonly repeated but benign messages from duplicate detection, e.g. event
+//! templates or congratulations after a tournament.
+
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of ignore patterns per guild, since every tracked message is matched against
+/// all of them.
+pub const MAX_IGNORE_PATTERNS: usize = 50;
+/// Maximum size in bytes of a compiled regex, which keeps pathological patterns from exhausting
+/// memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternKind {
+    Exact,
+    Prefix,
+    Regex,
+}
+
+impl PatternKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "exact" => Some(Self::Exact),
+            "prefix" => Some(Self::Prefix),
+            "regex" => Some(Self::Regex),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact => f.write_str("exact"),
+            Self::Prefix => f.write_str("prefix"),
+            Self::Regex => f.write_str("regex"),
+        }
+    }
+}
+
+/// A pattern as configured by the guild, matched against normalized message contents.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct IgnorePattern {
+    pub kind: PatternKind,
+    pub pattern: String,
+}
+
+impl IgnorePattern {
+    /// Creates a pattern, normalizing exact strings and prefixes the same way as the contents they
+    /// are matched against. Returns `None` if a regex is invalid.
+    pub fn new(kind: PatternKind, pattern: &str) -> Option<Self> {
+        let pattern = match kind {
+            PatternKind::Exact | PatternKind::Prefix => normalize(pattern),
+            PatternKind::Regex => {
+                compile(pattern)?;
+                pattern.to_string()
+            }
+        };
+
+        Some(Self { kind, pattern })
+    }
+}
+
+/// The ignore patterns of a guild with all regexes compiled up front.
+#[derive(Default)]
+pub struct IgnoreList {
+    exact: Vec<String>,
+    prefixes: Vec<String>,
+    regexes: Vec<Regex>,
+}
+
+impl IgnoreList {
+    pub fn new(patterns: &[IgnorePattern]) -> Self {
+        let mut list = Self::default();
+        for IgnorePattern { kind, pattern } in patterns {
+            match kind {
+                PatternKind::Exact => list.exact.push(pattern.clone()),
+                PatternKind::Prefix => list.prefixes.push(pattern.clone()),
+                // Regexes are validated when added, so this only skips patterns that became
+                // invalid due to a change of the regex syntax.
+                PatternKind::Regex => list.regexes.extend(compile(pattern)),
+            }
+        }
+
+        list
+    }
+
+    /// Whether the given content matches any of the patterns after normalization.
+    pub fn matches(&self, content: &str) -> bool {
+        let content = normalize(content);
+        self.exact.contains(&content)
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| content.starts_with(prefix))
+            || self.regexes.iter().any(|regex| regex.is_match(&content))
+    }
+}
+
+/// Lowercases the content and collapses all whitespace, so that patterns do not have to account
+/// for differences in casing or line breaks.
+pub fn normalize(content: &str) -> String {
+    content
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn compile(pattern: &str) -> Option<Regex> {
+    RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(kind: PatternKind, pattern: &str) -> IgnoreList {
+        IgnoreList::new(&[IgnorePattern::new(kind, pattern).unwrap()])
+    }
+
+    #[test]
+    fn matches_exact_contents() {
+        let list = list(PatternKind::Exact, "  Good  Game ");
+
+        assert!(list.matches("good game"));
+        assert!(list.matches("GOOD\ngame"));
+        assert!(!list.matches("good game everyone"));
+        assert!(!list.matches("a good game"));
+    }
+
+    #[test]
+    fn matches_prefixes() {
+        let list = list(PatternKind::Prefix, "Event starts");
+
+        assert!(list.matches("event starts at 8pm"));
+        assert!(list.matches("EVENT   starts"));
+        assert!(!list.matches("the event starts at 8pm"));
+    }
+
+    #[test]
+    fn matches_regexes_against_normalized_contents() {
+        let list = list(PatternKind::Regex, r"^gg( wp)?$");
+
+        assert!(list.matches("GG"));
+        assert!(list.matches("gg\nwp"));
+        assert!(!list.matches("gg ez"));
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        assert!(IgnorePattern::new(PatternKind::Regex, "(unclosed").is_none());
+        assert!(IgnorePattern::new(PatternKind::Regex, r"\w{100000}").is_none());
+        // Exact strings and prefixes are never compiled.
+        assert!(IgnorePattern::new(PatternKind::Exact, "(unclosed").is_some());
+    }
+
+    #[test]
+    fn matches_any_of_several_patterns() {
+        let patterns = [
+            IgnorePattern::new(PatternKind::Exact, "hello").unwrap(),
+            IgnorePattern::new(PatternKind::Regex, "^bye").unwrap(),
+        ];
+        let list = IgnoreList::new(&patterns);
+
+        assert!(list.matches("Hello"));
+        assert!(list.matches("bye for now"));
+        assert!(!list.matches("hello there"));
+        assert!(!IgnoreList::default().matches("hello"));
+    }
+}
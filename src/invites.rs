@@ -0,0 +1,170 @@
+//! Detection of Discord invites, which are commonly cross-posted to advertise other servers and
+//! too short to be tracked like other messages.
+
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use moka::future::Cache;
+use regex::Regex;
+use serenity::{client::Context, http::HttpError, model::prelude::GuildId, prelude::TypeMapKey};
+
+use crate::monitor;
+
+/// Time in seconds the guild an invite leads to is cached for.
+const TIME_TO_LIVE_IN_SECS: u64 = 60 * 60;
+/// Maximum number of invites whose guilds are cached at once.
+const MAX_CACHED_INVITES: u64 = 10_000;
+/// JSON error code of invites that do not exist or expired.
+const UNKNOWN_INVITE_ERROR_CODE: isize = 10006;
+
+/// Matches invite links including obfuscated ones such as `discord . gg / code` or
+/// `discord[.]com/invite/code`, capturing the invite code.
+static INVITE_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    let dot = r"(?:\s*(?:\.|[(\[{]\s*(?:\.|dot)\s*[)\]}]|\bdot\b)\s*|\s+)";
+    let pattern = format!(
+        r"(?i)\bdiscord(?:app)?{dot}(?:gg|com\s*/\s*invite)\s*/\s*([a-z0-9-]{{2,32}})\b",
+        dot = dot
+    );
+    Regex::new(&pattern).expect("The invite pattern is invalid")
+});
+
+/// Returns the code of the first invite in the given content, if any. Codes are case-sensitive
+/// and therefore returned as written.
+pub fn invite_code(content: &str) -> Option<&str> {
+    INVITE_LINK
+        .captures(content)
+        .and_then(|captures| captures.get(1))
+        .map(|code| code.as_str())
+}
+
+/// Caches the guilds invites lead to, since resolving them requires a request to the Discord
+/// API. Invalid invites are cached without a guild.
+pub struct InviteCache {
+    cache: Cache<String, Option<GuildId>>,
+}
+
+impl InviteCache {
+    pub fn new() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(MAX_CACHED_INVITES)
+                .time_to_live(Duration::from_secs(TIME_TO_LIVE_IN_SECS))
+                .build(),
+        }
+    }
+
+    /// Whether the invite with the given code leads to the given guild. Errors while resolving
+    /// the invite are logged and treated as leading elsewhere.
+    pub async fn leads_to(&self, context: &Context, code: &str, guild_id: GuildId) -> bool {
+        // The vanity invite of a guild can be recognized without resolving it.
+        let vanity_code = context
+            .cache
+            .guild(guild_id)
+            .and_then(|guild| guild.vanity_url_code.clone());
+        if vanity_code.as_deref() == Some(code) {
+            return true;
+        }
+
+        if let Some(target) = self.cache.get(code).await {
+            return target == Some(guild_id);
+        }
+
+        let result = context.http.get_invite(code, false, false, None).await;
+        monitor::record(context, &result).await;
+        let target = match result {
+            Ok(invite) => invite.guild.map(|guild| guild.id),
+            Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                if response.error.code == UNKNOWN_INVITE_ERROR_CODE =>
+            {
+                None
+            }
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to resolve an invite: {:?}",
+                    e
+                );
+                return false;
+            }
+        };
+        self.cache.insert(code.to_string(), target).await;
+
+        target == Some(guild_id)
+    }
+}
+
+pub struct InviteCaches;
+
+impl TypeMapKey for InviteCaches {
+    type Value = Arc<InviteCache>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_invite_links() {
+        for content in [
+            "discord.gg/abc123",
+            "join https://discord.gg/abc123 now",
+            "https://discord.com/invite/abc123",
+            "https://discordapp.com/invite/abc123",
+            "http://www.discord.gg/abc123",
+        ] {
+            assert_eq!(invite_code(content), Some("abc123"), "{}", content);
+        }
+        assert_eq!(
+            invite_code("discord.gg/abc123 discord.gg/def456"),
+            Some("abc123")
+        );
+    }
+
+    #[test]
+    fn finds_obfuscated_invite_links() {
+        for content in [
+            "discord . gg / abc123",
+            "discord[.]gg/abc123",
+            "discord(dot)com/invite/abc123",
+            "discord dot gg/abc123",
+            "discord gg/abc123",
+        ] {
+            assert_eq!(invite_code(content), Some("abc123"), "{}", content);
+        }
+    }
+
+    #[test]
+    fn ignores_the_case_of_links_but_not_of_codes() {
+        assert_eq!(invite_code("HTTPS://DISCORD.GG/AbC123"), Some("AbC123"));
+        assert_eq!(invite_code("Discord.Com/Invite/xYz-9"), Some("xYz-9"));
+    }
+
+    #[test]
+    fn stops_at_trailing_characters() {
+        for content in [
+            "discord.gg/abc123.",
+            "(discord.gg/abc123)",
+            "discord.gg/abc123!?",
+            "<https://discord.gg/abc123>",
+            "discord.gg/abc123/",
+            "discord.gg/abc123?event=1",
+        ] {
+            assert_eq!(invite_code(content), Some("abc123"), "{}", content);
+        }
+    }
+
+    #[test]
+    fn ignores_other_links() {
+        for content in [
+            "discord.gg",
+            "discord.gg/a",
+            "https://discord.com/channels/1/2",
+            "mydiscord.gg/abc123",
+            "discord.gg/abc_123",
+            "see you on discord, gg",
+        ] {
+            assert_eq!(invite_code(content), None, "{}", content);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Lookup of the preferred languages of users, used for the messages sent to them directly.
+
+use std::{sync::Arc, time::Duration};
+
+use moka::future::Cache;
+use serenity::{client::Context, model::prelude::UserId, prelude::TypeMapKey};
+
+use crate::{i18n, monitor};
+
+/// Time in seconds the locale of a user is cached for.
+const TIME_TO_LIVE_IN_SECS: u64 = 60 * 60;
+/// Maximum number of users whose locales are cached at once.
+const MAX_CACHED_USERS: u64 = 10_000;
+
+/// Caches the locales of users. Messages carry no locale, so it is remembered from the
+/// interactions of a user, which always do, and otherwise fetched from the Discord API. The API
+/// only returns the locale to applications authorized by the user, so users that are not cached
+/// are usually cached without one in order not to fetch them for every deletion.
+pub struct UserLocaleCache {
+    cache: Cache<UserId, Option<String>>,
+}
+
+impl UserLocaleCache {
+    pub fn new() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(MAX_CACHED_USERS)
+                .time_to_live(Duration::from_secs(TIME_TO_LIVE_IN_SECS))
+                .build(),
+        }
+    }
+
+    /// Remembers the locale a user sent along with an interaction.
+    pub async fn remember(&self, user_id: UserId, locale: &str) {
+        self.cache.insert(user_id, Some(locale.to_string())).await;
+    }
+
+    /// Returns the language of the bundled catalog matching the locale of the given user, or `None`
+    /// if their locale is unknown or no catalog matches it.
+    pub async fn language(&self, context: &Context, user_id: UserId) -> Option<&'static str> {
+        let locale = match self.cache.get(&user_id).await {
+            Some(locale) => locale,
+            None => {
+                let result = context.http.get_user(user_id).await;
+                monitor::record(context, &result).await;
+                // Failures are cached as well since the guild language is a sufficient fallback.
+                let locale = result
+                    .inspect_err(|e| {
+                        tracing::debug!(
+                            "There was an error while attempting to fetch the locale of a user: {:?}",
+                            e
+                        )
+                    })
+                    .ok()
+                    .and_then(|user| user.locale);
+                self.cache.insert(user_id, locale.clone()).await;
+                locale
+            }
+        };
+
+        i18n::language_for_locale(&locale?)
+    }
+}
+
+pub struct UserLocaleCaches;
+
+impl TypeMapKey for UserLocaleCaches {
+    type Value = Arc<UserLocaleCache>;
+}
@@ -0,0 +1,167 @@
+//! Coordination between multiple replicas so that only one of them takes action on a duplicate.
+//!
+//! Without the `redis` feature or a configured `REDIS_URL` every lock is granted immediately,
+//! which is the correct behavior for a single replica.
+
+use std::sync::Arc;
+
+use serenity::{
+    model::prelude::{GuildId, MessageId},
+    prelude::TypeMapKey,
+};
+
+/// Time in milliseconds until an acquired lock expires even if it was never released, e.g. because
+/// the replica holding it crashed while taking action.
+#[cfg(feature = "redis")]
+const LOCK_TTL_IN_MILLIS: u64 = 30_000;
+
+/// Deletes the lock only if it is still held by the replica that acquired it.
+#[cfg(feature = "redis")]
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+pub struct ActionLock {
+    #[cfg(feature = "redis")]
+    connection: Option<redis::aio::ConnectionManager>,
+}
+
+/// Proof of an acquired lock that has to be released once the action has been taken.
+pub struct ActionLockGuard {
+    #[cfg(feature = "redis")]
+    key: String,
+    #[cfg(feature = "redis")]
+    token: String,
+}
+
+impl ActionLock {
+    /// Connects to the Redis instance given by the `REDIS_URL` environment variable, if any.
+    #[cfg(feature = "redis")]
+    pub async fn from_env() -> Self {
+        let connection = match std::env::var("REDIS_URL") {
+            Ok(url) => match redis::Client::open(url) {
+                Ok(client) => match client.get_connection_manager().await {
+                    Ok(connection) => Some(connection),
+                    Err(e) => {
+                        tracing::error!(
+                            "Could not connect to Redis, action locks are disabled: {:?}",
+                            e
+                        );
+                        None
+                    }
+                },
+                Err(e) => {
+                    tracing::error!("The REDIS_URL environment variable is invalid, action locks are disabled: {:?}", e);
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        Self { connection }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    pub async fn from_env() -> Self {
+        Self {}
+    }
+
+    /// Grants every lock immediately regardless of the environment, for a process that is known
+    /// to be the only replica.
+    pub fn unshared() -> Self {
+        Self {
+            #[cfg(feature = "redis")]
+            connection: None,
+        }
+    }
+
+    /// Attempts to acquire the lock for taking action on the given message. Returns `None` if
+    /// another replica already holds it, in which case the caller must not take any action.
+    ///
+    /// Errors while talking to Redis grant the lock, since acting twice is preferable to not
+    /// acting at all.
+    #[cfg(feature = "redis")]
+    pub async fn acquire(
+        &self,
+        guild_id: Option<GuildId>,
+        message_id: MessageId,
+    ) -> Option<ActionLockGuard> {
+        let guard = ActionLockGuard {
+            key: match guild_id {
+                Some(guild_id) => format!("broom:lock:{}:{}", guild_id, message_id),
+                None => format!("broom:lock:dm:{}", message_id),
+            },
+            token: format!(
+                "{}:{:?}",
+                std::process::id(),
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            ),
+        };
+
+        let Some(mut connection) = self.connection.clone() else {
+            return Some(guard);
+        };
+
+        let result = redis::cmd("SET")
+            .arg(&guard.key)
+            .arg(&guard.token)
+            .arg("NX")
+            .arg("PX")
+            .arg(LOCK_TTL_IN_MILLIS)
+            .query_async::<Option<String>>(&mut connection)
+            .await;
+
+        match result {
+            Ok(Some(_)) => Some(guard),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!(
+                    "There was an error while attempting to acquire an action lock: {:?}",
+                    e
+                );
+                Some(guard)
+            }
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    pub async fn acquire(
+        &self,
+        _guild_id: Option<GuildId>,
+        _message_id: MessageId,
+    ) -> Option<ActionLockGuard> {
+        Some(ActionLockGuard {})
+    }
+
+    #[cfg(feature = "redis")]
+    pub async fn release(&self, guard: ActionLockGuard) {
+        let Some(mut connection) = self.connection.clone() else {
+            return;
+        };
+
+        let result = redis::Script::new(RELEASE_SCRIPT)
+            .key(&guard.key)
+            .arg(&guard.token)
+            .invoke_async::<i64>(&mut connection)
+            .await;
+
+        if let Err(e) = result {
+            tracing::warn!(
+                "There was an error while attempting to release an action lock: {:?}",
+                e
+            );
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    pub async fn release(&self, _guard: ActionLockGuard) {}
+}
+
+pub struct ActionLocks;
+
+impl TypeMapKey for ActionLocks {
+    type Value = Arc<ActionLock>;
+}
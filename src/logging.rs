@@ -0,0 +1,187 @@
+//! Logging to stdout and optionally to rotated files, configured through the following
+//! environment variables:
+//!
+//! - `LOG_STDOUT`: Whether to log to stdout, or to stderr in test mode, defaults to `true`.
+//! - `LOG_DIRECTORY`: Directory to write log files to. File logging is disabled if unset.
+//! - `LOG_FILE_PREFIX`: Prefix of the log file names, defaults to `broom`.
+//! - `LOG_ROTATION`: When to start a new log file, either `daily` (default), `hourly` or `size`.
+//! - `LOG_MAX_FILE_SIZE`: Size in bytes after which a new log file is started when rotating by
+//!   size, defaults to 10 MiB.
+//! - `LOG_MAX_FILES`: Number of log files to keep including the current one, defaults to 7.
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt::writer::BoxMakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+use crate::error::BroomError;
+
+const DEFAULT_FILE_PREFIX: &str = "broom";
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 7;
+
+/// Installs the global subscriber, logging to stderr instead of stdout if `to_stderr` is set. The
+/// returned guard flushes buffered lines when dropped and therefore has to be held until the
+/// process exits.
+pub fn init(to_stderr: bool) -> Result<Option<WorkerGuard>, BroomError> {
+    let stdout = match env::var("LOG_STDOUT") {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig("LOG_STDOUT"))?,
+        Err(_) => true,
+    };
+    let (file_layer, guard) = match env::var("LOG_DIRECTORY") {
+        Ok(directory) => {
+            let writer = file_writer(Path::new(&directory))?;
+            let (writer, guard) = tracing_appender::non_blocking(writer);
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        Err(_) => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(stdout.then(|| {
+            let writer = match to_stderr {
+                true => BoxMakeWriter::new(io::stderr),
+                false => BoxMakeWriter::new(io::stdout),
+            };
+            tracing_subscriber::fmt::layer().with_writer(writer)
+        }))
+        .with(file_layer)
+        .init();
+
+    Ok(guard)
+}
+
+fn file_writer(directory: &Path) -> Result<Box<dyn Write + Send>, BroomError> {
+    let prefix = env::var("LOG_FILE_PREFIX").unwrap_or_else(|_| DEFAULT_FILE_PREFIX.to_string());
+    let max_files = parse_env("LOG_MAX_FILES", DEFAULT_MAX_FILES)?.max(1);
+    let rotation = env::var("LOG_ROTATION").unwrap_or_else(|_| "daily".to_string());
+
+    // Fails at startup rather than silently losing every line logged later on.
+    fs::create_dir_all(directory)
+        .and_then(|_| File::create(directory.join(".write-test")))
+        .and_then(|_| fs::remove_file(directory.join(".write-test")))
+        .map_err(|e| BroomError::LogDirectoryError(directory.to_path_buf(), e))?;
+
+    let rotation = match rotation.as_str() {
+        "daily" => Rotation::DAILY,
+        "hourly" => Rotation::HOURLY,
+        "size" => {
+            let max_size = parse_env("LOG_MAX_FILE_SIZE", DEFAULT_MAX_FILE_SIZE)?;
+            let writer = SizeRotatingFile::open(directory, &prefix, max_size, max_files)
+                .map_err(|e| BroomError::LogDirectoryError(directory.to_path_buf(), e))?;
+            return Ok(Box::new(writer));
+        }
+        _ => return Err(BroomError::InvalidConfig("LOG_ROTATION")),
+    };
+
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix)
+        .filename_suffix("log")
+        .max_log_files(max_files)
+        .build(directory)
+        .map_err(|_| BroomError::InvalidConfig("LOG_DIRECTORY"))?;
+
+    Ok(Box::new(appender))
+}
+
+fn parse_env<T: std::str::FromStr>(name: &'static str, default: T) -> Result<T, BroomError> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig(name)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A log file that is rotated once it reaches a maximum size. The current file is always named
+/// `<prefix>.log`, older ones are suffixed with increasing numbers, i.e. `<prefix>.log.1` is the
+/// most recent rotated file.
+struct SizeRotatingFile {
+    directory: PathBuf,
+    prefix: String,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingFile {
+    fn open(directory: &Path, prefix: &str, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = directory.join(format!("{}.log", prefix));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            prefix: prefix.to_string(),
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.directory.join(format!("{}.log", self.prefix)),
+            index => self
+                .directory
+                .join(format!("{}.log.{}", self.prefix, index)),
+        }
+    }
+
+    /// Shifts every file to the next index, removing the oldest one, and starts a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let _ = fs::remove_file(self.path(self.max_files - 1));
+        for index in (0..self.max_files - 1).rev() {
+            let path = self.path(index);
+            if path.exists() {
+                fs::rename(&path, self.path(index + 1))?;
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
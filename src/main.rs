@@ -1,113 +1,268 @@
-use std::{
-    env,
-    sync::Arc,
-    time::{Duration, Instant},
-};
+use std::{env, sync::Arc};
+
+use clap::{Parser, ValueEnum};
+use serenity::{prelude::GatewayIntents, Client};
 
-use moka::future::Cache;
-use serenity::{
-    client::{Context, EventHandler},
-    model::{channel::Message, gateway::Ready, prelude::UserId},
-    prelude::{GatewayIntents, TypeMapKey},
-    utils::MessageBuilder,
-    Client,
+use crate::{
+    blocklist::{DomainBlocklist, DomainBlocklists},
+    config::ConfigLimits,
+    counters::{ActionCounters, SharedCounters},
+    db::{Database, SharedDatabase},
+    detectors::{RepeatedMentionDetector, RepeatedMentionDetectors},
+    error::BroomError,
+    error_tracking::Transaction,
+    fingerprints::{FingerprintRegistries, FingerprintRegistry, FingerprintSettings},
+    forum::{ThreadTagCache, ThreadTagCaches},
+    handler::Handler,
+    invites::{InviteCache, InviteCaches},
+    locale::{UserLocaleCache, UserLocaleCaches},
+    lock::{ActionLock, ActionLocks},
+    maintenance::{Maintenance, MaintenanceModes},
+    monitor::{ErrorRateMonitor, ErrorRateMonitors},
+    privacy::{ContentPolicies, ContentPolicy},
+    ratelimit::{ActionRateLimiter, ActionRateLimiters, RateLimitSettings},
+    sampling::{Sampler, Samplers},
+    shards::{ShardMonitor, ShardMonitors},
+    state::{write_batch_interval_from_env, GlobalState, GuildStates, Guilds, SharedGlobalState},
+    stats::{SharedStats, Stats},
 };
-use tokio::sync::RwLock;
 
-/// Time in seconds until a message is automatically evicted from the tracking cache.
-const TIME_TO_IDLE_IN_SECS: u64 = 120;
-/// Minimum length of messages to be tracked. Anything shorter than this is ignored entirely.
-const MIN_MESSAGE_LENGTH: usize = 50;
+mod actions;
+mod allowlist;
+mod alt_accounts;
+mod archive;
+mod blocklist;
+mod bootstrap;
+mod bridges;
+mod commands;
+mod config;
+mod content;
+mod counters;
+mod custom_commands;
+mod db;
+mod detectors;
+mod error;
+mod error_tracking;
+mod escalation;
+mod fingerprints;
+mod forum;
+mod handler;
+mod headless;
+mod history;
+mod i18n;
+mod ignore;
+mod invites;
+mod locale;
+mod lock;
+mod logging;
+mod maintenance;
+mod metrics;
+mod moderation;
+mod monitor;
+mod permissions;
+mod preferences;
+mod privacy;
+mod probation;
+mod quiet_hours;
+mod ratelimit;
+mod reports;
+mod retention;
+mod sampling;
+mod schedule;
+mod shards;
+mod state;
+mod stats;
+#[cfg(feature = "ml_suggestions")]
+mod suggestions;
+mod templates;
+mod verbose;
 
-struct MessageCache;
+// Everything besides the mode is configured through environment variables.
+#[derive(Parser)]
+#[command(
+    version,
+    about = "A Discord bot deleting messages reposted across channels in quick succession."
+)]
+struct Args {
+    /// Whether to connect to Discord or to handle messages read from stdin.
+    #[arg(long, value_enum, default_value_t = Mode::Discord)]
+    mode: Mode,
+}
 
-impl TypeMapKey for MessageCache {
-    type Value = Arc<RwLock<Cache<(UserId, String), Instant>>>;
+#[derive(Clone, Copy, Eq, PartialEq, ValueEnum)]
+enum Mode {
+    /// Connects to Discord and takes action on duplicates.
+    Discord,
+    /// Reads messages as JSON lines from stdin and prints the actions that would be taken to
+    /// stdout without connecting to Discord.
+    Test,
 }
 
-struct Handler;
+#[tokio::main]
+async fn main() -> Result<(), BroomError> {
+    let args = Args::parse();
+    // Held until the end of `main` so that buffered log lines are flushed on shutdown. Stdout is
+    // reserved for the printed actions in test mode, so logs go to stderr instead.
+    let _log_guard = logging::init(args.mode == Mode::Test)?;
+    if args.mode == Mode::Test {
+        return headless::run().await;
+    }
 
-#[serenity::async_trait]
-impl EventHandler for Handler {
-    async fn message(&self, context: Context, msg: Message) {
-        if msg.content.len() <= MIN_MESSAGE_LENGTH {
-            return;
-        }
+    // Held until the end of `main` so that pending errors are reported on shutdown.
+    let _error_tracking = error_tracking::init()?;
+    let token = discord_token()?;
+    let intents = GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::MESSAGE_CONTENT
+        | GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MEMBERS;
+    let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://broom.db".to_string());
+    let database = Arc::new(Database::connect(&database_url).await?);
+    let stats = Arc::new(Stats::default());
+    let counters = Arc::new(ActionCounters::new(database.clone()));
+    let limits = ConfigLimits::from_env()?;
+    let monitor = Arc::new(ErrorRateMonitor::from_env()?);
+    let maintenance = Arc::new(Maintenance::from_env()?);
+    let rate_limiter = Arc::new(ActionRateLimiter::new(RateLimitSettings::from_env()?));
+    let prune_hour = retention::prune_hour_from_env()?;
+    let fingerprints = Arc::new(
+        FingerprintRegistry::load(database.clone(), FingerprintSettings::from_env()?).await?,
+    );
+    let guilds = Arc::new(Guilds::new(
+        stats.clone(),
+        database.clone(),
+        limits,
+        write_batch_interval_from_env()?,
+    ));
+    let mut client = Client::builder(&token, intents)
+        .event_handler(Handler)
+        .await?;
 
-        let cache_lock = {
-            let data_read = context.data.read().await;
-            data_read
-                .get::<MessageCache>()
-                .expect("Expected MessageCache in TypeMap.")
-                .clone()
-        };
+    {
+        let mut data = client.data.write().await;
+        data.insert::<GuildStates>(guilds.clone());
+        data.insert::<ActionLocks>(Arc::new(ActionLock::from_env().await));
+        data.insert::<SharedStats>(stats.clone());
+        data.insert::<SharedDatabase>(database.clone());
+        data.insert::<SharedCounters>(counters.clone());
+        data.insert::<RepeatedMentionDetectors>(Arc::new(RepeatedMentionDetector::new()));
+        data.insert::<ThreadTagCaches>(Arc::new(ThreadTagCache::new()));
+        data.insert::<ErrorRateMonitors>(monitor.clone());
+        data.insert::<UserLocaleCaches>(Arc::new(UserLocaleCache::new()));
+        data.insert::<FingerprintRegistries>(fingerprints.clone());
+        data.insert::<InviteCaches>(Arc::new(InviteCache::new()));
+        data.insert::<MaintenanceModes>(maintenance);
+        data.insert::<ActionRateLimiters>(rate_limiter.clone());
+        data.insert::<SharedGlobalState>(Arc::new(GlobalState::from_env()?));
+        data.insert::<Samplers>(Arc::new(Sampler::from_env()?));
+        data.insert::<ContentPolicies>(Arc::new(ContentPolicy::from_env()?));
+        data.insert::<DomainBlocklists>(Arc::new(DomainBlocklist::from_env()?));
+        data.insert::<ShardMonitors>(Arc::new(ShardMonitor::from_env()?));
+        #[cfg(feature = "ml_suggestions")]
+        data.insert::<suggestions::SuggestionSessions>(Arc::new(suggestions::sessions()));
+    }
 
-        let now = Instant::now();
+    tokio::spawn(counters.clone().run());
+    tokio::spawn(monitor.run(client.http.clone()));
+    tokio::spawn(fingerprints.run());
+    tokio::spawn(rate_limiter.run());
+    tokio::spawn(guilds.clone().run_write_batches());
+    tokio::spawn(archive::run_purge(database.clone(), guilds.clone()));
+    tokio::spawn(retention::run_pruning(
+        database.clone(),
+        guilds.clone(),
+        stats.clone(),
+        prune_hour,
+    ));
+    tokio::spawn(escalation::run_expiry(
+        client.http.clone(),
+        database.clone(),
+        guilds.clone(),
+    ));
+    tokio::spawn(probation::run_expiry(database.clone(), guilds.clone()));
+    tokio::spawn(templates::run_refresh(client.http.clone(), guilds.clone()));
+    tokio::spawn(bootstrap::run_completion(
+        client.http.clone(),
+        database,
+        guilds,
+    ));
 
-        let key = (msg.author.id, msg.content.clone());
-        let timestamp = { cache_lock.read().await.get(&key) };
-        {
-            cache_lock.write().await.insert(key, now).await;
-        }
+    if let Ok(address) = env::var("METRICS_ADDRESS") {
+        tokio::spawn(metrics::serve(address, stats));
+    }
 
-        if let Some(timestamp) = timestamp {
-            let duration = now.checked_duration_since(timestamp);
-            if let Some(duration) = duration {
-                if duration.as_secs() <= TIME_TO_IDLE_IN_SECS {
-                    let dm_intro = match msg.guild(&context) {
-                        Some(guild) => format!(
-                            "Your recent message in the {} Discord server has been automatically deleted.",
-                            guild.name
-                        ),
-                        None => "Your recent message in a Discord server has been automatically deleted."
-                            .to_string(),
-                    };
-
-                    let content = MessageBuilder::new()
-                        .push(format!("{} It was recognized as a duplicate that you posted in several channels in quick succession. Please be patient and refrain from posting the same message in multiple channels.", dm_intro))
-                        .build();
-
-                    if let Err(e) = msg.delete(&context).await {
-                        tracing::error!("There was an error while attempting to delete a duplicate message: {:?}", e);
-                    } else if let Err(e) = msg.author.dm(&context, |m| m.content(content)).await {
-                        tracing::error!("There was an error while attempting to message an author of a deleted message: {:?}", e);
-                    }
-                }
+    // Racing the client against the signal also shuts down while shards are still connecting, in
+    // which case the shard manager has nothing to shut down yet.
+    let shard_manager = client.shard_manager.clone();
+    let transaction = Transaction::start("discord client", "client.start");
+    tokio::select! {
+        result = client.start() => {
+            if let Err(reason) = result {
+                tracing::error!(
+                    "An unexpected client error occurred during runtime: {:?}",
+                    reason
+                );
+                error_tracking::capture("Unexpected client error", &reason, None, None);
             }
         }
+        _ = shutdown_signal() => {
+            tracing::info!("Received a shutdown signal, disconnecting.");
+            shard_manager.shutdown_all().await;
+        }
     }
+    transaction.finish();
+    counters.flush().await;
 
-    async fn ready(&self, _: Context, data: Ready) {
-        tracing::info!("{} is connected and running.", data.user.name);
-    }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
+/// Reads the token from the file given by `DISCORD_TOKEN_FILE`, e.g. a container secret, or from
+/// `DISCORD_TOKEN` otherwise. Secret files commonly end with a newline, which is trimmed.
+fn discord_token() -> Result<String, BroomError> {
+    let token = env::var("DISCORD_TOKEN").ok();
+    let Ok(path) = env::var("DISCORD_TOKEN_FILE") else {
+        return token.ok_or(BroomError::MissingConfig("DISCORD_TOKEN"));
+    };
 
-    let token =
-        env::var("DISCORD_TOKEN").expect("Could not find the DISCORD_TOKEN environment variable.");
-    let intents =
-        GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT | GatewayIntents::GUILDS;
-    let mut client = Client::builder(&token, intents)
-        .event_handler(Handler)
-        .await
-        .expect("There was an unexpected error while attempting to create a client.");
+    if token.is_some() {
+        tracing::warn!("Both DISCORD_TOKEN and DISCORD_TOKEN_FILE are set, using the latter.");
+    }
+    let token = std::fs::read_to_string(&path)
+        .map_err(|e| BroomError::TokenFileError(path.into(), e))?
+        .trim()
+        .to_string();
+    if token.is_empty() {
+        return Err(BroomError::MissingConfig("DISCORD_TOKEN"));
+    }
 
+    Ok(token)
+}
+
+/// Resolves once the process is asked to terminate, either interactively or by a service manager.
+async fn shutdown_signal() {
+    #[cfg(unix)]
     {
-        let mut data = client.data.write().await;
-        data.insert::<MessageCache>(Arc::new(RwLock::new(
-            Cache::builder()
-                .time_to_idle(Duration::from_secs(TIME_TO_IDLE_IN_SECS))
-                .build(),
-        )))
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => tracing::error!(
+                "There was an error while attempting to listen for SIGTERM: {:?}",
+                e
+            ),
+        }
     }
 
-    if let Err(reason) = client.start().await {
+    if let Err(e) = tokio::signal::ctrl_c().await {
         tracing::error!(
-            "An unexpected client error occurred during runtime: {:?}",
-            reason
+            "There was an error while attempting to listen for Ctrl+C: {:?}",
+            e
         );
+        std::future::pending::<()>().await;
     }
 }
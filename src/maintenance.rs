@@ -0,0 +1,94 @@
+//! Maintenance mode, in which messages are still tracked but no action is taken on them, e.g.
+//! while a rolling restart leaves some replicas with a cold cache that would miss duplicates of
+//! messages posted before the restart.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use serenity::{builder::CreateEmbed, client::Context, prelude::TypeMapKey};
+
+use crate::{
+    error::BroomError,
+    i18n, moderation,
+    state::{shared, GuildStates},
+};
+
+pub struct Maintenance {
+    active: AtomicBool,
+}
+
+impl Maintenance {
+    /// Starts in maintenance mode if `MAINTENANCE_MODE` is set to `true`.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let active = match env::var("MAINTENANCE_MODE") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("MAINTENANCE_MODE"))?,
+            Err(_) => false,
+        };
+        if active {
+            tracing::warn!("Starting in maintenance mode, no actions will be taken.");
+        }
+
+        Ok(Self {
+            active: AtomicBool::new(active),
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables maintenance mode and returns whether it was changed.
+    pub fn set(&self, active: bool) -> bool {
+        self.active.swap(active, Ordering::Relaxed) != active
+    }
+}
+
+/// Posts a banner about maintenance mode being enabled or disabled to the audit channel of every
+/// guild the bot is in.
+pub async fn announce(context: Context, active: bool) {
+    let guilds = match shared::<GuildStates>(&*context.data.read().await) {
+        Ok(guilds) => guilds,
+        Err(e) => {
+            tracing::error!(
+                "There was an error while attempting to announce maintenance mode: {}",
+                e
+            );
+            return;
+        }
+    };
+    let key = match active {
+        true => "audit_maintenance_enabled",
+        false => "audit_maintenance_disabled",
+    };
+
+    for guild_id in context.cache.guilds() {
+        let config = match guilds.get(guild_id).await {
+            Ok(state) => state.config.read().await.clone(),
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to announce maintenance mode in guild {}: {}",
+                    guild_id,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let embed = CreateEmbed::new().title(i18n::translate(&config.language, key, &[]));
+        moderation::post_audit_log(&context, &config, embed).await;
+    }
+}
+
+pub struct MaintenanceModes;
+
+impl TypeMapKey for MaintenanceModes {
+    type Value = Arc<Maintenance>;
+}
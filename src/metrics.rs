@@ -0,0 +1,62 @@
+//! A minimal HTTP endpoint serving the shared stats to Prometheus.
+
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use crate::stats::Stats;
+
+/// Serves the metrics on every request to the given address until the process exits. Since there
+/// is only a single resource, the request itself is not inspected beyond being read.
+pub async fn serve(address: String, stats: Arc<Stats>) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(
+                "Could not bind the metrics endpoint to {}: {:?}",
+                address,
+                e
+            );
+            return;
+        }
+    };
+
+    tracing::info!("Serving metrics on {}.", address);
+
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!(
+                    "There was an error while accepting a metrics request: {:?}",
+                    e
+                );
+                continue;
+            }
+        };
+
+        let stats = stats.clone();
+        tokio::spawn(async move {
+            let mut buffer = [0; 1024];
+            if stream.read(&mut buffer).await.is_err() {
+                return;
+            }
+
+            let body = stats.render_prometheus();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                tracing::warn!(
+                    "There was an error while answering a metrics request: {:?}",
+                    e
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,144 @@
+//! Actions taken against users and the reporting of them to moderators.
+
+use chrono::Utc;
+use serenity::{
+    builder::{CreateEmbed, CreateMessage, EditGuild, EditMember},
+    client::Context,
+    model::{
+        channel::ChannelType,
+        guild::VerificationLevel,
+        mention::Mentionable,
+        prelude::{ChannelId, GuildId, UserId},
+        Timestamp,
+    },
+};
+
+use crate::{config::GuildConfig, error_tracking, i18n, monitor};
+
+/// Returns a link to the given channel. Links to deleted messages lead nowhere, but the channel
+/// they were posted in shows the context moderators need.
+pub fn channel_link(guild_id: GuildId, channel_id: ChannelId) -> String {
+    format!("https://discord.com/channels/{}/{}", guild_id, channel_id)
+}
+
+/// Returns the key of the label of the given type of channel, e.g. to tell moderators that a
+/// message was posted in the text chat of a stage rather than in a text channel.
+pub fn channel_type_label(kind: ChannelType) -> &'static str {
+    match kind {
+        ChannelType::Stage => "channel_type_stage",
+        ChannelType::Voice => "channel_type_voice",
+        ChannelType::News => "channel_type_news",
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread => {
+            "channel_type_thread"
+        }
+        _ => "channel_type_text",
+    }
+}
+
+/// Adds inline fields linking to the channel a duplicate was posted in and to the channel it was
+/// first seen in, along with the type of the former if it is known.
+pub fn channel_links(
+    embed: CreateEmbed,
+    language: &str,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    first_channel_id: ChannelId,
+    kind: Option<ChannelType>,
+) -> CreateEmbed {
+    let link = |channel_id: ChannelId| {
+        format!(
+            "{} ([{}]({}))",
+            channel_id.mention(),
+            i18n::translate(language, "audit_jump_to_channel", &[]),
+            channel_link(guild_id, channel_id)
+        )
+    };
+
+    let embed = embed
+        .field(
+            i18n::translate(language, "audit_view_channel", &[]),
+            link(channel_id),
+            true,
+        )
+        .field(
+            i18n::translate(language, "audit_first_seen_in", &[]),
+            link(first_channel_id),
+            true,
+        );
+    match kind {
+        Some(kind) => embed.field(
+            i18n::translate(language, "audit_channel_type", &[]),
+            i18n::translate(language, channel_type_label(kind), &[]),
+            true,
+        ),
+        None => embed,
+    }
+}
+
+/// Posts the given embed to the audit channel of the guild, if one is configured.
+pub async fn post_audit_log(context: &Context, config: &GuildConfig, embed: CreateEmbed) {
+    post_audit_message(context, config, CreateMessage::new().embed(embed)).await;
+}
+
+/// Posts the given message to the audit channel of the guild, if one is configured.
+pub async fn post_audit_message(context: &Context, config: &GuildConfig, message: CreateMessage) {
+    let Some(channel_id) = config.audit_channel_id else {
+        return;
+    };
+
+    let result = channel_id.send_message(context, message).await;
+    monitor::record(context, &result).await;
+    if let Err(e) = result {
+        tracing::error!(
+            "There was an error while attempting to post to the audit channel: {:?}",
+            e
+        );
+        error_tracking::capture("Could not post to the audit channel", &e, None, None);
+    }
+}
+
+/// Prevents the given member from communicating for the given number of seconds.
+pub async fn timeout(
+    context: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    duration_secs: u64,
+    reason: &str,
+) -> Result<(), serenity::Error> {
+    let until = Timestamp::from_unix_timestamp(Utc::now().timestamp() + duration_secs as i64)
+        .map_err(|_| serenity::Error::Other("The timeout ends too far in the future."))?;
+    let result = guild_id
+        .edit_member(
+            context,
+            user_id,
+            EditMember::new()
+                .disable_communication_until_datetime(until)
+                .audit_log_reason(reason),
+        )
+        .await;
+    monitor::record(context, &result).await;
+    result?;
+
+    Ok(())
+}
+
+/// Raises the verification level of the given guild to the highest one, which keeps members
+/// without a verified phone number, such as most accounts created for a raid, from posting.
+pub async fn lock_down(
+    context: &Context,
+    guild_id: GuildId,
+    reason: &str,
+) -> Result<(), serenity::Error> {
+    let result = guild_id
+        .edit(
+            context,
+            EditGuild::new()
+                .verification_level(VerificationLevel::Higher)
+                .audit_log_reason(reason),
+        )
+        .await;
+    monitor::record(context, &result).await;
+    result?;
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+//! Self-monitoring of the error rate of requests to the Discord API, alerting the operator of the
+//! bot when it is degraded.
+//!
+//! The error rate is approximated over a sliding window of one minute by weighting the counts of
+//! the previous window by how much of it still overlaps with the sliding window.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    client::Context,
+    http::{Http, HttpError},
+    model::{prelude::ChannelId, Timestamp},
+    prelude::TypeMapKey,
+};
+
+use crate::error::BroomError;
+
+const WINDOW_IN_SECS: u64 = 60;
+/// Default error rate in percent above which the operator is alerted.
+const DEFAULT_THRESHOLD_IN_PERCENT: u64 = 25;
+/// Minimum number of requests within the window for an alert, so that a single failed request
+/// after a quiet period is not reported as a 100% error rate.
+const MIN_REQUESTS_FOR_ALERT: f64 = 10.0;
+const ALERT_THROTTLE_IN_SECS: u64 = 5 * 60;
+const CHECK_INTERVAL_IN_SECS: u64 = 10;
+
+/// JSON error codes of failures that are expected during normal operation and do not indicate a
+/// degraded bot, i.e. users not accepting direct messages, messages deleted in the meantime and
+/// invites that are invalid or expired.
+const EXPECTED_ERROR_CODES: &[isize] = &[50007, 10008, 10006];
+
+/// The error rate within the sliding window.
+#[derive(Clone, Debug)]
+pub struct ErrorRate {
+    pub errors: f64,
+    pub total: f64,
+    pub last_error: Option<(String, DateTime<Utc>)>,
+}
+
+impl ErrorRate {
+    /// Returns the error rate in percent, which is zero if no requests were made.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0.0 {
+            0.0
+        } else {
+            self.errors / self.total * 100.0
+        }
+    }
+}
+
+pub struct ErrorRateMonitor {
+    started: Instant,
+    /// Start of the current window in seconds since `started`.
+    window_start: AtomicU64,
+    errors: AtomicU64,
+    total: AtomicU64,
+    previous_errors: AtomicU64,
+    previous_total: AtomicU64,
+    last_error: Mutex<Option<(String, DateTime<Utc>)>>,
+    threshold_percent: u64,
+    alert_channel_id: Option<ChannelId>,
+}
+
+impl ErrorRateMonitor {
+    /// Reads the channel to post alerts to from `ALERT_CHANNEL_ID` and the threshold in percent
+    /// from `ERROR_RATE_THRESHOLD`. Without an alert channel, error rates are only tracked.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let alert_channel_id = match env::var("ALERT_CHANNEL_ID") {
+            Ok(value) => match value.trim().parse() {
+                Ok(id) if id != 0 => Some(ChannelId::new(id)),
+                _ => return Err(BroomError::InvalidConfig("ALERT_CHANNEL_ID")),
+            },
+            Err(_) => None,
+        };
+        let threshold_percent = match env::var("ERROR_RATE_THRESHOLD") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("ERROR_RATE_THRESHOLD"))?,
+            Err(_) => DEFAULT_THRESHOLD_IN_PERCENT,
+        };
+
+        Ok(Self {
+            started: Instant::now(),
+            window_start: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            previous_errors: AtomicU64::new(0),
+            previous_total: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+            threshold_percent,
+            alert_channel_id,
+        })
+    }
+
+    /// Channel the operator is alerted in, if configured.
+    pub fn alert_channel_id(&self) -> Option<ChannelId> {
+        self.alert_channel_id
+    }
+
+    /// Counts the outcome of a request to the Discord API.
+    pub fn record<T>(&self, result: &Result<T, serenity::Error>) {
+        self.rotate();
+        self.total.fetch_add(1, Ordering::Relaxed);
+
+        if let Err(e) = result {
+            if is_expected(e) {
+                return;
+            }
+
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            if let Ok(mut last_error) = self.last_error.lock() {
+                *last_error = Some((e.to_string(), Utc::now()));
+            }
+        }
+    }
+
+    pub fn error_rate(&self) -> ErrorRate {
+        self.rotate();
+        let elapsed = self.elapsed_secs() - self.window_start.load(Ordering::Relaxed);
+        let overlap = 1.0 - (elapsed as f64 / WINDOW_IN_SECS as f64).min(1.0);
+        let weighted = |current: &AtomicU64, previous: &AtomicU64| {
+            current.load(Ordering::Relaxed) as f64
+                + previous.load(Ordering::Relaxed) as f64 * overlap
+        };
+
+        ErrorRate {
+            errors: weighted(&self.errors, &self.previous_errors),
+            total: weighted(&self.total, &self.previous_total),
+            last_error: self.last_error.lock().ok().and_then(|error| error.clone()),
+        }
+    }
+
+    /// Checks the error rate periodically and posts an alert whenever it exceeds the threshold,
+    /// at most once per throttling period.
+    pub async fn run(self: Arc<Self>, http: Arc<Http>) {
+        let Some(channel_id) = self.alert_channel_id else {
+            return;
+        };
+
+        let mut interval = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_IN_SECS));
+        let mut last_alert: Option<Instant> = None;
+        loop {
+            interval.tick().await;
+
+            let rate = self.error_rate();
+            if rate.total < MIN_REQUESTS_FOR_ALERT
+                || rate.percent() <= self.threshold_percent as f64
+                || last_alert.is_some_and(|last_alert| {
+                    last_alert.elapsed() < Duration::from_secs(ALERT_THROTTLE_IN_SECS)
+                })
+            {
+                continue;
+            }
+
+            last_alert = Some(Instant::now());
+            let mut description = format!(
+                "{:.1}% of the requests to Discord within the last minute failed ({:.0} of {:.0}).",
+                rate.percent(),
+                rate.errors,
+                rate.total
+            );
+            if let Some((error, at)) = &rate.last_error {
+                description.push_str(&format!(
+                    "\nMost recent error at <t:{}:T>: {}",
+                    at.timestamp(),
+                    error
+                ));
+            }
+            let embed = CreateEmbed::new()
+                .title("High error rate")
+                .description(description)
+                .timestamp(Timestamp::now());
+
+            if let Err(e) = channel_id
+                .send_message(&http, CreateMessage::new().embed(embed))
+                .await
+            {
+                tracing::error!(
+                    "There was an error while attempting to post an error rate alert: {:?}",
+                    e
+                );
+            }
+        }
+    }
+
+    fn elapsed_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Starts a new window once the current one has elapsed. The counts of the current window
+    /// become the previous ones, or are discarded if more than a whole window has passed since.
+    fn rotate(&self) {
+        let now = self.elapsed_secs();
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        if now - window_start < WINDOW_IN_SECS {
+            return;
+        }
+
+        // Only the thread winning the exchange rotates, concurrent counts may land in either
+        // window which is acceptable for an approximation.
+        let new_start = now - (now - window_start) % WINDOW_IN_SECS;
+        if self
+            .window_start
+            .compare_exchange(
+                window_start,
+                new_start,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return;
+        }
+
+        let adjacent = new_start - window_start == WINDOW_IN_SECS;
+        let errors = self.errors.swap(0, Ordering::Relaxed);
+        let total = self.total.swap(0, Ordering::Relaxed);
+        self.previous_errors
+            .store(if adjacent { errors } else { 0 }, Ordering::Relaxed);
+        self.previous_total
+            .store(if adjacent { total } else { 0 }, Ordering::Relaxed);
+    }
+}
+
+/// Counts the outcome of a request with the monitor shared through the given context.
+pub async fn record<T>(context: &Context, result: &Result<T, serenity::Error>) {
+    if let Some(monitor) = context.data.read().await.get::<ErrorRateMonitors>() {
+        monitor.record(result);
+    }
+}
+
+fn is_expected(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+            EXPECTED_ERROR_CODES.contains(&response.error.code)
+        }
+        _ => false,
+    }
+}
+
+pub struct ErrorRateMonitors;
+
+impl TypeMapKey for ErrorRateMonitors {
+    type Value = Arc<ErrorRateMonitor>;
+}
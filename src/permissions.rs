@@ -0,0 +1,21 @@
+//! The permissions the bot itself needs in a guild.
+
+use serenity::model::permissions::Permissions;
+
+/// All permissions required by any feature of the bot, which are requested when it is invited.
+/// Features requiring additional permissions have to add them here.
+pub const REQUIRED_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::EMBED_LINKS)
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    // Deleting duplicates.
+    .union(Permissions::MANAGE_MESSAGES)
+    // Timing out members repeatedly mentioning someone or posting invites.
+    .union(Permissions::MODERATE_MEMBERS)
+    // Executing approved ban proposals.
+    .union(Permissions::BAN_MEMBERS);
+
+/// Permissions without which the bot cannot reliably read a channel, in which case it takes no
+/// action on messages posted there.
+pub const READ_PERMISSIONS: Permissions =
+    Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
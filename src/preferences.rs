@@ -0,0 +1,35 @@
+//! Preferences users set for themselves, which apply in every guild.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How the messages sent to authors of deleted messages are formatted. Screen readers and other
+/// accessibility tools handle plain text better than embeds.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DmFormat {
+    #[serde(rename = "plain")]
+    PlainText,
+    #[default]
+    Embed,
+}
+
+impl DmFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "plain" => Some(Self::PlainText),
+            "embed" => Some(Self::Embed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DmFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PlainText => f.write_str("plain"),
+            Self::Embed => f.write_str("embed"),
+        }
+    }
+}
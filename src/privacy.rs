@@ -0,0 +1,87 @@
+//! Minimization of the message content the bot stores or shows to moderators.
+//!
+//! Privacy regulations like the GDPR require not to keep personal data beyond what is necessary,
+//! and messages commonly contain some. Operators can therefore limit what is kept of the content of
+//! messages that are stored in the database, e.g. as detection samples or archived deletions, and
+//! shown in audit embeds. Direct messages to authors are exempt, since it is their own data.
+
+use std::{env, sync::Arc};
+
+use serenity::{model::channel::Message, prelude::TypeMapKey};
+use sha2::{Digest, Sha256};
+
+use crate::{content, error::BroomError};
+
+/// How much of the content of messages is stored or shown to moderators.
+pub struct ContentPolicy {
+    /// Number of characters content is truncated to, if any.
+    max_chars: Option<usize>,
+    /// Whether only a SHA-256 hash of content is kept instead of the content itself.
+    hash_only: bool,
+}
+
+impl ContentPolicy {
+    /// Reads the number of characters content is truncated to from `CONTENT_MAX_CHARS_STORED` and
+    /// whether only hashes are kept from `CONTENT_HASH_ONLY`. Content is kept in full by default.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let max_chars = match env::var("CONTENT_MAX_CHARS_STORED") {
+            Ok(value) => match value.trim().parse() {
+                Ok(max_chars) if max_chars > 0 => Some(max_chars),
+                _ => return Err(BroomError::InvalidConfig("CONTENT_MAX_CHARS_STORED")),
+            },
+            Err(_) => None,
+        };
+        let hash_only = match env::var("CONTENT_HASH_ONLY") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("CONTENT_HASH_ONLY"))?,
+            Err(_) => false,
+        };
+
+        Ok(Self {
+            max_chars,
+            hash_only,
+        })
+    }
+
+    /// Whether only hashes of content are kept, which leaves nothing to learn from.
+    #[cfg(feature = "ml_suggestions")]
+    pub fn hash_only(&self) -> bool {
+        self.hash_only
+    }
+
+    /// Returns what may be kept of the given content.
+    pub fn minimize(&self, text: &str) -> String {
+        if self.hash_only {
+            let digest = Sha256::digest(text.as_bytes());
+            return digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        }
+
+        match self.max_chars {
+            Some(max_chars) => content::truncate(text, max_chars),
+            None => text.to_string(),
+        }
+    }
+
+    /// Returns a copy of the given message keeping only what may be kept of its content, the
+    /// contents it forwards and the content of the message it replies to.
+    pub fn minimize_message(&self, msg: &Message) -> Message {
+        let mut msg = msg.clone();
+        msg.content = self.minimize(&msg.content);
+        for snapshot in &mut msg.message_snapshots {
+            snapshot.content = self.minimize(&snapshot.content);
+        }
+        if let Some(referenced) = &mut msg.referenced_message {
+            referenced.content = self.minimize(&referenced.content);
+        }
+
+        msg
+    }
+}
+
+pub struct ContentPolicies;
+
+impl TypeMapKey for ContentPolicies {
+    type Value = Arc<ContentPolicy>;
+}
@@ -0,0 +1,134 @@
+//! Probation of users with many strikes, who are likely problem accounts that moderators may not
+//! want to ban outright. Users on probation are held to stricter settings until a moderator lifts
+//! their probation or they behaved for long enough.
+
+use std::{sync::Arc, time::Duration};
+
+use serenity::model::prelude::{GuildId, UserId};
+
+use crate::{
+    config::GuildConfig,
+    db::{unix_timestamp, Database},
+    error::BroomError,
+    history::{HistoryAction, HistoryEntry},
+    state::{GuildState, Guilds},
+};
+
+/// Interval in seconds in which probations of users that behaved long enough are ended.
+const EXPIRY_INTERVAL_IN_SECS: u64 = 60 * 60;
+
+/// Puts the given user on probation if they reached the configured number of deleted duplicates
+/// within the strike window and are not on probation already.
+pub async fn check(
+    database: &Database,
+    state: &GuildState,
+    guild_id: GuildId,
+    user_id: UserId,
+    config: &GuildConfig,
+) -> Result<(), BroomError> {
+    let Some(threshold) = config.probation_threshold else {
+        return Ok(());
+    };
+    if state.probations.read().await.contains(&user_id) {
+        return Ok(());
+    }
+
+    let now = unix_timestamp();
+    let strikes = database
+        .count_actions(
+            guild_id,
+            user_id,
+            HistoryAction::Deleted,
+            now - config.strike_window_secs as i64,
+        )
+        .await?;
+    if strikes < threshold {
+        return Ok(());
+    }
+
+    database.start_probation(guild_id, user_id, now).await?;
+    state.probations.write().await.insert(user_id);
+    database
+        .record_action(
+            guild_id,
+            &HistoryEntry::new(user_id, HistoryAction::ProbationStarted, now),
+        )
+        .await?;
+    tracing::info!(
+        "Put user {} on probation in guild {} after {} strikes.",
+        user_id,
+        guild_id,
+        strikes
+    );
+
+    Ok(())
+}
+
+/// Lifts the probation of the given user on behalf of the given moderator. Returns `false` if the
+/// user was not on probation.
+pub async fn lift(
+    database: &Database,
+    state: &GuildState,
+    guild_id: GuildId,
+    user_id: UserId,
+    moderator_id: UserId,
+) -> Result<bool, BroomError> {
+    if !database.end_probation(guild_id, user_id).await? {
+        return Ok(false);
+    }
+    state.probations.write().await.remove(&user_id);
+
+    let mut entry = HistoryEntry::new(user_id, HistoryAction::ProbationLifted, unix_timestamp());
+    entry.moderator_id = Some(moderator_id);
+    database.record_action(guild_id, &entry).await?;
+
+    Ok(true)
+}
+
+/// Ends the probations of users without strikes for the expiry of their guild periodically, until
+/// the process exits.
+pub async fn run_expiry(database: Arc<Database>, guilds: Arc<Guilds>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(EXPIRY_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = end_expired(&database, &guilds).await {
+            tracing::error!(
+                "There was an error while attempting to end expired probations: {}",
+                e
+            );
+        }
+    }
+}
+
+async fn end_expired(database: &Database, guilds: &Guilds) -> Result<(), BroomError> {
+    let now = unix_timestamp();
+    for (guild_id, user_id, started_at) in database.all_probations().await? {
+        let state = guilds.get(guild_id).await?;
+        let expiry_days = state.config.read().await.probation_expiry_days;
+        // Users behaved if they had no strikes since the start of the expiry period.
+        let since = now - i64::from(expiry_days) * 24 * 60 * 60;
+        if started_at > since {
+            continue;
+        }
+        let strikes = database
+            .count_actions(guild_id, user_id, HistoryAction::Deleted, since)
+            .await?;
+        if strikes > 0 || !database.end_probation(guild_id, user_id).await? {
+            continue;
+        }
+        state.probations.write().await.remove(&user_id);
+        database
+            .record_action(
+                guild_id,
+                &HistoryEntry::new(user_id, HistoryAction::ProbationExpired, now),
+            )
+            .await?;
+        tracing::info!(
+            "The probation of user {} in guild {} expired.",
+            user_id,
+            guild_id
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Quiet hours, weekly time ranges during which the moderators of a guild are unavailable, e.g. at
+//! night in their timezone. Detections are still reported, but acting on them is deferred until
+//! the quiet hours end, so that moderators are around to handle appeals.
+
+use std::{sync::Once, time::Duration};
+
+use chrono::Utc;
+use serenity::{
+    client::Context,
+    model::prelude::{ChannelId, MessageId},
+};
+
+use crate::{
+    actions::MessageContext,
+    db::SharedDatabase,
+    detectors::Detection,
+    error::BroomError,
+    handler::Handler,
+    maintenance::MaintenanceModes,
+    ratelimit::ActionRateLimiters,
+    state::{shared, GuildStates},
+};
+
+/// Interval in seconds in which guilds whose quiet hours ended are checked for deferred actions.
+const PROCESSING_INTERVAL_IN_SECS: u64 = 60;
+
+static PROCESSING: Once = Once::new();
+
+/// A detection that is acted on once the quiet hours of its guild end.
+pub struct DeferredAction {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub detection: Detection,
+}
+
+/// Whether acting on the given detection is deferred during quiet hours. Repeated mentions only
+/// alert moderators and time out the author, neither of which deletes a message or messages them.
+/// Links to blocked domains are deleted right away, since they do harm until they are, and
+/// coordinated attacks only alert moderators or lock the guild down.
+pub fn is_deferred(detection: &Detection) -> bool {
+    !matches!(
+        detection,
+        Detection::RepeatedMentions(_)
+            | Detection::BlockedLink { .. }
+            | Detection::CoordinatedAttack { .. }
+    )
+}
+
+/// Starts taking deferred actions unless it was started already, e.g. by another shard. Deferred
+/// actions are taken like any other, which requires the context of a shard.
+pub fn start(context: &Context) {
+    PROCESSING.call_once(|| {
+        tokio::spawn(run_processing(context.clone()));
+    });
+}
+
+async fn run_processing(context: Context) {
+    let mut interval = tokio::time::interval(Duration::from_secs(PROCESSING_INTERVAL_IN_SECS));
+    loop {
+        interval.tick().await;
+        if let Err(e) = process(&context).await {
+            tracing::error!(
+                "There was an error while attempting to take deferred actions: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Takes the deferred actions of all guilds whose quiet hours ended on messages that still exist.
+async fn process(context: &Context) -> Result<(), BroomError> {
+    let (guilds, database, maintenance, rate_limiter) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SharedDatabase>(&data_read)?,
+            shared::<MaintenanceModes>(&data_read)?,
+            shared::<ActionRateLimiters>(&data_read)?,
+        )
+    };
+    // Deferred actions are kept until actions are taken again.
+    if maintenance.is_active() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    for guild_id in database.guilds_with_deferred_actions().await? {
+        let config = guilds.get(guild_id).await?.config.read().await.clone();
+        if config.in_quiet_hours(now) {
+            continue;
+        }
+
+        let actions = database.take_deferred_actions(guild_id).await?;
+        tracing::info!(
+            "Taking {} deferred actions in guild {} after its quiet hours ended.",
+            actions.len(),
+            guild_id
+        );
+        let message_context =
+            MessageContext::new(context).rate_limited(guild_id, rate_limiter.clone());
+        for action in actions {
+            let mut msg = match action.channel_id.message(context, action.message_id).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    tracing::info!(
+                        "Skipped a deferred action on message {} in guild {} since it could not be fetched, e.g. as it was deleted in the meantime: {:?}",
+                        action.message_id,
+                        guild_id,
+                        e
+                    );
+                    continue;
+                }
+            };
+            // Messages fetched over HTTP do not know the guild they were posted in.
+            msg.guild_id = Some(guild_id);
+            if let Err(e) = Handler
+                .act_on(&message_context, guild_id, &msg, &config, action.detection)
+                .await
+            {
+                tracing::error!(
+                    "There was an error while attempting to take a deferred action on message {} in guild {}: {}",
+                    msg.id,
+                    guild_id,
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
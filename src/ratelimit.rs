@@ -0,0 +1,179 @@
+//! Limits on the rate of moderation actions taken in each guild, so that a coordinated spam wave
+//! of many accounts does not exhaust the rate limits of the Discord API within seconds.
+//!
+//! Actions exceeding the rate are queued and granted by [`ActionRateLimiter::run`] as soon as the
+//! rate allows. Once the queue of a guild is full, its oldest actions are dropped.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    num::NonZeroU32,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultDirectRateLimiter, Quota, RateLimiter,
+};
+use serenity::{model::prelude::GuildId, prelude::TypeMapKey};
+use tokio::sync::{oneshot, Notify};
+
+use crate::error::BroomError;
+
+/// Default number of actions per second each guild is limited to.
+const DEFAULT_ACTIONS_PER_SEC: NonZeroU32 = NonZeroU32::new(10).unwrap();
+/// Default number of actions each guild may take at once after a quiet period.
+const DEFAULT_ACTION_BURST: NonZeroU32 = NonZeroU32::new(30).unwrap();
+/// Default number of actions queued per guild before the oldest ones are dropped.
+const DEFAULT_MAX_QUEUED_ACTIONS: usize = 500;
+
+/// Operator settings limiting the rate of actions.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitSettings {
+    pub actions_per_sec: NonZeroU32,
+    pub burst: NonZeroU32,
+    pub max_queued_actions: usize,
+}
+
+impl RateLimitSettings {
+    /// Reads the settings from `ACTIONS_PER_SEC`, `ACTION_BURST` and `MAX_QUEUED_ACTIONS`, falling
+    /// back to the defaults for those that are not set.
+    pub fn from_env() -> Result<Self, BroomError> {
+        Ok(Self {
+            actions_per_sec: read("ACTIONS_PER_SEC", DEFAULT_ACTIONS_PER_SEC)?,
+            burst: read("ACTION_BURST", DEFAULT_ACTION_BURST)?,
+            max_queued_actions: read("MAX_QUEUED_ACTIONS", DEFAULT_MAX_QUEUED_ACTIONS)?,
+        })
+    }
+}
+
+fn read<T: FromStr>(name: &'static str, default: T) -> Result<T, BroomError> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig(name)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// The rate limit of a single guild along with the actions waiting for it, oldest first.
+struct GuildActionRateLimiter {
+    limiter: DefaultDirectRateLimiter,
+    queue: VecDeque<oneshot::Sender<()>>,
+}
+
+impl GuildActionRateLimiter {
+    fn new(settings: &RateLimitSettings, clock: &DefaultClock) -> Self {
+        let quota = Quota::per_second(settings.actions_per_sec).allow_burst(settings.burst);
+
+        Self {
+            limiter: RateLimiter::direct_with_clock(quota, clock.clone()),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Grants queued actions until the rate is exceeded. Returns the time until the next one can
+    /// be granted, or `None` if the queue is empty.
+    fn drain(&mut self, clock: &DefaultClock) -> Option<Duration> {
+        loop {
+            // Actions whose handling was cancelled in the meantime do not use up the rate.
+            while self.queue.front().is_some_and(|action| action.is_closed()) {
+                self.queue.pop_front();
+            }
+            if self.queue.is_empty() {
+                return None;
+            }
+
+            match self.limiter.check() {
+                Ok(()) => {
+                    if let Some(action) = self.queue.pop_front() {
+                        let _ = action.send(());
+                    }
+                }
+                Err(not_until) => return Some(not_until.wait_time_from(clock.now())),
+            }
+        }
+    }
+}
+
+pub struct ActionRateLimiter {
+    guilds: Mutex<HashMap<GuildId, GuildActionRateLimiter>>,
+    settings: RateLimitSettings,
+    clock: DefaultClock,
+    queued: Notify,
+}
+
+impl ActionRateLimiter {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            guilds: Mutex::new(HashMap::new()),
+            settings,
+            clock: DefaultClock::default(),
+            queued: Notify::new(),
+        }
+    }
+
+    /// Waits until the given guild may take another action. Returns `false` if the action was
+    /// dropped from the full queue, in which case it must not be taken.
+    pub async fn acquire(&self, guild_id: GuildId) -> bool {
+        let granted = {
+            let Ok(mut guilds) = self.guilds.lock() else {
+                return true;
+            };
+            let guild = guilds
+                .entry(guild_id)
+                .or_insert_with(|| GuildActionRateLimiter::new(&self.settings, &self.clock));
+            // Actions are granted in order, so none may skip ahead of those already queued.
+            if guild.queue.is_empty() && guild.limiter.check().is_ok() {
+                return true;
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            guild.queue.push_back(sender);
+            if guild.queue.len() > self.settings.max_queued_actions {
+                guild.queue.pop_front();
+                tracing::warn!(
+                    "Dropped the oldest queued action in guild {} since more than {} actions are queued.",
+                    guild_id,
+                    self.settings.max_queued_actions
+                );
+            }
+            receiver
+        };
+        self.queued.notify_one();
+
+        granted.await.is_ok()
+    }
+
+    /// Grants queued actions as soon as the rate of their guild allows until the process exits.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let wait = match self.guilds.lock() {
+                Ok(mut guilds) => guilds
+                    .values_mut()
+                    .filter_map(|guild| guild.drain(&self.clock))
+                    .min(),
+                Err(_) => return,
+            };
+
+            match wait {
+                Some(wait) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = self.queued.notified() => {}
+                    }
+                }
+                None => self.queued.notified().await,
+            }
+        }
+    }
+}
+
+pub struct ActionRateLimiters;
+
+impl TypeMapKey for ActionRateLimiters {
+    type Value = Arc<ActionRateLimiter>;
+}
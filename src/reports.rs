@@ -0,0 +1,230 @@
+//! Buttons attached to reports of messages that were detected but not deleted, e.g. since the
+//! guild only reports duplicates or warns their authors, so that moderators can act on them right
+//! from the audit channel.
+
+use serenity::{
+    builder::{
+        CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+        EditInteractionResponse,
+    },
+    client::Context,
+    model::{
+        application::{ButtonStyle, ComponentInteraction},
+        mention::Mentionable,
+        permissions::Permissions,
+        prelude::{ChannelId, MessageId, UserId},
+    },
+};
+
+use crate::{
+    actions::MessageContext,
+    db::{unix_timestamp, SharedDatabase},
+    error::BroomError,
+    handler::{Handler, Violation},
+    history::{HistoryAction, HistoryEntry},
+    i18n,
+    state::{shared, GuildStates},
+};
+
+/// Prefix of the custom IDs of all buttons attached to reports.
+pub const CUSTOM_ID_PREFIX: &str = "broom:report:";
+
+/// The message a report is about, encoded into the custom IDs of its buttons so that nothing has
+/// to be stored until a moderator acts on it.
+struct ReportedMessage {
+    violation: Violation,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    author_id: UserId,
+}
+
+impl ReportedMessage {
+    fn custom_id(&self, action: &str) -> String {
+        let violation = match self.violation {
+            Violation::Duplicate { forwarded: false } => "duplicate",
+            Violation::Duplicate { forwarded: true } => "forward",
+            Violation::KnownScam => "known_scam",
+            Violation::BlockedLink => "blocked_link",
+        };
+
+        format!(
+            "{}{}:{}:{}:{}:{}",
+            CUSTOM_ID_PREFIX, violation, self.channel_id, self.message_id, self.author_id, action
+        )
+    }
+
+    /// Parses a custom ID into the reported message and the action of the button.
+    fn parse(custom_id: &str) -> Option<(Self, &str)> {
+        let mut parts = custom_id.strip_prefix(CUSTOM_ID_PREFIX)?.splitn(5, ':');
+        let violation = match parts.next()? {
+            "duplicate" => Violation::Duplicate { forwarded: false },
+            "forward" => Violation::Duplicate { forwarded: true },
+            "known_scam" => Violation::KnownScam,
+            "blocked_link" => Violation::BlockedLink,
+            _ => return None,
+        };
+        let mut id = || parts.next()?.parse::<u64>().ok().filter(|id| *id != 0);
+        let message = Self {
+            violation,
+            channel_id: ChannelId::new(id()?),
+            message_id: MessageId::new(id()?),
+            author_id: UserId::new(id()?),
+        };
+
+        Some((message, parts.next()?))
+    }
+}
+
+/// Returns the buttons to delete the given message or to dismiss the report about it.
+pub fn buttons(
+    language: &str,
+    violation: Violation,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    author_id: UserId,
+) -> Vec<CreateActionRow> {
+    let message = ReportedMessage {
+        violation,
+        channel_id,
+        message_id,
+        author_id,
+    };
+
+    render(language, &message, false)
+}
+
+fn render(language: &str, message: &ReportedMessage, disabled: bool) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(message.custom_id("delete"))
+            .label(i18n::translate(language, "report_delete", &[]))
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+        CreateButton::new(message.custom_id("dismiss"))
+            .label(i18n::translate(language, "report_dismiss", &[]))
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled),
+    ])]
+}
+
+/// Handles a click on one of the buttons of a report.
+pub async fn handle_component(context: &Context, component: &ComponentInteraction) {
+    if let Err(e) = handle_click(context, component).await {
+        tracing::error!(
+            "There was an error while handling a click on a report: {}",
+            e
+        );
+    }
+}
+
+async fn handle_click(
+    context: &Context,
+    component: &ComponentInteraction,
+) -> Result<(), BroomError> {
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+    let Some((message, action)) = ReportedMessage::parse(&component.data.custom_id) else {
+        return Ok(());
+    };
+    let (guilds, database) = {
+        let data_read = context.data.read().await;
+        (
+            shared::<GuildStates>(&data_read)?,
+            shared::<SharedDatabase>(&data_read)?,
+        )
+    };
+    let config = guilds.get(guild_id).await?.config.read().await.clone();
+    let language = &config.language;
+
+    if !component
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| {
+            permissions.administrator() || permissions.contains(Permissions::MANAGE_MESSAGES)
+        })
+    {
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(i18n::translate(language, "report_missing_permission", &[]))
+                .ephemeral(true),
+        );
+        component.create_response(context, response).await?;
+        return Ok(());
+    }
+
+    // Fetching and deleting the message, telling its author and striking them may take longer than
+    // Discord waits for a response, so the report is only updated afterwards.
+    component
+        .create_response(context, CreateInteractionResponse::Acknowledge)
+        .await?;
+    let moderator = component.user.id;
+    let key = match action {
+        "delete" => match message
+            .channel_id
+            .message(context, message.message_id)
+            .await
+        {
+            Ok(mut msg) => {
+                // Messages fetched over HTTP do not know the guild they were posted in.
+                msg.guild_id = Some(guild_id);
+                // The author is told and gets a strike as if the message had been deleted when it
+                // was detected.
+                let deleted = Handler
+                    .delete_reported(
+                        &MessageContext::new(context),
+                        guild_id,
+                        &msg,
+                        &config,
+                        message.violation,
+                    )
+                    .await?;
+                if deleted {
+                    "report_outcome_deleted"
+                } else {
+                    "report_outcome_delete_failed"
+                }
+            }
+            Err(e) => {
+                tracing::info!(
+                    "Could not fetch reported message {} in guild {}, e.g. as it was deleted in the meantime: {:?}",
+                    message.message_id,
+                    guild_id,
+                    e
+                );
+                "report_outcome_gone"
+            }
+        },
+        "dismiss" => {
+            let mut entry = HistoryEntry::new(
+                message.author_id,
+                HistoryAction::ReportDismissed,
+                unix_timestamp(),
+            );
+            entry.channel_id = Some(message.channel_id);
+            entry.moderator_id = Some(moderator);
+            database.record_action(guild_id, &entry).await?;
+            "report_outcome_dismissed"
+        }
+        _ => return Ok(()),
+    };
+
+    let content = i18n::translate(
+        language,
+        key,
+        &[
+            ("user", &message.author_id.mention()),
+            ("moderator", &moderator.mention()),
+        ],
+    );
+    component
+        .edit_response(
+            context,
+            EditInteractionResponse::new()
+                .content(content)
+                .components(render(language, &message, true)),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Pruning of the actions, detection samples and perceptual hashes recorded for guilds once they
+//! are older than the retention of their guild, so that the database does not grow indefinitely.
+
+use std::{env, sync::Arc, time::Duration};
+
+use chrono::Utc;
+
+use crate::{
+    db::{self, unix_timestamp, Database},
+    error::BroomError,
+    schedule,
+    state::Guilds,
+    stats::{Prune, Stats},
+};
+
+/// Hour of the day in UTC at which is pruned unless `PRUNE_HOUR_UTC` is set, when there is little
+/// traffic in most guilds.
+const DEFAULT_PRUNE_HOUR: u32 = 3;
+/// Number of times pruning a guild is attempted while the database is locked.
+const MAX_PRUNE_ATTEMPTS: u32 = 5;
+/// Time in seconds after which pruning a guild is retried while the database is locked.
+const RETRY_DELAY_IN_SECS: u64 = 5;
+
+/// Reads the hour of the day in UTC at which expired events are pruned from `PRUNE_HOUR_UTC`.
+pub fn prune_hour_from_env() -> Result<u32, BroomError> {
+    match env::var("PRUNE_HOUR_UTC") {
+        Ok(value) => match value.trim().parse() {
+            Ok(hour) if hour < 24 => Ok(hour),
+            _ => Err(BroomError::InvalidConfig("PRUNE_HOUR_UTC")),
+        },
+        Err(_) => Ok(DEFAULT_PRUNE_HOUR),
+    }
+}
+
+/// Prunes expired events of every guild once a day at the given hour in UTC, until the process
+/// exits.
+pub async fn run_pruning(
+    database: Arc<Database>,
+    guilds: Arc<Guilds>,
+    stats: Arc<Stats>,
+    prune_hour: u32,
+) {
+    loop {
+        let now = Utc::now();
+        let next = schedule::next_hour_utc(prune_hour, now);
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        if let Err(e) = prune_expired(&database, &guilds, &stats).await {
+            tracing::error!(
+                "There was an error while attempting to prune expired events: {}",
+                e
+            );
+        }
+    }
+}
+
+async fn prune_expired(
+    database: &Database,
+    guilds: &Guilds,
+    stats: &Stats,
+) -> Result<(), BroomError> {
+    for guild_id in database.guilds_with_events().await? {
+        // A failing guild is retried on the next day without holding up the others.
+        let retention_days = match guilds.get(guild_id).await {
+            Ok(guild) => guild.config.read().await.event_retention_days,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to load guild {} for pruning: {}",
+                    guild_id,
+                    e
+                );
+                continue;
+            }
+        };
+        let before = unix_timestamp() - i64::from(retention_days) * 24 * 60 * 60;
+
+        let mut attempt = 1;
+        let result = loop {
+            match database.prune_events(guild_id, before).await {
+                Err(e) if db::is_locked(&e) && attempt < MAX_PRUNE_ATTEMPTS => {
+                    tracing::warn!(
+                        "The database was locked while pruning guild {}, retrying in {} seconds.",
+                        guild_id,
+                        RETRY_DELAY_IN_SECS
+                    );
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_secs(RETRY_DELAY_IN_SECS)).await;
+                }
+                result => break result,
+            }
+        };
+        let rows = match result {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::error!(
+                    "There was an error while attempting to prune guild {}: {}",
+                    guild_id,
+                    e
+                );
+                continue;
+            }
+        };
+
+        tracing::info!("Pruned {} expired events of guild {}.", rows, guild_id);
+        stats.record_prune(
+            guild_id,
+            Prune {
+                rows,
+                pruned_at: unix_timestamp(),
+            },
+        );
+    }
+
+    Ok(())
+}
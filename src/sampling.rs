@@ -0,0 +1,93 @@
+//! Sampling of detections for offline analysis of false positives. A sampled detection is stored
+//! along with the full content of the message and the configuration it was detected with, and can
+//! be dumped with the `dump_samples` binary to tune the detection settings.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use serenity::{
+    model::prelude::{ChannelId, GuildId, UserId},
+    prelude::TypeMapKey,
+};
+
+use crate::{config::GuildConfig, error::BroomError};
+
+/// Share of detections that are sampled unless `SAMPLING_RATE` is set.
+const DEFAULT_SAMPLING_RATE: f64 = 0.0;
+
+/// A detection as it is stored for later analysis.
+pub struct DetectionSample {
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub user_id: UserId,
+    /// What was detected, e.g. `duplicate`.
+    pub detection: &'static str,
+    pub content: String,
+    /// How similar the message is to the content it was detected as a repost of, if it was.
+    pub similarity: Option<f64>,
+    /// Snapshot of the configuration of the guild at the time of the detection.
+    pub config: GuildConfig,
+    pub created_at: i64,
+}
+
+/// Decides which detections are sampled. The rate is shared by all guilds and can be changed at
+/// runtime by the owner of the bot.
+pub struct Sampler {
+    /// The sampling rate as the bits of an `f64`, since there is no atomic float.
+    rate: AtomicU64,
+}
+
+impl Sampler {
+    /// Samples detections at the rate given by `SAMPLING_RATE`, between 0 and 1, or samples none
+    /// if it is not set.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let rate = match env::var("SAMPLING_RATE") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|rate| is_valid_rate(*rate))
+                .ok_or(BroomError::InvalidConfig("SAMPLING_RATE"))?,
+            Err(_) => DEFAULT_SAMPLING_RATE,
+        };
+
+        Ok(Self {
+            rate: AtomicU64::new(rate.to_bits()),
+        })
+    }
+
+    pub fn rate(&self) -> f64 {
+        f64::from_bits(self.rate.load(Ordering::Relaxed))
+    }
+
+    /// Changes the sampling rate and returns whether it was valid.
+    pub fn set_rate(&self, rate: f64) -> bool {
+        if !is_valid_rate(rate) {
+            return false;
+        }
+        self.rate.store(rate.to_bits(), Ordering::Relaxed);
+
+        true
+    }
+
+    /// Whether the current detection is sampled.
+    pub fn sample(&self) -> bool {
+        let rate = self.rate();
+        rate > 0.0 && rand::random::<f64>() < rate
+    }
+}
+
+fn is_valid_rate(rate: f64) -> bool {
+    (0.0..=1.0).contains(&rate)
+}
+
+pub struct Samplers;
+
+impl TypeMapKey for Samplers {
+    type Value = Arc<Sampler>;
+}
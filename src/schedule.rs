@@ -0,0 +1,100 @@
+//! Recurring weekly time ranges evaluated in the timezone of a guild.
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// A time range starting on the given weekday. Ranges whose end is not after their start continue
+/// into the following day, e.g. `22:00-02:00` on Friday ends on Saturday at 02:00.
+pub type WeeklyRange = (NaiveTime, NaiveTime, Weekday);
+
+/// Parses a range in the format `HH:MM-HH:MM`.
+pub fn parse_range(input: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (start, end) = input.split_once('-')?;
+    let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+
+    Some((start, end))
+}
+
+/// Parses a weekday given by its English name or abbreviation, e.g. `monday` or `Mon`.
+pub fn parse_weekday(input: &str) -> Option<Weekday> {
+    input.trim().parse().ok()
+}
+
+pub fn parse_timezone(input: &str) -> Option<Tz> {
+    input.trim().parse().ok()
+}
+
+/// Returns whether the given instant falls into the range when evaluated in the given timezone.
+pub fn contains(range: &WeeklyRange, timezone: Tz, now: DateTime<Utc>) -> bool {
+    let (start, end, weekday) = *range;
+    let local = now.with_timezone(&timezone);
+    let (today, time) = (local.weekday(), local.time());
+
+    if start < end {
+        today == weekday && start <= time && time < end
+    } else {
+        (today == weekday && start <= time) || (today == weekday.succ() && time < end)
+    }
+}
+
+/// Returns the current date in the given timezone.
+pub fn local_date(timezone: Tz, now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&timezone).date_naive()
+}
+
+/// Returns the instant at which the next day starts in the given timezone.
+pub fn next_midnight(timezone: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+    let tomorrow = local_date(timezone, now)
+        .succ_opt()
+        .unwrap_or(NaiveDate::MAX);
+
+    start_of_day(timezone, tomorrow).unwrap_or(now + TimeDelta::days(1))
+}
+
+/// Returns the next instant after the given one at which the given hour of the day starts in UTC.
+pub fn next_hour_utc(hour: u32, now: DateTime<Utc>) -> DateTime<Utc> {
+    match now.date_naive().and_hms_opt(hour, 0, 0) {
+        Some(today) if today.and_utc() > now => today.and_utc(),
+        Some(today) => today.and_utc() + TimeDelta::days(1),
+        None => now + TimeDelta::days(1),
+    }
+}
+
+/// Returns the instant at which the given day starts in the given timezone, or `None` if it is out
+/// of range.
+pub fn start_of_day(timezone: Tz, date: NaiveDate) -> Option<DateTime<Utc>> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    // Days on which midnight is skipped by a daylight saving transition start an hour later.
+    timezone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(midnight + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .map(|midnight| midnight.with_timezone(&Utc))
+}
+
+/// Returns the next instant at which the range starts, or the current instant if it is active.
+pub fn next_occurrence(range: &WeeklyRange, timezone: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+    if contains(range, timezone, now) {
+        return now;
+    }
+
+    let (start, _, weekday) = *range;
+    let local = now.with_timezone(&timezone);
+    (0..=7)
+        .filter_map(|offset| local.date_naive().checked_add_days(Days::new(offset)))
+        .filter(|date| date.weekday() == weekday)
+        // Skips dates on which the start does not exist, e.g. due to a daylight saving transition.
+        .filter_map(|date| {
+            timezone
+                .from_local_datetime(&date.and_time(start))
+                .earliest()
+        })
+        .map(|start| start.with_timezone(&Utc))
+        .find(|start| *start > now)
+        .unwrap_or(now)
+}
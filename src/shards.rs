@@ -0,0 +1,87 @@
+//! Tracking of the gateway connections of the shards, alerting the operator of the bot when a
+//! shard was disconnected for longer than acceptable.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    gateway::{ConnectionStage, ShardStageUpdateEvent},
+    model::id::ShardId,
+    prelude::TypeMapKey,
+};
+
+use crate::error::BroomError;
+
+/// Default downtime in seconds after which the operator is alerted once the shard reconnects.
+const DEFAULT_MAX_ACCEPTABLE_DOWNTIME_SECS: u64 = 30;
+
+/// A shard that is currently not connected.
+pub struct ShardDowntime {
+    pub start: Instant,
+}
+
+pub struct ShardMonitor {
+    downtimes: Mutex<HashMap<ShardId, ShardDowntime>>,
+    max_acceptable_downtime: Duration,
+}
+
+impl ShardMonitor {
+    /// Reads the downtime after which the operator is alerted from `MAX_ACCEPTABLE_DOWNTIME_SECS`.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let max_acceptable_downtime_secs = match env::var("MAX_ACCEPTABLE_DOWNTIME_SECS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("MAX_ACCEPTABLE_DOWNTIME_SECS"))?,
+            Err(_) => DEFAULT_MAX_ACCEPTABLE_DOWNTIME_SECS,
+        };
+
+        Ok(Self {
+            downtimes: Mutex::new(HashMap::new()),
+            max_acceptable_downtime: Duration::from_secs(max_acceptable_downtime_secs),
+        })
+    }
+
+    /// Records the transition of a shard and returns how long it was down if it just reconnected.
+    ///
+    /// The downtime starts as soon as the shard leaves the connected stage, since resuming a
+    /// session does not pass through the disconnected stage.
+    pub fn update(&self, event: &ShardStageUpdateEvent) -> Option<Duration> {
+        let Ok(mut downtimes) = self.downtimes.lock() else {
+            return None;
+        };
+
+        match event.new {
+            ConnectionStage::Connected => downtimes
+                .remove(&event.shard_id)
+                .map(|downtime| downtime.start.elapsed()),
+            stage
+                if stage == ConnectionStage::Disconnected
+                    || event.old == ConnectionStage::Connected =>
+            {
+                downtimes
+                    .entry(event.shard_id)
+                    .or_insert_with(|| ShardDowntime {
+                        start: Instant::now(),
+                    });
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the operator is alerted about a shard that was down for the given duration.
+    pub fn exceeds_acceptable(&self, downtime: Duration) -> bool {
+        downtime > self.max_acceptable_downtime
+    }
+}
+
+pub struct ShardMonitors;
+
+impl TypeMapKey for ShardMonitors {
+    type Value = Arc<ShardMonitor>;
+}
@@ -0,0 +1,854 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    env,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use chrono_tz::Tz;
+use moka::{future::Cache, notification::RemovalCause, Expiry};
+use serenity::{
+    model::prelude::{ChannelId, GuildId, MessageId, UserId},
+    prelude::{TypeMap, TypeMapKey},
+};
+use tokio::sync::{Mutex, RwLock};
+
+#[cfg(feature = "perceptual_hashing")]
+use crate::detectors::SimilarImageDetector;
+use crate::{
+    allowlist::PrefixAllowlist,
+    bootstrap::GuildJoin,
+    bridges::BridgeList,
+    config::{ConfigLimits, DetectionScope, GuildConfig},
+    db::{unix_timestamp, Database},
+    detectors::{
+        ContentFrequency, CoordinatedAttackDetector, Detector, DuplicateDetector, RepeatedMentions,
+    },
+    error::BroomError,
+    ignore::IgnoreList,
+    schedule,
+    stats::Stats,
+    templates::TemplateContents,
+};
+
+/// Default time in milliseconds between flushes of buffered writes to the tracking caches.
+const WRITE_BATCH_INTERVAL_IN_MILLIS: u64 = 50;
+
+pub type MessageKey = (UserId, String);
+pub type MessageCache = Cache<MessageKey, CacheEntry>;
+/// The most recent messages of each author along with when they were posted, oldest first.
+pub type RecentMessageCache = Cache<UserId, VecDeque<(Instant, String)>>;
+
+/// How often and when a tracked message was last posted.
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    pub last_seen: Instant,
+    pub occurrences: usize,
+    /// Copies posted before the threshold was reached, which are only remembered if they are
+    /// deleted retroactively once it is.
+    pub messages: Vec<(ChannelId, MessageId)>,
+    /// Channel the message was first posted in within the window.
+    pub first_channel_id: ChannelId,
+    /// Number of times the author was warned about posting this content instead of action being
+    /// taken on it.
+    pub warnings: u8,
+}
+
+/// Everything the bot keeps track of for a single guild.
+pub struct GuildState {
+    pub config: RwLock<GuildConfig>,
+    pub cache: Arc<RwLock<MessageCache>>,
+    /// Only filled under the recent messages scope, which compares messages against the most
+    /// recent ones of their author rather than against all messages within the window.
+    recent_messages: RwLock<RecentMessageCache>,
+    pub ignore_list: RwLock<IgnoreList>,
+    pub bridge_list: RwLock<BridgeList>,
+    pub prefix_allowlist: RwLock<PrefixAllowlist>,
+    /// Fetched when the guild becomes available and refreshed daily, since the templates are
+    /// messages on Discord rather than part of the configuration.
+    pub templates: RwLock<TemplateContents>,
+    /// When the bot joined the guild, if it was recorded.
+    pub join: RwLock<Option<GuildJoin>>,
+    /// Users on probation, who are held to stricter settings.
+    pub probations: RwLock<HashSet<UserId>>,
+    /// Users who posted in the guild at least once while its first messages were exempt.
+    known_users: RwLock<HashSet<UserId>>,
+    /// Members that might be alt accounts of banned users and are held to stricter settings, along
+    /// with the timestamp until which they are watched. Watches are short, so they are not
+    /// persisted.
+    watched_members: RwLock<HashMap<UserId, i64>>,
+    /// Who recently posted which content, to detect many users posting the same one at once.
+    pub content_frequency: ContentFrequency,
+    /// Whether the guild had fewer members than the operator requires when its last message was
+    /// handled, so that crossing the minimum is only logged once.
+    pub too_small: AtomicBool,
+    /// Writes to the tracking cache that have not been flushed yet, oldest first, or `None` if
+    /// writes are not batched.
+    pending_writes: Option<Mutex<Vec<(MessageKey, CacheEntry)>>>,
+}
+
+impl GuildState {
+    pub fn new(
+        config: GuildConfig,
+        join: Option<GuildJoin>,
+        probations: HashSet<UserId>,
+        known_users: HashSet<UserId>,
+        stats: Arc<Stats>,
+        batch_writes: bool,
+    ) -> Self {
+        let cache = build_cache(&config, stats);
+        let recent_messages = build_recent_message_cache(&config);
+        let ignore_list = IgnoreList::new(&config.ignore_patterns);
+        let bridge_list = BridgeList::new(&config.trusted_bridges);
+        let prefix_allowlist = PrefixAllowlist::new(&config.content_prefix_allowlist);
+
+        Self {
+            config: RwLock::new(config),
+            cache: Arc::new(RwLock::new(cache)),
+            recent_messages: RwLock::new(recent_messages),
+            ignore_list: RwLock::new(ignore_list),
+            bridge_list: RwLock::new(bridge_list),
+            prefix_allowlist: RwLock::new(prefix_allowlist),
+            templates: RwLock::new(TemplateContents::default()),
+            join: RwLock::new(join),
+            probations: RwLock::new(probations),
+            known_users: RwLock::new(known_users),
+            watched_members: RwLock::new(HashMap::new()),
+            content_frequency: ContentFrequency::new(),
+            too_small: AtomicBool::new(false),
+            pending_writes: batch_writes.then(|| Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Returns the detectors checking the messages of the guild, in the order in which they check
+    /// them.
+    pub fn detectors(&self) -> Vec<Box<dyn Detector>> {
+        // Blocked links are checked by the handler before any of these, since they are deleted
+        // regardless of what else the message is.
+        let detectors: Vec<Box<dyn Detector>> = vec![
+            Box::new(RepeatedMentions),
+            Box::new(CoordinatedAttackDetector),
+            Box::new(DuplicateDetector),
+        ];
+        #[cfg(feature = "perceptual_hashing")]
+        let detectors = {
+            let mut detectors = detectors;
+            detectors.push(Box::new(SimilarImageDetector));
+            detectors
+        };
+
+        detectors
+    }
+
+    /// Returns the tracked message with the given key, including writes that have not been
+    /// flushed yet, so that messages posted in quick succession are still counted.
+    pub async fn tracked(&self, key: &MessageKey) -> Option<CacheEntry> {
+        if let Some(pending_writes) = &self.pending_writes {
+            let pending_writes = pending_writes.lock().await;
+            if let Some((_, entry)) = pending_writes
+                .iter()
+                .rev()
+                .find(|(pending, _)| pending == key)
+            {
+                return Some(entry.clone());
+            }
+        }
+
+        self.cache.read().await.get(key).await
+    }
+
+    /// Watches the given member until the given timestamp, forgetting about expired watches.
+    pub async fn watch(&self, user_id: UserId, until: i64) {
+        let now = unix_timestamp();
+        let mut watched_members = self.watched_members.write().await;
+        watched_members.retain(|_, watched_until| *watched_until > now);
+        watched_members.insert(user_id, until);
+    }
+
+    /// Whether the given member is watched at the given timestamp.
+    pub async fn is_watched(&self, user_id: UserId, now: i64) -> bool {
+        self.watched_members
+            .read()
+            .await
+            .get(&user_id)
+            .is_some_and(|until| *until > now)
+    }
+
+    /// Tracks a message, buffering the write until the next flush if writes are batched.
+    pub async fn track(&self, key: MessageKey, entry: CacheEntry) {
+        match &self.pending_writes {
+            Some(pending_writes) => pending_writes.lock().await.push((key, entry)),
+            None => self.cache.write().await.insert(key, entry).await,
+        }
+    }
+
+    /// Remembers the content as the most recent message of the author and returns whether it is
+    /// among the given number of messages they posted before, ignoring those older than the
+    /// maximum age.
+    pub async fn push_recent_message(
+        &self,
+        user_id: UserId,
+        content: &str,
+        now: Instant,
+        window_size: usize,
+        max_age: Duration,
+    ) -> bool {
+        let mut seen = false;
+        self.recent_messages
+            .read()
+            .await
+            .entry(user_id)
+            .and_upsert_with(|previous| {
+                let mut window = previous.map(|entry| entry.into_value()).unwrap_or_default();
+                window.retain(|(posted_at, _)| now.duration_since(*posted_at) <= max_age);
+                seen = window.iter().any(|(_, recent)| recent == content);
+                window.push_back((now, content.to_string()));
+                while window.len() > window_size {
+                    window.pop_front();
+                }
+                std::future::ready(window)
+            })
+            .await;
+
+        seen
+    }
+
+    /// Returns the approximate number of tracked messages, counting buffered writes as well.
+    pub async fn tracked_count(&self) -> u64 {
+        let pending = match &self.pending_writes {
+            Some(pending_writes) => pending_writes.lock().await.len() as u64,
+            None => 0,
+        };
+        let cache = self.cache.read().await;
+        // The number of entries is only updated by pending maintenance.
+        cache.run_pending_tasks().await;
+
+        cache.entry_count() + pending
+    }
+
+    /// Writes all buffered writes to the tracking cache at once. The buffer stays locked until
+    /// they are written, since lookups in the meantime would find them in neither.
+    pub async fn flush_writes(&self) {
+        let Some(pending_writes) = &self.pending_writes else {
+            return;
+        };
+        let mut pending_writes = pending_writes.lock().await;
+        if pending_writes.is_empty() {
+            return;
+        }
+
+        let cache = self.cache.write().await;
+        for (key, entry) in pending_writes.drain(..) {
+            cache.insert(key, entry).await;
+        }
+    }
+
+    /// Discards all buffered writes.
+    async fn discard_writes(&self) {
+        if let Some(pending_writes) = &self.pending_writes {
+            pending_writes.lock().await.clear();
+        }
+    }
+
+    /// Stops tracking all messages of the given user, or of everyone if no user is given, and
+    /// returns how many tracked messages were removed, including buffered ones.
+    pub async fn clear_cache(&self, user_id: Option<UserId>) -> usize {
+        let clears = |key: &MessageKey| user_id.is_none_or(|user_id| key.0 == user_id);
+        // Holding the buffer keeps a flush from writing cleared messages back in the meantime.
+        let mut pending_writes = match &self.pending_writes {
+            Some(pending_writes) => Some(pending_writes.lock().await),
+            None => None,
+        };
+        let mut keys = HashSet::new();
+        if let Some(pending_writes) = pending_writes.as_mut() {
+            for (key, _) in pending_writes.iter().filter(|(key, _)| clears(key)) {
+                keys.insert(key.clone());
+            }
+            pending_writes.retain(|(key, _)| !clears(key));
+        }
+
+        let recent_messages = self.recent_messages.read().await;
+        match user_id {
+            Some(user_id) => recent_messages.invalidate(&user_id).await,
+            None => recent_messages.invalidate_all(),
+        }
+        let cache = self.cache.write().await;
+        let cached: Vec<_> = cache
+            .iter()
+            .filter(|(key, _)| clears(key))
+            .map(|(key, _)| key)
+            .collect();
+        for key in cached {
+            cache.invalidate(key.as_ref()).await;
+            keys.insert(key.as_ref().clone());
+        }
+
+        keys.len()
+    }
+}
+
+/// When tracked messages are evicted from the cache.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CacheExpiry {
+    /// After the time to idle has elapsed without a repost, or the time to live has elapsed.
+    Window {
+        time_to_idle_secs: u64,
+        time_to_live_secs: u64,
+    },
+    /// At the next midnight in the given timezone after they were first tracked.
+    Daily(Tz),
+}
+
+/// Returns the capacity of the tracking cache for the given configuration and when it evicts
+/// tracked messages. Messages have to be tracked for the longest window any of them is checked
+/// against, which may exceed the configured maximum age.
+fn cache_parameters(config: &GuildConfig) -> (u64, CacheExpiry) {
+    let expiry = match config.detection_scope {
+        DetectionScope::AllMessages => {
+            let time_to_idle_secs = config.max_window_secs();
+            CacheExpiry::Window {
+                time_to_idle_secs,
+                time_to_live_secs: config.max_entry_age_secs.max(time_to_idle_secs),
+            }
+        }
+        DetectionScope::DailyFirstOnly => CacheExpiry::Daily(config.timezone()),
+        // Reposts count as long as they are among the most recent messages and not older than the
+        // maximum age.
+        DetectionScope::RecentMessages => CacheExpiry::Window {
+            time_to_idle_secs: config.max_entry_age_secs,
+            time_to_live_secs: config.max_entry_age_secs,
+        },
+    };
+
+    (config.max_tracked_messages, expiry)
+}
+
+/// Messages are compared against the most recent ones regardless of the window, so they are only
+/// remembered for the maximum age.
+fn build_recent_message_cache(config: &GuildConfig) -> RecentMessageCache {
+    Cache::builder()
+        .max_capacity(config.max_tracked_messages)
+        .time_to_idle(Duration::from_secs(config.max_entry_age_secs))
+        .build()
+}
+
+fn build_cache(config: &GuildConfig, stats: Arc<Stats>) -> MessageCache {
+    let (capacity, expiry) = cache_parameters(config);
+    let builder = Cache::builder()
+        .max_capacity(capacity)
+        .eviction_listener(move |key, _, cause| record_removal(&stats, &key, cause));
+
+    match expiry {
+        CacheExpiry::Window {
+            time_to_idle_secs,
+            time_to_live_secs,
+        } => builder
+            .time_to_idle(Duration::from_secs(time_to_idle_secs))
+            .time_to_live(Duration::from_secs(time_to_live_secs))
+            .build(),
+        // Reposts must not extend the lifetime of an entry past the end of its day.
+        CacheExpiry::Daily(timezone) => builder.expire_after(UntilMidnight(timezone)).build(),
+    }
+}
+
+/// Lets tracked messages live until the next midnight in the given timezone.
+struct UntilMidnight(Tz);
+
+impl Expiry<MessageKey, CacheEntry> for UntilMidnight {
+    fn expire_after_create(
+        &self,
+        _key: &MessageKey,
+        _value: &CacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        let now = Utc::now();
+        let until_midnight = schedule::next_midnight(self.0, now) - now;
+
+        Some(until_midnight.to_std().unwrap_or_default())
+    }
+}
+
+/// Counts tracked messages that aged out or were pushed out of the cache. Reposts replace their
+/// entry and explicit invalidations are intentional, so neither of them is recorded.
+fn record_removal(stats: &Stats, key: &MessageKey, cause: RemovalCause) {
+    match cause {
+        RemovalCause::Expired => {
+            stats.expired_entries.fetch_add(1, Ordering::Relaxed);
+        }
+        RemovalCause::Size => {
+            stats.evicted_entries.fetch_add(1, Ordering::Relaxed);
+
+            let (user_id, content) = key;
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            tracing::debug!(
+                "Evicted a tracked message of user {} with content hash {:016x} since the cache is full.",
+                user_id,
+                hasher.finish()
+            );
+        }
+        RemovalCause::Explicit | RemovalCause::Replaced => {}
+    }
+}
+
+/// The states of all guilds, lazily loaded from the database as the guilds become active.
+pub struct Guilds {
+    states: RwLock<HashMap<GuildId, Arc<GuildState>>>,
+    stats: Arc<Stats>,
+    database: Arc<Database>,
+    limits: ConfigLimits,
+    /// Time between flushes of buffered writes to the tracking caches, or `None` if writes are
+    /// not batched.
+    write_batch_interval: Option<Duration>,
+}
+
+impl Guilds {
+    pub fn new(
+        stats: Arc<Stats>,
+        database: Arc<Database>,
+        limits: ConfigLimits,
+        write_batch_interval: Option<Duration>,
+    ) -> Self {
+        Self {
+            states: RwLock::new(HashMap::new()),
+            stats,
+            database,
+            limits,
+            write_batch_interval,
+        }
+    }
+
+    /// Flushes the buffered writes of all guilds periodically until the process exits, if writes
+    /// are batched.
+    pub async fn run_write_batches(self: Arc<Self>) {
+        let Some(period) = self.write_batch_interval else {
+            return;
+        };
+
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let states: Vec<_> = self.states.read().await.values().cloned().collect();
+            for state in states {
+                state.flush_writes().await;
+            }
+        }
+    }
+
+    /// Returns the states of all guilds that have been seen since startup.
+    pub async fn loaded(&self) -> Vec<(GuildId, Arc<GuildState>)> {
+        self.states
+            .read()
+            .await
+            .iter()
+            .map(|(guild_id, state)| (*guild_id, state.clone()))
+            .collect()
+    }
+
+    /// Returns the state of the given guild, loading its configuration if the guild has not been
+    /// seen since startup.
+    pub async fn get(&self, guild_id: GuildId) -> Result<Arc<GuildState>, BroomError> {
+        if let Some(state) = self.states.read().await.get(&guild_id) {
+            return Ok(state.clone());
+        }
+
+        let config = self
+            .database
+            .guild_config(guild_id)
+            .await?
+            .unwrap_or_default();
+        // Configurations saved before the limits were raised keep working, since rejecting them
+        // would leave the guild without any configuration.
+        if let Err(e) = config.validate(&self.limits) {
+            tracing::warn!("The configuration of guild {} is invalid: {}", guild_id, e);
+        }
+        let join = self.join(guild_id).await?;
+        let probations = self
+            .database
+            .probations(guild_id)
+            .await?
+            .into_iter()
+            .map(|(user_id, _)| user_id)
+            .collect();
+        let known_users = self
+            .database
+            .known_users(guild_id)
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(self
+            .states
+            .write()
+            .await
+            .entry(guild_id)
+            .or_insert_with(|| {
+                Arc::new(GuildState::new(
+                    config,
+                    join,
+                    probations,
+                    known_users,
+                    self.stats.clone(),
+                    self.write_batch_interval.is_some(),
+                ))
+            })
+            .clone())
+    }
+
+    async fn join(&self, guild_id: GuildId) -> Result<Option<GuildJoin>, BroomError> {
+        let join =
+            self.database
+                .guild_join(guild_id)
+                .await?
+                .map(|(joined_at, bootstrap_completed)| GuildJoin {
+                    joined_at,
+                    bootstrap_completed,
+                });
+
+        Ok(join)
+    }
+
+    /// Records when the bot joined the given guild, which starts its bootstrap period unless the
+    /// join is too long ago, e.g. since the guild was joined before bootstrapping was introduced.
+    /// Joins that were already recorded are ignored, so this can be called on every connect.
+    pub async fn record_join(&self, guild_id: GuildId, joined_at: i64) -> Result<(), BroomError> {
+        let state = self.get(guild_id).await?;
+        let mut join = state.join.write().await;
+        if join.is_some_and(|join| join.joined_at == joined_at) {
+            return Ok(());
+        }
+
+        let bootstrap_completed =
+            unix_timestamp() >= state.config.read().await.bootstrap_ends_at(joined_at);
+        self.database
+            .record_guild_join(guild_id, joined_at, bootstrap_completed)
+            .await?;
+        *join = Some(GuildJoin {
+            joined_at,
+            bootstrap_completed,
+        });
+
+        Ok(())
+    }
+
+    /// Marks the bootstrap period of the given guild as completed. Returns `false` if it already
+    /// was.
+    pub async fn complete_bootstrap(&self, guild_id: GuildId) -> Result<bool, BroomError> {
+        let state = self.get(guild_id).await?;
+        let mut join = state.join.write().await;
+        let completed = self.database.complete_bootstrap(guild_id).await?;
+        if let Some(join) = join.as_mut() {
+            join.bootstrap_completed = true;
+        }
+
+        Ok(completed)
+    }
+
+    /// Remembers that the given user posted in the given guild. Returns `false` if they had
+    /// posted there before.
+    pub async fn record_known_user(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<bool, BroomError> {
+        let state = self.get(guild_id).await?;
+        if state.known_users.read().await.contains(&user_id) {
+            return Ok(false);
+        }
+
+        let mut known_users = state.known_users.write().await;
+        // Another message of the user may have been handled in the meantime.
+        if known_users.contains(&user_id) {
+            return Ok(false);
+        }
+        self.database.record_known_user(guild_id, user_id).await?;
+        known_users.insert(user_id);
+
+        Ok(true)
+    }
+
+    /// Applies the given change to the configuration of a guild and persists it. Changes violating
+    /// the limits are rejected without being applied. The tracking cache is rebuilt if the change
+    /// affects its parameters, which forgets all tracked messages.
+    pub async fn update_config<T>(
+        &self,
+        guild_id: GuildId,
+        update: impl FnOnce(&mut GuildConfig) -> T,
+    ) -> Result<T, BroomError> {
+        let state = self.get(guild_id).await?;
+        let mut config = state.config.write().await;
+        let previous_parameters = cache_parameters(&config);
+        let previous_patterns = config.ignore_patterns.clone();
+        let previous_bridges = config.trusted_bridges.clone();
+        let previous_prefixes = config.content_prefix_allowlist.clone();
+        let mut updated = config.clone();
+        let result = update(&mut updated);
+        updated.validate(&self.limits)?;
+        self.database.set_guild_config(guild_id, &updated).await?;
+        *config = updated;
+
+        if cache_parameters(&config) != previous_parameters {
+            state.discard_writes().await;
+            *state.cache.write().await = build_cache(&config, self.stats.clone());
+            *state.recent_messages.write().await = build_recent_message_cache(&config);
+        }
+        if config.ignore_patterns != previous_patterns {
+            *state.ignore_list.write().await = IgnoreList::new(&config.ignore_patterns);
+        }
+        if config.trusted_bridges != previous_bridges {
+            *state.bridge_list.write().await = BridgeList::new(&config.trusted_bridges);
+        }
+        if config.content_prefix_allowlist != previous_prefixes {
+            *state.prefix_allowlist.write().await =
+                PrefixAllowlist::new(&config.content_prefix_allowlist);
+        }
+
+        Ok(result)
+    }
+
+    /// Deletes all persisted data of the given guild and replaces its state with a fresh one using
+    /// the default configuration. Returns the number of deleted rows by table.
+    pub async fn reset(&self, guild_id: GuildId) -> Result<Vec<(&'static str, u64)>, BroomError> {
+        // Holding the lock keeps the old configuration from being loaded again in the meantime.
+        let mut states = self.states.write().await;
+        let deleted = self.database.delete_guild_data(guild_id).await?;
+        let join = self.join(guild_id).await?;
+        states.insert(
+            guild_id,
+            Arc::new(GuildState::new(
+                GuildConfig::default(),
+                join,
+                HashSet::new(),
+                HashSet::new(),
+                self.stats.clone(),
+                self.write_batch_interval.is_some(),
+            )),
+        );
+
+        Ok(deleted)
+    }
+}
+
+/// Reads the time in milliseconds between flushes of buffered writes to the tracking caches from
+/// `WRITE_BATCH_INTERVAL_MS`, where zero writes to them directly.
+pub fn write_batch_interval_from_env() -> Result<Option<Duration>, BroomError> {
+    let millis = match env::var("WRITE_BATCH_INTERVAL_MS") {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| BroomError::InvalidConfig("WRITE_BATCH_INTERVAL_MS"))?,
+        Err(_) => WRITE_BATCH_INTERVAL_IN_MILLIS,
+    };
+
+    Ok((millis > 0).then(|| Duration::from_millis(millis)))
+}
+
+pub struct GuildStates;
+
+impl TypeMapKey for GuildStates {
+    type Value = Arc<Guilds>;
+}
+
+/// Default time in seconds within which detections of the same user in different guilds are
+/// reported to the operator.
+const GLOBAL_COOLDOWN_IN_SECS: u64 = 10 * 60;
+/// Maximum number of users whose last detection is remembered at once.
+const MAX_COOLDOWN_USERS: u64 = 10_000;
+/// Default time in seconds after a guild became available during which its messages are skipped.
+const STARTUP_GRACE_IN_SECS: u64 = 5;
+
+/// What the bot keeps track of across all guilds. None of it is ever shown to guilds, since
+/// members of one guild must not learn about the activity of a user in another one.
+pub struct GlobalState {
+    /// The guild each user last triggered a detection in and when, until the cooldown elapsed.
+    global_user_cooldown: Cache<UserId, (GuildId, Instant)>,
+    cooldown: Duration,
+    /// Number of members below which guilds are not checked at all, e.g. test servers the operator
+    /// debugs the bot in.
+    min_guild_member_count: u64,
+    /// Time after a guild became available during which its messages are skipped, since neither
+    /// the cache of the guild is warm nor its configuration loaded yet.
+    startup_grace: Duration,
+    /// Guilds within the startup grace period.
+    starting_guilds: RwLock<HashSet<GuildId>>,
+}
+
+impl GlobalState {
+    /// Reads the cooldown in seconds from `GLOBAL_COOLDOWN_SECS`, where zero disables it, the
+    /// minimum number of members of checked guilds from `MIN_GUILD_MEMBER_COUNT`, where zero checks
+    /// all guilds, and the startup grace period in seconds from `STARTUP_GRACE_SECS`.
+    pub fn from_env() -> Result<Self, BroomError> {
+        let cooldown_secs = match env::var("GLOBAL_COOLDOWN_SECS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("GLOBAL_COOLDOWN_SECS"))?,
+            Err(_) => GLOBAL_COOLDOWN_IN_SECS,
+        };
+        let cooldown = Duration::from_secs(cooldown_secs);
+        let min_guild_member_count = match env::var("MIN_GUILD_MEMBER_COUNT") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("MIN_GUILD_MEMBER_COUNT"))?,
+            Err(_) => 0,
+        };
+        let startup_grace_secs = match env::var("STARTUP_GRACE_SECS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|_| BroomError::InvalidConfig("STARTUP_GRACE_SECS"))?,
+            Err(_) => STARTUP_GRACE_IN_SECS,
+        };
+
+        Ok(Self {
+            global_user_cooldown: Cache::builder()
+                .max_capacity(MAX_COOLDOWN_USERS)
+                .time_to_live(cooldown.max(Duration::from_secs(1)))
+                .build(),
+            cooldown,
+            min_guild_member_count,
+            startup_grace: Duration::from_secs(startup_grace_secs),
+            starting_guilds: RwLock::new(HashSet::new()),
+        })
+    }
+
+    /// Skips the messages of the given guild until the startup grace period elapsed.
+    pub async fn start_grace_period(self: Arc<Self>, guild_id: GuildId) {
+        if self.startup_grace.is_zero() {
+            return;
+        }
+
+        self.starting_guilds.write().await.insert(guild_id);
+        tokio::spawn(async move {
+            tokio::time::sleep(self.startup_grace).await;
+            self.starting_guilds.write().await.remove(&guild_id);
+        });
+    }
+
+    /// Whether the given guild is within the startup grace period.
+    pub async fn in_grace_period(&self, guild_id: GuildId) -> bool {
+        self.starting_guilds.read().await.contains(&guild_id)
+    }
+
+    /// Whether a guild with the given number of members is too small to be checked.
+    pub fn is_too_small(&self, member_count: u64) -> bool {
+        member_count < self.min_guild_member_count
+    }
+
+    /// Records that the given user triggered a detection in the given guild. Returns the other
+    /// guild they triggered one in within the cooldown along with the time since, if any.
+    pub async fn record_detection(
+        &self,
+        user_id: UserId,
+        guild_id: GuildId,
+        now: Instant,
+    ) -> Option<(GuildId, Duration)> {
+        if self.cooldown.is_zero() {
+            return None;
+        }
+
+        let previous = self.global_user_cooldown.get(&user_id).await;
+        self.global_user_cooldown
+            .insert(user_id, (guild_id, now))
+            .await;
+
+        previous
+            .filter(|(previous_guild_id, _)| *previous_guild_id != guild_id)
+            .map(|(previous_guild_id, seen_at)| {
+                (previous_guild_id, now.saturating_duration_since(seen_at))
+            })
+            .filter(|(_, elapsed)| *elapsed <= self.cooldown)
+    }
+}
+
+pub struct SharedGlobalState;
+
+impl TypeMapKey for SharedGlobalState {
+    type Value = Arc<GlobalState>;
+}
+
+/// Looks up a value that was inserted into the `TypeMap` during startup.
+pub fn shared<K>(data: &TypeMap) -> Result<K::Value, BroomError>
+where
+    K: TypeMapKey,
+    K::Value: Clone,
+{
+    data.get::<K>()
+        .cloned()
+        .ok_or(BroomError::MissingState(std::any::type_name::<K>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(user_id: u64, content: &str) -> MessageKey {
+        (UserId::new(user_id), content.to_string())
+    }
+
+    fn entry() -> CacheEntry {
+        CacheEntry {
+            last_seen: Instant::now(),
+            occurrences: 1,
+            messages: Vec::new(),
+            first_channel_id: ChannelId::new(1),
+            warnings: 0,
+        }
+    }
+
+    async fn state(batch_writes: bool) -> GuildState {
+        let state = GuildState::new(
+            GuildConfig::default(),
+            None,
+            HashSet::new(),
+            HashSet::new(),
+            Arc::new(Stats::default()),
+            batch_writes,
+        );
+        for (user_id, content) in [(1, "a"), (1, "b"), (2, "a"), (3, "c")] {
+            state.track(key(user_id, content), entry()).await;
+        }
+
+        state
+    }
+
+    #[tokio::test]
+    async fn clears_the_messages_of_a_user() {
+        let state = state(false).await;
+
+        assert_eq!(state.clear_cache(Some(UserId::new(1))).await, 2);
+        assert!(state.tracked(&key(1, "a")).await.is_none());
+        assert!(state.tracked(&key(2, "a")).await.is_some());
+        assert_eq!(state.clear_cache(Some(UserId::new(1))).await, 0);
+    }
+
+    #[tokio::test]
+    async fn clears_the_messages_of_the_guild() {
+        let state = state(false).await;
+
+        assert_eq!(state.clear_cache(None).await, 4);
+        assert_eq!(state.tracked_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn clears_buffered_and_flushed_messages() {
+        let state = state(true).await;
+        state.flush_writes().await;
+        // A repost of a flushed message is counted once.
+        state.track(key(1, "a"), entry()).await;
+        state.track(key(2, "b"), entry()).await;
+
+        assert_eq!(state.clear_cache(Some(UserId::new(1))).await, 2);
+        state.flush_writes().await;
+        assert!(state.tracked(&key(1, "a")).await.is_none());
+        assert_eq!(state.clear_cache(None).await, 3);
+        state.flush_writes().await;
+        assert_eq!(state.tracked_count().await, 0);
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use serenity::{model::prelude::GuildId, prelude::TypeMapKey};
+
+/// Counters shared across all guilds since the bot was started.
+#[derive(Default)]
+pub struct Stats {
+    /// Tracked messages that were evicted because their duplicate detection window elapsed.
+    pub expired_entries: AtomicU64,
+    /// Tracked messages that were evicted early because a tracking cache was full.
+    pub evicted_entries: AtomicU64,
+    /// Lookups of messages in the tracking cache of each guild.
+    cache_lookups: RwLock<HashMap<GuildId, Arc<CacheLookups>>>,
+    /// The most recent pruning of expired events of each guild.
+    prunes: RwLock<HashMap<GuildId, Prune>>,
+}
+
+/// How many expired events of a guild were pruned and when.
+#[derive(Clone, Copy, Debug)]
+pub struct Prune {
+    pub rows: u64,
+    pub pruned_at: i64,
+}
+
+/// How often a message was found in the tracking cache of a guild when it was looked up. Many
+/// misses mean that reposts are posted after the window elapsed, i.e. that it may be too short.
+#[derive(Default)]
+pub struct CacheLookups {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+}
+
+impl CacheLookups {
+    /// Returns the share of lookups that found a message, or `None` if there were none.
+    pub fn detection_rate(&self) -> Option<f64> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let total = hits + self.misses.load(Ordering::Relaxed);
+
+        (total > 0).then(|| hits as f64 / total as f64)
+    }
+}
+
+impl Stats {
+    /// Records a lookup of a message in the tracking cache of the given guild and returns the
+    /// lookups of the guild so far.
+    pub fn record_lookup(&self, guild_id: GuildId, hit: bool) -> Arc<CacheLookups> {
+        let existing = self
+            .cache_lookups
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&guild_id)
+            .cloned();
+        let lookups = existing.unwrap_or_else(|| {
+            self.cache_lookups
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(guild_id)
+                .or_default()
+                .clone()
+        });
+        let counter = if hit { &lookups.hits } else { &lookups.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        lookups
+    }
+
+    /// Returns the lookups of messages in the tracking cache of the given guild, if there were any.
+    pub fn cache_lookups(&self, guild_id: GuildId) -> Option<Arc<CacheLookups>> {
+        self.cache_lookups
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&guild_id)
+            .cloned()
+    }
+
+    /// Records that the given number of expired events of the given guild were pruned.
+    pub fn record_prune(&self, guild_id: GuildId, prune: Prune) {
+        self.prunes
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(guild_id, prune);
+    }
+
+    /// Returns the most recent pruning of the given guild since the bot was started, if any.
+    pub fn last_prune(&self, guild_id: GuildId) -> Option<Prune> {
+        self.prunes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&guild_id)
+            .copied()
+    }
+
+    /// Renders all counters in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut output = String::new();
+        push_counter(
+            &mut output,
+            "broom_cache_removals_total",
+            "Tracked messages removed from the tracking caches.",
+            &[
+                ("cause=\"expired\"", &self.expired_entries),
+                ("cause=\"size\"", &self.evicted_entries),
+            ],
+        );
+
+        let cache_lookups = self
+            .cache_lookups
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(guild_id, lookups)| (*guild_id, lookups.clone()))
+            .collect::<Vec<_>>();
+        let values = cache_lookups
+            .iter()
+            .flat_map(|(guild_id, lookups)| {
+                [
+                    (
+                        format!("guild_id=\"{}\",result=\"hit\"", guild_id),
+                        &lookups.hits,
+                    ),
+                    (
+                        format!("guild_id=\"{}\",result=\"miss\"", guild_id),
+                        &lookups.misses,
+                    ),
+                ]
+            })
+            .collect::<Vec<_>>();
+        push_counter(
+            &mut output,
+            "broom_cache_lookups_total",
+            "Lookups of messages in the tracking caches by guild and whether they were found.",
+            &values,
+        );
+        output
+    }
+}
+
+fn push_counter<L: AsRef<str>>(
+    output: &mut String,
+    name: &str,
+    help: &str,
+    values: &[(L, &AtomicU64)],
+) {
+    output.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} counter\n",
+        name, help, name
+    ));
+    for (labels, value) in values {
+        output.push_str(&format!(
+            "{}{{{}}} {}\n",
+            name,
+            labels.as_ref(),
+            value.load(Ordering::Relaxed)
+        ));
+    }
+}
+
+pub struct SharedStats;
+
+impl TypeMapKey for SharedStats {
+    type Value = Arc<Stats>;
+}
@@ -0,0 +1,167 @@
+//! Suggestions of ignore patterns learned from sampled duplicates. Contents that are detected over
+//! and over again are commonly benign, e.g. greetings or event announcements, so clusters of
+//! similar contents are turned into regexes that moderators may add to the ignore list.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use moka::future::Cache;
+use serenity::{model::prelude::GuildId, prelude::TypeMapKey};
+
+use crate::ignore::{normalize, IgnoreList, IgnorePattern, PatternKind};
+
+/// Days of sampled duplicates that are analyzed.
+pub const ANALYZED_DAYS: i64 = 30;
+/// Maximum number of the most recent samples that are analyzed, which bounds the time clustering
+/// takes.
+pub const MAX_ANALYZED_SAMPLES: u32 = 2_000;
+/// Maximum number of patterns suggested at once.
+const MAX_SUGGESTIONS: usize = 10;
+/// Minimum number of samples a cluster has to consist of to be suggested.
+const MIN_CLUSTER_SIZE: usize = 3;
+/// Share of word pairs a content has to have in common with the first content of a cluster to
+/// belong to it.
+const MIN_SIMILARITY: f64 = 0.5;
+/// Minimum number of characters of the words a pattern consists of, since shorter patterns would
+/// ignore far more than the cluster they were learned from.
+const MIN_PATTERN_LENGTH: usize = 8;
+/// Time in seconds suggestions can be acted on after they were made.
+const SESSION_TTL_IN_SECS: u64 = 15 * 60;
+
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    pub pattern: String,
+    /// Number of analyzed samples the pattern matches.
+    pub matches: usize,
+    /// One of the contents the pattern was learned from.
+    pub example: String,
+}
+
+/// Suggestions made to a moderator that have neither been added nor dismissed yet.
+#[derive(Clone)]
+pub struct SuggestionSession {
+    pub guild_id: GuildId,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// Sessions by the ID of the interaction that started them.
+pub struct SuggestionSessions;
+
+impl TypeMapKey for SuggestionSessions {
+    type Value = Arc<Cache<u64, SuggestionSession>>;
+}
+
+pub fn sessions() -> Cache<u64, SuggestionSession> {
+    Cache::builder()
+        .time_to_live(Duration::from_secs(SESSION_TTL_IN_SECS))
+        .build()
+}
+
+/// A group of similar contents, compared by the word pairs of the content that started it.
+struct Cluster {
+    shingles: HashSet<String>,
+    members: Vec<String>,
+}
+
+/// Clusters the given contents by the word pairs they have in common and suggests a pattern for
+/// each of the largest clusters. Contents that are ignored already are skipped.
+pub fn suggest(contents: &[String], ignored: &IgnoreList) -> Vec<Suggestion> {
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for content in contents {
+        let normalized = normalize(content);
+        if normalized.is_empty() || ignored.matches(&normalized) {
+            continue;
+        }
+        let shingles = shingles(&normalized);
+        match clusters
+            .iter_mut()
+            .find(|cluster| similarity(&cluster.shingles, &shingles) >= MIN_SIMILARITY)
+        {
+            Some(cluster) => cluster.members.push(normalized),
+            None => clusters.push(Cluster {
+                shingles,
+                members: vec![normalized],
+            }),
+        }
+    }
+
+    clusters.retain(|cluster| cluster.members.len() >= MIN_CLUSTER_SIZE);
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.members.len()));
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    for cluster in clusters {
+        let Some(pattern) = pattern(&cluster.members) else {
+            continue;
+        };
+        if suggestions
+            .iter()
+            .any(|suggestion| suggestion.pattern == pattern)
+        {
+            continue;
+        }
+        suggestions.push(Suggestion {
+            pattern,
+            matches: cluster.members.len(),
+            example: cluster.members[0].clone(),
+        });
+        if suggestions.len() == MAX_SUGGESTIONS {
+            break;
+        }
+    }
+
+    suggestions
+}
+
+/// Returns the pairs of consecutive words of a normalized content, or its only word.
+fn shingles(content: &str) -> HashSet<String> {
+    let words = content.split(' ').collect::<Vec<_>>();
+    if words.len() == 1 {
+        return words.into_iter().map(str::to_string).collect();
+    }
+
+    words.windows(2).map(|pair| pair.join(" ")).collect()
+}
+
+/// The Jaccard index of two sets of word pairs.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Builds a regex matching the words all members have in common, in the order of the first one.
+/// Returns `None` if they have too little in common or the words appear in different orders.
+fn pattern(members: &[String]) -> Option<String> {
+    let first = &members[0];
+    let pattern = if members.iter().all(|member| member == first) {
+        format!("^{}$", regex::escape(first))
+    } else {
+        let words = members
+            .iter()
+            .map(|member| member.split(' ').collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let mut common = first
+            .split(' ')
+            .filter(|word| words.iter().all(|members| members.contains(word)))
+            .collect::<Vec<_>>();
+        common.dedup();
+        if common.iter().map(|word| word.len()).sum::<usize>() < MIN_PATTERN_LENGTH {
+            return None;
+        }
+        common
+            .iter()
+            .map(|word| regex::escape(word))
+            .collect::<Vec<_>>()
+            .join(".*")
+    };
+
+    // Suggestions are validated the same way as patterns added by moderators.
+    let entry = IgnorePattern::new(PatternKind::Regex, &pattern)?;
+    let list = IgnoreList::new(std::slice::from_ref(&entry));
+
+    members
+        .iter()
+        .all(|member| list.matches(member))
+        .then_some(entry.pattern)
+}
@@ -0,0 +1,107 @@
+//! Template messages that users are instructed to copy, fill out and post, e.g. pinned bug report
+//! templates. Users posting a template without filling it out are not spamming, even though their
+//! messages are identical.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serenity::{
+    cache::Cache,
+    http::Http,
+    model::prelude::{ChannelId, GuildId, MessageId},
+};
+
+use crate::state::{GuildState, Guilds};
+
+/// Maximum number of template messages per guild.
+pub const MAX_TEMPLATES: usize = 25;
+/// Interval in seconds in which the contents of template messages are fetched again, since
+/// moderators may edit them.
+const REFRESH_INTERVAL_IN_SECS: u64 = 24 * 60 * 60;
+
+/// The contents of the template messages of a guild by their message ID.
+#[derive(Default)]
+pub struct TemplateContents {
+    contents: HashMap<MessageId, String>,
+}
+
+impl TemplateContents {
+    /// Whether the given content is exactly one of the templates, ignoring surrounding whitespace.
+    pub fn matches(&self, content: &str) -> bool {
+        let content = content.trim();
+
+        !content.is_empty() && self.contents.values().any(|template| template == content)
+    }
+}
+
+/// Parses a message given either as a link or as `<channel ID>-<message ID>` as copied by Discord,
+/// as long as it was posted in the given guild.
+pub fn parse_message(
+    cache: &Cache,
+    guild_id: GuildId,
+    value: &str,
+) -> Option<(ChannelId, MessageId)> {
+    let value = value.trim();
+    let (channel_id, message_id) = match value.rsplit_once('/') {
+        Some((rest, message_id)) => {
+            let (rest, channel_id) = rest.rsplit_once('/')?;
+            let (_, link_guild_id) = rest.rsplit_once('/')?;
+            if link_guild_id.parse::<u64>().ok()? != guild_id.get() {
+                return None;
+            }
+            (channel_id, message_id)
+        }
+        None => value.split_once('-')?,
+    };
+    let channel_id = ChannelId::new(channel_id.parse().ok().filter(|id| *id != 0)?);
+    let message_id = MessageId::new(message_id.parse().ok().filter(|id| *id != 0)?);
+
+    // Messages of other guilds the bot is in must not be shown to this one.
+    let guild = cache.guild(guild_id)?;
+    let in_guild = guild.channels.contains_key(&channel_id)
+        || guild.threads.iter().any(|thread| thread.id == channel_id);
+
+    in_guild.then_some((channel_id, message_id))
+}
+
+/// Fetches the contents of the template messages of the given guild. Templates that cannot be
+/// fetched keep their previous content, if any.
+pub async fn refresh(http: &Http, guild_id: GuildId, state: &GuildState) {
+    let templates = state.config.read().await.template_message_ids.clone();
+    let mut contents = HashMap::new();
+    for (message_id, channel_id) in templates {
+        match channel_id.message(http, message_id).await {
+            Ok(message) => {
+                contents.insert(message_id, message.content.trim().to_string());
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Could not fetch template message {} of guild {}: {:?}",
+                    message_id,
+                    guild_id,
+                    e
+                );
+                if let Some(content) = state.templates.read().await.contents.get(&message_id) {
+                    contents.insert(message_id, content.clone());
+                }
+            }
+        }
+    }
+
+    *state.templates.write().await = TemplateContents { contents };
+}
+
+/// Fetches the contents of the template messages of all loaded guilds periodically, until the
+/// process exits.
+pub async fn run_refresh(http: Arc<Http>, guilds: Arc<Guilds>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(REFRESH_INTERVAL_IN_SECS));
+    // Templates are fetched as guilds become available, so the first tick is skipped.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        for (guild_id, state) in guilds.loaded().await {
+            if !state.config.read().await.template_message_ids.is_empty() {
+                refresh(&http, guild_id, &state).await;
+            }
+        }
+    }
+}
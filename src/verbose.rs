@@ -0,0 +1,248 @@
+//! Verbose mode, in which the messages of a guild are explained in a debug channel as they are
+//! checked, so that moderators tuning the settings see why a message was or was not detected. It
+//! disables itself after a day, since it posts about far more messages than anyone should read.
+
+use std::sync::{Arc, Mutex};
+
+use serenity::{
+    builder::CreateEmbed,
+    model::{
+        channel::Message,
+        mention::Mentionable,
+        prelude::{ChannelId, GuildId},
+    },
+    utils::MessageBuilder,
+};
+
+use crate::{
+    actions::DiscordActions,
+    config::GuildConfig,
+    content,
+    db::unix_timestamp,
+    error::BroomError,
+    i18n,
+    state::{GuildState, Guilds},
+};
+
+/// Time in seconds verbose mode stays enabled for after the debug channel was set.
+pub const VERBOSE_DEBUG_DURATION_IN_SECS: i64 = 24 * 60 * 60;
+/// Maximum length in characters of the content of a message as it is shown in the debug channel.
+const MAX_SNIPPET_LENGTH: usize = 100;
+
+/// What the detectors found out about a message besides whether they detected something.
+#[derive(Default)]
+pub struct DebugTrace {
+    skip_reason: Mutex<Option<&'static str>>,
+    occurrences: Mutex<Option<(usize, usize)>>,
+}
+
+impl DebugTrace {
+    /// Records why the detector currently checking the message skipped it.
+    pub fn skip(&self, reason: &'static str) {
+        let Ok(mut skip_reason) = self.skip_reason.lock() else {
+            tracing::warn!("Could not record why a message was skipped in its debug trace.");
+            return;
+        };
+        *skip_reason = Some(reason);
+    }
+
+    /// Returns why the last detector skipped the message, if it did, so that the next one starts
+    /// without a reason.
+    pub fn take_skip_reason(&self) -> Option<&'static str> {
+        let Ok(mut skip_reason) = self.skip_reason.lock() else {
+            tracing::warn!("Could not read why a message was skipped from its debug trace.");
+            return None;
+        };
+        skip_reason.take()
+    }
+
+    /// Records how often the tracked content was posted after the message, and how often it has
+    /// to be to be a duplicate.
+    pub fn record_occurrences(&self, occurrences: usize, threshold: usize) {
+        let Ok(mut recorded) = self.occurrences.lock() else {
+            tracing::warn!("Could not record the occurrences of a message in its debug trace.");
+            return;
+        };
+        *recorded = Some((occurrences, threshold));
+    }
+}
+
+/// What a single detector made of a message.
+pub enum Outcome {
+    /// The detector detected the given kind of abuse.
+    Hit(&'static str),
+    Miss,
+    Skipped(&'static str),
+}
+
+/// Returns the debug channel of the guild if verbose mode is enabled, disabling it once it
+/// expired.
+pub async fn debug_channel(
+    actions: &dyn DiscordActions,
+    guilds: &Guilds,
+    guild_id: GuildId,
+    config: &GuildConfig,
+) -> Result<Option<ChannelId>, BroomError> {
+    let Some(channel_id) = config.verbose_debug_channel_id else {
+        return Ok(None);
+    };
+    if config
+        .verbose_debug_expires_at
+        .is_some_and(|expires_at| expires_at > unix_timestamp())
+    {
+        return Ok(Some(channel_id));
+    }
+
+    let disabled = guilds
+        .update_config(guild_id, |config| {
+            let enabled = config.verbose_debug_channel_id.is_some();
+            config.verbose_debug_channel_id = None;
+            config.verbose_debug_expires_at = None;
+            enabled
+        })
+        .await?;
+    // Only the first message after the expiry disables it.
+    if disabled {
+        tracing::info!("Disabled the expired verbose mode of guild {}.", guild_id);
+        let embed = CreateEmbed::new().description(i18n::translate(
+            &config.language,
+            "verbose_debug_expired",
+            &[],
+        ));
+        actions.post_debug_log(channel_id, embed).await;
+    }
+
+    Ok(None)
+}
+
+/// What became of checking a message.
+#[derive(Default)]
+pub struct Check<'a> {
+    /// Why the message was skipped before any detector checked it, if it was.
+    pub skipped: Option<&'a str>,
+    pub outcomes: &'a [(&'static str, Outcome)],
+    /// Why no actions are taken, if they are not.
+    pub preview: Option<&'a str>,
+    pub trace: Option<&'a DebugTrace>,
+}
+
+impl<'a> Check<'a> {
+    pub fn skipped(reason: &'a str) -> Self {
+        Self {
+            skipped: Some(reason),
+            ..Self::default()
+        }
+    }
+}
+
+/// Explains the outcome of checking a message in the debug channel of the guild, if verbose mode
+/// is enabled. The explanation is posted in the background, since it is no reason to hold up
+/// handling further messages.
+pub async fn explain(
+    actions: &Arc<dyn DiscordActions>,
+    channel_id: Option<ChannelId>,
+    state: &GuildState,
+    config: &GuildConfig,
+    msg: &Message,
+    check: Check<'_>,
+) {
+    let Some(channel_id) = channel_id else {
+        return;
+    };
+
+    let cached = state.tracked_count().await;
+    let embed = embed(config, msg, &check, cached);
+    let actions = actions.clone();
+    tokio::spawn(async move { actions.post_debug_log(channel_id, embed).await });
+}
+
+fn embed(config: &GuildConfig, msg: &Message, check: &Check<'_>, cached: u64) -> CreateEmbed {
+    let language = &config.language;
+    let mut results = MessageBuilder::new();
+    match check.skipped {
+        Some(reason) => {
+            results.push_line(i18n::translate(
+                language,
+                "verbose_debug_skipped",
+                &[("reason", &reason)],
+            ));
+        }
+        None => {
+            for (detector, outcome) in check.outcomes {
+                let line = match outcome {
+                    Outcome::Hit(kind) => i18n::translate(
+                        language,
+                        "verbose_debug_hit",
+                        &[("detector", detector), ("kind", kind)],
+                    ),
+                    Outcome::Miss => {
+                        i18n::translate(language, "verbose_debug_miss", &[("detector", detector)])
+                    }
+                    Outcome::Skipped(reason) => i18n::translate(
+                        language,
+                        "verbose_debug_detector_skipped",
+                        &[("detector", detector), ("reason", reason)],
+                    ),
+                };
+                results.push_line(line);
+            }
+        }
+    }
+    if let Some(reason) = check.preview {
+        results.push_line(i18n::translate(
+            language,
+            "verbose_debug_preview",
+            &[("reason", &reason)],
+        ));
+    }
+
+    let occurrences = check.trace.and_then(|trace| {
+        let Ok(occurrences) = trace.occurrences.lock() else {
+            tracing::warn!("Could not read the occurrences of a message from its debug trace.");
+            return None;
+        };
+        *occurrences
+    });
+    let cache = match occurrences {
+        Some((occurrences, threshold)) => i18n::translate(
+            language,
+            "verbose_debug_cache_tracked",
+            &[
+                ("occurrences", &occurrences),
+                ("threshold", &threshold),
+                ("count", &cached),
+            ],
+        ),
+        None => i18n::translate(language, "verbose_debug_cache", &[("count", &cached)]),
+    };
+
+    CreateEmbed::new()
+        .description(
+            MessageBuilder::new()
+                .push_safe(content::truncate(&msg.content, MAX_SNIPPET_LENGTH))
+                .build(),
+        )
+        .field(
+            i18n::translate(language, "verbose_debug_message", &[]),
+            i18n::translate(
+                language,
+                "verbose_debug_message_value",
+                &[
+                    ("author", &msg.author.id.mention()),
+                    ("channel", &msg.channel_id.mention()),
+                    ("link", &msg.link()),
+                ],
+            ),
+            false,
+        )
+        .field(
+            i18n::translate(language, "verbose_debug_result", &[]),
+            results.build(),
+            false,
+        )
+        .field(
+            i18n::translate(language, "verbose_debug_cache_title", &[]),
+            cache,
+            false,
+        )
+}